@@ -0,0 +1,36 @@
+//! How the steps between fetching and the first frame scale with a huge
+//! repo list: `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use repo_archiver::bench::Fixture;
+
+const REPOS: usize = 10_000;
+
+fn list(c: &mut Criterion) {
+    let fixture = Fixture::new(REPOS);
+    c.bench_function("filter and sort 10k repos", |b| {
+        b.iter_batched(
+            || fixture.repos(),
+            Fixture::candidate_count,
+            BatchSize::LargeInput,
+        );
+    });
+    c.bench_function("sample 500 of 10k repos", |b| {
+        b.iter_batched(|| fixture.repos(), Fixture::sample, BatchSize::LargeInput);
+    });
+    c.bench_function("score 10k repos", |b| b.iter(|| black_box(fixture.score())));
+}
+
+fn render(c: &mut Criterion) {
+    let fixture = Fixture::new(REPOS);
+    let mut screen = fixture.screen(200, 60);
+    c.bench_function("render a frame of 10k repos", |b| {
+        b.iter(|| {
+            screen.scroll();
+            screen.render();
+        });
+    });
+}
+
+criterion_group!(benches, list, render);
+criterion_main!(benches);
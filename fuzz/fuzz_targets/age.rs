@@ -0,0 +1,19 @@
+//! Any input either fails to parse or gives an age whose cutoff date can be
+//! computed and whose short form parses back to the same age.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/age.rs"]
+mod age;
+
+use age::Age;
+
+fuzz_target!(|input: &str| {
+    if let Ok(age) = Age::parse(input) {
+        age.cutoff_date();
+        assert_eq!(Age::parse(&age.arg()).unwrap(), age);
+    }
+});
@@ -0,0 +1,232 @@
+//! Who could reach a repo: its teams, for backups, and everyone who could
+//! still write to it, for the access review in the run report.
+//!
+//! Archiving a repo leaves its collaborators and teams in place, so with
+//! `--access-review` each archived repo's report entry lists the people and
+//! teams with write, maintain or admin access, for a security team to clean
+//! up afterwards. Outside collaborators and given teams can also be removed
+//! right before archiving (see [`Revoke`]), with what was removed recorded in
+//! the report so it can be granted again.
+//!
+//! In org mode, each archived repo's owning teams are recorded too, for the
+//! report's per-team summary.
+
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+use crate::{error, gh};
+
+/// A team's access to a repo.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamAccess {
+    pub slug: String,
+    pub name: String,
+    /// "pull", "triage", "push", "maintain" or "admin"
+    pub permission: String,
+}
+
+/// Someone, or a team, who can write to a repo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    /// Login of a user, or `org/slug` of a team
+    pub who: String,
+    pub kind: GrantKind,
+    /// "write", "maintain" or "admin"
+    pub permission: String,
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.who, self.permission)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrantKind {
+    User,
+    Team,
+}
+
+/// A collaborator as listed by `gh api`, trimmed by `--jq`.
+#[derive(Deserialize)]
+struct Collaborator {
+    login: String,
+    role_name: String,
+}
+
+/// The teams with access to the repo; none for a user's repo.
+pub fn teams(name_with_owner: &str, token: Option<&str>) -> Result<Vec<TeamAccess>> {
+    list(
+        &format!("repos/{name_with_owner}/teams"),
+        ".[] | {slug, name, permission}",
+        token,
+    )
+    .with_context(|| format!("Failed to list the teams of {name_with_owner}"))
+}
+
+/// The teams that own the repo, as `org/slug`: those with its highest
+/// role, if that's write or more. None for a user's repo.
+pub fn owning_teams(name_with_owner: &str, token: Option<&str>) -> Result<Vec<String>> {
+    let grants = team_grants(name_with_owner, token)?;
+    let top = grants
+        .iter()
+        .map(|g| rank(&g.permission))
+        .max()
+        .unwrap_or(0);
+    Ok(grants
+        .into_iter()
+        .filter(|g| top > 0 && rank(&g.permission) == top)
+        .map(|g| g.who)
+        .collect())
+}
+
+/// Everyone, and every team, with write access or more to the repo, admins
+/// first.
+pub fn writers(name_with_owner: &str, token: Option<&str>) -> Result<Vec<Grant>> {
+    let mut grants: Vec<Grant> = collaborators(name_with_owner, "all", token)?
+        .into_iter()
+        .chain(team_grants(name_with_owner, token)?)
+        .filter(|g| rank(&g.permission) > 0)
+        .collect();
+    grants.sort_by_key(|g| std::cmp::Reverse(rank(&g.permission)));
+    Ok(grants)
+}
+
+/// The repo's collaborators of one `affiliation` ("all", "outside" or
+/// "direct").
+fn collaborators(
+    name_with_owner: &str,
+    affiliation: &str,
+    token: Option<&str>,
+) -> Result<Vec<Grant>> {
+    let collaborators: Vec<Collaborator> = list(
+        &format!("repos/{name_with_owner}/collaborators?affiliation={affiliation}"),
+        ".[] | {login, role_name}",
+        token,
+    )
+    .with_context(|| format!("Failed to list the collaborators of {name_with_owner}"))?;
+    Ok(collaborators
+        .into_iter()
+        .map(|c| Grant {
+            who: c.login,
+            kind: GrantKind::User,
+            permission: c.role_name,
+        })
+        .collect())
+}
+
+/// The repo's teams, as grants.
+fn team_grants(name_with_owner: &str, token: Option<&str>) -> Result<Vec<Grant>> {
+    let org = name_with_owner.split('/').next().unwrap_or_default();
+    Ok(teams(name_with_owner, token)?
+        .into_iter()
+        .map(|t| Grant {
+            who: format!("{org}/{}", t.slug),
+            kind: GrantKind::Team,
+            // Teams are listed with the REST API's older names for the roles
+            permission: match t.permission.as_str() {
+                "push" => "write".to_string(),
+                "pull" => "read".to_string(),
+                _ => t.permission,
+            },
+        })
+        .collect())
+}
+
+/// Access taken away from each repo right before archiving it.
+#[derive(Debug, Clone, Default)]
+pub struct Revoke {
+    /// Remove every outside collaborator
+    pub outside_collaborators: bool,
+    /// Slugs of teams to remove, wherever they have access
+    pub teams: Vec<String>,
+}
+
+impl Revoke {
+    pub fn is_empty(&self) -> bool {
+        !self.outside_collaborators && self.teams.is_empty()
+    }
+
+    /// Who would be removed from the repo.
+    pub fn grants(&self, name_with_owner: &str, token: Option<&str>) -> Result<Vec<Grant>> {
+        let mut grants = Vec::new();
+        if self.outside_collaborators {
+            grants.extend(collaborators(name_with_owner, "outside", token)?);
+        }
+        if !self.teams.is_empty() {
+            grants.extend(
+                team_grants(name_with_owner, token)?
+                    .into_iter()
+                    .filter(|g| {
+                        let slug = g.who.split_once('/').map_or("", |(_, slug)| slug);
+                        self.teams.iter().any(|t| t == slug)
+                    }),
+            );
+        }
+        Ok(grants)
+    }
+
+    /// Remove them, calling `removed` with each one as it's done, so a
+    /// failure partway still leaves a record of what went.
+    pub fn run(
+        &self,
+        name_with_owner: &str,
+        token: Option<&str>,
+        removed: &dyn Fn(Grant),
+    ) -> Result<()> {
+        for grant in self.grants(name_with_owner, token)? {
+            let endpoint = match grant.kind {
+                GrantKind::User => format!("repos/{name_with_owner}/collaborators/{}", grant.who),
+                GrantKind::Team => {
+                    let (org, slug) = grant.who.split_once('/').unwrap_or_default();
+                    format!("orgs/{org}/teams/{slug}/repos/{name_with_owner}")
+                }
+            };
+            let output = gh(token)
+                .args(["api", "-X", "DELETE", &endpoint])
+                .output()
+                .context("Failed to run gh CLI. Is it installed?")?;
+            error::check(output).with_context(|| format!("Failed to remove {}", grant.who))?;
+            removed(grant);
+        }
+        Ok(())
+    }
+}
+
+/// "alice (admin), octo/core (write)"
+pub fn describe(grants: &[Grant]) -> String {
+    grants
+        .iter()
+        .map(Grant::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// How much a role may do; 0 for anything that can't write.
+fn rank(permission: &str) -> u8 {
+    match permission {
+        "admin" => 3,
+        "maintain" => 2,
+        "write" => 1,
+        _ => 0,
+    }
+}
+
+/// Every item of the paged list at `endpoint`, each picked out by `jq`; an
+/// endpoint that isn't there (a user's repo has no teams) lists nothing.
+fn list<T: DeserializeOwned>(endpoint: &str, jq: &str, token: Option<&str>) -> Result<Vec<T>> {
+    let output = gh(token)
+        .args(["api", endpoint, "--paginate", "--jq", jq])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    match error::check(output) {
+        Ok(stdout) => Ok(serde_json::Deserializer::from_slice(&stdout)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .map_err(|e| error::Error::Parse(e.to_string()))?),
+        Err(error::Error::NotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
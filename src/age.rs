@@ -0,0 +1,231 @@
+//! How old a repo has to be to be a candidate, parsed from `--age`, the
+//! config file and the remembered last age.
+//!
+//! Ages are the first thing the tool reads from the user, so the parser
+//! takes the forms people actually type ("2y", "18 months", "1y 6m") and
+//! rejects anything else with a message instead of guessing. It depends
+//! only on chrono and anyhow, so the fuzz target in `fuzz/` can build it on
+//! its own.
+
+use anyhow::{Context, Result};
+use chrono::{Months, NaiveDate, Utc};
+
+/// GitHub is younger than this; anything larger is a typo.
+pub const MAX_YEARS: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Age {
+    Months(u32),
+    Years(u32),
+    /// Nothing pushed since the repo was created, however old it is
+    NeverPushed,
+}
+
+/// Presets in the age picker, on keys `1` to `6`.
+pub const AGE_PRESETS: [Age; 6] = [
+    Age::Months(6),
+    Age::Years(1),
+    Age::Years(2),
+    Age::Years(3),
+    Age::Years(5),
+    Age::NeverPushed,
+];
+
+const HINT: &str = "Use 'y' for years or 'm' for months (e.g., '8y', '6m', '1y 6m'), \
+                    or 'never' for repos never pushed to";
+
+impl Age {
+    /// Parse e.g. "2y", "6m", "18 months", "1y6m" or "never". Case and
+    /// whitespace don't matter; an age with both years and months becomes
+    /// months.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        if s.is_empty() {
+            anyhow::bail!("Age cannot be empty");
+        }
+        if s == "never" {
+            return Ok(Self::NeverPushed);
+        }
+
+        let mut years = None;
+        let mut months = None;
+        let mut chars = s.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+            let Some(&first) = chars.peek() else { break };
+
+            let mut digits = String::new();
+            while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                digits.push(c);
+            }
+            if digits.is_empty() {
+                if first.is_numeric() {
+                    anyhow::bail!("Use ASCII digits (0-9) in ages, got '{first}'");
+                }
+                anyhow::bail!("Expected a number in age '{s}'. {HINT}");
+            }
+            let num: u32 = digits
+                .parse()
+                .ok()
+                .filter(|n| *n <= MAX_YEARS * 12)
+                .with_context(|| format!("Age {digits} is too large; at most {MAX_YEARS} years"))?;
+
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let mut unit = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+                unit.push(c);
+            }
+            let slot = match unit.as_str() {
+                "y" | "yr" | "yrs" | "year" | "years" => &mut years,
+                "m" | "mo" | "mos" | "month" | "months" => &mut months,
+                "" => anyhow::bail!("Missing unit after {num} in age. {HINT}"),
+                _ => anyhow::bail!("Invalid age unit '{unit}'. {HINT}"),
+            };
+            if slot.replace(num).is_some() {
+                anyhow::bail!("Age '{s}' gives the same unit twice");
+            }
+        }
+
+        match (years, months) {
+            (Some(y), None) if y <= MAX_YEARS => Ok(Self::Years(y)),
+            (None, Some(m)) if m <= MAX_YEARS * 12 => Ok(Self::Months(m)),
+            (Some(y), Some(m)) if y * 12 + m <= MAX_YEARS * 12 => Ok(Self::Months(y * 12 + m)),
+            (None, None) => anyhow::bail!("Age cannot be empty"),
+            _ => anyhow::bail!("Age '{s}' is too large; at most {MAX_YEARS} years"),
+        }
+    }
+
+    /// Repos created before this date are candidates.
+    pub fn cutoff_date(self) -> NaiveDate {
+        let today = Utc::now().date_naive();
+        let months = match self {
+            Self::Years(y) => y * 12,
+            Self::Months(m) => m,
+            Self::NeverPushed => 0,
+        };
+        // Clamps Feb 29 to Feb 28 instead of failing in non-leap years
+        today
+            .checked_sub_months(Months::new(months))
+            .unwrap_or(NaiveDate::MIN)
+    }
+
+    /// "older than 2 years", for messages about which repos were looked for.
+    pub fn display(self) -> String {
+        match self {
+            Self::Years(y) => format!("older than {y} year{}", if y == 1 { "" } else { "s" }),
+            Self::Months(m) => format!("older than {m} month{}", if m == 1 { "" } else { "s" }),
+            Self::NeverPushed => "never pushed to".to_string(),
+        }
+    }
+
+    /// Short form accepted by [`Age::parse`].
+    pub fn arg(self) -> String {
+        match self {
+            Self::Years(y) => format!("{y}y"),
+            Self::Months(m) => format!("{m}m"),
+            Self::NeverPushed => "never".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn any_age() -> impl Strategy<Value = Age> {
+        prop_oneof![
+            (0..=MAX_YEARS).prop_map(Age::Years),
+            (0..=MAX_YEARS * 12).prop_map(Age::Months),
+            Just(Age::NeverPushed),
+        ]
+    }
+
+    fn unit_word(years: bool) -> impl Strategy<Value = &'static str> {
+        if years {
+            prop::sample::select(vec!["y", "yr", "yrs", "year", "years", "Y", "Years"])
+        } else {
+            prop::sample::select(vec!["m", "mo", "mos", "month", "months", "M", "MONTHS"])
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn arg_round_trips(age in any_age()) {
+            prop_assert_eq!(Age::parse(&age.arg()).unwrap(), age);
+        }
+
+        #[test]
+        fn whitespace_and_unit_spelling_dont_matter(
+            n in 0..=MAX_YEARS,
+            (years, unit) in any::<bool>().prop_flat_map(|years| (Just(years), unit_word(years))),
+            before in "[ \t\n]{0,3}",
+            between in "[ \t]{0,2}",
+            after in "[ \t\n]{0,3}",
+        ) {
+            let input = format!("{before}{n}{between}{unit}{after}");
+            let expected = if years {
+                Age::Years(n)
+            } else {
+                Age::Months(n)
+            };
+            prop_assert_eq!(Age::parse(&input).unwrap(), expected);
+        }
+
+        #[test]
+        fn mixed_units_add_up(
+            y in 0..MAX_YEARS,
+            m in 0..12u32,
+            separator in "[ ,]{0,2}",
+            months_first in any::<bool>(),
+        ) {
+            let input = if months_first {
+                format!("{m}m{separator}{y}y")
+            } else {
+                format!("{y}y{separator}{m}m")
+            };
+            prop_assert_eq!(Age::parse(&input).unwrap(), Age::Months(y * 12 + m));
+        }
+
+        #[test]
+        fn huge_values_are_rejected(n in MAX_YEARS * 12 + 1.., unit in "[ym]") {
+            let input = format!("{n}{unit}");
+            prop_assert!(Age::parse(&input).is_err());
+        }
+
+        #[test]
+        fn years_beyond_the_limit_are_rejected(y in MAX_YEARS + 1..=MAX_YEARS * 12) {
+            let input = format!("{y}y");
+            prop_assert!(Age::parse(&input).is_err());
+        }
+
+        #[test]
+        fn unicode_digits_are_rejected(
+            digit in prop::sample::select(vec!['٣', '۵', '७', '৯', '３', '𝟠']),
+            unit in "[ym]",
+        ) {
+            let error = Age::parse(&format!("{digit}{unit}")).unwrap_err();
+            prop_assert!(error.to_string().contains("ASCII digits"));
+        }
+
+        #[test]
+        fn anything_parses_or_fails_without_panicking(input in any::<String>()) {
+            if let Ok(age) = Age::parse(&input) {
+                age.cutoff_date();
+                prop_assert_eq!(Age::parse(&age.arg()).unwrap(), age);
+            }
+        }
+    }
+
+    #[test]
+    fn never_is_never_pushed() {
+        assert_eq!(Age::parse(" Never ").unwrap(), Age::NeverPushed);
+    }
+
+    #[test]
+    fn units_are_required() {
+        for input in ["", "  ", "5", "y", "1y 2", "never 1y", "2w", "1y1y", "-1y"] {
+            assert!(Age::parse(input).is_err(), "{input:?} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! `repo-archiver analyze`: abandonment signals per repo, to help decide
+//! between archiving, deleting and reviving it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Months, Utc};
+use clap::Args;
+use serde_json::Value;
+use std::{thread, time::Duration};
+
+use crate::{dates::DateFormat, gh, graphql, mirror, pipeline::ArchiveJob};
+
+/// Commit authors whose commits count as dependency updates
+const DEPENDENCY_BOTS: [&str; 2] = ["dependabot[bot]", "renovate[bot]"];
+
+/// Recent default-branch commits searched for dependency updates
+const HISTORY: usize = 100;
+
+/// GitHub allows 10 code searches a minute
+const SEARCH_PAUSE: Duration = Duration::from_secs(6);
+
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// Repos to analyze, as "owner/name" [default: all your non-archived
+    /// source repos]
+    repos: Vec<String>,
+
+    /// Also count TODO/FIXME comments with code search; slow, since GitHub
+    /// allows 10 searches a minute
+    #[arg(long)]
+    todos: bool,
+}
+
+/// What suggests a repo has been abandoned.
+pub struct Signals {
+    name_with_owner: String,
+    pushed_at: Option<DateTime<Utc>>,
+    last_release: Option<DateTime<Utc>>,
+    last_dependency_update: Option<DateTime<Utc>>,
+    /// Default branch CI state ("SUCCESS", "FAILURE", ...) and the date of
+    /// the commit it's for
+    ci: Option<(String, DateTime<Utc>)>,
+    stars: u64,
+    forks: u64,
+    todos: Option<u64>,
+    /// Community content that archiving freezes and deleting destroys, e.g.
+    /// "12 discussions"
+    community: Vec<String>,
+}
+
+impl Signals {
+    pub fn from_json(name_with_owner: &str, repo: &Value) -> Self {
+        let date = |value: &Value| value.as_str().and_then(|s| s.parse().ok());
+        let head = &repo["defaultBranchRef"]["target"];
+        let last_dependency_update = head["history"]["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|commit| {
+                let author = commit["author"]["name"].as_str().unwrap_or_default();
+                let headline = commit["messageHeadline"].as_str().unwrap_or_default();
+                DEPENDENCY_BOTS.contains(&author)
+                    || headline.starts_with("Bump ")
+                    || headline.starts_with("chore(deps")
+            })
+            .and_then(|commit| date(&commit["committedDate"]));
+
+        Self {
+            name_with_owner: name_with_owner.to_string(),
+            pushed_at: date(&repo["pushedAt"]),
+            last_release: date(&repo["releases"]["nodes"][0]["createdAt"]),
+            last_dependency_update,
+            ci: head["statusCheckRollup"]["state"]
+                .as_str()
+                .zip(date(&head["committedDate"]))
+                .map(|(state, at)| (state.to_string(), at)),
+            stars: repo["stargazerCount"].as_u64().unwrap_or(0),
+            forks: repo["forkCount"].as_u64().unwrap_or(0),
+            todos: None,
+            community: community(repo),
+        }
+    }
+
+    /// CI on the default branch has been red since a commit older than this.
+    fn failing_since(&self, before: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ci
+            .as_ref()
+            .filter(|(state, at)| matches!(state.as_str(), "FAILURE" | "ERROR") && *at < before)
+            .map(|(_, at)| *at)
+    }
+
+    /// Rough call: repos nobody depends on or looks at are safe to delete,
+    /// ones with an audience that were active lately are worth reviving,
+    /// and everything else is archived.
+    pub fn suggestion(&self) -> &'static str {
+        let year_ago = Utc::now() - Months::new(12);
+        let stale = self.pushed_at.is_none_or(|at| at < year_ago);
+        if stale
+            && self.last_release.is_none()
+            && self.stars == 0
+            && self.forks == 0
+            && self.community.is_empty()
+        {
+            "delete"
+        } else if !stale && self.stars + self.forks >= 5 {
+            "revive"
+        } else {
+            "archive"
+        }
+    }
+
+    fn describe(&self, date_format: &DateFormat) -> String {
+        let format = |at: DateTime<Utc>| date_format.format(at.date_naive());
+        let mut signals = vec![
+            self.pushed_at.map_or_else(
+                || "never pushed".to_string(),
+                |at| format!("last push {}", format(at)),
+            ),
+            self.last_release.map_or_else(
+                || "no releases".to_string(),
+                |at| format!("last release {}", format(at)),
+            ),
+            self.last_dependency_update.map_or_else(
+                || format!("no dependency updates in the last {HISTORY} commits"),
+                |at| format!("last dependency update {}", format(at)),
+            ),
+        ];
+        let months_ago = Utc::now() - Months::new(3);
+        match (&self.ci, self.failing_since(months_ago)) {
+            (_, Some(since)) => signals.push(format!("CI failing since {}", format(since))),
+            (Some((state, _)), None) => signals.push(format!("CI {}", state.to_lowercase())),
+            (None, None) => signals.push("no CI".to_string()),
+        }
+        signals.push(format!("{} stars, {} forks", self.stars, self.forks));
+        if let Some(todos) = self.todos {
+            signals.push(format!("{todos} files with TODO/FIXME"));
+        }
+        let community = if self.community.is_empty() {
+            "no discussions, projects, wiki pages or issue templates".to_string()
+        } else {
+            self.community.join(", ")
+        };
+        format!("{}\n  {community}", signals.join(", "))
+    }
+}
+
+/// Non-empty discussions, projects and issue templates, from the GraphQL
+/// response. Wiki pages are checked separately, since the API doesn't say.
+fn community(repo: &Value) -> Vec<String> {
+    let count = |enabled: &str, connection: &str| {
+        let total = repo[connection]["totalCount"].as_u64().unwrap_or(0);
+        (repo[enabled].as_bool() == Some(true) && total > 0).then_some(total)
+    };
+    let plural = |n: u64, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+
+    let mut community = Vec::new();
+    if let Some(n) = count("hasDiscussionsEnabled", "discussions") {
+        community.push(plural(n, "discussion"));
+    }
+    if let Some(n) = count("hasProjectsEnabled", "projectsV2") {
+        community.push(plural(n, "project"));
+    }
+    let templates = repo["issueTemplates"].as_array().map_or(0, Vec::len) as u64;
+    if templates > 0 {
+        community.push(plural(templates, "issue template"));
+    }
+    community
+}
+
+pub fn run(args: &AnalyzeArgs, date_format: &DateFormat, job: &ArchiveJob) -> Result<()> {
+    let token = job.token.as_deref();
+    let names = if args.repos.is_empty() {
+        println!("Fetching your repos...");
+        job.provider
+            .list_repos(None, &mut |_| {})?
+            .into_iter()
+            .filter(|r| !r.is_fork)
+            .map(|r| r.name_with_owner)
+            .collect()
+    } else {
+        args.repos.clone()
+    };
+    if let Some(name) = names.iter().find(|name| !name.contains('/')) {
+        anyhow::bail!("Repo must be given as owner/name, got '{name}'");
+    }
+
+    let fields = format!(
+        "pushedAt stargazerCount forkCount hasWikiEnabled \
+         hasDiscussionsEnabled discussions {{ totalCount }} hasProjectsEnabled projectsV2 {{ totalCount }} \
+         issueTemplates {{ name }} \
+         releases(first: 1, orderBy: {{field: CREATED_AT, direction: DESC}}) {{ nodes {{ createdAt }} }} \
+         defaultBranchRef {{ target {{ ... on Commit {{ committedDate statusCheckRollup {{ state }} \
+         history(first: {HISTORY}) {{ nodes {{ committedDate messageHeadline author {{ name }} }} }} }} }} }}"
+    );
+    let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    let results = graphql::per_repo(&name_refs, &fields, job.github.as_ref())
+        .context("Failed to fetch repo activity")?;
+
+    for (i, (name, repo)) in names.iter().zip(results).enumerate() {
+        if repo.is_null() {
+            println!("{name}: not found\n");
+            continue;
+        }
+        let mut signals = Signals::from_json(name, &repo);
+        if repo["hasWikiEnabled"].as_bool() == Some(true) && mirror::wiki_has_pages(name, token)? {
+            signals.community.push("wiki pages".to_string());
+        }
+        if args.todos {
+            if i > 0 {
+                thread::sleep(SEARCH_PAUSE);
+            }
+            signals.todos = Some(count_todos(name, token)?);
+        }
+        println!(
+            "{} - suggest: {}\n  {}\n",
+            signals.name_with_owner,
+            signals.suggestion(),
+            signals.describe(date_format)
+        );
+    }
+    Ok(())
+}
+
+/// Files in the repo's default branch that contain TODO or FIXME.
+fn count_todos(name_with_owner: &str, token: Option<&str>) -> Result<u64> {
+    let output = gh(token)
+        .args([
+            "api",
+            "-X",
+            "GET",
+            "search/code",
+            "-f",
+            &format!("q=TODO OR FIXME repo:{name_with_owner}"),
+            "--jq",
+            ".total_count",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to search {name_with_owner} for TODOs: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Unexpected search result from gh")
+}
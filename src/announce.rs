@@ -0,0 +1,169 @@
+//! A final "This repository is now archived" post, made right before
+//! archiving as the last write the repo receives, so watchers and visitors
+//! learn why and where to go instead.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::{backend::Gh, gh, graphql, ssh::quote};
+
+pub const TITLE: &str = "This repository is now archived";
+
+/// Discussion category used when the repo has one by this name
+const CATEGORY: &str = "Announcements";
+
+const CREATE_DISCUSSION: &str = "mutation($repo: ID!, $category: ID!, $title: String!, $body: String!) { \
+     createDiscussion(input: {repositoryId: $repo, categoryId: $category, title: $title, body: $body}) \
+     { discussion { url } } }";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// An issue, pinned to the top of the issue list
+    #[default]
+    Issue,
+    /// A discussion, in the "Announcements" category if there is one
+    Discussion,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Issue => "issue",
+            Self::Discussion => "discussion",
+        })
+    }
+}
+
+/// What to post and where. The template may use `{name}` and `{date}`
+/// (today, as YYYY-MM-DD).
+#[derive(Debug, Clone)]
+pub struct Announcement {
+    pub kind: Kind,
+    pub template: String,
+}
+
+impl Announcement {
+    fn body(&self, name_with_owner: &str) -> String {
+        let name = name_with_owner
+            .split_once('/')
+            .map_or(name_with_owner, |(_, name)| name);
+        self.template
+            .replace("{name}", name)
+            .replace("{date}", &Utc::now().format("%Y-%m-%d").to_string())
+    }
+
+    pub fn post(&self, name_with_owner: &str, token: Option<&str>) -> Result<()> {
+        let body = self.body(name_with_owner);
+        match self.kind {
+            Kind::Issue => post_issue(name_with_owner, &body, token),
+            Kind::Discussion => post_discussion(name_with_owner, &body, token),
+        }
+        .with_context(|| format!("Failed to post the announcement {}", self.kind))
+    }
+
+    /// Shell commands that post the announcement, for `apply-plan --script`.
+    pub fn script(&self, name_with_owner: &str) -> String {
+        let body = quote(&self.body(name_with_owner));
+        let title = quote(TITLE);
+        match self.kind {
+            Kind::Issue => format!(
+                "url=$(gh issue create --repo {name_with_owner} --title {title} --body {body})\n\
+                 gh issue pin \"$url\"\n"
+            ),
+            Kind::Discussion => {
+                let (owner, name) = name_with_owner.split_once('/').unwrap_or_default();
+                let query = format!(
+                    "query {{ repository(owner: \"{owner}\", name: \"{name}\") \
+                     {{ id discussionCategories(first: 25) {{ nodes {{ id name }} }} }} }}"
+                );
+                format!(
+                    "repo=$(gh api graphql -f query={query} --jq .data.repository)\n\
+                     category=$(echo \"$repo\" | jq -r '.discussionCategories.nodes \
+                     | (map(select(.name == \"{CATEGORY}\")) + .)[0].id')\n\
+                     gh api graphql -f query={mutation} -f repo=\"$(echo \"$repo\" | jq -r .id)\" \
+                     -f category=\"$category\" -f title={title} -f body={body}\n",
+                    query = quote(&query),
+                    mutation = quote(CREATE_DISCUSSION),
+                )
+            }
+        }
+    }
+}
+
+fn post_issue(name_with_owner: &str, body: &str, token: Option<&str>) -> Result<()> {
+    let output = gh(token)
+        .args([
+            "issue",
+            "create",
+            "--repo",
+            name_with_owner,
+            "--title",
+            TITLE,
+            "--body",
+            body,
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let output = gh(token)
+        .args(["issue", "pin", &url])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "{url} was created but not pinned: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn post_discussion(name_with_owner: &str, body: &str, token: Option<&str>) -> Result<()> {
+    let repo = graphql::per_repo(
+        &[name_with_owner],
+        "id hasDiscussionsEnabled discussionCategories(first: 25) { nodes { id name } }",
+        &Gh::new(token.map(str::to_string)),
+    )?
+    .pop()
+    .unwrap_or_default();
+
+    if repo["hasDiscussionsEnabled"].as_bool() != Some(true) {
+        anyhow::bail!("discussions aren't enabled");
+    }
+    let categories = repo["discussionCategories"]["nodes"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let category = categories
+        .iter()
+        .find(|c| c["name"] == CATEGORY)
+        .or_else(|| categories.first())
+        .and_then(|c| c["id"].as_str())
+        .context("the repo has no discussion categories")?;
+    let repo_id = repo["id"].as_str().unwrap_or_default();
+
+    let output = gh(token)
+        .args(["api", "graphql"])
+        .args(["-f", &format!("query={CREATE_DISCUSSION}")])
+        .args(["-f", &format!("repo={repo_id}")])
+        .args(["-f", &format!("category={category}")])
+        .args(["-f", &format!("title={TITLE}")])
+        .args(["-f", &format!("body={body}")])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
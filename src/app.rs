@@ -0,0 +1,1004 @@
+//! The TUI's state: the repo table, what's selected, which screen is up,
+//! and the confirm modal's checks, with the methods key handling and
+//! drawing go through.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ratatui::{prelude::*, widgets::TableState};
+use serde::Deserialize;
+use std::{
+    path::Path,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    challenge::{self, Challenge},
+    compare::{Decision, Sheet},
+    dates::DateFormat,
+    description,
+    diagnostics::Diagnostics,
+    pipeline::{Action, ArchiveJob, Pools},
+    plan,
+    preflight::{self, DiskCheck},
+    repo::Repo,
+    report::{Report, Stopwatch},
+    session::Session,
+    stats::Snapshot,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Name,
+    Created,
+    Pushed,
+    Description,
+    Notes,
+    /// Default branch CI status
+    Ci,
+    /// How long archiving took, or has been going; added while archiving
+    Time,
+}
+
+impl Column {
+    pub const DEFAULT: [Self; 4] = [Self::Name, Self::Created, Self::Pushed, Self::Description];
+
+    pub const fn header(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Created => "Created",
+            Self::Pushed => "Last Push",
+            Self::Description => "Description",
+            Self::Notes => "Notes",
+            Self::Ci => "CI",
+            Self::Time => "Time",
+        }
+    }
+
+    pub const fn constraint(self) -> Constraint {
+        match self {
+            Self::Name => Constraint::Length(30),
+            Self::Created | Self::Pushed => Constraint::Length(12),
+            Self::Description | Self::Notes => Constraint::Min(20),
+            Self::Ci => Constraint::Length(4),
+            Self::Time => Constraint::Length(8),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum RepoStatus {
+    Idle,
+    Pending,
+    /// Backing up, with how many of the repo's steps are done; it's only
+    /// archived once this succeeds
+    BackingUp(usize),
+    /// With how many of the repo's steps are done
+    Archiving(usize),
+    /// Closing open issues and pull requests, after `done` steps
+    Closing {
+        done: usize,
+        closed: usize,
+        total: usize,
+    },
+    Done,
+    Failed(String),
+}
+
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "independent UI state and run settings"
+)]
+pub struct App {
+    pub repos: Vec<Repo>,
+    pub statuses: Vec<RepoStatus>,
+    pub state: TableState,
+    pub selected: Vec<bool>,
+    pub mode: Mode,
+    pub dry_run: bool,
+    pub columns: Vec<Column>,
+    pub date_format: DateFormat,
+    /// `owner/name` of the git checkout we were launched from, if any
+    current_repo: Option<String>,
+    pub job: ArchiveJob,
+    /// Worker pools of the batch being archived
+    pub pools: Option<Pools>,
+    /// When the next chunk starts, while pausing between chunks
+    pub cool_down_until: Option<Instant>,
+    /// Free space check for the batch in the confirm modal
+    pub disk_check: Option<DiskCheck>,
+    /// Repos deselected for lack of admin permission when the confirm modal
+    /// opened, or why the check failed; `None` if it wasn't run
+    pub permission_check: Option<Result<usize, String>>,
+    /// Repos the `--compare` sheet marks "archive" that aren't candidates;
+    /// `None` when not comparing
+    pub sheet_missing: Option<Vec<String>>,
+    /// The CSV import screen
+    pub import: Import,
+    /// The description editing screen
+    pub describe: Describe,
+    /// The order screen, and the order the batch gets archived in
+    pub order: Order,
+    /// What the user typed to confirm deleting repos
+    pub delete_confirmation: String,
+    /// Confirming a deletion takes typing each repo's name (`--delete`)
+    /// rather than a phrase
+    pub confirm_names: bool,
+    /// How many of the repos' names have been typed so far
+    pub names_confirmed: usize,
+    /// The code a big or cross-owner deletion also takes, or why it
+    /// couldn't be written; `None` when none is needed
+    pub challenge: Option<Result<Challenge, String>>,
+    /// What was typed of the code
+    pub code_typed: String,
+    /// Whether keys go to the delete confirmation rather than the modal's
+    /// buttons; Tab moves between them
+    pub typing: bool,
+    /// Deleting more repos than this takes a code (`--delete-code-over`)
+    pub code_limit: usize,
+    /// Who is logged in, looked up the first time a deletion needs it
+    viewer: Option<String>,
+    /// The confirm modal's lookups, while they run
+    pub checking: Option<JoinHandle<Lookup>>,
+    /// Repo counts from earlier runs, for the stats view
+    pub history: Vec<Snapshot>,
+    /// Repo counts for this run, kept up to date as repos get archived
+    pub snapshot: Option<Snapshot>,
+    /// Queue confirmed repos in the plan instead of archiving them
+    pub offline: bool,
+    /// Queue confirmed repos in a plan that needs a second person's approval
+    pub require_approval: bool,
+    /// What each dry-run batch predicted, printed on exit
+    pub dry_run_report: Vec<String>,
+    /// The repos archived or deleted so far, or that failed to be, saved on
+    /// exit
+    pub report: Report,
+    /// When each repo of the batch being archived started
+    pub stopwatch: Stopwatch,
+    /// When the batch being archived started
+    pub batch_started: Option<Instant>,
+    /// Where key presses come from, and whether they're recorded
+    pub session: Session,
+    /// Timings for the `F12` overlay
+    pub diagnostics: Diagnostics,
+    /// The `L` legend of status glyphs, row colors and name markers is open
+    pub legend: bool,
+    pub refresh: Refresh,
+    spinner_tick: usize,
+    last_tick: Instant,
+    pub modal_button: usize, // 0 = Cancel, 1 = Continue
+}
+
+/// How often the table redraws.
+#[derive(Clone, Copy)]
+pub struct Refresh {
+    /// Longest wait for a key press between frames
+    pub poll: Duration,
+    pub spinner: Duration,
+    /// Slow both down to [`Refresh::LOW_POWER`] while idle
+    pub low_power: bool,
+}
+
+impl Refresh {
+    pub const DEFAULT: Self = Self {
+        poll: Duration::from_millis(50),
+        spinner: Duration::from_millis(80),
+        low_power: false,
+    };
+    const LOW_POWER: Duration = Duration::from_millis(500);
+
+    pub fn poll(self, idle: bool) -> Duration {
+        if self.low_power && idle {
+            Self::LOW_POWER.max(self.poll)
+        } else {
+            self.poll
+        }
+    }
+
+    fn spinner(self, idle: bool) -> Duration {
+        if self.low_power && idle {
+            Self::LOW_POWER.max(self.spinner)
+        } else {
+            self.spinner
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    Selecting,
+    Import,
+    Describe,
+    Stats,
+    ConfirmModal,
+    Order,
+    Archiving,
+    Done,
+}
+
+/// State of the CSV import screen.
+#[derive(Default)]
+pub struct Import {
+    pub path: String,
+    /// Outcome of the last import: the report, or why the file couldn't be read
+    pub report: Option<Result<ImportReport, String>>,
+}
+
+/// State of the description editing screen.
+#[derive(Default)]
+pub struct Describe {
+    pub edit: description::Edit,
+    /// Repos whose descriptions couldn't be changed, once the edit was applied
+    pub failed: Option<Vec<String>>,
+}
+
+/// What the confirm modal looks up in the background before the batch can
+/// start.
+pub struct Lookup {
+    /// Indices of the repos that were selected
+    selected: Vec<usize>,
+    /// The user's permission on each of them, unless it wasn't checked
+    permissions: Option<Result<Vec<Option<String>>, String>>,
+    /// Who is logged in, when a deletion needs it
+    viewer: Option<String>,
+}
+
+/// The order confirmed repos get archived in, set on the order screen.
+#[derive(Default)]
+pub struct Order {
+    /// Indices of the selected repos, first to be archived first
+    pub repos: Vec<usize>,
+    /// Position of the highlighted repo in `repos`
+    pub cursor: usize,
+}
+
+impl Order {
+    /// Swap the highlighted repo with the one `offset` places away, keeping
+    /// it highlighted.
+    fn shift(&mut self, offset: isize) {
+        let Some(to) = self
+            .cursor
+            .checked_add_signed(offset)
+            .filter(|&to| to < self.repos.len())
+        else {
+            return;
+        };
+        self.repos.swap(self.cursor, to);
+        self.cursor = to;
+    }
+
+    pub fn move_cursor(&mut self, offset: isize) {
+        if let Some(to) = self
+            .cursor
+            .checked_add_signed(offset)
+            .filter(|&to| to < self.repos.len())
+        {
+            self.cursor = to;
+        }
+    }
+}
+
+pub struct ImportReport {
+    pub loaded: usize,
+    /// Rows naming repos that aren't in the list
+    pub unknown: Vec<String>,
+    /// Bare names that several repos in the list have, which are left alone
+    pub ambiguous: Vec<String>,
+}
+
+pub const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+impl App {
+    pub fn new(
+        repos: Vec<Repo>,
+        dry_run: bool,
+        columns: Vec<Column>,
+        date_format: DateFormat,
+        current_repo: Option<String>,
+        job: ArchiveJob,
+    ) -> Self {
+        let len = repos.len();
+        let action = job.action;
+        let mut state = TableState::default();
+        if !repos.is_empty() {
+            state.select(Some(0));
+        }
+        Self {
+            repos,
+            statuses: vec![RepoStatus::Idle; len],
+            state,
+            selected: vec![false; len],
+            mode: Mode::Selecting,
+            dry_run,
+            columns,
+            date_format,
+            current_repo,
+            job,
+            pools: None,
+            cool_down_until: None,
+            disk_check: None,
+            permission_check: None,
+            sheet_missing: None,
+            import: Import::default(),
+            describe: Describe::default(),
+            order: Order::default(),
+            delete_confirmation: String::new(),
+            confirm_names: false,
+            names_confirmed: 0,
+            challenge: None,
+            code_typed: String::new(),
+            typing: false,
+            code_limit: challenge::DEFAULT_LIMIT,
+            viewer: None,
+            checking: None,
+            history: Vec::new(),
+            snapshot: None,
+            offline: false,
+            require_approval: false,
+            dry_run_report: Vec::new(),
+            report: Report::new(action),
+            stopwatch: Stopwatch::default(),
+            batch_started: None,
+            session: Session::default(),
+            diagnostics: Diagnostics::default(),
+            legend: false,
+            refresh: Refresh::DEFAULT,
+            spinner_tick: 0,
+            last_tick: Instant::now(),
+            modal_button: 1, // Default to "Continue"
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.repos.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => (i + 1) % self.repos.len(),
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.repos.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    self.repos.len() - 1
+                } else {
+                    i - 1
+                }
+            }
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn toggle_selection(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.selected[i] = !self.selected[i] && self.repos[i].selectable();
+        }
+    }
+
+    /// Mark or unmark the highlighted repo as high priority, selecting it
+    /// when marked.
+    pub fn toggle_priority(&mut self) {
+        if let Some(i) = self.state.selected() {
+            if !self.repos[i].selectable() {
+                return;
+            }
+            self.repos[i].priority = !self.repos[i].priority;
+            if self.repos[i].priority {
+                self.selected[i] = true;
+            }
+        }
+    }
+
+    /// What the screen shows, in a line that a replay can compare.
+    pub fn session_state(&self) -> String {
+        let failed = self
+            .statuses
+            .iter()
+            .filter(|s| matches!(s, RepoStatus::Failed(_)))
+            .count();
+        format!(
+            "{:?}: {} repos, {} selected, {failed} failed, row {}",
+            self.mode,
+            self.repos.len(),
+            self.selected_count(),
+            self.state.selected().map_or(0, |i| i + 1),
+        )
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.selected.iter().filter(|&&s| s).count()
+    }
+
+    pub fn is_current(&self, i: usize) -> bool {
+        self.current_repo
+            .as_deref()
+            .is_some_and(|current| self.repos[i].name_with_owner.eq_ignore_ascii_case(current))
+    }
+
+    /// The selected repo that is checked out in the working directory, if any.
+    pub fn selected_current(&self) -> Option<&Repo> {
+        (0..self.repos.len())
+            .find(|&i| self.selected[i] && self.is_current(i))
+            .map(|i| &self.repos[i])
+    }
+
+    /// Open the confirm modal, which can't start the batch until the checks
+    /// looking things up in the background are in.
+    pub fn open_confirm_modal(&mut self) {
+        self.delete_confirmation.clear();
+        self.names_confirmed = 0;
+        self.code_typed.clear();
+        self.typing = self.takes_typing();
+        self.permission_check = None;
+        self.challenge = None;
+        self.disk_check = None;
+        self.reset_order();
+        self.mode = Mode::ConfirmModal;
+        self.checking = self.start_checks();
+        if self.checking.is_none() {
+            self.finish_checks(None);
+        }
+    }
+
+    /// Take the background checks' results once they're in, unless the
+    /// modal was closed in the meantime.
+    pub fn poll_checks(&mut self) {
+        if !self.checking.as_ref().is_some_and(JoinHandle::is_finished) {
+            return;
+        }
+        let lookup = self.checking.take().map(JoinHandle::join);
+        if self.mode != Mode::ConfirmModal {
+            return;
+        }
+        self.finish_checks(lookup.map(|joined| {
+            joined.unwrap_or_else(|_| Lookup {
+                selected: Vec::new(),
+                permissions: Some(Err("The permission check panicked".to_string())),
+                viewer: None,
+            })
+        }));
+    }
+
+    /// Apply what was looked up, then run the checks that don't look
+    /// anything up.
+    fn finish_checks(&mut self, lookup: Option<Lookup>) {
+        if let Some(lookup) = lookup {
+            if lookup.viewer.is_some() {
+                self.viewer = lookup.viewer;
+            }
+            self.permission_check = match lookup.permissions {
+                Some(Ok(permissions)) => {
+                    Some(Ok(self.apply_permissions(&lookup.selected, permissions)))
+                }
+                Some(Err(e)) => Some(Err(e)),
+                None => None,
+            };
+        }
+        self.challenge = self.challenge();
+        self.disk_check = self.disk_check();
+        // Make the user move to PROCEED deliberately when the batch includes
+        // the repo they are working in
+        self.modal_button = usize::from(self.can_proceed() && self.selected_current().is_none());
+        // Without the repos the permission check deselected
+        self.reset_order();
+    }
+
+    /// Table order, until the order screen changes it.
+    fn reset_order(&mut self) {
+        self.order = Order {
+            repos: (0..self.repos.len())
+                .filter(|&i| self.selected[i])
+                .collect(),
+            cursor: 0,
+        };
+        self.prioritize_order();
+    }
+
+    /// Put the smallest repos first, so quick wins finish before the big
+    /// backups and mirrors start.
+    pub fn sort_order_by_size(&mut self) {
+        let repos = &self.repos;
+        self.order.repos.sort_by_key(|&i| repos[i].disk_usage);
+        self.order.cursor = 0;
+        self.prioritize_order();
+    }
+
+    /// Move the high priority repos to the front of the order, keeping the
+    /// order within each group.
+    fn prioritize_order(&mut self) {
+        let repos = &self.repos;
+        self.order.repos.sort_by_key(|&i| !repos[i].priority);
+    }
+
+    /// Move the highlighted repo `offset` places, but never past a repo of
+    /// the other priority.
+    pub fn shift_in_order(&mut self, offset: isize) {
+        let order = &self.order;
+        let crosses = order
+            .cursor
+            .checked_add_signed(offset)
+            .and_then(|to| order.repos.get(to))
+            .is_some_and(|&to| {
+                self.repos[to].priority != self.repos[order.repos[order.cursor]].priority
+            });
+        if !crosses {
+            self.order.shift(offset);
+        }
+    }
+
+    /// Mark or unmark the highlighted repo of the order as high priority,
+    /// keeping it highlighted as it moves.
+    pub fn toggle_priority_in_order(&mut self) {
+        let Some(&i) = self.order.repos.get(self.order.cursor) else {
+            return;
+        };
+        self.repos[i].priority = !self.repos[i].priority;
+        self.prioritize_order();
+        self.order.cursor = self
+            .order
+            .repos
+            .iter()
+            .position(|&j| j == i)
+            .unwrap_or_default();
+    }
+
+    /// Start looking up the user's permission on the selected repos, and who
+    /// they are if a deletion needs it, on a thread of its own; `None` if
+    /// there's nothing to look up.
+    fn start_checks(&self) -> Option<JoinHandle<Lookup>> {
+        let github = preflight::checks_permissions(&self.job);
+        let permissions = github && !self.offline;
+        let viewer = github && self.takes_typing() && self.viewer.is_none();
+        if !permissions && !viewer {
+            return None;
+        }
+        let selected: Vec<usize> = (0..self.repos.len())
+            .filter(|&i| self.selected[i])
+            .collect();
+        let names: Vec<String> = selected
+            .iter()
+            .map(|&i| self.repos[i].name_with_owner.clone())
+            .collect();
+        let github = self.job.github.clone();
+        Some(thread::spawn(move || {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            Lookup {
+                permissions: permissions.then(|| {
+                    preflight::fetch_permissions(&names, github.as_ref())
+                        .map_err(|e| format!("{e:#}"))
+                }),
+                viewer: viewer
+                    .then(|| plan::viewer_login(github.as_ref()).ok())
+                    .flatten(),
+                selected,
+            }
+        }))
+    }
+
+    /// Record the user's permission on each repo and deselect the ones they
+    /// can't archive. Returns how many were deselected.
+    fn apply_permissions(&mut self, selected: &[usize], permissions: Vec<Option<String>>) -> usize {
+        let mut deselected = 0;
+        for (&i, permission) in selected.iter().zip(permissions) {
+            // Repos that vanished can't be archived either
+            self.repos[i].permission = Some(permission.unwrap_or_else(|| "NONE".to_string()));
+            if self.repos[i].lacks_admin() {
+                self.selected[i] = false;
+                deselected += 1;
+            }
+        }
+        deselected
+    }
+
+    /// What has to be typed before repos get deleted: the next repo's
+    /// `owner/name` with `--delete`, empty once all are typed.
+    pub fn delete_phrase(&self) -> String {
+        if self.confirm_names {
+            self.order
+                .repos
+                .get(self.names_confirmed)
+                .map(|&i| self.repos[i].name_with_owner.clone())
+                .unwrap_or_default()
+        } else {
+            format!("delete {} forks", self.selected_count())
+        }
+    }
+
+    /// Issue a code if deleting the selected repos takes one.
+    fn challenge(&self) -> Option<Result<Challenge, String>> {
+        if self.job.action != Action::Delete || self.dry_run || self.job.demo {
+            return None;
+        }
+        let names: Vec<&str> = (0..self.repos.len())
+            .filter(|&i| self.selected[i])
+            .map(|i| self.repos[i].name_with_owner.as_str())
+            .collect();
+        let reason = challenge::needed(&names, self.viewer.as_deref(), self.code_limit)?;
+        Some(Challenge::issue(reason).map_err(|e| format!("{e:#}")))
+    }
+
+    /// Whether the confirm modal has a delete confirmation to type.
+    pub fn takes_typing(&self) -> bool {
+        self.job.action == Action::Delete && !self.dry_run
+    }
+
+    /// What the modal's keys do, for its help lines.
+    pub fn confirm_help(&self) -> &'static str {
+        if self.typing {
+            "Type to confirm | Tab/Enter: Buttons | Esc: Cancel"
+        } else if self.takes_typing() {
+            "←/→: Switch | Tab: Type | Enter: Select | o: Order | Esc: Cancel"
+        } else {
+            "←/→ or Tab: Switch | Enter: Select | o: Order | Esc: Cancel"
+        }
+    }
+
+    /// Whether the phrase or every name has been typed.
+    pub fn phrase_typed(&self) -> bool {
+        if self.confirm_names {
+            self.names_confirmed >= self.order.repos.len()
+        } else {
+            self.delete_confirmation == self.delete_phrase()
+        }
+    }
+
+    fn delete_confirmed(&self) -> bool {
+        self.phrase_typed()
+            && self
+                .challenge
+                .as_ref()
+                .is_none_or(|c| c.as_ref().is_ok_and(|c| c.matches(&self.code_typed)))
+    }
+
+    /// Whether typing goes to the code, once the phrase is typed.
+    pub fn typing_code(&self) -> bool {
+        self.phrase_typed() && matches!(self.challenge, Some(Ok(_)))
+    }
+
+    /// Take a key typed into the delete confirmation, moving on to the next
+    /// repo's name once one is typed in full, and to the code once all are.
+    pub fn type_delete_confirmation(&mut self, c: Option<char>) {
+        if self.typing_code() && (c.is_some() || !self.code_typed.is_empty()) {
+            if let Some(c) = c {
+                self.code_typed.push(c);
+            } else {
+                self.code_typed.pop();
+            }
+            return;
+        }
+        if let Some(c) = c {
+            self.delete_confirmation.push(c);
+        } else {
+            self.delete_confirmation.pop();
+        }
+        if self.confirm_names
+            && !self.phrase_typed()
+            && self.delete_confirmation == self.delete_phrase()
+        {
+            self.names_confirmed += 1;
+            self.delete_confirmation.clear();
+        }
+    }
+
+    /// Whether the confirm modal allows starting the batch at all.
+    pub fn can_proceed(&self) -> bool {
+        self.checking.is_none()
+            && self.selected_count() > 0
+            && (self.job.action != Action::Delete || self.dry_run || self.delete_confirmed())
+            && !self
+                .disk_check
+                .as_ref()
+                .is_some_and(DiskCheck::insufficient)
+    }
+
+    /// Compare the space the selected repos' backups need with what is free.
+    fn disk_check(&self) -> Option<DiskCheck> {
+        if self.dry_run || self.queues() {
+            return None;
+        }
+        let sizes = (0..self.repos.len())
+            .filter(|&i| self.selected[i])
+            .map(|i| self.repos[i].disk_usage);
+        preflight::disk_check(&self.job, sizes)
+    }
+
+    /// Nothing on screen moves unless repos are being archived or the
+    /// confirm modal is checking them.
+    pub fn idle(&self) -> bool {
+        self.mode != Mode::Archiving && self.checking.is_none()
+    }
+
+    pub fn tick_spinner(&mut self) {
+        if self.last_tick.elapsed() >= self.refresh.spinner(self.idle()) {
+            self.spinner_tick = (self.spinner_tick + 1) % SPINNER_FRAMES.len();
+            self.last_tick = Instant::now();
+        }
+    }
+
+    pub fn spinner(&self) -> &'static str {
+        SPINNER_FRAMES[self.spinner_tick]
+    }
+
+    pub fn mark_selected_as_pending(&mut self) {
+        for (i, selected) in self.selected.iter().enumerate() {
+            if *selected {
+                self.statuses[i] = RepoStatus::Pending;
+            }
+        }
+    }
+
+    pub fn is_all_done(&self) -> bool {
+        self.statuses.iter().enumerate().all(|(i, status)| {
+            !self.selected[i] || matches!(status, RepoStatus::Done | RepoStatus::Failed(_))
+        })
+    }
+
+    /// Confirmed repos go into a plan instead of being archived right away.
+    pub const fn queues(&self) -> bool {
+        self.offline || self.require_approval
+    }
+
+    pub fn open_import(&mut self) {
+        self.import = Import::default();
+        self.mode = Mode::Import;
+    }
+
+    pub fn open_describe(&mut self) {
+        self.describe = Describe {
+            edit: self.job.description_edit.clone().unwrap_or_default(),
+            failed: None,
+        };
+        self.mode = Mode::Describe;
+    }
+
+    /// Change the selected repos' descriptions right away.
+    pub fn edit_descriptions(&mut self) {
+        let edit = &self.describe.edit;
+        let mut failed = Vec::new();
+        for (i, repo) in self.repos.iter_mut().enumerate() {
+            if !self.selected[i] {
+                continue;
+            }
+            let new = edit.apply(&repo.name_with_owner, repo.description.as_deref());
+            if self.job.demo {
+                repo.description = Some(new);
+                continue;
+            }
+            match description::set(&repo.name_with_owner, &new, self.job.token.as_deref()) {
+                Ok(()) => repo.description = Some(new),
+                Err(e) => failed.push(format!("{}: {e:#}", repo.name_with_owner)),
+            }
+        }
+        self.describe.failed = Some(failed);
+    }
+
+    /// Load decisions and notes from the CSV at `import.path`: listed repos are
+    /// selected if marked "archive" and deselected otherwise.
+    pub fn import_sheet(&mut self) {
+        let sheet = match Sheet::load(Path::new(self.import.path.trim())) {
+            Ok(sheet) => sheet,
+            Err(e) => {
+                self.import.report = Some(Err(format!("{e:#}")));
+                return;
+            }
+        };
+
+        let mut loaded = 0;
+        for i in 0..self.repos.len() {
+            if let Some(entry) = sheet.entry(&self.repos[i], &self.repos) {
+                let repo = &mut self.repos[i];
+                self.selected[i] = entry.decision == Decision::Archive && repo.selectable();
+                repo.note.clone_from(&entry.note);
+                loaded += 1;
+            }
+        }
+        if self.repos.iter().any(|r| r.note.is_some()) && !self.columns.contains(&Column::Notes) {
+            self.columns.push(Column::Notes);
+        }
+
+        self.import.report = Some(Ok(ImportReport {
+            loaded,
+            unknown: sheet.unknown(&self.repos),
+            ambiguous: sheet.ambiguous(&self.repos),
+        }));
+    }
+
+    pub fn remove_archived_and_reset(&mut self) {
+        // Keep only repos that were not successfully archived
+        let mut new_repos = Vec::new();
+        let mut new_statuses = Vec::new();
+        let mut new_selected = Vec::new();
+
+        for i in 0..self.repos.len() {
+            if self.statuses[i] != RepoStatus::Done {
+                new_repos.push(Repo {
+                    took: None,
+                    ..self.repos[i].clone()
+                });
+                new_statuses.push(RepoStatus::Idle);
+                new_selected.push(false);
+            }
+        }
+
+        self.repos = new_repos;
+        self.statuses = new_statuses;
+        self.selected = new_selected;
+
+        // Reset table selection
+        if self.repos.is_empty() {
+            self.state.select(None);
+        } else {
+            self.state.select(Some(0));
+        }
+
+        // Reset modal button
+        self.modal_button = 1;
+        self.pools = None;
+        self.batch_started = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        access, backend, backup,
+        config::Config,
+        pipeline::{DEFAULT_ARCHIVE_WORKERS, DEFAULT_BACKUP_WORKERS},
+        provider,
+    };
+    use std::sync::Arc;
+
+    /// The confirm modal for deleting `me/a` and `me/b`, as `me`, off GitHub
+    /// so nothing is looked up.
+    fn deleting(confirm_names: bool) -> App {
+        let job = ArchiveJob {
+            action: Action::Delete,
+            transfer_to: None,
+            access_review: false,
+            team_summary: false,
+            revoke: access::Revoke::default(),
+            token: None,
+            github: Arc::new(backend::Gh::new(None)),
+            backup_dir: None,
+            backup_as: backup::Format::default(),
+            org_settings: None,
+            export_wiki: false,
+            export_issues: false,
+            migrate_to: None,
+            bw_limit: None,
+            encrypt_to: None,
+            backup_workers: DEFAULT_BACKUP_WORKERS,
+            archive_workers: DEFAULT_ARCHIVE_WORKERS,
+            chunking: None,
+            ssh: None,
+            description_edit: None,
+            topics: Vec::new(),
+            readme_notice: None,
+            close_items: None,
+            stamp: None,
+            disable_features: false,
+            announcement: None,
+            provider: provider::new(
+                true,
+                None,
+                provider::Kind::GitLab,
+                Arc::new(backend::Gh::new(None)),
+                &Config::default(),
+            )
+            .unwrap(),
+            forge: provider::Kind::GitLab,
+            demo: false,
+        };
+        let repos = ["me/a", "me/b"]
+            .map(|name| Repo {
+                name_with_owner: name.to_string(),
+                ..Repo::default()
+            })
+            .to_vec();
+        let mut app = App::new(
+            repos,
+            false,
+            Column::DEFAULT.to_vec(),
+            DateFormat::Iso,
+            None,
+            job,
+        );
+        app.selected = vec![true; 2];
+        app.viewer = Some("me".to_string());
+        app.confirm_names = confirm_names;
+        app.open_confirm_modal();
+        app
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.type_delete_confirmation(Some(c));
+        }
+    }
+
+    #[test]
+    fn only_the_whole_phrase_unlocks_deletion() {
+        let mut app = deleting(false);
+
+        type_text(&mut app, "delete 2 fork");
+        assert!(!app.can_proceed(), "partial phrase");
+        type_text(&mut app, "z");
+        assert!(!app.can_proceed(), "wrong phrase");
+        app.type_delete_confirmation(None);
+        type_text(&mut app, "s");
+        assert!(app.can_proceed());
+    }
+
+    #[test]
+    fn each_name_has_to_be_typed_in_full() {
+        let mut app = deleting(true);
+
+        type_text(&mut app, "me/");
+        assert_eq!(app.names_confirmed, 0);
+        type_text(&mut app, "b");
+        assert_eq!(app.names_confirmed, 0, "names go in order");
+        app.type_delete_confirmation(None);
+        type_text(&mut app, "a");
+        assert_eq!(app.names_confirmed, 1);
+        assert!(!app.can_proceed(), "one name of two");
+        type_text(&mut app, "me/b");
+        assert!(app.phrase_typed());
+        assert!(app.can_proceed());
+    }
+
+    #[test]
+    fn a_wrong_or_partial_code_doesnt_unlock_deletion() {
+        let mut app = deleting(true);
+        app.challenge = Some(Ok(Challenge::unwritten("123456")));
+
+        type_text(&mut app, "me/ame/b");
+        assert!(app.typing_code());
+        assert!(!app.can_proceed(), "names without the code");
+        type_text(&mut app, "12345");
+        assert!(!app.can_proceed(), "partial code");
+        type_text(&mut app, "7");
+        assert!(!app.can_proceed(), "wrong code");
+        app.type_delete_confirmation(None);
+        type_text(&mut app, "6");
+        assert!(app.can_proceed());
+    }
+
+    #[test]
+    fn a_code_that_couldnt_be_written_never_unlocks_deletion() {
+        let mut app = deleting(true);
+        app.challenge = Some(Err("No state directory".to_string()));
+
+        type_text(&mut app, "me/ame/b");
+        assert!(app.phrase_typed());
+        assert!(!app.typing_code());
+        assert!(!app.can_proceed());
+    }
+
+    #[test]
+    fn reopening_the_modal_after_esc_starts_over() {
+        let mut app = deleting(true);
+        app.challenge = Some(Ok(Challenge::unwritten("123456")));
+        type_text(&mut app, "me/ame/b12");
+        assert_eq!(app.names_confirmed, 2);
+        assert_eq!(app.code_typed, "12");
+
+        // Esc goes back to the table; the modal opens afresh next time
+        app.mode = Mode::Selecting;
+        app.open_confirm_modal();
+
+        assert_eq!(app.names_confirmed, 0);
+        assert!(app.delete_confirmation.is_empty());
+        assert!(app.code_typed.is_empty());
+        assert!(!app.can_proceed());
+    }
+}
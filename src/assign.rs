@@ -0,0 +1,276 @@
+//! Dividing a big cleanup between teammates.
+//!
+//! `repo-archiver assign` splits the queued plan into a plan file per person,
+//! each archived with `apply-plan --plan FILE` by whoever it was given to,
+//! and with `--issue-repo` opens an issue assigned to each of them listing
+//! their repos. `repo-archiver assignments` shows how far everyone has got by
+//! checking on GitHub which of their repos are archived, so it works wherever
+//! the plans are applied. Assignments are recorded in `assignments.json` in
+//! the state dir.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backend::Backend,
+    gh, graphql, paths,
+    plan::{self, Plan},
+};
+
+#[derive(clap::Args)]
+pub struct AssignArgs {
+    /// GitHub logins to share the queued repos between, each getting an even,
+    /// contiguous share in the plan's order
+    #[arg(long, value_name = "LOGIN", value_delimiter = ',', required = true)]
+    to: Vec<String>,
+
+    /// Plan file to split [default: plan.json in the state dir]
+    #[arg(long)]
+    plan: Option<PathBuf>,
+
+    /// Directory to write each share to, as LOGIN.json [default: assignments/
+    /// in the state dir]
+    #[arg(long, value_name = "DIR")]
+    out: Option<PathBuf>,
+
+    /// Also open an issue in this repo for each person, assigned to them and
+    /// listing their repos
+    #[arg(long, value_name = "OWNER/NAME")]
+    issue_repo: Option<String>,
+}
+
+/// One person's share, as recorded in `assignments.json`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Assignment {
+    pub assignee: String,
+    pub assigned_at: DateTime<Utc>,
+    pub repos: Vec<String>,
+    /// Where their plan was written
+    pub plan: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+}
+
+fn ledger_file() -> Result<PathBuf> {
+    paths::state_dir()
+        .map(|dir| dir.join("assignments.json"))
+        .context("No home directory to keep the assignments in")
+}
+
+fn load_ledger(path: &Path) -> Result<Vec<Assignment>> {
+    match fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .with_context(|| format!("Invalid assignments in {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// `repo-archiver assign`: hand the queued repos out, taking them off the
+/// plan they were queued in.
+pub fn run(args: &AssignArgs, token: Option<&str>) -> Result<()> {
+    let path = args.plan.clone().map_or_else(plan::default_file, Ok)?;
+    let queued = Plan::load(&path)?;
+    if queued.repos.is_empty() {
+        println!("Nothing queued.");
+        return Ok(());
+    }
+    // The shares stay approved, since the approval covered them already; a
+    // plan changed since it was approved mustn't pass that on
+    if queued.review.as_ref().is_some_and(|r| r.approval.is_some()) {
+        if let Some(error) = queued.approval_error() {
+            anyhow::bail!(error);
+        }
+    }
+    let reviewed = queued.review.is_some() || plan::is_reviewed(&path)?;
+    let out = match &args.out {
+        Some(dir) => dir.clone(),
+        None => paths::state_dir()
+            .map(|dir| dir.join("assignments"))
+            .context("No home directory to keep the assignments in")?,
+    };
+    for login in &args.to {
+        let file = out.join(format!("{login}.json"));
+        if file.exists() {
+            anyhow::bail!(
+                "{login} already has an assignment in {}; apply or remove it first",
+                file.display()
+            );
+        }
+    }
+
+    let ledger_path = ledger_file()?;
+    let mut ledger = load_ledger(&ledger_path)?;
+    let mut assigned = Vec::new();
+    for (login, repos) in args.to.iter().zip(shares(&queued, args.to.len())) {
+        if repos.is_empty() {
+            println!("{login}: nothing left to assign");
+            continue;
+        }
+        let share = Plan {
+            repos,
+            review: queued.review.clone(),
+            assignee: Some(login.clone()),
+            steps: Vec::new(),
+        };
+        let file = out.join(format!("{login}.json"));
+        share.save(&file)?;
+        if reviewed {
+            plan::set_reviewed(&file, true)?;
+        }
+
+        let names: Vec<String> = share
+            .repos
+            .iter()
+            .map(|r| r.name_with_owner.clone())
+            .collect();
+        let issue = match &args.issue_repo {
+            Some(repo) => Some(open_issue(repo, login, &names, &file, token)?),
+            None => None,
+        };
+        println!(
+            "{login}: {} repos in {}{}",
+            names.len(),
+            file.display(),
+            issue
+                .as_deref()
+                .map(|url| format!(", {url}"))
+                .unwrap_or_default()
+        );
+        assigned.extend(names.iter().cloned());
+        ledger.push(Assignment {
+            assignee: login.clone(),
+            assigned_at: Utc::now(),
+            repos: names,
+            plan: file,
+            issue,
+        });
+        // Recorded as it goes, so a failure halfway leaves nothing assigned
+        // twice or lost
+        save_ledger(&ledger_path, &ledger)?;
+        Plan::remove(&path, &assigned)?;
+    }
+    Ok(())
+}
+
+/// The plan's repos in `n` even, contiguous shares, the first ones getting
+/// one more when they don't divide evenly.
+fn shares(plan: &Plan, n: usize) -> Vec<Vec<plan::QueuedRepo>> {
+    let (size, extra) = (plan.repos.len() / n, plan.repos.len() % n);
+    let mut repos = plan.repos.iter().cloned();
+    (0..n)
+        .map(|i| repos.by_ref().take(size + usize::from(i < extra)).collect())
+        .collect()
+}
+
+fn save_ledger(path: &Path, ledger: &[Assignment]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(ledger)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Open an issue assigned to `login` listing their repos, returning its URL.
+fn open_issue(
+    repo: &str,
+    login: &str,
+    names: &[String],
+    plan: &Path,
+    token: Option<&str>,
+) -> Result<String> {
+    let mut body = format!("@{login}, these repos are yours to archive:\n\n");
+    for name in names {
+        let _ = writeln!(body, "- [ ] {name}");
+    }
+    let file = plan.file_name().unwrap_or_default().to_string_lossy();
+    let _ = write!(
+        body,
+        "\nArchive them with `repo-archiver apply-plan --plan {file}`, using the plan file \
+         you were given."
+    );
+    let output = gh(token)
+        .args([
+            "issue",
+            "create",
+            "--repo",
+            repo,
+            "--title",
+            &format!("Archive {} repos", names.len()),
+            "--assignee",
+            login,
+            "--body",
+            &body,
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to open an issue for {login}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `repo-archiver assignments`: how many of each person's repos are archived
+/// so far, and which are left.
+pub fn status(github: &dyn Backend) -> Result<()> {
+    let ledger = load_ledger(&ledger_file()?)?;
+    if ledger.is_empty() {
+        println!("Nothing assigned.");
+        return Ok(());
+    }
+    let names: Vec<&str> = ledger
+        .iter()
+        .flat_map(|a| a.repos.iter().map(String::as_str))
+        .collect();
+    let mut archived = graphql::per_repo(&names, "isArchived", github)
+        .context("Failed to look up the assigned repos")?
+        .into_iter()
+        .map(|repo| repo["isArchived"].as_bool());
+
+    let mut done = 0;
+    for assignment in &ledger {
+        let left: Vec<(&String, Option<bool>)> = assignment
+            .repos
+            .iter()
+            .zip(archived.by_ref())
+            .filter(|(_, archived)| *archived != Some(true))
+            .collect();
+        let issue = assignment
+            .issue
+            .as_deref()
+            .map(|url| format!(" ({url})"))
+            .unwrap_or_default();
+        let total = assignment.repos.len();
+        if left.is_empty() {
+            done += 1;
+            println!("✓ {}: all {total} archived{issue}", assignment.assignee);
+            continue;
+        }
+        println!(
+            "  {}: {} of {total} archived{issue}",
+            assignment.assignee,
+            total - left.len()
+        );
+        for (name, archived) in left {
+            let gone = if archived.is_none() {
+                " (not found)"
+            } else {
+                ""
+            };
+            println!("    {name}{gone}");
+        }
+    }
+    println!("{done} of {} assignments done.", ledger.len());
+    Ok(())
+}
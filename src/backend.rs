@@ -0,0 +1,236 @@
+//! How the GitHub provider (see `provider`) reaches GitHub: through `gh`,
+//! the default, or straight to the API with `--backend api` on machines
+//! without `gh`. `api-then-gh` and `gh-then-api` try one and fall back to the
+//! other call by call, for machines where either may be missing or logged
+//! out.
+//!
+//! The API backend shells out to curl (see `http`), and authenticates with `REPO_ARCHIVER_TOKEN`,
+//! `GH_TOKEN` or `GITHUB_TOKEN`. Lookups go through the backend too (the
+//! checks that screen candidates, dry-run predictions, the confirm modal's
+//! permission check, fork status), so `--dry-run` works without `gh`.
+//! Everything else (backups, description edits, the steps before archiving,
+//! `analyze`'s signals) still uses `gh`.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{env, io::Write, process::Stdio, sync::Arc};
+
+use crate::{config::Config, error, gh, http::Client};
+
+/// Where the API backend sends requests, unless `api_url` says otherwise
+const API_URL: &str = "https://api.github.com";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// The `gh` CLI, with its login or the configured token
+    #[default]
+    Gh,
+    /// The GitHub API over HTTPS, with a token
+    Api,
+    /// The API, falling back to `gh` when a call fails
+    ApiThenGh,
+    /// `gh`, falling back to the API when a call fails
+    GhThenApi,
+}
+
+pub trait Backend: Send + Sync {
+    /// Run a GraphQL query with string `variables`, returning its `data`.
+    fn graphql(&self, query: &str, variables: &[(&str, &str)]) -> Result<Value>;
+
+    /// Send `body`, if any, to the REST API's `path` (e.g. `repos/o/r`, with
+    /// any query string), returning the JSON response (`null` if empty).
+    fn rest(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value>;
+
+    fn set_archived(&self, name_with_owner: &str, archived: bool) -> Result<()>;
+}
+
+/// The backend picked with `--backend` or `backend` in the config file.
+///
+/// Without a token, the fallback kinds are just `gh`.
+pub fn new(kind: Kind, config: &Config) -> Result<Arc<dyn Backend>> {
+    let gh = Arc::new(Gh::new(config.token.clone()));
+    match kind {
+        Kind::Gh => Ok(gh),
+        Kind::Api => Ok(Arc::new(Api::new(config)?)),
+        Kind::ApiThenGh | Kind::GhThenApi => {
+            let Ok(api) = Api::new(config) else {
+                return Ok(gh);
+            };
+            let api = Arc::new(api);
+            Ok(Arc::new(if kind == Kind::ApiThenGh {
+                Fallback {
+                    first: ("the API", api),
+                    then: ("gh", gh),
+                }
+            } else {
+                Fallback {
+                    first: ("gh", gh),
+                    then: ("the API", api),
+                }
+            }))
+        }
+    }
+}
+
+pub struct Gh {
+    token: Option<String>,
+}
+
+impl Gh {
+    pub const fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+}
+
+impl Backend for Gh {
+    fn graphql(&self, query: &str, variables: &[(&str, &str)]) -> Result<Value> {
+        let mut cmd = gh(self.token.as_deref());
+        cmd.args(["api", "graphql", "-f", &format!("query={query}")]);
+        for (name, value) in variables {
+            cmd.args(["-f", &format!("{name}={value}")]);
+        }
+        let output = cmd
+            .args(["--jq", ".data"])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        let stdout = error::check(output)?;
+        Ok(serde_json::from_slice(&stdout).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    fn rest(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        let mut cmd = gh(self.token.as_deref());
+        cmd.args(["api", "-X", method, path]);
+        if body.is_some() {
+            cmd.args(["--input", "-"]).stdin(Stdio::piped());
+        }
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run gh CLI. Is it installed?")?;
+        if let (Some(mut stdin), Some(body)) = (child.stdin.take(), body) {
+            stdin.write_all(body.to_string().as_bytes())?;
+        }
+        let stdout = error::check(child.wait_with_output()?)?;
+        if stdout.trim_ascii().is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&stdout).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    fn set_archived(&self, name_with_owner: &str, archived: bool) -> Result<()> {
+        let command = if archived { "archive" } else { "unarchive" };
+        let output = gh(self.token.as_deref())
+            .args(["repo", command, name_with_owner, "--yes"])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output)?;
+        Ok(())
+    }
+}
+
+pub struct Api {
+    http: Client,
+}
+
+impl Api {
+    fn new(config: &Config) -> Result<Self> {
+        let token = config
+            .token
+            .clone()
+            .or_else(|| env::var("GH_TOKEN").ok())
+            .or_else(|| env::var("GITHUB_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+            .context(
+                "--backend api needs a token: set REPO_ARCHIVER_TOKEN, GH_TOKEN or GITHUB_TOKEN",
+            )?;
+        let url = config.api_url.as_deref().unwrap_or(API_URL);
+        Ok(Self {
+            http: Client::new(url, token, "application/vnd.github+json", config),
+        })
+    }
+}
+
+impl Backend for Api {
+    fn graphql(&self, query: &str, variables: &[(&str, &str)]) -> Result<Value> {
+        let variables: Map<String, Value> = variables
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), json!(value)))
+            .collect();
+        let mut response = self.http.request(
+            "POST",
+            "/graphql",
+            Some(&json!({"query": query, "variables": variables})),
+        )?;
+        if let Some(errors) = response["errors"].as_array().filter(|e| !e.is_empty()) {
+            let messages: Vec<&str> = errors
+                .iter()
+                .filter_map(|e| e["message"].as_str())
+                .collect();
+            return Err(error::Error::from_stderr(
+                format!("GraphQL: {}", messages.join(", ")).as_bytes(),
+            )
+            .into());
+        }
+        Ok(response["data"].take())
+    }
+
+    fn rest(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        self.http.request(method, &format!("/{path}"), body)
+    }
+
+    fn set_archived(&self, name_with_owner: &str, archived: bool) -> Result<()> {
+        self.http.request(
+            "PATCH",
+            &format!("/repos/{name_with_owner}"),
+            Some(&json!({"archived": archived})),
+        )?;
+        Ok(())
+    }
+}
+
+/// Two backends, each named for error messages: every call goes to `first`,
+/// and to `then` if it fails.
+///
+/// Rate limits aren't retried, since GitHub counts both paths against the same
+/// limit and the archive pool backs off on them (see `pipeline`).
+pub struct Fallback {
+    first: (&'static str, Arc<dyn Backend>),
+    then: (&'static str, Arc<dyn Backend>),
+}
+
+impl Fallback {
+    fn call<T>(&self, f: impl Fn(&dyn Backend) -> Result<T>) -> Result<T> {
+        let (first_name, first) = &self.first;
+        let (then_name, then) = &self.then;
+        match f(first.as_ref()) {
+            Err(e) if !matches!(error::Error::find(&e), Some(error::Error::RateLimit(_))) => {
+                f(then.as_ref()).map_err(|then_error| {
+                    then_error.context(format!(
+                        "{then_name} failed too, after {first_name} failed with: {e:#}"
+                    ))
+                })
+            }
+            result => result,
+        }
+    }
+}
+
+impl Backend for Fallback {
+    fn graphql(&self, query: &str, variables: &[(&str, &str)]) -> Result<Value> {
+        self.call(|backend| backend.graphql(query, variables))
+    }
+
+    fn rest(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        self.call(|backend| backend.rest(method, path, body))
+    }
+
+    fn set_archived(&self, name_with_owner: &str, archived: bool) -> Result<()> {
+        self.call(|backend| backend.set_archived(name_with_owner, archived))
+    }
+}
@@ -0,0 +1,417 @@
+//! Local copies of repo data, taken right before a repo is archived.
+//!
+//! Each repo gets its own directory under `--backup-dir`:
+//!
+//! ```text
+//! <backup-dir>/<owner>/<name>/metadata.json
+//! <backup-dir>/<owner>/<name>/social-preview.png
+//! <backup-dir>/<owner>/<name>/mirror.git/ (or repo.bundle)
+//! <backup-dir>/<owner>/<name>/access.json
+//! <backup-dir>/<owner>/<name>/issues.json
+//! <backup-dir>/<owner>/org-settings.json
+//! ```
+//!
+//! `access.json` and `org-settings.json` are only written with
+//! `--export-org-settings`: which teams could reach the repo, and the
+//! organization's defaults that applied to it, once per organization.
+//! `issues.json` is only written with `--export-issues`: every issue and
+//! pull request, open or closed, with its comments.
+//!
+//! The git data is a `git clone --mirror`, which later runs fetch into, or
+//! with `--backup-as bundle` a single-file bundle (see `mirror`).
+//!
+//! With `--encrypt-to`, files are written encrypted with an extra `.age` or
+//! `.gpg` extension, and the git data is always a bundle.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{access, access::TeamAccess, encrypt, encrypt::Recipient, error, gh};
+
+/// How the git data of each repo is kept in the backup dir.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// A bare `mirror.git`, fetched into on later runs
+    #[default]
+    Mirror,
+    /// A single `repo.bundle` file, written again on every run
+    Bundle,
+}
+
+/// Everything needed to restore how a repo presented itself.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    pub name_with_owner: String,
+    pub description: Option<String>,
+    pub homepage_url: Option<String>,
+    pub topics: Vec<String>,
+    pub visibility: String,
+    pub default_branch: Option<String>,
+    pub has_issues_enabled: bool,
+    pub has_wiki_enabled: bool,
+    pub has_projects_enabled: bool,
+    /// File name of the custom social preview image, if the repo had one
+    pub social_preview: Option<String>,
+    pub snapshot_at: DateTime<Utc>,
+}
+
+/// Which teams had access to a repo, and how much.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Access {
+    pub name_with_owner: String,
+    pub teams: Vec<TeamAccess>,
+    pub snapshot_at: DateTime<Utc>,
+}
+
+/// A repo's issues and pull requests, so the discussion outlives the repo.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issues {
+    pub name_with_owner: String,
+    /// Oldest first
+    pub items: Vec<Issue>,
+    pub snapshot_at: DateTime<Utc>,
+}
+
+/// One issue or pull request, trimmed by `--jq` to `ISSUE_FIELDS`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    /// "open" or "closed"
+    pub state: String,
+    pub pull_request: bool,
+    /// Missing for deleted accounts
+    pub author: Option<String>,
+    pub labels: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Conversation comments, then for pull requests the review comments on
+    /// their diffs, each oldest first
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    /// Number of the issue or pull request it's on; implied by where it's
+    /// kept, so not written
+    #[serde(default, skip_serializing)]
+    pub issue: u64,
+    pub author: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    /// File a review comment is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+const ISSUE_FIELDS: &str = ".[] | {number, title, body, state, \
+    pullRequest: (.pull_request != null), author: .user.login, labels: [.labels[].name], \
+    createdAt: .created_at, closedAt: .closed_at}";
+
+const COMMENT_FIELDS: &str = ".[] | {issue: (.issue_url | split(\"/\") | last | tonumber), \
+    author: .user.login, body, createdAt: .created_at}";
+
+const REVIEW_COMMENT_FIELDS: &str = ".[] | \
+    {issue: (.pull_request_url | split(\"/\") | last | tonumber), \
+    author: .user.login, body, createdAt: .created_at, path}";
+
+/// Organization settings that decide who can do what with its repos. GitHub
+/// only shows most of them to owners of the organization.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrgSettings {
+    pub login: String,
+    /// Base permission every member has on every repo
+    pub default_repository_permission: Option<String>,
+    pub members_can_create_repositories: Option<bool>,
+    pub members_can_create_public_repositories: Option<bool>,
+    pub members_can_create_private_repositories: Option<bool>,
+    pub members_can_fork_private_repositories: Option<bool>,
+    pub two_factor_requirement_enabled: Option<bool>,
+    pub web_commit_signoff_required: Option<bool>,
+    /// Not in gh's output; filled in when it's read
+    #[serde(default = "Utc::now")]
+    pub snapshot_at: DateTime<Utc>,
+}
+
+/// `OrgSettings` picked out of `gh api orgs/ORG`.
+const ORG_FIELDS: &str = "{login, \
+    defaultRepositoryPermission: .default_repository_permission, \
+    membersCanCreateRepositories: .members_can_create_repositories, \
+    membersCanCreatePublicRepositories: .members_can_create_public_repositories, \
+    membersCanCreatePrivateRepositories: .members_can_create_private_repositories, \
+    membersCanForkPrivateRepositories: .members_can_fork_private_repositories, \
+    twoFactorRequirementEnabled: .two_factor_requirement_enabled, \
+    webCommitSignoffRequired: .web_commit_signoff_required}";
+
+/// Shape of `gh repo view --json`.
+#[derive(Deserialize)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "mirrors the fields gh returns"
+)]
+#[serde(rename_all = "camelCase")]
+struct RepoView {
+    name_with_owner: String,
+    description: Option<String>,
+    homepage_url: Option<String>,
+    repository_topics: Option<Vec<Topic>>,
+    visibility: String,
+    default_branch_ref: Option<BranchRef>,
+    has_issues_enabled: bool,
+    has_wiki_enabled: bool,
+    has_projects_enabled: bool,
+    open_graph_image_url: String,
+    uses_custom_open_graph_image: bool,
+}
+
+#[derive(Deserialize)]
+struct Topic {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct BranchRef {
+    name: String,
+}
+
+const VIEW_FIELDS: &str = "nameWithOwner,description,homepageUrl,repositoryTopics,visibility,\
+    defaultBranchRef,hasIssuesEnabled,hasWikiEnabled,hasProjectsEnabled,openGraphImageUrl,\
+    usesCustomOpenGraphImage";
+
+/// Directory holding the backup of `name_with_owner`.
+pub fn repo_dir(backup_dir: &Path, name_with_owner: &str) -> PathBuf {
+    name_with_owner
+        .split('/')
+        .fold(backup_dir.to_path_buf(), |dir, part| dir.join(part))
+}
+
+/// Write `metadata.json` (and the custom social preview, if any) for a repo.
+pub fn snapshot_metadata(
+    backup_dir: &Path,
+    name_with_owner: &str,
+    token: Option<&str>,
+    recipient: Option<&Recipient>,
+) -> Result<()> {
+    let output = gh(token)
+        .args(["repo", "view", name_with_owner, "--json", VIEW_FIELDS])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "gh repo view failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let view: RepoView = serde_json::from_slice(&output.stdout)?;
+    let dir = repo_dir(backup_dir, name_with_owner);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    // The generated default preview can be recreated by GitHub, so only
+    // custom uploads are worth keeping
+    let social_preview = if view.uses_custom_open_graph_image {
+        let file_name = "social-preview.png";
+        let image = download(&view.open_graph_image_url)?;
+        encrypt::write(&dir.join(file_name), &image, recipient)?;
+        Some(file_name.to_string())
+    } else {
+        None
+    };
+
+    let metadata = Metadata {
+        name_with_owner: view.name_with_owner,
+        description: view.description.filter(|d| !d.is_empty()),
+        homepage_url: view.homepage_url.filter(|url| !url.is_empty()),
+        topics: view
+            .repository_topics
+            .unwrap_or_default()
+            .into_iter()
+            .map(|topic| topic.name)
+            .collect(),
+        visibility: view.visibility,
+        default_branch: view.default_branch_ref.map(|branch| branch.name),
+        has_issues_enabled: view.has_issues_enabled,
+        has_wiki_enabled: view.has_wiki_enabled,
+        has_projects_enabled: view.has_projects_enabled,
+        social_preview,
+        snapshot_at: Utc::now(),
+    };
+
+    encrypt::write(
+        &dir.join("metadata.json"),
+        &serde_json::to_vec_pretty(&metadata)?,
+        recipient,
+    )
+}
+
+/// Write `access.json`, the teams with access to a repo.
+pub fn snapshot_access(
+    backup_dir: &Path,
+    name_with_owner: &str,
+    token: Option<&str>,
+    recipient: Option<&Recipient>,
+) -> Result<()> {
+    let access = Access {
+        name_with_owner: name_with_owner.to_string(),
+        teams: access::teams(name_with_owner, token)?,
+        snapshot_at: Utc::now(),
+    };
+    let dir = repo_dir(backup_dir, name_with_owner);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    encrypt::write(
+        &dir.join("access.json"),
+        &serde_json::to_vec_pretty(&access)?,
+        recipient,
+    )
+}
+
+/// Write `issues.json`, every issue and pull request of a repo with their
+/// comments.
+pub fn snapshot_issues(
+    backup_dir: &Path,
+    name_with_owner: &str,
+    token: Option<&str>,
+    recipient: Option<&Recipient>,
+) -> Result<()> {
+    let mut items: Vec<Issue> = list(
+        &format!("repos/{name_with_owner}/issues?state=all&direction=asc"),
+        ISSUE_FIELDS,
+        token,
+    )
+    .with_context(|| format!("Failed to list the issues of {name_with_owner}"))?;
+    let comments: Vec<Comment> = list(
+        &format!("repos/{name_with_owner}/issues/comments?direction=asc"),
+        COMMENT_FIELDS,
+        token,
+    )
+    .with_context(|| format!("Failed to list the comments of {name_with_owner}"))?;
+    let reviews: Vec<Comment> = list(
+        &format!("repos/{name_with_owner}/pulls/comments?direction=asc"),
+        REVIEW_COMMENT_FIELDS,
+        token,
+    )
+    .with_context(|| format!("Failed to list the review comments of {name_with_owner}"))?;
+
+    let index: HashMap<u64, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (item.number, i))
+        .collect();
+    for comment in comments.into_iter().chain(reviews) {
+        if let Some(&i) = index.get(&comment.issue) {
+            items[i].comments.push(comment);
+        }
+    }
+
+    let issues = Issues {
+        name_with_owner: name_with_owner.to_string(),
+        items,
+        snapshot_at: Utc::now(),
+    };
+    let dir = repo_dir(backup_dir, name_with_owner);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    encrypt::write(
+        &dir.join("issues.json"),
+        &serde_json::to_vec_pretty(&issues)?,
+        recipient,
+    )
+}
+
+/// Every page of a REST listing, each entry trimmed by `filter`.
+fn list<T: DeserializeOwned>(endpoint: &str, filter: &str, token: Option<&str>) -> Result<Vec<T>> {
+    let output = gh(token)
+        .args(["api", endpoint, "--paginate", "--jq", filter])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    let stdout = error::check(output)?;
+    Ok(serde_json::Deserializer::from_slice(&stdout)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| error::Error::Parse(e.to_string()))?)
+}
+
+/// Organizations whose settings this run has exported, so each is fetched
+/// once however many of its repos are backed up.
+#[derive(Clone, Default)]
+pub struct OrgExport {
+    done: Arc<Mutex<HashSet<String>>>,
+}
+
+impl OrgExport {
+    /// Write `org-settings.json` for the owner of `name_with_owner`, unless
+    /// this run already has. Owners that are users have none, and are skipped.
+    pub fn snapshot(
+        &self,
+        backup_dir: &Path,
+        name_with_owner: &str,
+        token: Option<&str>,
+        recipient: Option<&Recipient>,
+    ) -> Result<()> {
+        let (owner, _) = name_with_owner.split_once('/').unwrap_or_default();
+        // Held throughout, so parallel backups don't fetch the same org twice
+        let mut done = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+        if done.contains(owner) {
+            return Ok(());
+        }
+
+        let output = gh(token)
+            .args(["api", &format!("orgs/{owner}"), "--jq", ORG_FIELDS])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+        let settings: OrgSettings = match error::check(output) {
+            Ok(stdout) => {
+                serde_json::from_slice(&stdout).map_err(|e| error::Error::Parse(e.to_string()))?
+            }
+            Err(error::Error::NotFound(_)) => {
+                done.insert(owner.to_string());
+                return Ok(());
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to look up org {owner}")),
+        };
+
+        let dir = backup_dir.join(owner);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        encrypt::write(
+            &dir.join("org-settings.json"),
+            &serde_json::to_vec_pretty(&settings)?,
+            recipient,
+        )?;
+        done.insert(owner.to_string());
+        Ok(())
+    }
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .arg(url)
+        .output()
+        .context("Failed to run curl. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to download {url}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(output.stdout)
+}
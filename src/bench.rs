@@ -0,0 +1,233 @@
+//! Synthetic repo lists for the criterion benches in `benches/`, which can
+//! only reach what the library exports. Not a stable interface.
+
+use chrono::{Duration, TimeZone, Utc};
+use ratatui::{backend::TestBackend, Terminal};
+use serde_json::{json, Value};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    access,
+    age::Age,
+    analyze::Signals,
+    backend, backup,
+    cache::RepoList,
+    candidates,
+    config::Config,
+    dates::DateFormat,
+    pipeline::{Action, ArchiveJob, DEFAULT_ARCHIVE_WORKERS, DEFAULT_BACKUP_WORKERS},
+    provider,
+    sample::{self, Strategy},
+    ui, AgeBasis, App, Column, Repo, RepoFilter,
+};
+
+/// `n` repos spread over 15 years, with a mix of descriptions, stars,
+/// sizes and the kinds of repos that are filtered out by default.
+pub struct Fixture {
+    repos: Vec<Repo>,
+    pinned: HashSet<String>,
+    /// Per-repo GraphQL results, as `analyze` gets them
+    activity: Vec<(String, Value)>,
+}
+
+/// A copy of the fixture's repos, to be consumed by one benchmark iteration.
+pub struct Repos(Vec<Repo>, HashSet<String>);
+
+/// A TUI showing the fixture's repos, drawn to an in-memory terminal.
+pub struct Screen {
+    terminal: Terminal<TestBackend>,
+    app: App,
+}
+
+/// Cheap deterministic spread of values, so runs are comparable.
+const fn mix(i: usize, salt: u64) -> u64 {
+    let mut z = (i as u64 ^ salt).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z ^ (z >> 31)
+}
+
+impl Fixture {
+    #[must_use]
+    pub fn new(n: usize) -> Self {
+        let epoch = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        let repos: Vec<Repo> = (0..n)
+            .map(|i| {
+                let created_at = epoch + Duration::days((mix(i, 1) % 5475) as i64);
+                let pushed_at = if mix(i, 2).is_multiple_of(10) {
+                    created_at
+                } else {
+                    created_at + Duration::days((mix(i, 3) % 2000) as i64)
+                };
+                let name = format!("project-{i:05}");
+                Repo {
+                    name_with_owner: format!("bench/{name}"),
+                    name,
+                    created_at,
+                    pushed_at,
+                    description: (!mix(i, 4).is_multiple_of(3))
+                        .then(|| format!("Synthetic repo number {i} for benchmarking the table")),
+                    is_template: mix(i, 5).is_multiple_of(50),
+                    is_mirror: mix(i, 6).is_multiple_of(80),
+                    is_fork: mix(i, 7).is_multiple_of(6),
+                    stargazer_count: mix(i, 8) % 40,
+                    disk_usage: mix(i, 9) % 500_000,
+                    ..Repo::default()
+                }
+            })
+            .collect();
+        let pinned = repos
+            .iter()
+            .step_by(997)
+            .map(|r| r.name_with_owner.clone())
+            .collect();
+        let activity = repos
+            .iter()
+            .enumerate()
+            .map(|(i, repo)| {
+                let json = json!({
+                    "pushedAt": repo.pushed_at.to_rfc3339(),
+                    "stargazerCount": repo.stargazer_count,
+                    "forkCount": mix(i, 10) % 5,
+                    "discussions": {"totalCount": mix(i, 11) % 3},
+                    "releases": {"nodes": if mix(i, 12).is_multiple_of(4) {
+                        json!([{"createdAt": repo.created_at.to_rfc3339()}])
+                    } else {
+                        json!([])
+                    }},
+                    "defaultBranchRef": {"target": {
+                        "committedDate": repo.pushed_at.to_rfc3339(),
+                        "statusCheckRollup": {"state": "FAILURE"},
+                        "history": {"nodes": (0..20).map(|c| json!({
+                            "committedDate": repo.pushed_at.to_rfc3339(),
+                            "messageHeadline": if c == 19 { "Bump serde" } else { "Fix things" },
+                            "author": {"name": "someone"},
+                        })).collect::<Vec<_>>()},
+                    }},
+                });
+                (repo.name_with_owner.clone(), json)
+            })
+            .collect();
+        Self {
+            repos,
+            pinned,
+            activity,
+        }
+    }
+
+    #[must_use]
+    pub fn repos(&self) -> Repos {
+        Repos(self.repos.clone(), self.pinned.clone())
+    }
+
+    /// Screen of `width` x `height` cells listing the fixture's candidates.
+    #[must_use]
+    pub fn screen(&self, width: u16, height: u16) -> Screen {
+        let repos = Self::candidates(self.repos());
+        let job = ArchiveJob {
+            action: Action::Archive,
+            transfer_to: None,
+            access_review: false,
+            team_summary: false,
+            revoke: access::Revoke::default(),
+            token: None,
+            github: Arc::new(backend::Gh::new(None)),
+            backup_dir: None,
+            backup_as: backup::Format::default(),
+            org_settings: None,
+            export_wiki: false,
+            export_issues: false,
+            migrate_to: None,
+            bw_limit: None,
+            encrypt_to: None,
+            backup_workers: DEFAULT_BACKUP_WORKERS,
+            archive_workers: DEFAULT_ARCHIVE_WORKERS,
+            chunking: None,
+            ssh: None,
+            description_edit: None,
+            topics: Vec::new(),
+            readme_notice: None,
+            close_items: None,
+            stamp: None,
+            disable_features: false,
+            announcement: None,
+            provider: provider::new(
+                true,
+                None,
+                provider::Kind::GitHub,
+                Arc::new(backend::Gh::new(None)),
+                &Config::default(),
+            )
+            .unwrap(),
+            forge: provider::Kind::GitHub,
+            demo: true,
+        };
+        let mut app = App::new(
+            repos,
+            false,
+            Column::DEFAULT.to_vec(),
+            DateFormat::Iso,
+            None,
+            job,
+        );
+        // Select every other repo, so the selection styling is drawn too
+        for i in (0..app.selected.len()).step_by(2) {
+            app.selected[i] = true;
+        }
+        Screen {
+            terminal: Terminal::new(TestBackend::new(width, height)).unwrap(),
+            app,
+        }
+    }
+
+    fn candidates(Repos(repos, pinned): Repos) -> Vec<Repo> {
+        let list = RepoList {
+            fetched_at: Utc::now(),
+            repos,
+            pinned,
+        };
+        let filter = RepoFilter {
+            include_templates: false,
+            include_mirrors: false,
+            include_pinned: false,
+            include_forks: false,
+            age_basis: AgeBasis::Created,
+            min_repo_age: None,
+        };
+        candidates(list, Age::Years(2), filter)
+    }
+
+    /// Filter repos by age and kind and sort them, as after fetching.
+    /// Returns how many are candidates.
+    #[must_use]
+    pub fn candidate_count(repos: Repos) -> usize {
+        Self::candidates(repos).len()
+    }
+
+    /// Draw a `--sample` of 500 spread over creation years.
+    #[must_use]
+    pub fn sample(Repos(repos, _): Repos) -> usize {
+        sample::sample(repos, 500, Strategy::Age, Some(1)).len()
+    }
+
+    /// Work out `analyze`'s archive/delete/revive suggestion for every repo.
+    /// Returns how many it would delete.
+    #[must_use]
+    pub fn score(&self) -> usize {
+        self.activity
+            .iter()
+            .filter(|(name, json)| Signals::from_json(name, json).suggestion() == "delete")
+            .count()
+    }
+}
+
+impl Screen {
+    /// Draw one full frame.
+    pub fn render(&mut self) {
+        self.terminal.draw(|f| ui(f, &mut self.app)).unwrap();
+    }
+
+    /// Move the cursor down a row, so consecutive frames differ.
+    pub fn scroll(&mut self) {
+        self.app.next();
+    }
+}
@@ -0,0 +1,226 @@
+//! Bitbucket Cloud repos, for `--provider bitbucket`: listed through the
+//! REST API with a token from `REPO_ARCHIVER_TOKEN` or `BITBUCKET_TOKEN`
+//! (e.g. a workspace access token).
+//!
+//! Bitbucket can't archive repos, so the closest thing stands in: the repo is
+//! moved into the workspace's archive project (`bitbucket_project`, "ARCHIVE"
+//! unless configured), which has to exist, and repos in it count as archived
+//! and aren't listed. Workspaces take the place of organizations. There is no
+//! push time, so the last update stands in for the last push.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::json;
+use std::env;
+
+use crate::{
+    config::Config,
+    error,
+    http::Client,
+    provider::{self, Provider},
+    report::FinalState,
+    Repo,
+};
+
+/// Where the API lives, unless `host` says otherwise
+const BITBUCKET_URL: &str = "https://api.bitbucket.org";
+
+/// Project that archived repos are moved into, unless configured
+const ARCHIVE_PROJECT: &str = "ARCHIVE";
+
+/// Repos per page of a repo list; the most Bitbucket allows.
+const PAGE: usize = 100;
+
+pub struct Bitbucket {
+    http: Client,
+    /// Key of the archive project
+    project: String,
+}
+
+#[derive(Deserialize)]
+struct BitbucketRepo {
+    slug: String,
+    full_name: String,
+    created_on: DateTime<Utc>,
+    updated_on: DateTime<Utc>,
+    #[serde(default)]
+    description: String,
+    website: Option<String>,
+    /// Only there for forks
+    parent: Option<IgnoredAny>,
+    project: Option<Project>,
+    /// In bytes
+    #[serde(default)]
+    size: u64,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    key: String,
+}
+
+/// A page of a repo list.
+#[derive(Deserialize)]
+struct RepoPage {
+    values: Vec<BitbucketRepo>,
+    next: Option<IgnoredAny>,
+}
+
+impl BitbucketRepo {
+    fn in_project(&self, key: &str) -> bool {
+        self.project.as_ref().is_some_and(|p| p.key == key)
+    }
+}
+
+impl From<BitbucketRepo> for Repo {
+    fn from(repo: BitbucketRepo) -> Self {
+        Self {
+            name: repo.slug,
+            name_with_owner: repo.full_name,
+            created_at: repo.created_on,
+            pushed_at: repo.updated_on,
+            description: Some(repo.description).filter(|d| !d.is_empty()),
+            homepage_url: repo.website.filter(|w| !w.is_empty()),
+            is_fork: repo.parent.is_some(),
+            // Kept in KB, like GitHub's diskUsage
+            disk_usage: repo.size / 1024,
+            ..Self::default()
+        }
+    }
+}
+
+impl Bitbucket {
+    pub fn new(config: &Config) -> Result<Self> {
+        let token = config
+            .token
+            .clone()
+            .or_else(|| env::var("BITBUCKET_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+            .context(
+                "--provider bitbucket needs a token: set REPO_ARCHIVER_TOKEN or BITBUCKET_TOKEN",
+            )?;
+        let url = config
+            .host
+            .as_deref()
+            .map_or_else(|| BITBUCKET_URL.to_string(), provider::server_url);
+        Ok(Self {
+            http: Client::new(&format!("{url}/2.0"), token, "application/json", config),
+            project: config
+                .bitbucket_project
+                .clone()
+                .unwrap_or_else(|| ARCHIVE_PROJECT.to_string()),
+        })
+    }
+
+    fn repo(&self, full_name: &str) -> Result<BitbucketRepo> {
+        let response = self
+            .http
+            .request("GET", &format!("/repositories/{full_name}"), None)?;
+        Ok(serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    /// Every page of the repo list at `path`, which ends in `?` or `&` for
+    /// the paging query to follow, without the archive project's repos.
+    fn list(&self, path: &str, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let response = self.http.request(
+                "GET",
+                &format!("{path}sort=created_on&pagelen={PAGE}&page={page}"),
+                None,
+            )?;
+            let page: RepoPage =
+                serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?;
+            repos.extend(
+                page.values
+                    .into_iter()
+                    .filter(|r| !r.in_project(&self.project))
+                    .map(Repo::from),
+            );
+            on_page(repos.len());
+            if page.next.is_none() {
+                break;
+            }
+        }
+        Ok(repos)
+    }
+}
+
+impl Provider for Bitbucket {
+    /// The repos the user owns, or those of a workspace.
+    fn list_repos(&self, owner: Option<&str>, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let Some(workspace) = owner else {
+            return self.list("/repositories?role=owner&", on_page);
+        };
+        self.list(&format!("/repositories/{workspace}?"), on_page)
+            .map_err(|e| match error::Error::find(&e) {
+                Some(error::Error::NotFound(_)) => error::Error::NotFound(format!(
+                    "Could not resolve to a workspace with the slug of '{workspace}'"
+                ))
+                .into(),
+                _ => e,
+            })
+    }
+
+    fn archive_repo(&self, full_name: &str) -> Result<()> {
+        self.http
+            .request(
+                "PUT",
+                &format!("/repositories/{full_name}"),
+                Some(&json!({"project": {"key": self.project}})),
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to move {full_name} into the {} project (does it exist?)",
+                    self.project
+                )
+            })?;
+        Ok(())
+    }
+
+    fn unarchive_repo(&self, full_name: &str) -> Result<()> {
+        // Where the repo was before isn't recorded anywhere
+        anyhow::bail!(
+            "Bitbucket can't unarchive: move {full_name} out of the {} project on bitbucket.org",
+            self.project
+        )
+    }
+
+    fn final_state(&self, full_name: &str) -> Result<Option<FinalState>> {
+        let repo = self
+            .repo(full_name)
+            .with_context(|| format!("Failed to look up {full_name} after archiving"))?;
+        Ok(Some(FinalState {
+            archived: repo.in_project(&self.project),
+            pushed_at: repo.updated_on,
+            updated_at: repo.updated_on,
+            access: None,
+            teams: None,
+        }))
+    }
+
+    fn dry_run(&self, full_name: &str) -> Result<String> {
+        if self.repo(full_name)?.in_project(&self.project) {
+            anyhow::bail!(
+                "would fail: {full_name} is already in the {} project",
+                self.project
+            );
+        }
+        Ok(format!("would move into the {} project", self.project))
+    }
+
+    fn check_org(&self, workspace: &str) -> Result<()> {
+        match self
+            .http
+            .request("GET", &format!("/workspaces/{workspace}"), None)
+        {
+            Err(e) if !matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => Err(e),
+            Err(_) => Err(error::Error::NotFound(format!(
+                "'{workspace}' is not a workspace, or you can't see it"
+            ))
+            .into()),
+            Ok(_) => Ok(()),
+        }
+    }
+}
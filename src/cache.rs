@@ -0,0 +1,46 @@
+//! The last repo list fetched from GitHub, for `--offline`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use crate::{paths, Repo};
+
+/// All of the user's source repos, before any age or kind filtering, so an
+/// offline run can use a different cutoff than the run that fetched them.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoList {
+    pub fetched_at: DateTime<Utc>,
+    pub repos: Vec<Repo>,
+    /// `owner/name` of the repos pinned on the profile
+    pub pinned: HashSet<String>,
+}
+
+fn cache_file() -> Result<PathBuf> {
+    paths::cache_dir()
+        .map(|dir| dir.join("repos.json"))
+        .context("No home directory to cache the repo list in")
+}
+
+pub fn save(list: &RepoList) -> Result<()> {
+    let path = cache_file()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_vec(list)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn load() -> Result<RepoList> {
+    let path = cache_file()?;
+    let contents = fs::read(&path).with_context(|| {
+        format!(
+            "No cached repo list at {}; run once while online first",
+            path.display()
+        )
+    })?;
+    serde_json::from_slice(&contents)
+        .with_context(|| format!("Invalid cache in {}", path.display()))
+}
@@ -0,0 +1,194 @@
+//! The gh features a run needs, checked against the installed gh's version
+//! before anything starts, so an old gh is caught up front with upgrade
+//! advice instead of failing halfway through a batch with "unknown flag".
+
+use anyhow::{Context, Result};
+use std::{
+    env, fmt,
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A gh release, e.g. 2.40.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(u32, u32, u32);
+
+impl Version {
+    /// Read the version from `gh --version`, whose first line looks like
+    /// "gh version 2.40.1 (2023-12-13)".
+    fn parse(output: &str) -> Option<Self> {
+        let version = output.split_whitespace().nth(2)?;
+        let mut parts = version
+            .trim_start_matches('v')
+            .split(['.', '-'])
+            .map(str::parse);
+        Some(Self(
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+            parts.next().and_then(Result::ok).unwrap_or(0),
+        ))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Something a run does through gh, and the first gh release that can.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Feature {
+    what: &'static str,
+    command: &'static str,
+    since: Version,
+}
+
+pub const ARCHIVE: Feature = Feature {
+    what: "archive repos",
+    command: "gh repo archive --yes",
+    since: Version(2, 19, 0),
+};
+
+pub const UNARCHIVE: Feature = Feature {
+    what: "unarchive repos",
+    command: "gh repo unarchive --yes",
+    since: Version(2, 34, 0),
+};
+
+pub const DELETE: Feature = Feature {
+    what: "delete forks",
+    command: "gh repo delete --yes",
+    since: Version(2, 19, 0),
+};
+
+pub const EDIT: Feature = Feature {
+    what: "add topics and edit descriptions",
+    command: "gh repo edit",
+    since: Version(2, 3, 0),
+};
+
+pub const PIN: Feature = Feature {
+    what: "pin announcement issues",
+    command: "gh issue pin",
+    since: Version(2, 27, 0),
+};
+
+/// Fail with upgrade advice unless the installed gh has all of `features`.
+///
+/// A version that can't be read (e.g. a development build) gets the benefit
+/// of the doubt.
+pub fn check(features: &[&Feature]) -> Result<()> {
+    if features.is_empty() {
+        return Ok(());
+    }
+    let output = match Command::new("gh").arg("--version").output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let needed: Vec<&str> = features.iter().map(|f| f.what).collect();
+            anyhow::bail!(
+                "gh isn't installed, and this run needs it to {}. Install it: {}",
+                needed.join(", "),
+                INSTALL_URL
+            );
+        }
+        Err(e) => return Err(e).context("Failed to run gh --version"),
+    };
+    let Some(installed) = Version::parse(&String::from_utf8_lossy(&output.stdout)) else {
+        return Ok(());
+    };
+
+    let missing: Vec<&&Feature> = features.iter().filter(|f| f.since > installed).collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("gh {installed} is too old for this run:\n");
+    for feature in missing {
+        let _ = writeln!(
+            message,
+            "  ✗ {}: `{}` needs gh {} or newer",
+            feature.what, feature.command, feature.since
+        );
+    }
+    let _ = write!(
+        message,
+        "Upgrade gh: {}",
+        upgrade_hint(find_gh().as_deref())
+    );
+    anyhow::bail!(message)
+}
+
+const INSTALL_URL: &str = "https://github.com/cli/cli#installation";
+
+/// Where `gh` is on the `PATH`, with symlinks resolved so a Homebrew install
+/// shows up as one.
+fn find_gh() -> Option<PathBuf> {
+    let name = if cfg!(windows) { "gh.exe" } else { "gh" };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+        .map(|path| path.canonicalize().unwrap_or(path))
+}
+
+/// How to upgrade the gh at `path`, going by how it was installed.
+fn upgrade_hint(path: Option<&Path>) -> String {
+    let path = path
+        .map(|p| p.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if path.contains("homebrew") || path.contains("linuxbrew") || path.contains("/cellar/") {
+        "brew upgrade gh".to_string()
+    } else if path.contains("scoop") {
+        "scoop update gh".to_string()
+    } else if path.contains("/snap/") {
+        "sudo snap refresh gh".to_string()
+    } else if cfg!(windows) {
+        "winget upgrade --id GitHub.cli".to_string()
+    } else if path.starts_with("/usr/") {
+        "through your package manager, from GitHub's package repository \
+         (https://github.com/cli/cli/blob/trunk/docs/install_linux.md); distribution \
+         packages are often far behind"
+            .to_string()
+    } else {
+        format!("download the latest release ({INSTALL_URL})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gh_version_output() {
+        assert_eq!(
+            Version::parse(
+                "gh version 2.40.1 (2023-12-13)\nhttps://github.com/cli/cli/releases/tag/v2.40.1\n"
+            ),
+            Some(Version(2, 40, 1))
+        );
+        assert_eq!(
+            Version::parse("gh version 2.45.0-12-gabcdef (2024-03-01)"),
+            Some(Version(2, 45, 0))
+        );
+        assert_eq!(Version::parse("gh version DEV"), None);
+    }
+
+    #[test]
+    fn orders_versions_numerically() {
+        assert!(Version(2, 9, 0) < Version(2, 19, 0));
+        assert!(Version(3, 0, 0) > Version(2, 99, 9));
+    }
+
+    #[test]
+    fn advises_by_install_method() {
+        assert_eq!(
+            upgrade_hint(Some(Path::new("/opt/homebrew/Cellar/gh/2.10.0/bin/gh"))),
+            "brew upgrade gh"
+        );
+        assert_eq!(
+            upgrade_hint(Some(Path::new("/snap/gh/current/bin/gh"))),
+            "sudo snap refresh gh"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! The out-of-band code a big or cross-owner deletion takes on top of typing
+//! the names: it's written to a file only the user can read, so whoever is
+//! at the keyboard has to be able to read the user's files too, and a
+//! confirmation typed from habit (or pasted) doesn't go through.
+
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{paths, sample::Rng};
+
+/// Deleting more repos than this takes a code, unless `--delete-code-over`
+/// says otherwise.
+pub const DEFAULT_LIMIT: usize = 10;
+
+/// Why deleting `names` takes a code, if it does: more than `limit` repos,
+/// or any not owned by `viewer` (all of them when who's logged in isn't
+/// known).
+pub fn needed(names: &[&str], viewer: Option<&str>, limit: usize) -> Option<String> {
+    if names.len() > limit {
+        return Some(format!("more than {limit} repos"));
+    }
+    let owned = |name: &&str| {
+        viewer.is_some_and(|viewer| {
+            name.split_once('/')
+                .is_some_and(|(owner, _)| owner.eq_ignore_ascii_case(viewer))
+        })
+    };
+    let others = names.iter().filter(|name| !owned(name)).count();
+    (others > 0).then(|| {
+        format!(
+            "{others} repo{} you don't own",
+            if others == 1 { "" } else { "s" }
+        )
+    })
+}
+
+/// A code written to a file, removed again when dropped.
+pub struct Challenge {
+    code: String,
+    path: PathBuf,
+    /// Why it's needed, shown in the confirm modal
+    pub reason: String,
+}
+
+impl Challenge {
+    /// Write a new code to `delete-code` in the state directory.
+    pub fn issue(reason: String) -> Result<Self> {
+        let dir = paths::state_dir().context("No state directory to write the code to")?;
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let code = format!("{:06}", Rng::new(None).next() % 1_000_000);
+        let path = dir.join("delete-code");
+        write_private(&path, &code)
+            .with_context(|| format!("Failed to write the code to {}", path.display()))?;
+        Ok(Self { code, path, reason })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn matches(&self, typed: &str) -> bool {
+        typed.trim() == self.code
+    }
+}
+
+#[cfg(test)]
+impl Challenge {
+    /// A challenge for `code` that isn't written anywhere.
+    pub(crate) fn unwritten(code: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            path: PathBuf::new(),
+            reason: "more than 10 repos".to_string(),
+        }
+    }
+}
+
+impl Drop for Challenge {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn write_private(path: &Path, code: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    writeln!(options.open(path)?, "{code}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_a_code_for_big_or_cross_owner_deletions() {
+        assert_eq!(needed(&["me/a", "Me/b"], Some("me"), 2), None);
+        assert_eq!(
+            needed(&["me/a", "me/b", "me/c"], Some("me"), 2).as_deref(),
+            Some("more than 2 repos")
+        );
+        assert_eq!(
+            needed(&["me/a", "org/b"], Some("me"), 10).as_deref(),
+            Some("1 repo you don't own")
+        );
+        assert_eq!(
+            needed(&["me/a"], None, 10).as_deref(),
+            Some("1 repo you don't own")
+        );
+        assert_eq!(
+            needed(&["org/a", "org/b"], Some("me"), 10).as_deref(),
+            Some("2 repos you don't own")
+        );
+    }
+
+    #[test]
+    fn only_the_whole_code_matches() {
+        let challenge = Challenge::unwritten("012345");
+
+        assert!(challenge.matches("012345"));
+        assert!(challenge.matches(" 012345\n"), "pasted with whitespace");
+        assert!(!challenge.matches("01234"));
+        assert!(!challenge.matches("0123456"));
+        assert!(!challenge.matches("12345"));
+        assert!(!challenge.matches(""));
+    }
+}
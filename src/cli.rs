@@ -0,0 +1,563 @@
+//! The command line: flags and subcommands, and how they combine with the
+//! config file into the settings a run goes by.
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Utc};
+use clap::{Parser, Subcommand};
+use std::{num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{
+    access,
+    age::Age,
+    analyze, announce,
+    app::Column,
+    assign,
+    backend::{self, Backend},
+    backup, challenge,
+    config::Config,
+    description,
+    encrypt::Recipient,
+    items,
+    mirror::BwLimit,
+    notice,
+    pipeline::{self, Action, ArchiveJob},
+    plan, provider,
+    repo::{AgeBasis, RepoFilter},
+    restore, sample, serve, ssh, steps, unarchive, web,
+};
+
+#[derive(Parser)]
+#[allow(clippy::struct_excessive_bools, reason = "clap flags")]
+#[command(name = "repo-archiver")]
+#[command(about = "Interactive CLI to archive old GitHub repos")]
+pub struct Args {
+    /// Dry run - show what would be archived without making changes
+    #[arg(long, overrides_with = "no_dry_run")]
+    pub dry_run: bool,
+
+    /// Disable dry run, even if the config file enables it
+    #[arg(long, overrides_with = "dry_run")]
+    pub no_dry_run: bool,
+
+    /// Archive repos older than this age (e.g., "8y" for 8 years, "6m" for 6 months,
+    /// "1y 6m" for 18 months), or "never" for repos never pushed to since they were created
+    /// If not provided, an interactive picker will be shown.
+    #[arg(long)]
+    pub age: Option<String>,
+
+    /// Path to the config file [default: platform config dir]
+    #[arg(long, env = "REPO_ARCHIVER_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Table columns to show, in order (e.g., "name,pushed,description")
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub columns: Option<Vec<Column>>,
+
+    /// strftime pattern for dates (e.g., "%d/%m/%Y") [default: from locale]
+    #[arg(long)]
+    pub date_format: Option<String>,
+
+    /// Include template repos, which are skipped by default
+    #[arg(long)]
+    pub include_templates: bool,
+
+    /// List the repos of this user or org instead of your own; repeat for
+    /// several, which are fetched at once
+    #[arg(long = "owner", value_name = "LOGIN")]
+    pub owners: Vec<String>,
+
+    /// List the repos of this organization, like --owner, but first check
+    /// that it is one; repeat for several
+    #[arg(long = "org", value_name = "ORG")]
+    pub orgs: Vec<String>,
+
+    /// Include mirror repos, which are skipped by default
+    #[arg(long)]
+    pub include_mirrors: bool,
+
+    /// Include repos pinned on the owner's profile, which are skipped by default
+    #[arg(long)]
+    pub include_pinned: bool,
+
+    /// Include forks, which are skipped by default, showing how each compares
+    /// to its upstream
+    #[arg(long)]
+    pub include_forks: bool,
+
+    /// What a repo's age is measured from: when it was "created" (the
+    /// default), or its latest "activity" (push, issue or comment, pull
+    /// request update or release), for repos still discussed but not pushed to
+    #[arg(long, value_name = "BASIS")]
+    pub filter_by: Option<AgeBasis>,
+
+    /// Never list repos created less than this long ago (e.g., "3m"), whatever
+    /// --age is, so brand-new repos nobody has pushed to yet don't turn up as
+    /// never pushed to
+    #[arg(long, value_name = "AGE")]
+    pub min_repo_age: Option<String>,
+
+    /// Edit each repo's description before archiving it: "prepend:", "append:"
+    /// or "replace:" followed by a template that may use {name}, {description}
+    /// and {date} (e.g., "prepend:[Archived {date}] ")
+    #[arg(long, value_name = "OP:TEMPLATE", global = true)]
+    pub edit_description: Option<description::Edit>,
+
+    /// Add this topic to each repo before archiving it, so a cleanup wave can
+    /// be found again with GitHub search; "{year}" is replaced with the
+    /// current year (e.g., "archived-{year}"). Repeat it, or separate topics
+    /// with commas, to add several
+    #[arg(long, value_delimiter = ',', global = true)]
+    pub topic: Vec<String>,
+
+    /// Commit this notice to the top of each repo's README before archiving
+    /// it, so visitors see the repo is archived first; may use {name} and
+    /// {date}. Without a text, a "> ⚠️ This repository is archived and
+    /// unmaintained." quote is used
+    #[arg(long, value_name = "TEXT", num_args = 0..=1, default_missing_value = notice::DEFAULT, global = true)]
+    pub readme_notice: Option<String>,
+
+    /// Close each repo's open issues and pull requests before archiving it,
+    /// leaving this comment on each; may use {name} and {date}. Without a
+    /// comment, "Repository archived on <date>." is left
+    #[arg(long, value_name = "COMMENT", num_args = 0..=1, default_missing_value = items::DEFAULT_COMMENT, global = true)]
+    pub close_items: Option<String>,
+
+    /// Commit an ARCHIVED.md saying when each repo was archived, why (see
+    /// --stamp-reason) and what replaced it (`successors` in the config file)
+    /// as the last commit before archiving it
+    #[arg(long, global = true)]
+    pub stamp: bool,
+
+    /// Reason given in ARCHIVED.md; may use {name} and {date}
+    #[arg(long, value_name = "TEXT", requires = "stamp", global = true)]
+    pub stamp_reason: Option<String>,
+
+    /// Turn off issues, the wiki and projects on each repo as the last step
+    /// before archiving it, so the archived repo shows only its code
+    #[arg(long, global = true)]
+    pub disable_features: bool,
+
+    /// Post a final "This repository is now archived" announcement with this
+    /// body right before archiving each repo, e.g. the rationale and links to
+    /// successors; may use {name} and {date}
+    #[arg(long, value_name = "TEMPLATE", global = true)]
+    pub announce: Option<String>,
+
+    /// Post the announcement as a pinned issue or as a discussion
+    #[arg(long, value_name = "KIND", global = true)]
+    pub announce_as: Option<announce::Kind>,
+
+    /// Archive bare repos on a plain git server instead of on GitHub: they're
+    /// listed by when their refs last changed, and moved under `archive/` and
+    /// made read-only (e.g., "git@git.example.com:/srv/git")
+    #[arg(long, value_name = "HOST:PATH", global = true)]
+    pub ssh: Option<ssh::SshRemote>,
+
+    /// Back each repo up into this directory before archiving it: its
+    /// metadata and a mirror (or bundle) of its git data. A repo whose backup
+    /// fails isn't archived
+    #[arg(long, global = true)]
+    pub backup_dir: Option<PathBuf>,
+
+    /// Keep each repo's git data in --backup-dir as a bare "mirror" clone,
+    /// updated on later runs, or as a single "bundle" file
+    #[arg(long, value_name = "FORMAT", global = true)]
+    pub backup_as: Option<backup::Format>,
+
+    /// With --backup-dir, also back up which teams can access each repo and
+    /// its organization's settings (default permission, who may create and
+    /// fork repos), for a complete restore
+    #[arg(long)]
+    pub export_org_settings: bool,
+
+    /// With --backup-dir, also back up each repo's wiki (as wiki.git or
+    /// wiki.bundle next to its git data) when it has any pages
+    #[arg(long, global = true)]
+    pub export_wiki: bool,
+
+    /// With --backup-dir, also export each repo's issues and pull requests,
+    /// open or closed, with their comments to issues.json, so the project's
+    /// history survives the repo being deleted
+    #[arg(long, global = true)]
+    pub export_issues: bool,
+
+    /// Mirror each repo to this git URL before archiving it; "{owner}" and
+    /// "{name}" are replaced (e.g., "git@gitea.example.com:me/{name}.git")
+    #[arg(long, value_name = "URL")]
+    pub migrate_to: Option<String>,
+
+    /// Cap clone/push bandwidth when mirroring (e.g., "500k" or "2m" per second);
+    /// requires `trickle`
+    #[arg(long, value_name = "RATE")]
+    pub bw_limit: Option<BwLimit>,
+
+    /// Encrypt backups to this age public key ("age1...") or GPG key ID/email
+    #[arg(long, value_name = "RECIPIENT")]
+    pub encrypt_to: Option<Recipient>,
+
+    /// Number of repos to back up or migrate in parallel [default: 2]
+    #[arg(long, value_name = "N")]
+    pub backup_workers: Option<NonZeroUsize>,
+
+    /// Number of repos to archive in parallel; lowered for a while after
+    /// failures or rate limits [default: 1]
+    #[arg(long, value_name = "N")]
+    pub concurrency: Option<NonZeroUsize>,
+
+    /// Archive this many repos at a time, checking them off in the plan file
+    /// between chunks so an interrupted run can be resumed with `apply-plan`
+    #[arg(long, value_name = "N", global = true)]
+    pub chunk_size: Option<NonZeroUsize>,
+
+    /// Seconds to pause between chunks [default: 60]
+    #[arg(long, value_name = "SECS", requires = "chunk_size", global = true)]
+    pub cool_down: Option<u64>,
+
+    /// CSV of "repo,decision" rows to diff the candidates against; repos marked
+    /// "archive" are pre-selected and disagreements are flagged
+    #[arg(long, value_name = "CSV")]
+    pub compare: Option<PathBuf>,
+
+    /// Ask this HTTP endpoint whether each candidate may be archived; denied
+    /// repos can't be selected
+    #[arg(long, value_name = "URL")]
+    pub policy_url: Option<String>,
+
+    /// Flag repos that deployed within this many days [default: 30; 0 turns
+    /// the check off]
+    #[arg(long, value_name = "DAYS")]
+    pub deploy_window: Option<u32>,
+
+    /// Skip repos whose default branch contains a `.repo-archiver-keep` (or
+    /// `.repoarchiverignore`) file
+    #[arg(long)]
+    pub respect_keep_files: bool,
+
+    /// Queue confirmed repos in a plan that someone else has to `approve`
+    /// before `apply-plan` archives them
+    #[arg(long)]
+    pub require_approval: bool,
+
+    /// Keep runs by different people apart: hold an open issue in this repo
+    /// while archiving, and refuse to start while someone else holds one
+    #[arg(long, value_name = "OWNER/NAME", global = true)]
+    pub lock_repo: Option<String>,
+
+    /// Close a lock issue left behind by a run that's no longer going, and
+    /// take the lock
+    #[arg(long, requires = "lock_repo", global = true)]
+    pub break_lock: bool,
+
+    /// Delete (not archive) forks that have no commits of their own, no open
+    /// PRs upstream and no stars
+    #[arg(long)]
+    pub prune_forks: bool,
+
+    /// Delete (not archive) the confirmed repos, for junk like old
+    /// experiments; confirming takes typing each repo's name
+    #[arg(long, conflicts_with = "prune_forks")]
+    pub delete: bool,
+
+    /// Deleting more repos than this, or any you don't own, also takes a
+    /// code written to a file in the state directory
+    #[arg(long, value_name = "N", default_value_t = challenge::DEFAULT_LIMIT)]
+    pub delete_code_over: usize,
+
+    /// What to do with the confirmed repos: "archive", "transfer" them to
+    /// the owner given with --to, "privatize" them (make them private), or
+    /// "tag" them with --topic and leave them be
+    #[arg(long, value_name = "ACTION", conflicts_with_all = ["prune_forks", "delete"])]
+    pub action: Option<Action>,
+
+    /// User or organization that --action transfer moves repos to
+    #[arg(long, value_name = "OWNER")]
+    pub to: Option<String>,
+
+    /// List the collaborators and teams that can still write to each archived
+    /// repo in the run report, for following up on access cleanup
+    #[arg(long)]
+    pub access_review: bool,
+
+    /// Remove outside collaborators from each repo right before archiving it;
+    /// who was removed goes in the run report
+    #[arg(long)]
+    pub remove_outside_collaborators: bool,
+
+    /// Remove this team's access to each repo right before archiving it (by
+    /// slug; repeat it or separate slugs with commas); removals go in the run
+    /// report
+    #[arg(long, value_name = "SLUG", value_delimiter = ',')]
+    pub remove_team: Vec<String>,
+
+    /// Only offer repos whose default branch CI is failing
+    #[arg(long)]
+    pub only_failing_ci: bool,
+
+    /// Only offer N of the candidates, to trial a policy on a subset of a huge
+    /// org first
+    #[arg(long, value_name = "N")]
+    pub sample: Option<NonZeroUsize>,
+
+    /// How --sample picks candidates
+    #[arg(long, value_enum, default_value_t, requires = "sample")]
+    pub sample_by: sample::Strategy,
+
+    /// Seed for --sample, to get the same sample again
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Use the repo list cached by the last online run; confirmed repos are
+    /// queued in a plan for `apply-plan` instead of archived
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Try the tool on made-up repos: archiving is only pretended and
+    /// nothing touches GitHub
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Redraw only every 500ms while nothing is being archived, to save
+    /// battery
+    #[arg(long)]
+    pub low_power: bool,
+
+    /// Show the candidates in a browser instead of the TUI, served on this
+    /// address [default: 127.0.0.1:8080]; needs --age
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, default_missing_value = web::DEFAULT_ADDR)]
+    pub web: Option<String>,
+
+    /// Skip the TUI and archive every candidate without asking, printing how
+    /// each repo went, for cron jobs and CI; needs --age, and --yes unless
+    /// it's a dry run
+    #[arg(long, conflicts_with_all = ["web", "record", "replay"])]
+    pub non_interactive: bool,
+
+    /// Confirm that --non-interactive may archive everything it finds
+    #[arg(long, requires = "non_interactive")]
+    pub yes: bool,
+
+    /// Save key presses and the screens they led to in this file (no tokens
+    /// or repo data), e.g. to attach to a bug report
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Play back a --record file against the --demo repos, reporting where
+    /// the UI went somewhere else than when it was recorded
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// How to talk to GitHub: through the gh CLI, or straight to the API
+    /// with a token from `REPO_ARCHIVER_TOKEN`, `GH_TOKEN` or `GITHUB_TOKEN`, for
+    /// machines without gh; api-then-gh and gh-then-api fall back to the other
+    /// whenever a call fails [default: gh]
+    #[arg(long, value_enum, global = true)]
+    pub backend: Option<backend::Kind>,
+
+    /// Where the repos live: GitHub, GitLab (gitlab.com, or the instance at
+    /// `REPO_ARCHIVER_GITLAB_URL` or --host) with a token from
+    /// `REPO_ARCHIVER_TOKEN` or `GITLAB_TOKEN`, a Gitea/Forgejo server at
+    /// --host with a token from `REPO_ARCHIVER_TOKEN` or `GITEA_TOKEN`, or
+    /// Bitbucket Cloud with a token from `REPO_ARCHIVER_TOKEN` or
+    /// `BITBUCKET_TOKEN`, where archiving moves repos into an archive project
+    /// [default: github]
+    #[arg(long, value_enum, global = true)]
+    pub provider: Option<provider::Kind>,
+
+    /// Server of the gitea provider (e.g. codeberg.org), or of a
+    /// self-managed GitLab
+    #[arg(long, value_name = "HOST", global = true)]
+    pub host: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Recreate a repo on GitHub from its backup in the backup dir
+    Restore(restore::RestoreArgs),
+    /// Archive the repos queued while offline or for approval
+    ApplyPlan(plan::ApplyArgs),
+    /// Print the candidates as JSON without the TUI, for jq and other tools
+    /// (needs --age)
+    List,
+    /// Write every candidate to a plan file, with what archiving each would
+    /// do, for review before `apply` (needs --age)
+    Plan(plan::PlanArgs),
+    /// Archive the repos of a plan file written by `plan`, with the flags it
+    /// was planned with
+    Apply(plan::ApplyFileArgs),
+    /// Approve a plan queued by someone else with --require-approval
+    Approve(plan::ApproveArgs),
+    /// Split the queued plan between teammates, as a plan file (and
+    /// optionally an issue) per person
+    Assign(assign::AssignArgs),
+    /// Show how many of each teammate's assigned repos are archived
+    Assignments,
+    /// Report abandonment signals per repo, to help decide between archiving,
+    /// deleting and reviving it
+    Analyze(analyze::AnalyzeArgs),
+    /// Back repos up to --backup-dir and/or mirror them to --migrate-to,
+    /// without archiving them
+    Backup(steps::RepoArgs),
+    /// Commit the --readme-notice text (or the default notice) to the top of
+    /// each repo's README
+    Notice(steps::RepoArgs),
+    /// Close each repo's open issues and pull requests
+    CloseItems(steps::CloseArgs),
+    /// Archive repos by name, with any pre-archive steps given by flags
+    Archive(steps::RepoArgs),
+    /// Unarchive repos, e.g. ones archived by mistake
+    Unarchive(unarchive::UnarchiveArgs),
+    /// Answer JSON-RPC requests to list, plan and archive repos, for editor
+    /// plugins and other front ends
+    Serve(serve::ServeArgs),
+    /// Show how many repos you've cleaned up and the time it saved (needs
+    /// `usage_stats = true` in the config file)
+    Stats,
+}
+
+impl Args {
+    /// Resolve the dry-run setting, letting either CLI flag override the config.
+    pub fn dry_run(&self, config: &Config) -> bool {
+        if self.no_dry_run {
+            false
+        } else {
+            self.dry_run || config.dry_run.unwrap_or(false)
+        }
+    }
+
+    pub fn archive_job(&self, config: &Config) -> Result<ArchiveJob> {
+        let ssh = self.ssh.clone().or_else(|| config.ssh.clone());
+        // Replays run against the demo repos
+        let demo = self.demo || self.replay.is_some();
+        let forge = self.provider.or(config.provider).unwrap_or_default();
+        let github: Arc<dyn Backend> = if demo || ssh.is_some() || forge != provider::Kind::GitHub {
+            Arc::new(backend::Gh::new(config.token.clone()))
+        } else {
+            backend::new(self.backend.or(config.backend).unwrap_or_default(), config)?
+        };
+        Ok(ArchiveJob {
+            action: if self.prune_forks || self.delete {
+                Action::Delete
+            } else {
+                self.action.unwrap_or_default()
+            },
+            transfer_to: self.to.clone(),
+            access_review: self.access_review || config.access_review.unwrap_or(false),
+            team_summary: !self.orgs.is_empty() || matches!(self.command, Some(Commands::Serve(_))),
+            revoke: access::Revoke {
+                outside_collaborators: self.remove_outside_collaborators
+                    || config.remove_outside_collaborators.unwrap_or(false),
+                teams: if self.remove_team.is_empty() {
+                    config.remove_teams.clone().unwrap_or_default()
+                } else {
+                    self.remove_team.clone()
+                },
+            },
+            close_items: self
+                .close_items
+                .clone()
+                .or_else(|| config.close_items.clone()),
+            token: config.token.clone(),
+            github: github.clone(),
+            backup_dir: self
+                .backup_dir
+                .clone()
+                .or_else(|| config.backup_dir.clone()),
+            backup_as: self.backup_as.or(config.backup_as).unwrap_or_default(),
+            org_settings: (self.export_org_settings || config.export_org_settings.unwrap_or(false))
+                .then(backup::OrgExport::default),
+            export_wiki: self.export_wiki || config.export_wiki.unwrap_or(false),
+            export_issues: self.export_issues || config.export_issues.unwrap_or(false),
+            migrate_to: self
+                .migrate_to
+                .clone()
+                .or_else(|| config.migrate_to.clone()),
+            bw_limit: self.bw_limit.or(config.bw_limit),
+            encrypt_to: self
+                .encrypt_to
+                .clone()
+                .or_else(|| config.encrypt_to.clone()),
+            backup_workers: self
+                .backup_workers
+                .or(config.backup_workers)
+                .unwrap_or(pipeline::DEFAULT_BACKUP_WORKERS),
+            archive_workers: self
+                .concurrency
+                .or(config.concurrency)
+                .unwrap_or(pipeline::DEFAULT_ARCHIVE_WORKERS),
+            chunking: self
+                .chunk_size
+                .or(config.chunk_size)
+                .map(|size| pipeline::Chunking {
+                    size,
+                    cool_down: self
+                        .cool_down
+                        .or(config.cool_down)
+                        .map_or(pipeline::DEFAULT_COOL_DOWN, Duration::from_secs),
+                }),
+            description_edit: self
+                .edit_description
+                .clone()
+                .or_else(|| config.edit_description.clone()),
+            topics: if self.topic.is_empty() {
+                config
+                    .topic
+                    .iter()
+                    .chain(config.topics.iter().flatten())
+                    .cloned()
+                    .collect()
+            } else {
+                self.topic.clone()
+            }
+            .iter()
+            .map(|topic| topic.replace("{year}", &Utc::now().year().to_string()))
+            .collect(),
+            readme_notice: self
+                .readme_notice
+                .clone()
+                .or_else(|| config.readme_notice.clone()),
+            stamp: (self.stamp || config.stamp.unwrap_or(false)).then(|| notice::Stamp {
+                reason: self
+                    .stamp_reason
+                    .clone()
+                    .or_else(|| config.stamp_reason.clone()),
+                successors: config.successors.clone().unwrap_or_default(),
+            }),
+            announcement: self
+                .announce
+                .clone()
+                .or_else(|| config.announce.clone())
+                .map(|template| announce::Announcement {
+                    kind: self.announce_as.or(config.announce_as).unwrap_or_default(),
+                    template,
+                }),
+            disable_features: self.disable_features || config.disable_features.unwrap_or(false),
+            provider: provider::new(demo, ssh.as_ref(), forge, github, config)?,
+            forge,
+            ssh,
+            demo,
+        })
+    }
+
+    pub fn repo_filter(&self, config: &Config) -> Result<RepoFilter> {
+        let min_repo_age = match self.min_repo_age.as_ref().or(config.min_repo_age.as_ref()) {
+            Some(min) => match Age::parse(min).context("Invalid --min-repo-age")? {
+                Age::NeverPushed => {
+                    anyhow::bail!("--min-repo-age takes an age like '3m', not 'never'")
+                }
+                min => Some(min),
+            },
+            None => None,
+        };
+        Ok(RepoFilter {
+            include_templates: self.include_templates || config.include_templates.unwrap_or(false),
+            include_mirrors: self.include_mirrors || config.include_mirrors.unwrap_or(false),
+            include_pinned: self.include_pinned || config.include_pinned.unwrap_or(false),
+            include_forks: self.include_forks || config.include_forks.unwrap_or(false),
+            age_basis: self.filter_by.or(config.filter_by).unwrap_or_default(),
+            min_repo_age,
+        })
+    }
+}
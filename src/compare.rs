@@ -0,0 +1,186 @@
+//! `--compare`: diff the candidates against a spreadsheet of decisions.
+//!
+//! Cleanups are usually agreed on in a spreadsheet first. Exported as CSV
+//! with the repo in the first column and the decision in the second, it
+//! pre-selects the repos marked "archive" and flags where the sheet and the
+//! candidate list disagree. The same sheets, with an optional third column
+//! of notes, can be imported from the TUI.
+
+use anyhow::{Context, Result};
+use std::{collections::HashMap, path::Path};
+
+use crate::{parse_github_remote, Repo};
+
+/// What the sheet says to do with a repo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Archive,
+    /// Anything else ("keep", "migrate", ...), as written in the sheet
+    Other(String),
+}
+
+impl Decision {
+    fn parse(s: &str) -> Self {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("archive") {
+            Self::Archive
+        } else {
+            Self::Other(s.to_lowercase())
+        }
+    }
+}
+
+/// One row of the sheet.
+pub struct Entry {
+    pub decision: Decision,
+    pub note: Option<String>,
+}
+
+pub struct Sheet {
+    /// Keyed by the lowercased repo as written in the sheet: `owner/name` or
+    /// a bare name
+    entries: HashMap<String, Entry>,
+}
+
+impl Sheet {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let mut entries = HashMap::new();
+        for (i, record) in reader.records().enumerate() {
+            let record = record.with_context(|| format!("Failed to parse {}", path.display()))?;
+            let repo = record.get(0).unwrap_or_default();
+            if repo.is_empty()
+                || (i == 0
+                    && ["repo", "repository", "name"].contains(&repo.to_lowercase().as_str()))
+            {
+                continue;
+            }
+            let entry = Entry {
+                decision: Decision::parse(record.get(1).unwrap_or_default()),
+                note: record
+                    .get(2)
+                    .filter(|note| !note.is_empty())
+                    .map(str::to_string),
+            };
+            entries.insert(normalize(repo), entry);
+        }
+        Ok(Self { entries })
+    }
+
+    /// The sheet's row for `repo`, one of `repos`, if it's listed. A bare
+    /// name only counts if no other of `repos` has it, since with several
+    /// owners it could mean any of them.
+    pub fn entry(&self, repo: &Repo, repos: &[Repo]) -> Option<&Entry> {
+        self.entries
+            .get(&repo.name_with_owner.to_lowercase())
+            .or_else(|| {
+                let name = repo.name.to_lowercase();
+                self.entries.get(&name).filter(|_| named(repos, &name) == 1)
+            })
+    }
+
+    /// Bare names in the sheet that more than one of `repos` have, so they
+    /// match none of them.
+    pub fn ambiguous(&self, repos: &[Repo]) -> Vec<String> {
+        let mut ambiguous: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|repo| !repo.contains('/') && named(repos, repo) > 1)
+            .cloned()
+            .collect();
+        ambiguous.sort();
+        ambiguous
+    }
+
+    /// Repos the sheet marks "archive" that aren't among `candidates`, e.g.
+    /// because they're too recent or already archived.
+    pub fn missing_archives(&self, candidates: &[Repo]) -> Vec<String> {
+        self.unmatched(candidates, |entry| entry.decision == Decision::Archive)
+    }
+
+    /// Rows of the sheet that don't match any of `repos`.
+    pub fn unknown(&self, repos: &[Repo]) -> Vec<String> {
+        self.unmatched(repos, |_| true)
+    }
+
+    /// Ambiguous rows are left to [`Sheet::ambiguous`].
+    fn unmatched(&self, repos: &[Repo], include: impl Fn(&Entry) -> bool) -> Vec<String> {
+        let mut unmatched: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| include(entry))
+            .map(|(repo, _)| repo)
+            .filter(|repo| {
+                !repos
+                    .iter()
+                    .any(|r| r.name_with_owner.eq_ignore_ascii_case(repo))
+                    && named(repos, repo) == 0
+            })
+            .cloned()
+            .collect();
+        unmatched.sort();
+        unmatched
+    }
+}
+
+/// How many of `repos` are called `name`, whoever owns them.
+fn named(repos: &[Repo], name: &str) -> usize {
+    repos
+        .iter()
+        .filter(|r| r.name.eq_ignore_ascii_case(name))
+        .count()
+}
+
+/// Sheets may hold GitHub URLs instead of names.
+fn normalize(repo: &str) -> String {
+    parse_github_remote(repo)
+        .unwrap_or_else(|| repo.to_string())
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name_with_owner: &str) -> Repo {
+        Repo {
+            name: name_with_owner.split_once('/').unwrap().1.to_string(),
+            name_with_owner: name_with_owner.to_string(),
+            ..Repo::default()
+        }
+    }
+
+    fn sheet(rows: &[&str]) -> Sheet {
+        Sheet {
+            entries: rows
+                .iter()
+                .map(|row| {
+                    let entry = Entry {
+                        decision: Decision::Archive,
+                        note: None,
+                    };
+                    (normalize(row), entry)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn bare_names_only_match_a_repo_nobody_else_has() {
+        let repos = [repo("me/api"), repo("org/api"), repo("org/web")];
+        let sheet = sheet(&["api", "web", "me/api"]);
+
+        assert!(sheet.entry(&repos[0], &repos).is_some(), "by owner/name");
+        assert!(sheet.entry(&repos[1], &repos).is_none(), "ambiguous");
+        assert!(sheet.entry(&repos[2], &repos).is_some());
+        assert_eq!(sheet.ambiguous(&repos), ["api"]);
+        assert!(sheet.missing_archives(&repos).is_empty());
+        assert_eq!(sheet.missing_archives(&[]), ["api", "me/api", "web"]);
+    }
+}
@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    announce, backend, backup, description::Edit, encrypt::Recipient, mirror::BwLimit, paths,
+    provider, rules, ssh::SshRemote, AgeBasis, Column,
+};
+
+/// Defaults loaded from `config.toml` (see [`paths::config_file`]), then
+/// overridden by `REPO_ARCHIVER_*` environment variables.
+///
+/// Every field is optional; anything passed on the command line wins.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Default age, same syntax as `--age` (e.g. "2y", "6m")
+    pub age: Option<String>,
+    /// Run in dry-run mode unless `--no-dry-run` is given
+    pub dry_run: Option<bool>,
+    /// Columns to show in the repo table, in order
+    pub columns: Option<Vec<Column>>,
+    /// strftime pattern for dates, instead of the locale's format
+    pub date_format: Option<String>,
+    /// Users or orgs whose repos are listed instead of your own
+    pub owners: Option<Vec<String>>,
+    /// Include template repos, which are skipped by default
+    pub include_templates: Option<bool>,
+    /// Include mirror repos, which are skipped by default
+    pub include_mirrors: Option<bool>,
+    /// Include repos pinned on your profile, which are skipped by default
+    pub include_pinned: Option<bool>,
+    /// Include forks, which are skipped by default
+    pub include_forks: Option<bool>,
+    /// Measure age from when a repo was "created" or from its latest "activity"
+    pub filter_by: Option<AgeBasis>,
+    /// Never list repos created less than this long ago (e.g. "3m")
+    pub min_repo_age: Option<String>,
+    /// Queue confirmed repos for a second person's approval instead of archiving
+    pub require_approval: Option<bool>,
+    /// Flag repos that deployed within this many days (0 turns the check off)
+    pub deploy_window: Option<u32>,
+    /// HTTP endpoint that decides whether each candidate may be archived
+    pub policy_url: Option<String>,
+    /// Repo whose lock issue keeps runs by different people apart
+    pub lock_repo: Option<String>,
+    /// Skip repos that contain a keep file in their default branch
+    pub respect_keep_files: Option<bool>,
+    /// Protect or preselect candidates whose description matches a pattern
+    pub description_rules: Option<Vec<rules::DescriptionRule>>,
+    /// Edit to each repo's description before archiving it, as "op:template"
+    pub edit_description: Option<Edit>,
+    /// Topic to add to each repo before archiving it; "{year}" is replaced
+    pub topic: Option<String>,
+    /// More topics to add, alongside `topic`
+    pub topics: Option<Vec<String>>,
+    /// Notice committed to the top of each README before archiving; may use
+    /// {name} and {date}
+    pub readme_notice: Option<String>,
+    /// Close open issues and pull requests before archiving, leaving this
+    /// comment on each; may use {name} and {date}
+    pub close_items: Option<String>,
+    /// Commit an ARCHIVED.md as the last commit before archiving
+    pub stamp: Option<bool>,
+    /// Why the repos are archived, for ARCHIVED.md; may use {name} and {date}
+    pub stamp_reason: Option<String>,
+    /// What replaced each repo, by "owner/name", for ARCHIVED.md: another
+    /// repo's "owner/name" or a URL
+    pub successors: Option<HashMap<String, String>>,
+    /// Body of a final post made before archiving each repo; may use {name}
+    /// and {date}
+    pub announce: Option<String>,
+    /// Post the announcement as an "issue" (pinned) or a "discussion"
+    pub announce_as: Option<announce::Kind>,
+    /// Turn off issues, the wiki and projects as the last step before
+    /// archiving
+    pub disable_features: Option<bool>,
+    /// Archive bare repos on this git server over SSH instead of on GitHub
+    pub ssh: Option<SshRemote>,
+    /// Back each repo's metadata and git data up into this directory before
+    /// archiving it
+    pub backup_dir: Option<PathBuf>,
+    /// Keep the git data in `backup_dir` as a "mirror" (default) or "bundle"
+    pub backup_as: Option<backup::Format>,
+    /// List who can still write to each archived repo in the run report
+    pub access_review: Option<bool>,
+    /// Remove outside collaborators right before archiving
+    pub remove_outside_collaborators: Option<bool>,
+    /// Slugs of teams to remove right before archiving
+    pub remove_teams: Option<Vec<String>>,
+    /// Also back up team access and the organization's settings
+    pub export_org_settings: Option<bool>,
+    /// Also back up each repo's wiki, when it has pages
+    pub export_wiki: Option<bool>,
+    /// Also export each repo's issues and pull requests with their comments
+    pub export_issues: Option<bool>,
+    /// Mirror each repo to this git URL template before archiving it
+    pub migrate_to: Option<String>,
+    /// Cap clone/push bandwidth when mirroring (e.g. "500k", "2m")
+    pub bw_limit: Option<BwLimit>,
+    /// Encrypt backups to this age public key or GPG key ID/email
+    pub encrypt_to: Option<Recipient>,
+    /// Number of repos to back up or migrate in parallel
+    pub backup_workers: Option<NonZeroUsize>,
+    /// Number of repos to archive in parallel
+    pub concurrency: Option<NonZeroUsize>,
+    /// Archive this many repos at a time, checkpointing between chunks
+    pub chunk_size: Option<NonZeroUsize>,
+    /// Seconds to pause between chunks
+    pub cool_down: Option<u64>,
+    /// CA bundle to trust instead of the system roots, for TLS-intercepting proxies
+    pub ca_bundle: Option<PathBuf>,
+    /// Client certificate (PEM) for git and API requests to present to servers
+    /// that require one
+    pub client_cert: Option<PathBuf>,
+    /// Private key for `client_cert`, if it isn't in the same file
+    pub client_key: Option<PathBuf>,
+    /// Keep a local record of cleanups for `repo-archiver stats`
+    pub usage_stats: Option<bool>,
+    /// How long the table waits for a key press before redrawing, in ms
+    pub poll_interval_ms: Option<NonZeroU64>,
+    /// How often the archiving spinner advances, in ms
+    pub spinner_interval_ms: Option<NonZeroU64>,
+    /// Redraw only every 500ms while nothing is being archived
+    pub low_power: Option<bool>,
+    /// How to talk to GitHub: "gh", "api", or "api-then-gh" / "gh-then-api"
+    /// to fall back to the other when a call fails
+    pub backend: Option<backend::Kind>,
+    /// Base URL of the GitHub API for the "api" backend
+    pub api_url: Option<String>,
+    /// Where repos live: "github", "gitlab", "gitea" or "bitbucket"
+    pub provider: Option<provider::Kind>,
+    /// Base URL of the GitLab instance for the "gitlab" provider
+    pub gitlab_url: Option<String>,
+    /// Server of the "gitea" provider (e.g. "codeberg.org"), or of the
+    /// "gitlab" provider when `gitlab_url` isn't set
+    pub host: Option<String>,
+    /// Key of the Bitbucket project that archived repos are moved into
+    pub bitbucket_project: Option<String>,
+    /// GitHub token passed to `gh` as `GH_TOKEN` (environment only)
+    #[serde(skip)]
+    pub token: Option<String>,
+    /// Bearer token `serve --http` requires of its clients (environment only)
+    #[serde(skip)]
+    pub api_token: Option<String>,
+}
+
+impl Config {
+    /// Load the config file and apply environment overrides on top.
+    ///
+    /// An explicit `path` (from `--config`) must exist; the default one is optional.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = Self::load_file(path)?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn load_file(explicit: Option<&Path>) -> Result<Self> {
+        let Some(path) = explicit.map(Path::to_path_buf).or_else(paths::config_file) else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && explicit.is_none() => {
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        toml::from_str(&contents).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(age) = env_var("REPO_ARCHIVER_AGE") {
+            self.age = Some(age);
+        }
+        if let Some(dry_run) = env_var("REPO_ARCHIVER_DRY_RUN") {
+            self.dry_run = Some(parse_bool(&dry_run).context("Invalid REPO_ARCHIVER_DRY_RUN")?);
+        }
+        if let Some(owners) = env_var("REPO_ARCHIVER_OWNER") {
+            self.owners = Some(owners.split(',').map(|o| o.trim().to_string()).collect());
+        }
+        if let Some(low_power) = env_var("REPO_ARCHIVER_LOW_POWER") {
+            self.low_power =
+                Some(parse_bool(&low_power).context("Invalid REPO_ARCHIVER_LOW_POWER")?);
+        }
+        if let Some(api_url) = env_var("REPO_ARCHIVER_API_URL") {
+            self.api_url = Some(api_url);
+        }
+        if let Some(gitlab_url) = env_var("REPO_ARCHIVER_GITLAB_URL") {
+            self.gitlab_url = Some(gitlab_url);
+        }
+        if let Some(host) = env_var("REPO_ARCHIVER_HOST") {
+            self.host = Some(host);
+        }
+        if let Some(token) = env_var("REPO_ARCHIVER_TOKEN") {
+            self.token = Some(token);
+        }
+        if let Some(token) = env_var("REPO_ARCHIVER_API_TOKEN") {
+            self.api_token = Some(token);
+        }
+        Ok(())
+    }
+}
+
+/// Read an environment variable, treating empty values as unset.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        other => anyhow::bail!("Expected true or false, got '{other}'"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::Parser;
+
+    /// Write `contents` to a config file of its own in the temp dir.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            env::temp_dir().join(format!("repo-archiver-{name}-{}.toml", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn env_overrides_the_file_and_flags_override_both() {
+        let path = config_file(
+            "precedence",
+            "age = \"3y\"\ndry_run = false\nowners = [\"from-file\"]\nhost = \"git.example.com\"\n",
+        );
+        env::set_var("REPO_ARCHIVER_AGE", "1y");
+        env::set_var("REPO_ARCHIVER_DRY_RUN", " Yes ");
+        // Empty counts as unset, so the file's value stays
+        env::set_var("REPO_ARCHIVER_HOST", "");
+        let config = Config::load(Some(&path));
+        for name in [
+            "REPO_ARCHIVER_AGE",
+            "REPO_ARCHIVER_DRY_RUN",
+            "REPO_ARCHIVER_HOST",
+        ] {
+            env::remove_var(name);
+        }
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.age.as_deref(), Some("1y"));
+        assert_eq!(config.dry_run, Some(true));
+        assert_eq!(config.owners, Some(vec!["from-file".to_string()]));
+        assert_eq!(config.host.as_deref(), Some("git.example.com"));
+
+        let args = Args::parse_from(["repo-archiver", "--age", "6m", "--no-dry-run"]);
+        assert_eq!(args.age.as_ref().or(config.age.as_ref()).unwrap(), "6m");
+        assert!(!args.dry_run(&config));
+        assert!(Args::parse_from(["repo-archiver"]).dry_run(&config));
+    }
+
+    #[test]
+    fn parses_bools_leniently_but_rejects_anything_else() {
+        for (value, expected) in [("1", true), (" ON ", true), ("no", false), ("False", false)] {
+            assert_eq!(parse_bool(value).unwrap(), expected, "{value}");
+        }
+        for value in ["", "maybe", "2", "y"] {
+            let error = parse_bool(value).unwrap_err().to_string();
+            assert!(
+                error.starts_with("Expected true or false"),
+                "{value}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let error = toml::from_str::<Config>("agee = \"2y\"").unwrap_err();
+        assert!(error.message().contains("unknown field `agee`"), "{error}");
+
+        let path = config_file(
+            "unknown",
+            "[[description_rules]]\npattern = \"x\"\naction = \"select\"\nwhy = \"typo\"\n",
+        );
+        let error = Config::load_file(Some(&path)).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(
+            format!("{error:#}").contains("unknown field `why`"),
+            "{error:#}"
+        );
+        assert!(
+            error.to_string().starts_with("Invalid config in"),
+            "{error}"
+        );
+    }
+}
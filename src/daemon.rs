@@ -0,0 +1,341 @@
+//! `repo-archiver serve --http ADDR`: a long-running REST API over the same
+//! listing, archiving and run reports as the CLI, so internal platforms can
+//! build archiving into their own portals.
+//!
+//! Endpoints, all answering JSON:
+//! - `GET /api/candidates?age=2y[&owner=LOGIN...]`: the candidates, as for
+//!   `--age` and `--owner`, filtered as the flags and config say
+//! - `POST /api/runs` `{"repos": ["owner/name", ...], "age": "2y"}`: archive
+//!   those in the background, answering with the run's id; one run at a
+//!   time. Only candidates `GET /api/candidates` would offer as selectable
+//!   for that age (`--age` if none is given) are taken: the rest get a 422
+//! - `GET /api/runs` and `GET /api/runs/ID`: runs since the daemon started,
+//!   each repo's status, and the id of the run's report once it's saved
+//! - `GET /api/reports` and `GET /api/reports/ID`: the saved run reports,
+//!   newest first, including those of CLI runs
+//!
+//! Every request needs `Authorization: Bearer` with the token from
+//! `REPO_ARCHIVER_API_TOKEN`. Runs hold the `--lock-repo` lock while they go.
+//! It shares its HTTP loop with `--web` (see `web`).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    net::TcpListener,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    age::Age,
+    lock, paths, pipeline, preflight,
+    screen::Guards,
+    serve,
+    web::{self, refuse, reply, Request, Response, Settings, Status, Tally},
+    Repo, RepoFilter,
+};
+
+/// The body of a `POST /api/runs`.
+#[derive(Deserialize)]
+struct RunRequest {
+    repos: Vec<String>,
+    /// What the repos must be candidates for, as `--age` takes it
+    #[serde(default)]
+    age: Option<String>,
+}
+
+/// One `POST /api/runs`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    id: usize,
+    repos: Vec<String>,
+    statuses: Vec<Status>,
+    finished: bool,
+    /// Id of the saved run report, once finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<String>,
+}
+
+struct Daemon {
+    settings: Settings,
+    filter: RepoFilter,
+    guards: Guards,
+    /// `--age`, for runs that don't give one
+    age: Option<Age>,
+    /// Owners listed when a request names none
+    owners: Vec<String>,
+    token: String,
+    runs: Mutex<Vec<Run>>,
+}
+
+/// Serve the API on `addr` until the process is stopped.
+pub fn run(
+    addr: &str,
+    settings: Settings,
+    filter: RepoFilter,
+    guards: Guards,
+    age: Option<Age>,
+    owners: Vec<String>,
+    token: Option<String>,
+) -> Result<()> {
+    let Some(token) = token else {
+        anyhow::bail!("serve --http needs an API token: set REPO_ARCHIVER_API_TOKEN");
+    };
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to listen on {addr}"))?;
+    println!(
+        "Serving the API at http://{}{} (Ctrl+C to stop)",
+        listener.local_addr()?,
+        if settings.dry_run { ", dry run" } else { "" }
+    );
+    let daemon = Arc::new(Daemon {
+        settings,
+        filter,
+        guards,
+        age,
+        owners,
+        token,
+        runs: Mutex::new(Vec::new()),
+    });
+    web::listen(&listener, move |request| daemon.handle(request));
+    Ok(())
+}
+
+impl Daemon {
+    fn handle(self: &Arc<Self>, request: &Request) -> Response {
+        if !request
+            .bearer
+            .as_deref()
+            .is_some_and(|token| same(token, &self.token))
+        {
+            return refuse("401 Unauthorized", "Send the API token as a bearer token");
+        }
+        let path: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), path.as_slice()) {
+            ("GET", ["api", "candidates"]) => self.candidates(request),
+            ("POST", ["api", "runs"]) if !request.json => {
+                refuse("415 Unsupported Media Type", "Send application/json")
+            }
+            ("POST", ["api", "runs"]) => {
+                match serde_json::from_slice::<RunRequest>(&request.body) {
+                    Ok(run) if run.repos.is_empty() => refuse("400 Bad Request", "No repos given"),
+                    Ok(run) => {
+                        if let Some(refusal) = self.check(&run) {
+                            return refusal;
+                        }
+                        match self.start(run.repos) {
+                            Ok(id) => reply("202 Accepted", &json!({ "id": id })),
+                            Err(e) => refuse("409 Conflict", &format!("{e:#}")),
+                        }
+                    }
+                    Err(e) => refuse("400 Bad Request", &e.to_string()),
+                }
+            }
+            ("GET", ["api", "runs"]) => reply("200 OK", &json!(*self.runs.lock().unwrap())),
+            ("GET", ["api", "runs", id]) => {
+                let runs = self.runs.lock().unwrap();
+                match id.parse::<usize>().ok().and_then(|id| runs.get(id)) {
+                    Some(run) => reply("200 OK", &json!(run)),
+                    None => refuse("404 Not Found", "No such run"),
+                }
+            }
+            ("GET", ["api", "reports"]) => match reports() {
+                Ok(ids) => reply("200 OK", &json!(ids)),
+                Err(e) => refuse("500 Internal Server Error", &format!("{e:#}")),
+            },
+            ("GET", ["api", "reports", id]) => match report(id) {
+                Some(report) => reply("200 OK", &report),
+                None => refuse("404 Not Found", "No such report"),
+            },
+            _ => refuse("404 Not Found", "Not found"),
+        }
+    }
+
+    fn candidates(&self, request: &Request) -> Response {
+        let param = |name: &'static str| {
+            request
+                .query
+                .iter()
+                .filter(move |(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let Some(age) = param("age").next() else {
+            return refuse("400 Bad Request", "Missing age");
+        };
+        let age = match Age::parse(&age) {
+            Ok(age) => age,
+            Err(e) => return refuse("400 Bad Request", &format!("{e:#}")),
+        };
+        let owners: Vec<String> = param("owner").collect();
+        let owners = if owners.is_empty() {
+            &self.owners
+        } else {
+            &owners
+        };
+        match serve::list(&self.settings.job, owners, age, self.filter, &self.guards) {
+            Ok(repos) => reply("200 OK", &json!(serve::listing(&repos))),
+            Err(e) => refuse("502 Bad Gateway", &format!("{e:#}")),
+        }
+    }
+
+    /// Refuse a run naming repos that aren't selectable candidates, listed
+    /// and screened as for `GET /api/candidates` so naming a repo can't get
+    /// around the policy, keep files or description rules, or that don't
+    /// pass the checks made before a batch starts (see `preflight`).
+    fn check(&self, run: &RunRequest) -> Option<Response> {
+        let age = match run.age.as_deref().map(Age::parse).transpose() {
+            Ok(Some(age)) => age,
+            Ok(None) => match self.age {
+                Some(age) => age,
+                None => return Some(refuse("422 Unprocessable Entity", "Missing age")),
+            },
+            Err(e) => return Some(refuse("422 Unprocessable Entity", &format!("{e:#}"))),
+        };
+        let mut owners: Vec<String> = run
+            .repos
+            .iter()
+            .filter_map(|name| Some(name.split_once('/')?.0.to_string()))
+            .collect();
+        owners.sort_unstable();
+        owners.dedup();
+        // Sizes in KB, for the disk check
+        let selectable: HashMap<String, u64> =
+            match serve::list(&self.settings.job, &owners, age, self.filter, &self.guards) {
+                Ok(repos) => repos
+                    .into_iter()
+                    .filter(Repo::selectable)
+                    .map(|r| (r.name_with_owner, r.disk_usage))
+                    .collect(),
+                Err(e) => return Some(refuse("502 Bad Gateway", &format!("{e:#}"))),
+            };
+        let refused: Vec<&str> = run
+            .repos
+            .iter()
+            .filter(|name| !selectable.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !refused.is_empty() {
+            return Some(refuse(
+                "422 Unprocessable Entity",
+                &format!(
+                    "Not candidates that may be archived: {}",
+                    refused.join(", ")
+                ),
+            ));
+        }
+
+        let batch: Vec<(&str, u64)> = run
+            .repos
+            .iter()
+            .map(|name| (name.as_str(), selectable[name]))
+            .collect();
+        match preflight::check_batch(&batch, self.settings.dry_run, &self.settings.job) {
+            Ok(refused) if refused.is_empty() => None,
+            Ok(refused) => {
+                let refused: Vec<String> = refused
+                    .iter()
+                    .map(|(name, why)| format!("{name} ({why})"))
+                    .collect();
+                Some(refuse(
+                    "422 Unprocessable Entity",
+                    &format!("Can't archive {}", refused.join(", ")),
+                ))
+            }
+            Err(e) => Some(refuse("507 Insufficient Storage", &format!("{e:#}"))),
+        }
+    }
+
+    /// Start archiving `repos` in the background, unless a run is going.
+    fn start(self: &Arc<Self>, repos: Vec<String>) -> Result<usize> {
+        // A run of our own is refused before the lock issue is asked for,
+        // so it isn't reported as held by another machine
+        if self.runs.lock().unwrap().iter().any(|run| !run.finished) {
+            anyhow::bail!("A run is already going");
+        }
+        let lock = lock::for_batch(
+            self.settings.lock_repo.as_deref(),
+            self.settings.dry_run,
+            &self.settings.job,
+        )?;
+        let id = {
+            let mut runs = self.runs.lock().unwrap();
+            if runs.iter().any(|run| !run.finished) {
+                anyhow::bail!("A run is already going");
+            }
+            let id = runs.len();
+            runs.push(Run {
+                id,
+                statuses: vec![Status::pending(); repos.len()],
+                repos: repos.clone(),
+                finished: false,
+                report: None,
+            });
+            id
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let batch = repos.iter().cloned().enumerate().collect();
+        pipeline::start(batch, self.settings.dry_run, &self.settings.job, None, &tx);
+        drop(tx);
+        let daemon = Arc::clone(self);
+        thread::spawn(move || {
+            let mut tally = Tally::new(&daemon.settings);
+            for result in rx {
+                let mut runs = daemon.runs.lock().unwrap();
+                tally.record(result, &repos, &mut runs[id].statuses);
+            }
+            let report = tally
+                .finish(&daemon.settings)
+                .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()));
+            drop(lock);
+            let mut runs = daemon.runs.lock().unwrap();
+            runs[id].finished = true;
+            runs[id].report = report;
+        });
+        Ok(id)
+    }
+}
+
+/// Compare tokens without stopping at the first difference, so response
+/// times don't give away how much of a guess was right.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Ids of the saved run reports, newest first.
+fn reports() -> Result<Vec<String>> {
+    let dir = paths::reports_dir().context("No home directory to keep run reports in")?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut ids: Vec<String> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            Some(name.strip_suffix(".json")?.to_string())
+        })
+        .collect();
+    // Named by when they finished, so newest first is reverse order
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(ids)
+}
+
+fn report(id: &str) -> Option<Value> {
+    // Ids are timestamps; anything else could reach outside the directory
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+        || id.contains("..")
+    {
+        return None;
+    }
+    let path = paths::reports_dir()?.join(format!("{id}.json"));
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
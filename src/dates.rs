@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use chrono::{format::StrftimeItems, Locale, NaiveDate};
+use std::env;
+
+/// How dates are rendered in the age picker and the repo table.
+#[derive(Debug, Clone)]
+pub enum DateFormat {
+    /// Explicit strftime pattern from `--date-format`
+    Custom(String),
+    /// The locale's preferred date representation (`%x`)
+    Locale(Locale),
+    /// No usable locale: unambiguous `YYYY-MM-DD`
+    Iso,
+}
+
+impl DateFormat {
+    /// Use `pattern` if given, otherwise detect the locale from the environment.
+    pub fn resolve(pattern: Option<String>) -> Result<Self> {
+        if let Some(pattern) = pattern {
+            StrftimeItems::new(&pattern)
+                .parse()
+                .with_context(|| format!("Invalid date format: {pattern}"))?;
+            return Ok(Self::Custom(pattern));
+        }
+        Ok(system_locale().map_or(Self::Iso, Self::Locale))
+    }
+
+    pub fn format(&self, date: NaiveDate) -> String {
+        match self {
+            Self::Custom(pattern) => date.format(pattern).to_string(),
+            Self::Locale(locale) => date.format_localized("%x", *locale).to_string(),
+            Self::Iso => date.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Locale used for dates, following the usual `LC_ALL` > `LC_TIME` > `LANG` precedence.
+///
+/// "C" and "POSIX" are treated as unset so they get ISO dates rather than `%m/%d/%y`.
+fn system_locale() -> Option<Locale> {
+    let value = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+
+    // Strip encoding and modifier, e.g. "de_DE.UTF-8" or "de_DE@euro"
+    let name = value.split(['.', '@']).next().unwrap_or_default();
+    if name == "C" || name == "POSIX" {
+        return None;
+    }
+    Locale::try_from(name).ok()
+}
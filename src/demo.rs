@@ -0,0 +1,134 @@
+//! `--demo`: made-up repos and pretend archiving, for trying the whole flow
+//! (or taking screenshots, or smoke-testing a build) without a GitHub
+//! account.
+
+use anyhow::Result;
+use chrono::{Days, Months, Utc};
+use std::{collections::HashSet, thread, time::Duration};
+
+use crate::{cache::RepoList, provider::Provider, Repo};
+
+pub const OWNER: &str = "demo";
+
+/// How long pretend archiving takes per repo, so progress is visible
+const ARCHIVE_DELAY: Duration = Duration::from_millis(400);
+
+/// Archiving this one fails, to show what failures look like
+const LOCKED: &str = "demo/payments-legacy";
+
+/// Name, description, years since creation, months since last push, stars
+const REPOS: [(&str, &str, u32, u32, u64); 24] = [
+    ("dotfiles", "My shell and editor config", 9, 1, 12),
+    (
+        "jquery-carousel",
+        "A tiny jQuery image carousel",
+        11,
+        120,
+        3,
+    ),
+    ("angular1-todo", "TodoMVC in AngularJS", 10, 110, 0),
+    ("hackathon-2016", "Weekend hackathon project", 9, 108, 1),
+    (
+        "payments-legacy",
+        "Old payments service, replaced by payments-v2",
+        8,
+        40,
+        0,
+    ),
+    ("payments-v2", "Payments service", 4, 0, 6),
+    ("blog", "Personal blog (Jekyll)", 8, 30, 2),
+    ("blog-hugo", "Personal blog, now on Hugo", 3, 2, 4),
+    ("coffee-bot", "Slack bot that orders coffee", 7, 70, 9),
+    ("resume", "", 7, 14, 0),
+    ("advent-of-code-2019", "Solutions in Rust", 6, 70, 5),
+    ("advent-of-code-2023", "Solutions in Zig", 2, 22, 7),
+    ("react-playground", "Trying out hooks", 6, 60, 0),
+    ("graphql-experiments", "", 6, 66, 1),
+    ("terraform-modules", "Shared Terraform modules", 5, 3, 21),
+    ("k8s-sandbox", "Minikube experiments", 5, 50, 0),
+    ("thesis", "Master's thesis (LaTeX)", 5, 48, 0),
+    ("ml-course", "Coursework for an ML course", 4, 44, 2),
+    ("vscode-theme", "A dark VS Code theme", 4, 12, 38),
+    ("empty-repo", "", 3, 36, 0),
+    (
+        "rust-cli-template",
+        "Template for small Rust CLIs",
+        2,
+        4,
+        15,
+    ),
+    ("scratch", "Throwaway experiments", 2, 20, 0),
+    ("homelab", "Ansible for the home server", 1, 0, 3),
+    ("new-idea", "", 0, 0, 0),
+];
+
+/// The made-up repo list, dated relative to today so every age finds some.
+pub fn repo_list() -> RepoList {
+    let now = Utc::now();
+    let repos = REPOS
+        .iter()
+        .map(|&(name, description, years, months, stars)| {
+            let created_at = now - Months::new(years * 12) - Days::new(u64::from(years) * 17);
+            let pushed_at = (now - Months::new(months)).max(created_at);
+            Repo {
+                name: name.to_string(),
+                name_with_owner: format!("{OWNER}/{name}"),
+                created_at,
+                // "empty-repo" was never pushed to after being created
+                pushed_at: if name == "empty-repo" {
+                    created_at
+                } else {
+                    pushed_at
+                },
+                description: Some(description.to_string()).filter(|d| !d.is_empty()),
+                stargazer_count: stars,
+                disk_usage: 100 + stars * 250,
+                ..Repo::default()
+            }
+        })
+        .collect();
+    RepoList {
+        fetched_at: now,
+        repos,
+        pinned: HashSet::from([format!("{OWNER}/dotfiles")]),
+    }
+}
+
+/// Pretends to archive the made-up repos.
+pub struct Demo;
+
+impl Provider for Demo {
+    fn list_repos(
+        &self,
+        _owner: Option<&str>,
+        on_page: &mut dyn FnMut(usize),
+    ) -> Result<Vec<Repo>> {
+        let repos = repo_list().repos;
+        on_page(repos.len());
+        Ok(repos)
+    }
+
+    fn archive_repo(&self, name_with_owner: &str) -> Result<()> {
+        thread::sleep(ARCHIVE_DELAY);
+        if name_with_owner == LOCKED {
+            anyhow::bail!("demo: archiving is blocked for {LOCKED}, to show a failure");
+        }
+        Ok(())
+    }
+
+    fn unarchive_repo(&self, _name_with_owner: &str) -> Result<()> {
+        thread::sleep(ARCHIVE_DELAY);
+        Ok(())
+    }
+
+    fn pinned(&self, _owner: Option<&str>) -> Result<HashSet<String>> {
+        Ok(repo_list().pinned)
+    }
+
+    fn dry_run(&self, name_with_owner: &str) -> Result<String> {
+        if name_with_owner == LOCKED {
+            anyhow::bail!("would fail: demo: archiving is blocked for {LOCKED}");
+        }
+        Ok("would archive (demo)".to_string())
+    }
+}
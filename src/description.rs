@@ -0,0 +1,181 @@
+//! Bulk edits of repo descriptions, often done alongside archiving (e.g.
+//! prefixing "[Archived]" or pointing at a successor repo).
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+use crate::{error, gh};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Op {
+    #[default]
+    Prepend,
+    Append,
+    Replace,
+}
+
+impl Op {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Prepend => Self::Append,
+            Self::Append => Self::Replace,
+            Self::Replace => Self::Prepend,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Prepend => "prepend",
+            Self::Append => "append",
+            Self::Replace => "replace",
+        })
+    }
+}
+
+/// `op:template`, where the template may use `{name}`, `{description}` (the
+/// current one) and `{date}` (today, as YYYY-MM-DD).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Edit {
+    pub op: Op,
+    pub template: String,
+}
+
+impl FromStr for Edit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (op, template) = s.split_once(':').with_context(|| {
+            format!("Invalid description edit '{s}'. Use e.g. 'prepend:[Archived] '")
+        })?;
+        let op = Op::from_str(op.trim(), true).map_err(|_| {
+            anyhow::anyhow!("Unknown description edit '{op}'. Use prepend, append or replace")
+        })?;
+        Ok(Self {
+            op,
+            template: template.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Edit {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl Edit {
+    /// The new description for a repo that currently has `current`, which
+    /// is left as it is if it already starts (or ends, when appending) with
+    /// the text, so running the edit again doesn't add it twice.
+    pub fn apply(&self, name_with_owner: &str, current: Option<&str>) -> String {
+        let name = name_with_owner
+            .split_once('/')
+            .map_or(name_with_owner, |(_, name)| name);
+        let current = current.unwrap_or_default();
+        let text = self
+            .template
+            .replace("{name}", name)
+            .replace("{description}", current)
+            .replace("{date}", &Utc::now().format("%Y-%m-%d").to_string());
+        match self.op {
+            Op::Prepend if current.starts_with(&text) => current.to_string(),
+            Op::Prepend => format!("{text}{current}"),
+            Op::Append if current.ends_with(&text) => current.to_string(),
+            Op::Append => format!("{current}{text}"),
+            Op::Replace => text,
+        }
+    }
+}
+
+/// Shell commands that apply `edit` to the repo, for `apply-plan --script`.
+pub fn script(edit: &Edit, name_with_owner: &str) -> String {
+    // Stands in for the current description until the rest is escaped
+    const CURRENT: &str = "\0";
+
+    let escaped = edit
+        .apply(name_with_owner, Some(CURRENT))
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+        .replace(CURRENT, "$desc");
+    format!(
+        "desc=$(gh repo view {name_with_owner} --json description --jq .description)\n\
+         gh repo edit {name_with_owner} --description \"{escaped}\"\n"
+    )
+}
+
+/// The repo's description as it is on GitHub now.
+pub fn fetch(name_with_owner: &str, token: Option<&str>) -> Result<Option<String>> {
+    let output = gh(token)
+        .args([
+            "repo",
+            "view",
+            name_with_owner,
+            "--json",
+            "description",
+            "--jq",
+            ".description",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    let stdout = error::check(output).context("Failed to fetch the description")?;
+    let description = String::from_utf8_lossy(&stdout).trim().to_string();
+    Ok(Some(description).filter(|d| !d.is_empty()))
+}
+
+pub fn set(name_with_owner: &str, description: &str, token: Option<&str>) -> Result<()> {
+    let output = gh(token)
+        .args([
+            "repo",
+            "edit",
+            name_with_owner,
+            "--description",
+            description,
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    error::check(output).context("Failed to set the description")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_fill_in_the_template() {
+        let edit: Edit = "prepend:[Archived] ".parse().unwrap();
+        assert_eq!(edit.apply("octo/old", Some("A tool")), "[Archived] A tool");
+        let edit: Edit = "append: (see {name}-next)".parse().unwrap();
+        assert_eq!(
+            edit.apply("octo/old", Some("A tool")),
+            "A tool (see old-next)"
+        );
+        let edit: Edit = "replace:Moved; was: {description}".parse().unwrap();
+        assert_eq!(edit.apply("octo/old", None), "Moved; was: ");
+    }
+
+    #[test]
+    fn edits_already_made_are_not_made_again() {
+        for (edit, current) in [
+            ("prepend:[Archived] ", "A tool"),
+            ("append: (archived {date})", "A tool"),
+            ("replace:Archived", "A tool"),
+        ] {
+            let edit: Edit = edit.parse().unwrap();
+            let once = edit.apply("octo/old", Some(current));
+            assert_eq!(edit.apply("octo/old", Some(&once)), once);
+        }
+    }
+}
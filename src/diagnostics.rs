@@ -0,0 +1,101 @@
+//! The hidden `F12` overlay: how long frames take to draw, how long key
+//! presses wait for the frame that shows them, how many worker results pile
+//! up between frames, and how much memory the process uses. Meant for
+//! performance reports from users with huge repo lists.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
+
+/// Frames and key presses the averages and maximums cover
+const WINDOW: usize = 60;
+
+#[derive(Default)]
+pub struct Diagnostics {
+    pub visible: bool,
+    frames: VecDeque<Duration>,
+    latencies: VecDeque<Duration>,
+    /// Results handled between the last two frames
+    backlog: usize,
+    max_backlog: usize,
+    /// When the key press the next frame will show was read
+    pending_key: Option<Instant>,
+}
+
+fn push(window: &mut VecDeque<Duration>, value: Duration) {
+    if window.len() == WINDOW {
+        window.pop_front();
+    }
+    window.push_back(value);
+}
+
+/// "avg 1.2ms, max 4.0ms" over `window`.
+fn summary(window: &VecDeque<Duration>) -> String {
+    let Some(max) = window.iter().max() else {
+        return "-".to_string();
+    };
+    let avg = window.iter().sum::<Duration>() / window.len() as u32;
+    format!(
+        "avg {:.1}ms, max {:.1}ms",
+        avg.as_secs_f64() * 1000.0,
+        max.as_secs_f64() * 1000.0
+    )
+}
+
+impl Diagnostics {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// A frame was drawn in `took`.
+    pub fn frame(&mut self, took: Duration) {
+        push(&mut self.frames, took);
+        if let Some(at) = self.pending_key.take() {
+            push(&mut self.latencies, at.elapsed());
+        }
+    }
+
+    /// A key press was read; it shows once the next frame is drawn.
+    pub fn key(&mut self) {
+        self.pending_key.get_or_insert_with(Instant::now);
+    }
+
+    /// `n` worker results were waiting when the loop came round.
+    pub fn backlog(&mut self, n: usize) {
+        self.backlog = n;
+        self.max_backlog = self.max_backlog.max(n);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("Frame:     {}", summary(&self.frames)),
+            format!("Key→frame: {}", summary(&self.latencies)),
+            format!(
+                "Backlog:   {} results (max {})",
+                self.backlog, self.max_backlog
+            ),
+            format!(
+                "Memory:    {}",
+                resident_kib().map_or_else(
+                    || "unknown".to_string(),
+                    |kib| format!("{:.1} MiB", kib as f64 / 1024.0)
+                )
+            ),
+        ]
+    }
+}
+
+/// Resident memory of this process, where the OS tells (Linux).
+fn resident_kib() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
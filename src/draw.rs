@@ -0,0 +1,1180 @@
+//! Drawing the TUI: the repo table, the help bar, and the modals and screens
+//! on top of it.
+
+use chrono::Utc;
+use ratatui::{
+    prelude::*,
+    symbols,
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table, Wrap,
+    },
+};
+use std::time::Instant;
+
+use crate::{
+    app::{App, Column, Mode, RepoStatus},
+    compare::Decision,
+    forks::ForkStatus,
+    pipeline::Action,
+    preflight,
+    repo::Repo,
+    report, rules,
+    stats::{self, Snapshot},
+};
+
+pub fn ui(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Title
+            Constraint::Min(10),   // Table
+            Constraint::Length(3), // Help/Status
+        ])
+        .split(f.area());
+
+    // Title
+    let title = match app.mode {
+        Mode::Selecting
+        | Mode::Import
+        | Mode::Describe
+        | Mode::Stats
+        | Mode::ConfirmModal
+        | Mode::Order => {
+            let missing = match app.sheet_missing.as_deref() {
+                Some(missing) if !missing.is_empty() => {
+                    format!(
+                        "· {} marked archive in sheet but not candidates ",
+                        missing.len()
+                    )
+                }
+                _ => String::new(),
+            };
+            format!(
+                " Repo Archiver {} ({} selected) {missing}",
+                mode_tag(app),
+                app.selected_count()
+            )
+        }
+        Mode::Archiving => {
+            let done = app
+                .statuses
+                .iter()
+                .filter(|s| matches!(s, RepoStatus::Done | RepoStatus::Failed(_)))
+                .count();
+            let total = app.selected_count();
+            let cool_down = app
+                .cool_down_until
+                .map(|until| {
+                    let left = until.saturating_duration_since(Instant::now());
+                    format!("· cooling down, next chunk in {}s ", left.as_secs())
+                })
+                .unwrap_or_default();
+            let elapsed = app
+                .batch_started
+                .map(|started| format!("· {} ", report::format_duration(started.elapsed())))
+                .unwrap_or_default();
+            format!(
+                " {} {} ({}/{}) {elapsed}{cool_down}",
+                app.job.action.doing(),
+                mode_tag(app),
+                done,
+                total
+            )
+        }
+        Mode::Done if app.queues() => " All repos queued! ".to_string(),
+        Mode::Done => format!(" All repos {}! ", app.job.action.done()),
+    };
+    let title_block = Paragraph::new(title)
+        .style(Style::default().fg(Color::Cyan).bold())
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title_block, chunks[0]);
+
+    // Table
+    let header_cells = std::iter::once("Status")
+        .chain(app.columns.iter().map(|c| c.header()))
+        .map(|h| Cell::from(h).style(Style::default().fg(Color::Yellow).bold()));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    // Repos going through several steps show how far along they are
+    let steps = if app.dry_run { 1 } else { app.job.step_count() };
+    let rows = app.repos.iter().enumerate().map(|(i, repo)| {
+        let status_cell = match &app.statuses[i] {
+            RepoStatus::Idle => {
+                if app.selected[i] {
+                    Cell::from("✓").style(Style::default().fg(Color::Green))
+                } else {
+                    Cell::from(" ")
+                }
+            }
+            RepoStatus::Pending => Cell::from("⏳").style(Style::default().fg(Color::Yellow)),
+            RepoStatus::BackingUp(done) if steps > 1 => {
+                Cell::from(progress_bar(*done, steps)).style(Style::default().fg(Color::LightBlue))
+            }
+            RepoStatus::BackingUp(_) => {
+                Cell::from(app.spinner()).style(Style::default().fg(Color::LightBlue))
+            }
+            RepoStatus::Archiving(done) if steps > 1 => {
+                Cell::from(progress_bar(*done, steps)).style(Style::default().fg(Color::Cyan))
+            }
+            RepoStatus::Archiving(_) => {
+                Cell::from(app.spinner()).style(Style::default().fg(Color::Cyan))
+            }
+            RepoStatus::Closing { closed, total, .. } => {
+                Cell::from(format!("{closed}/{total}")).style(Style::default().fg(Color::Cyan))
+            }
+            RepoStatus::Done => Cell::from("✓").style(Style::default().fg(Color::Green)),
+            RepoStatus::Failed(_) => Cell::from("✗").style(Style::default().fg(Color::Red)),
+        };
+
+        let style = match &app.statuses[i] {
+            RepoStatus::Done => Style::default().fg(Color::Green),
+            RepoStatus::Failed(_) => Style::default().fg(Color::Red),
+            RepoStatus::BackingUp(_) => Style::default().fg(Color::LightBlue),
+            RepoStatus::Archiving(_) | RepoStatus::Closing { .. } => {
+                Style::default().fg(Color::Cyan)
+            }
+            _ if app.selected[i] => Style::default().fg(Color::White),
+            _ => Style::default().fg(Color::DarkGray),
+        };
+
+        let cells = app.columns.iter().map(|column| match column {
+            Column::Name => name_cell(app, i),
+            Column::Created => Cell::from(app.date_format.format(repo.created_at.date_naive())),
+            Column::Pushed => Cell::from(app.date_format.format(repo.pushed_at.date_naive())),
+            Column::Description => Cell::from(
+                repo.description
+                    .as_deref()
+                    .unwrap_or("-")
+                    .chars()
+                    .take(50)
+                    .collect::<String>(),
+            ),
+            Column::Notes => Cell::from(repo.note.clone().unwrap_or_default()),
+            Column::Time => Cell::from(
+                repo.took
+                    .or_else(|| app.stopwatch.running(i))
+                    .map(report::format_duration)
+                    .unwrap_or_default(),
+            ),
+            Column::Ci => match repo.ci_state.as_deref() {
+                Some("SUCCESS") => Cell::from("✓").style(Style::default().fg(Color::Green)),
+                _ if repo.ci_failing() => Cell::from("✗").style(Style::default().fg(Color::Red)),
+                _ => Cell::from("–").style(Style::default().fg(Color::DarkGray)),
+            },
+        });
+
+        Row::new(std::iter::once(status_cell).chain(cells))
+            .style(style)
+            .height(1)
+    });
+
+    let widths = std::iter::once(Constraint::Length(6)) // Status
+        .chain(app.columns.iter().map(|c| c.constraint()));
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(" Repos "))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("▶ ");
+
+    f.render_stateful_widget(table, chunks[1], &mut app.state);
+
+    // Help bar
+    let help_text = match app.mode {
+        Mode::Selecting => {
+            "↑/↓ or j/k: Navigate | Space/Tab: Toggle | P: Priority | i: Import CSV | e: Edit descriptions | s: Stats | L: Legend | Enter: Confirm | q: Quit"
+        }
+        Mode::Stats => "Esc: Close",
+        Mode::Import => "Enter: Import | Esc: Cancel",
+        Mode::Describe => "Tab: Operation | Enter: Apply now | Ctrl+A: Apply when archiving | Esc: Cancel",
+        Mode::ConfirmModal => app.confirm_help(),
+        Mode::Order => {
+            "↑/↓ or j/k: Navigate | Shift+↑/↓ or J/K: Move | P: Priority | s: Smallest first | Enter: Start | Esc: Back"
+        }
+        Mode::Archiving => &format!(
+            "{} | ↑/↓ or j/k: Scroll | L: Legend | q: Quit",
+            pool_status(app)
+        ),
+        Mode::Done => "All done! Press q or Enter to exit.",
+    };
+
+    let help = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::Gray))
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(help, chunks[2]);
+
+    // Confirmation modal
+    if app.mode == Mode::ConfirmModal {
+        render_modal(f, app);
+    }
+    if app.mode == Mode::Order {
+        render_order(f, app);
+    }
+    if app.mode == Mode::Import {
+        render_import(f, app);
+    }
+    if app.mode == Mode::Describe {
+        render_describe(f, app);
+    }
+    if app.mode == Mode::Stats {
+        render_stats(f, app);
+    }
+    if app.legend && matches!(app.mode, Mode::Selecting | Mode::Archiving) {
+        render_legend(f, chunks[1]);
+    }
+    if app.diagnostics.visible {
+        render_diagnostics(f, app);
+    }
+}
+
+/// What's shown, its color and what it means.
+type LegendEntry = (&'static str, Color, &'static str);
+
+/// Status glyphs, row colors and name markers, with what they mean. Keep in
+/// sync with `ui` and `name_cell`.
+const LEGEND: [(&str, &[LegendEntry]); 3] = [
+    (
+        "Status",
+        &[
+            ("✓", Color::Green, "selected, or archived"),
+            ("⏳", Color::Yellow, "waiting for a free worker"),
+            ("⠋", Color::LightBlue, "being backed up"),
+            ("⠋", Color::Cyan, "being archived"),
+            ("▰▱", Color::Cyan, "steps done, when there are several"),
+            ("✗", Color::Red, "failed"),
+        ],
+    ),
+    (
+        "Rows",
+        &[
+            ("white", Color::White, "selected"),
+            ("gray", Color::DarkGray, "not selected"),
+            ("light blue", Color::LightBlue, "backing up"),
+            ("cyan", Color::Cyan, "archiving"),
+            ("green", Color::Green, "done"),
+            ("red", Color::Red, "failed"),
+        ],
+    ),
+    (
+        "Markers",
+        &[
+            ("blue", Color::Blue, "kind of repo: template, mirror, fork"),
+            (
+                "yellow",
+                Color::Yellow,
+                "worth a second look: pinned, deployed, used by, app, site up",
+            ),
+            (
+                "red",
+                Color::Red,
+                "shouldn't be archived: no admin, policy, sheet, protected",
+            ),
+            ("gray", Color::DarkGray, "site down"),
+            ("⌂", Color::Magenta, "the checkout you ran from"),
+            (
+                "green",
+                Color::Green,
+                "priority: archived first; rule: preselected",
+            ),
+        ],
+    ),
+];
+
+/// The `L` legend, in the bottom right corner of the table.
+fn render_legend(f: &mut Frame, area: Rect) {
+    let mut lines = Vec::new();
+    for (heading, entries) in LEGEND {
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(heading, Style::default().bold())));
+        lines.extend(entries.iter().map(|(key, color, meaning)| {
+            Line::from(vec![
+                Span::styled(format!("  {key:<7}"), Style::default().fg(*color)),
+                Span::raw(*meaning),
+            ])
+        }));
+    }
+    let width = 70.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let overlay = Rect {
+        x: area.x + area.width - width,
+        y: area.y + area.height - height,
+        width,
+        height,
+    };
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Gray))
+                .title(" Legend (L) "),
+        ),
+        overlay,
+    );
+}
+
+/// The `F12` overlay, in the top right corner over everything else.
+fn render_diagnostics(f: &mut Frame, app: &App) {
+    let lines: Vec<Line> = app
+        .diagnostics
+        .lines()
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    let area = f.area();
+    let width = 46.min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let overlay = Rect {
+        x: area.width - width,
+        y: 0,
+        width,
+        height,
+    };
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Magenta))
+                .title(" Diagnostics (F12) "),
+        ),
+        overlay,
+    );
+}
+
+/// Busy workers per pool, e.g. "Backup 2/2 · Archive 1/1".
+fn pool_status(app: &App) -> String {
+    let Some(pools) = &app.pools else {
+        return String::new();
+    };
+    let archive = if pools.archive.limit() < pools.archive.size() {
+        format!(
+            "Archive {}/{} (backed off from {})",
+            pools.archive.busy(),
+            pools.archive.limit(),
+            pools.archive.size()
+        )
+    } else {
+        format!("Archive {}/{}", pools.archive.busy(), pools.archive.size())
+    };
+    match &pools.backup {
+        Some(backup) => format!("Backup {}/{} · {archive}", backup.busy(), backup.size()),
+        None => archive,
+    }
+}
+
+/// Repo name followed by markers for anything that deserves a second look.
+/// A bar with a filled segment for each of `steps` that's `done`, e.g.
+/// "▰▰▱▱".
+fn progress_bar(done: usize, steps: usize) -> String {
+    let done = done.min(steps);
+    format!("{}{}", "▰".repeat(done), "▱".repeat(steps - done))
+}
+
+fn name_cell(app: &App, i: usize) -> Cell<'static> {
+    let repo = &app.repos[i];
+    let mut spans = vec![Span::raw(repo.name.clone())];
+    if repo.is_template {
+        spans.push(Span::styled(
+            " [template]",
+            Style::default().fg(Color::Blue),
+        ));
+    }
+    if repo.is_mirror {
+        spans.push(Span::styled(" [mirror]", Style::default().fg(Color::Blue)));
+    }
+    if repo.is_pinned {
+        spans.push(Span::styled(
+            " [pinned]",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    match &repo.fork_status {
+        Some(ForkStatus { upstream: None, .. }) => spans.push(Span::styled(
+            " [fork: upstream gone]",
+            Style::default().fg(Color::Blue),
+        )),
+        Some(ForkStatus {
+            upstream: Some(upstream),
+            ahead,
+            behind,
+            open_prs,
+            branches_ahead,
+        }) => {
+            let prs = match open_prs {
+                0 => String::new(),
+                1 => ", 1 open PR".to_string(),
+                n => format!(", {n} open PRs"),
+            };
+            let branches = match branches_ahead.len() {
+                0 => String::new(),
+                1 => format!(", {} ahead too", branches_ahead[0]),
+                n => format!(", {n} other branches ahead"),
+            };
+            spans.push(Span::styled(
+                format!(" [fork of {upstream}: {ahead} ahead, {behind} behind{branches}{prs}]"),
+                Style::default().fg(Color::Blue),
+            ));
+        }
+        None if repo.is_fork => {
+            spans.push(Span::styled(" [fork]", Style::default().fg(Color::Blue)));
+        }
+        None => {}
+    }
+    if app.sheet_missing.is_some() {
+        match &repo.decision {
+            Some(Decision::Archive) => {}
+            Some(Decision::Other(decision)) => spans.push(Span::styled(
+                format!(" [sheet: {decision}]"),
+                Style::default().fg(Color::Red),
+            )),
+            None => spans.push(Span::styled(
+                " [not in sheet]",
+                Style::default().fg(Color::Red),
+            )),
+        }
+    }
+    if repo.lacks_admin() {
+        spans.push(Span::styled(" [no admin]", Style::default().fg(Color::Red)));
+    }
+    if let Some(deployment) = &repo.recent_deployment {
+        let days = (Utc::now() - deployment.created_at).num_days();
+        spans.push(Span::styled(
+            match &deployment.environment {
+                Some(env) => format!(" [deployed to {env} {days}d ago]"),
+                None => format!(" [deployed {days}d ago]"),
+            },
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if !repo.consumers.is_empty() {
+        spans.push(Span::styled(
+            format!(
+                " [used by {} repo{}]",
+                repo.consumers.len(),
+                if repo.consumers.len() == 1 { "" } else { "s" }
+            ),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(app) = &repo.app {
+        spans.push(Span::styled(
+            format!(" [app: {app}]"),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    match repo.homepage_alive {
+        Some(true) => spans.push(Span::styled(
+            " [site up]",
+            Style::default().fg(Color::Yellow),
+        )),
+        Some(false) => spans.push(Span::styled(
+            " [site down]",
+            Style::default().fg(Color::DarkGray),
+        )),
+        None => {}
+    }
+    if repo.policy_denied {
+        spans.push(Span::styled(
+            " [policy: deny]",
+            Style::default().fg(Color::Red),
+        ));
+    }
+    match &repo.rule {
+        Some(rules::Matched {
+            effect: rules::Effect::Protect,
+            pattern,
+        }) => spans.push(Span::styled(
+            format!(" [protected: {pattern}]"),
+            Style::default().fg(Color::Red),
+        )),
+        Some(rules::Matched {
+            effect: rules::Effect::Select,
+            pattern,
+        }) => spans.push(Span::styled(
+            format!(" [rule: {pattern}]"),
+            Style::default().fg(Color::Green),
+        )),
+        None => {}
+    }
+    if repo.priority {
+        spans.push(Span::styled(
+            " [priority]",
+            Style::default().fg(Color::Green).bold(),
+        ));
+    }
+    if app.is_current(i) {
+        spans.push(Span::styled(
+            " ⌂ you are here",
+            Style::default().fg(Color::Magenta).bold(),
+        ));
+    }
+    Cell::from(Line::from(spans))
+}
+
+/// "[DEMO]" and/or "[DRY RUN]" for the title bar.
+fn mode_tag(app: &App) -> String {
+    let mut tags = Vec::new();
+    if app.job.demo {
+        tags.push("[DEMO]");
+    }
+    if app.dry_run {
+        tags.push("[DRY RUN]");
+    }
+    tags.join(" ")
+}
+
+fn render_modal(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let count = app.selected_count();
+    let can_proceed = app.can_proceed();
+
+    // Build button styles
+    let (cancel_style, proceed_style) = if app.modal_button == 0 {
+        (
+            Style::default().fg(Color::Black).bg(Color::White).bold(),
+            Style::default().fg(Color::DarkGray),
+        )
+    } else {
+        (
+            Style::default().fg(Color::DarkGray),
+            Style::default()
+                .fg(Color::Black)
+                .bg(if app.job.action == Action::Delete {
+                    Color::Red
+                } else {
+                    Color::Green
+                })
+                .bold(),
+        )
+    };
+    let proceed_style = if can_proceed {
+        proceed_style
+    } else {
+        Style::default().fg(Color::DarkGray).crossed_out()
+    };
+
+    let buttons = Line::from(vec![
+        Span::styled(" [ CANCEL ] ", cancel_style),
+        Span::raw("     "),
+        Span::styled(" [ PROCEED ] ", proceed_style),
+    ]);
+
+    let mut text = vec![
+        Line::from(""),
+        Line::from(format!(
+            "{} {} repo{}?",
+            if app.queues() {
+                "Queue"
+            } else {
+                app.job.action.verb()
+            },
+            count,
+            if count == 1 { "" } else { "s" }
+        ))
+        .style(Style::default().bold())
+        .centered(),
+        Line::from(""),
+    ];
+    if let Some(repo) = app.selected_current() {
+        text.push(
+            Line::from(format!("⌂ Includes {}, the repo you are in!", repo.name))
+                .style(Style::default().fg(Color::Magenta).bold())
+                .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    let deployed = (0..app.repos.len())
+        .filter(|&i| app.selected[i] && app.repos[i].recent_deployment.is_some())
+        .count();
+    if deployed > 0 {
+        text.push(
+            Line::from(format!(
+                "{deployed} of them deployed recently and may be in production"
+            ))
+            .style(Style::default().fg(Color::Yellow).bold())
+            .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    let consumed = (0..app.repos.len())
+        .filter(|&i| app.selected[i] && !app.repos[i].consumers.is_empty())
+        .count();
+    if consumed > 0 {
+        text.push(
+            Line::from(format!(
+                "{consumed} of them are used as workflows/actions by other repos"
+            ))
+            .style(Style::default().fg(Color::Yellow).bold())
+            .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    let apps = (0..app.repos.len())
+        .filter(|&i| app.selected[i] && app.repos[i].app.is_some())
+        .count();
+    if apps > 0 {
+        text.push(
+            Line::from(format!(
+                "{apps} of them look like the source of a GitHub App, which archiving may break"
+            ))
+            .style(Style::default().fg(Color::Yellow).bold())
+            .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    let live_sites = (0..app.repos.len())
+        .filter(|&i| app.selected[i] && app.repos[i].homepage_alive == Some(true))
+        .count();
+    if live_sites > 0 {
+        text.push(
+            Line::from(format!(
+                "{live_sites} of them link to a homepage that's still up"
+            ))
+            .style(Style::default().fg(Color::Yellow).bold())
+            .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    if app.checking.is_some() {
+        text.push(
+            Line::from(format!("{} Checking permissions...", app.spinner()))
+                .style(Style::default().fg(Color::Yellow))
+                .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    match &app.permission_check {
+        Some(Ok(0)) | None => {}
+        Some(Ok(deselected)) => {
+            text.push(
+                Line::from(format!(
+                    "Deselected {deselected} repo{} you don't have admin on",
+                    if *deselected == 1 { "" } else { "s" }
+                ))
+                .style(Style::default().fg(Color::Yellow).bold())
+                .centered(),
+            );
+            text.push(Line::from(""));
+        }
+        Some(Err(e)) => {
+            text.push(
+                Line::from(format!("Couldn't check permissions: {e}"))
+                    .style(Style::default().fg(Color::Yellow))
+                    .centered(),
+            );
+            text.push(Line::from(""));
+        }
+    }
+    if let Some(check) = &app.disk_check {
+        let (message, color) = if check.insufficient() {
+            ("Not enough disk space for backups!", Color::Red)
+        } else if check.tight() {
+            ("Backups will nearly fill the disk", Color::Yellow)
+        } else {
+            ("Backups fit on disk", Color::Green)
+        };
+        text.push(
+            Line::from(message)
+                .style(Style::default().fg(color).bold())
+                .centered(),
+        );
+        text.push(
+            Line::from(format!(
+                "Need ~{}, {} free",
+                preflight::format_size(check.needed),
+                preflight::format_size(check.available)
+            ))
+            .style(Style::default().fg(color))
+            .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    // Spell out the whole batch when archiving isn't the only step
+    let bytes = (0..app.repos.len())
+        .filter(|&i| app.selected[i])
+        .map(|i| app.repos[i].disk_usage * 1024)
+        .sum();
+    let steps = app.job.plan_summary(count, bytes);
+    if steps.len() > 1 {
+        text.extend(steps.iter().enumerate().map(|(i, step)| {
+            Line::from(format!("{}. {step}", i + 1))
+                .style(Style::default().fg(Color::Cyan))
+                .centered()
+        }));
+        text.push(Line::from(""));
+    }
+    if let (Action::Transfer, Some(to)) = (app.job.action, &app.job.transfer_to) {
+        text.push(
+            Line::from(format!("New owner: {to}"))
+                .style(Style::default().fg(Color::Cyan).bold())
+                .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    if let Some(edit) = &app.job.description_edit {
+        text.push(
+            Line::from(format!("Descriptions: {} \"{}\"", edit.op, edit.template))
+                .style(Style::default().fg(Color::Cyan))
+                .centered(),
+        );
+        text.push(Line::from(""));
+    }
+    if app.job.action == Action::Delete && !app.dry_run {
+        text.extend([
+            Line::from("Deleting destroys the repos for good!")
+                .style(Style::default().fg(Color::Red).bold())
+                .centered(),
+            Line::from(if app.confirm_names && app.phrase_typed() {
+                format!("All {count} names typed")
+            } else if app.confirm_names {
+                format!(
+                    "Type \"{}\" to confirm ({} of {count}):",
+                    app.delete_phrase(),
+                    app.names_confirmed + 1
+                )
+            } else {
+                format!("Type \"{}\" to confirm:", app.delete_phrase())
+            })
+            .centered(),
+            Line::from(vec![
+                Span::raw("> "),
+                Span::styled(&app.delete_confirmation, Style::default().fg(Color::Red)),
+                Span::styled(
+                    if app.typing && !app.typing_code() {
+                        "█"
+                    } else {
+                        " "
+                    },
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+            .centered(),
+            Line::from(""),
+        ]);
+        match &app.challenge {
+            Some(Ok(challenge)) => text.extend([
+                Line::from(format!(
+                    "Deleting {} also takes the code in",
+                    challenge.reason
+                ))
+                .centered(),
+                Line::from(format!("{}:", challenge.path().display())).centered(),
+                Line::from(vec![
+                    Span::raw("> "),
+                    Span::styled(&app.code_typed, Style::default().fg(Color::Red)),
+                    Span::styled(
+                        if app.typing && app.typing_code() {
+                            "█"
+                        } else {
+                            " "
+                        },
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+                .centered(),
+                Line::from(""),
+            ]),
+            Some(Err(e)) => text.extend([
+                Line::from(format!("Can't write the confirmation code: {e}"))
+                    .style(Style::default().fg(Color::Red))
+                    .centered(),
+                Line::from(""),
+            ]),
+            None => {}
+        }
+    }
+    text.extend([
+        Line::from(if app.require_approval {
+            "(Needs approval before apply-plan archives them)"
+        } else if app.offline {
+            "(Offline - archived later by apply-plan)"
+        } else if app.dry_run {
+            "(Dry run - no changes will be made)"
+        } else if app.job.demo {
+            "(Demo - nothing on GitHub changes)"
+        } else if app.job.action == Action::Privatize {
+            "Their stars and watchers are lost for good."
+        } else if app.job.action == Action::Tag {
+            "Only topics are added; nothing is archived."
+        } else {
+            "This action cannot be undone."
+        })
+        .style(Style::default().fg(if app.dry_run || app.queues() {
+            Color::Yellow
+        } else {
+            Color::Red
+        }))
+        .centered(),
+        Line::from(""),
+        buttons.centered(),
+        Line::from(""),
+        Line::from(app.confirm_help())
+            .style(Style::default().fg(Color::DarkGray))
+            .centered(),
+    ]);
+
+    // Center the modal, sized to its contents
+    let modal_width = 56;
+    let modal_height = text.len() as u16 + 2;
+    let modal_area = Rect {
+        x: area.width.saturating_sub(modal_width) / 2,
+        y: area.height.saturating_sub(modal_height) / 2,
+        width: modal_width.min(area.width),
+        height: modal_height.min(area.height),
+    };
+
+    // Clear the area behind the modal
+    f.render_widget(Clear, modal_area);
+
+    // Deleting can't be taken back, so it doesn't look like the usual dialog
+    let (border, title) = if app.job.action == Action::Delete {
+        (Style::default().fg(Color::Red).bold(), " Delete ")
+    } else {
+        (Style::default().fg(Color::Cyan), " Confirm ")
+    };
+    let modal = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(title),
+    );
+
+    f.render_widget(modal, modal_area);
+}
+
+/// Repo counts for this run and a chart of active repos across runs.
+fn render_stats(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let modal_width = 64.min(area.width);
+    let modal_height = 20.min(area.height);
+    let modal_area = Rect {
+        x: area.width.saturating_sub(modal_width) / 2,
+        y: area.height.saturating_sub(modal_height) / 2,
+        width: modal_width,
+        height: modal_height,
+    };
+    f.render_widget(Clear, modal_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(" Stats ");
+    let inner = block.inner(modal_area);
+    f.render_widget(block, modal_area);
+
+    let history: Vec<Snapshot> = app.history.iter().chain(&app.snapshot).cloned().collect();
+    let Some(latest) = history.last() else {
+        f.render_widget(
+            Paragraph::new("No repo counts yet; they're recorded on every run.")
+                .style(Style::default().fg(Color::DarkGray))
+                .centered(),
+            inner,
+        );
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(5)])
+        .split(inner);
+
+    let summary = vec![
+        Line::from(format!(
+            "{} active · {} archived · {} total",
+            latest.active(),
+            latest.archived,
+            latest.total
+        ))
+        .style(Style::default().bold())
+        .centered(),
+        Line::from(""),
+        Line::from(stats::headline(&history).unwrap_or_default())
+            .style(Style::default().fg(Color::Green))
+            .centered(),
+    ];
+    f.render_widget(Paragraph::new(summary), chunks[0]);
+
+    // Active repos by days since the first recorded run
+    let first = history[0].at;
+    let points: Vec<(f64, f64)> = history
+        .iter()
+        .map(|s| ((s.at - first).num_hours() as f64 / 24.0, s.active() as f64))
+        .collect();
+    let days = points.last().map_or(0.0, |p| p.0).max(1.0);
+    let (low, high) = points.iter().fold((f64::MAX, 0.0_f64), |(low, high), p| {
+        (low.min(p.1), high.max(p.1))
+    });
+    let (low, high) = ((low - 5.0).max(0.0), high + 5.0);
+
+    let dataset = Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Cyan))
+        .data(&points);
+    let chart = Chart::new(vec![dataset])
+        .x_axis(
+            Axis::default()
+                .bounds([0.0, days])
+                .labels([
+                    app.date_format.format(first.date_naive()),
+                    app.date_format.format(latest.at.date_naive()),
+                ])
+                .style(Style::default().fg(Color::DarkGray)),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([low, high])
+                .labels([format!("{low:.0}"), format!("{high:.0}")])
+                .style(Style::default().fg(Color::DarkGray)),
+        );
+    f.render_widget(chart, chunks[1]);
+}
+
+/// Template prompt for editing the selected repos' descriptions, with a
+/// preview, then which edits failed.
+fn render_describe(f: &mut Frame, app: &App) {
+    /// Repos previewed or listed as failed before the rest are summarized
+    const MAX_LISTED: usize = 6;
+
+    let area = f.area();
+    let mut text = vec![Line::from("")];
+    if let Some(failed) = &app.describe.failed {
+        let updated = app.selected_count() - failed.len();
+        text.push(
+            Line::from(format!(
+                "Updated {updated} description{}",
+                if updated == 1 { "" } else { "s" }
+            ))
+            .style(Style::default().fg(Color::Green).bold()),
+        );
+        if !failed.is_empty() {
+            text.push(Line::from(""));
+            text.push(
+                Line::from(format!("{} failed:", failed.len()))
+                    .style(Style::default().fg(Color::Red).bold()),
+            );
+            text.extend(failed.iter().take(MAX_LISTED).map(|error| {
+                Line::from(format!("  {error}")).style(Style::default().fg(Color::Red))
+            }));
+            if failed.len() > MAX_LISTED {
+                text.push(
+                    Line::from(format!("  ...and {} more", failed.len() - MAX_LISTED))
+                        .style(Style::default().fg(Color::Red)),
+                );
+            }
+        }
+        text.push(Line::from(""));
+        text.push(Line::from("Enter: Close").style(Style::default().fg(Color::DarkGray)));
+    } else {
+        let edit = &app.describe.edit;
+        text.extend([
+            Line::from(vec![
+                Span::raw("Operation: "),
+                Span::styled(edit.op.to_string(), Style::default().fg(Color::Cyan).bold()),
+            ]),
+            Line::from(vec![
+                Span::raw("> "),
+                Span::styled(&edit.template, Style::default().fg(Color::Cyan)),
+                Span::styled("█", Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from("{name}, {description} and {date} are filled in")
+                .style(Style::default().fg(Color::DarkGray)),
+            Line::from(""),
+        ]);
+        let selected: Vec<&Repo> = (0..app.repos.len())
+            .filter(|&i| app.selected[i])
+            .map(|i| &app.repos[i])
+            .collect();
+        for repo in selected.iter().take(MAX_LISTED) {
+            text.push(Line::from(repo.name.as_str()).style(Style::default().bold()));
+            text.push(
+                Line::from(format!(
+                    "  {}",
+                    edit.apply(&repo.name_with_owner, repo.description.as_deref())
+                ))
+                .style(Style::default().fg(Color::Green)),
+            );
+        }
+        if selected.len() > MAX_LISTED {
+            text.push(
+                Line::from(format!("...and {} more", selected.len() - MAX_LISTED))
+                    .style(Style::default().fg(Color::DarkGray)),
+            );
+        }
+    }
+
+    let modal_width = 70;
+    let modal_height = text.len() as u16 + 2;
+    let modal_area = Rect {
+        x: area.width.saturating_sub(modal_width) / 2,
+        y: area.height.saturating_sub(modal_height) / 2,
+        width: modal_width.min(area.width),
+        height: modal_height.min(area.height),
+    };
+    f.render_widget(Clear, modal_area);
+
+    let modal = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(" Edit descriptions "),
+    );
+    f.render_widget(modal, modal_area);
+}
+
+/// Path prompt for the CSV import, then what the import did.
+/// The order screen: the confirmed repos in the order they'll be archived.
+fn render_order(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let modal_width = 60.min(area.width);
+    let modal_height = (app.order.repos.len() as u16 + 4)
+        .min(area.height.saturating_sub(4))
+        .max(5);
+    let modal_area = Rect {
+        x: area.width.saturating_sub(modal_width) / 2,
+        y: area.height.saturating_sub(modal_height) / 2,
+        width: modal_width,
+        height: modal_height.min(area.height),
+    };
+    f.render_widget(Clear, modal_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(" Archive order ");
+    let inner = block.inner(modal_area);
+    f.render_widget(block, modal_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(inner);
+
+    // Keep the highlighted repo in view
+    let visible = chunks[0].height as usize;
+    let skip = (app.order.cursor + 1).saturating_sub(visible);
+    let lines: Vec<Line> = app
+        .order
+        .repos
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(position, &i)| {
+            let repo = &app.repos[i];
+            let line = Line::from(vec![
+                Span::raw(format!("{:>3}. ", position + 1)),
+                Span::raw(repo.name_with_owner.clone()),
+                Span::styled(
+                    if repo.priority { " [priority]" } else { "" },
+                    Style::default().fg(Color::Green).bold(),
+                ),
+                Span::styled(
+                    format!("  {}", preflight::format_size(repo.disk_usage * 1024)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]);
+            if position == app.order.cursor {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+    f.render_widget(
+        Paragraph::new(vec![
+            Line::from(""),
+            Line::from("J/K: Move | P: Priority | s: Smallest first | Enter: Start").centered(),
+        ])
+        .style(Style::default().fg(Color::DarkGray)),
+        chunks[1],
+    );
+}
+
+fn render_import(f: &mut Frame, app: &App) {
+    /// Unknown rows listed before the rest are summarized
+    const MAX_UNKNOWN: usize = 8;
+
+    let area = f.area();
+    let mut text = vec![Line::from("")];
+    match &app.import.report {
+        None => text.extend([
+            Line::from("CSV of repo,decision,notes rows:"),
+            Line::from(vec![
+                Span::raw("> "),
+                Span::styled(&app.import.path, Style::default().fg(Color::Cyan)),
+                Span::styled("█", Style::default().fg(Color::DarkGray)),
+            ]),
+            Line::from(""),
+            Line::from("Repos marked \"archive\" get selected, others deselected")
+                .style(Style::default().fg(Color::DarkGray)),
+        ]),
+        Some(Err(e)) => text.extend([
+            Line::from("Import failed").style(Style::default().fg(Color::Red).bold()),
+            Line::from(e.as_str()).style(Style::default().fg(Color::Red)),
+            Line::from(""),
+            Line::from("Enter: Edit path").style(Style::default().fg(Color::DarkGray)),
+        ]),
+        Some(Ok(report)) => {
+            text.push(
+                Line::from(format!(
+                    "Loaded decisions for {} repo{}",
+                    report.loaded,
+                    if report.loaded == 1 { "" } else { "s" }
+                ))
+                .style(Style::default().fg(Color::Green).bold()),
+            );
+            if !report.unknown.is_empty() {
+                text.push(Line::from(""));
+                text.push(
+                    Line::from(format!("{} unknown rows:", report.unknown.len()))
+                        .style(Style::default().fg(Color::Yellow).bold()),
+                );
+                text.extend(report.unknown.iter().take(MAX_UNKNOWN).map(|repo| {
+                    Line::from(format!("  {repo}")).style(Style::default().fg(Color::Yellow))
+                }));
+                if report.unknown.len() > MAX_UNKNOWN {
+                    text.push(
+                        Line::from(format!(
+                            "  ...and {} more",
+                            report.unknown.len() - MAX_UNKNOWN
+                        ))
+                        .style(Style::default().fg(Color::Yellow)),
+                    );
+                }
+            }
+            if !report.ambiguous.is_empty() {
+                text.push(Line::from(""));
+                text.push(
+                    Line::from(format!(
+                        "Ignored, as several repos have the name (use owner/name): {}",
+                        report.ambiguous.join(", ")
+                    ))
+                    .style(Style::default().fg(Color::Yellow)),
+                );
+            }
+            text.push(Line::from(""));
+            text.push(Line::from("Enter: Close").style(Style::default().fg(Color::DarkGray)));
+        }
+    }
+
+    let modal_width = 60;
+    let modal_height = text.len() as u16 + 2;
+    let modal_area = Rect {
+        x: area.width.saturating_sub(modal_width) / 2,
+        y: area.height.saturating_sub(modal_height) / 2,
+        width: modal_width.min(area.width),
+        height: modal_height.min(area.height),
+    };
+    f.render_widget(Clear, modal_area);
+
+    let modal = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(" Import CSV "),
+    );
+    f.render_widget(modal, modal_area);
+}
@@ -0,0 +1,202 @@
+//! Optional encryption of backup files with `age` or `gpg`.
+//!
+//! Plaintext is piped straight into the encryption tool, so it never touches
+//! the backup directory.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+/// Who backups are encrypted to. `age1...` keys use `age`; anything else is
+/// treated as a GPG key ID or email.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Recipient {
+    Age(String),
+    Gpg(String),
+}
+
+impl FromStr for Recipient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            anyhow::bail!("Encryption recipient cannot be empty");
+        }
+        if s.starts_with("age1") {
+            Ok(Self::Age(s.to_string()))
+        } else {
+            Ok(Self::Gpg(s.to_string()))
+        }
+    }
+}
+
+impl TryFrom<String> for Recipient {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl Recipient {
+    /// `path` with the encryption extension appended, e.g. `metadata.json.age`.
+    pub fn encrypted_path(&self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.tool());
+        PathBuf::from(name)
+    }
+
+    fn command(&self, output: &Path) -> Command {
+        match self {
+            Self::Age(key) => {
+                let mut cmd = Command::new("age");
+                cmd.args(["--recipient", key, "--output"]).arg(output);
+                cmd
+            }
+            Self::Gpg(key) => {
+                let mut cmd = Command::new("gpg");
+                cmd.args([
+                    "--batch",
+                    "--yes",
+                    "--encrypt",
+                    "--recipient",
+                    key,
+                    "--output",
+                ])
+                .arg(output);
+                cmd
+            }
+        }
+    }
+
+    /// Encrypt `data` into `path` plus the encryption extension.
+    pub fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let output = self.encrypted_path(path);
+        let mut child = self
+            .command(&output)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}. Is it installed?", self.tool()))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(data)?;
+        }
+
+        let result = child.wait_with_output()?;
+        if !result.status.success() {
+            anyhow::bail!(
+                "Failed to encrypt {}: {}",
+                output.display(),
+                String::from_utf8_lossy(&result.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// Encrypt the file at `src` into `dest` plus the encryption extension.
+    pub fn encrypt_file(&self, src: &Path, dest: &Path) -> Result<()> {
+        let output = self.encrypted_path(dest);
+        let result = self
+            .command(&output)
+            .arg(src)
+            .output()
+            .with_context(|| format!("Failed to run {}. Is it installed?", self.tool()))?;
+
+        if !result.status.success() {
+            anyhow::bail!(
+                "Failed to encrypt {}: {}",
+                output.display(),
+                String::from_utf8_lossy(&result.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// The command doing the encryption, which doubles as the file extension.
+    const fn tool(&self) -> &'static str {
+        match self {
+            Self::Age(_) => "age",
+            Self::Gpg(_) => "gpg",
+        }
+    }
+}
+
+/// The file backing `path`: either `path` itself or an encrypted copy of it.
+fn locate(path: &Path) -> Option<PathBuf> {
+    ["", ".age", ".gpg"].iter().find_map(|ext| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(ext);
+        let candidate = PathBuf::from(name);
+        candidate.exists().then_some(candidate)
+    })
+}
+
+/// Whether `path` or an encrypted copy of it exists.
+pub fn exists(path: &Path) -> bool {
+    locate(path).is_some()
+}
+
+/// Read `path`, decrypting it if only an encrypted copy exists.
+///
+/// `identity` is the age identity file; GPG finds its keys itself.
+pub fn read(path: &Path, identity: Option<&Path>) -> Result<Vec<u8>> {
+    let Some(found) = locate(path) else {
+        anyhow::bail!("{} not found", path.display());
+    };
+    let Some(mut cmd) = decrypt_command(&found, identity)? else {
+        return std::fs::read(&found)
+            .with_context(|| format!("Failed to read {}", found.display()));
+    };
+
+    let output = cmd
+        .output()
+        .context("Failed to run decryption tool. Is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to decrypt {}: {}",
+            found.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn decrypt_command(path: &Path, identity: Option<&Path>) -> Result<Option<Command>> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("age") => {
+            let Some(identity) = identity else {
+                anyhow::bail!("{} is age-encrypted; pass --identity", path.display());
+            };
+            let mut cmd = Command::new("age");
+            cmd.args(["--decrypt", "--identity"])
+                .arg(identity)
+                .arg(path);
+            Ok(Some(cmd))
+        }
+        Some("gpg") => {
+            let mut cmd = Command::new("gpg");
+            cmd.args(["--batch", "--quiet", "--decrypt"]).arg(path);
+            Ok(Some(cmd))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Write `data` to `path`, encrypted if a recipient is configured.
+pub fn write(path: &Path, data: &[u8], recipient: Option<&Recipient>) -> Result<()> {
+    match recipient {
+        Some(recipient) => recipient.write(path, data),
+        None => std::fs::write(path, data)
+            .with_context(|| format!("Failed to write {}", path.display())),
+    }
+}
@@ -0,0 +1,118 @@
+//! Typed failures of GitHub calls, so the UI and exit code can react to what
+//! went wrong instead of only showing a message.
+//!
+//! Errors are still passed around as `anyhow::Error`; these are created where
+//! `gh` fails and found again with [`Error::find`].
+
+use std::process::Output;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("GitHub authentication failed: {0}")]
+    Auth(String),
+    #[error("GitHub rate limit hit: {0}")]
+    RateLimit(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("can't reach GitHub: {0}")]
+    Network(String),
+    #[error("unexpected response from gh: {0}")]
+    Parse(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    /// Classify what `gh` printed to stderr.
+    pub fn from_stderr(stderr: &[u8]) -> Self {
+        let message = String::from_utf8_lossy(stderr).trim().to_string();
+        let lower = message.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+
+        if has(&[
+            "gh auth login",
+            "http 401",
+            "bad credentials",
+            "authentication",
+        ]) {
+            Self::Auth(message)
+        } else if has(&["rate limit", "http 429"]) {
+            Self::RateLimit(message)
+        } else if has(&["could not resolve to a", "http 404", "not found"]) {
+            Self::NotFound(message)
+        } else if has(&[
+            "http 403",
+            "resource not accessible",
+            "must have admin",
+            "permission",
+        ]) {
+            Self::PermissionDenied(message)
+        } else if has(&[
+            "dial tcp",
+            "no such host",
+            "could not resolve host",
+            "connection refused",
+            "timeout",
+            "network is unreachable",
+            "tls handshake",
+        ]) {
+            Self::Network(message)
+        } else {
+            Self::Other(message)
+        }
+    }
+
+    /// The first typed error anywhere in `error`'s chain.
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// Process exit code; 1 is any other failure and 2 a usage error.
+    pub const fn exit_code(&self) -> u8 {
+        match self {
+            Self::Auth(_) => 3,
+            Self::RateLimit(_) => 4,
+            Self::NotFound(_) => 5,
+            Self::PermissionDenied(_) => 6,
+            Self::Network(_) => 7,
+            Self::Parse(_) => 8,
+            Self::Other(_) => 1,
+        }
+    }
+
+    /// A short cause and what may fix it, for the error screen.
+    pub const fn explain(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Auth(_) => (
+                "GitHub authentication failed",
+                "Your gh login or token may have expired.",
+            ),
+            Self::RateLimit(_) => ("GitHub rate limit hit", "Wait a few minutes, then retry."),
+            Self::NotFound(_) => (
+                "Not found",
+                "Check the --owner and --org logins and repo names for typos.",
+            ),
+            Self::PermissionDenied(_) => (
+                "Permission denied",
+                "Your token may lack a scope; try logging in to gh again.",
+            ),
+            Self::Network(_) => (
+                "Can't reach GitHub",
+                "Check your network connection or proxy settings.",
+            ),
+            Self::Parse(_) => ("Unexpected response from gh", "Try updating gh."),
+            Self::Other(_) => ("GitHub call failed", ""),
+        }
+    }
+}
+
+/// Turn a finished `gh` call into its stdout, or a typed error if it failed.
+pub fn check(output: Output) -> Result<Vec<u8>, Error> {
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(Error::from_stderr(&output.stderr))
+    }
+}
@@ -0,0 +1,175 @@
+//! Fetching the repo lists of several owners at once, tracking how far along
+//! each one is, so the loading screen can show progress instead of a silent
+//! stall before the table opens.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::{
+    collections::HashSet,
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{cache::RepoList, provider::Provider, Repo, SPINNER_FRAMES};
+
+enum Progress {
+    /// Repos fetched so far
+    Fetching(usize),
+    Done(usize),
+    Failed(String),
+}
+
+/// What a fetch thread reports about the owner at the index it's for.
+enum Message {
+    Page(usize, usize),
+    Done(usize, Result<Vec<Repo>>),
+}
+
+/// Repo lists being fetched in the background, one thread per owner.
+pub struct Fetch {
+    labels: Vec<String>,
+    rx: mpsc::Receiver<Message>,
+    progress: Vec<Progress>,
+    results: Vec<Option<Result<Vec<Repo>>>>,
+    pinned: JoinHandle<Result<HashSet<String>>>,
+    started: Instant,
+}
+
+/// Start fetching the non-archived repos of every owner in `owners` (the
+/// authenticated user when empty), and which repos are pinned on their
+/// profiles.
+pub fn start(owners: &[String], provider: &Arc<dyn Provider>) -> Fetch {
+    let owners: Vec<Option<String>> = if owners.is_empty() {
+        vec![None]
+    } else {
+        owners.iter().cloned().map(Some).collect()
+    };
+    let labels: Vec<String> = owners
+        .iter()
+        .map(|o| o.clone().unwrap_or_else(|| "your repos".to_string()))
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    for (i, owner) in owners.iter().cloned().enumerate() {
+        let tx = tx.clone();
+        let provider = Arc::clone(provider);
+        thread::spawn(move || {
+            let result = provider.list_repos(owner.as_deref(), &mut |n| {
+                let _ = tx.send(Message::Page(i, n));
+            });
+            let _ = tx.send(Message::Done(i, result));
+        });
+    }
+    let provider = Arc::clone(provider);
+    let pinned = thread::spawn(move || {
+        let mut pinned = HashSet::new();
+        for owner in &owners {
+            pinned.extend(provider.pinned(owner.as_deref())?);
+        }
+        Ok(pinned)
+    });
+
+    Fetch {
+        progress: labels.iter().map(|_| Progress::Fetching(0)).collect(),
+        results: labels.iter().map(|_| None).collect(),
+        labels,
+        rx,
+        pinned,
+        started: Instant::now(),
+    }
+}
+
+impl Fetch {
+    /// Wait up to `timeout` for progress on an owner's list. Returns the
+    /// index of the owner that finished, if any.
+    pub fn poll(&mut self, timeout: Duration) -> Option<usize> {
+        let (i, result) = match self.rx.recv_timeout(timeout) {
+            Ok(Message::Page(i, n)) => {
+                self.progress[i] = Progress::Fetching(n);
+                return None;
+            }
+            Ok(Message::Done(i, result)) => (i, result),
+            Err(mpsc::RecvTimeoutError::Timeout) => return None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                // A fetch thread died without reporting back
+                let i = self.results.iter().position(Option::is_none)?;
+                (i, Err(anyhow::anyhow!("Fetch panicked")))
+            }
+        };
+        self.progress[i] = match &result {
+            Ok(repos) => Progress::Done(repos.len()),
+            // One line, so it fits the loading screen
+            Err(e) => Progress::Failed(
+                format!("{e:#}")
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+        };
+        self.results[i] = Some(result);
+        Some(i)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.results.iter().all(Option::is_some)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// A status line for the owner at `i`, with the spinner at `frame`.
+    pub fn line(&self, i: usize, frame: usize) -> String {
+        let width = self.labels.iter().map(String::len).max().unwrap_or(0);
+        let label = &self.labels[i];
+        match &self.progress[i] {
+            Progress::Fetching(0) => format!(
+                "{} {label:width$}  fetching...",
+                SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]
+            ),
+            Progress::Fetching(n) => format!(
+                "{} {label:width$}  fetching... {n} repos so far",
+                SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]
+            ),
+            Progress::Done(n) => format!(
+                "✓ {label:width$}  {n} repo{}",
+                if *n == 1 { "" } else { "s" }
+            ),
+            Progress::Failed(e) => format!("✗ {label:width$}  {e}"),
+        }
+    }
+
+    pub fn owners(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Block until every list is in, printing a line per finished owner.
+    pub fn wait(&mut self) {
+        while !self.is_done() {
+            if let Some(i) = self.poll(Duration::from_secs(1)) {
+                println!("{}", self.line(i, 0));
+            }
+        }
+    }
+
+    /// The combined list, once [`Self::is_done`]. Fails if any owner's list
+    /// couldn't be fetched.
+    pub fn finish(self) -> Result<RepoList> {
+        let mut repos = Vec::new();
+        for (label, result) in self.labels.iter().zip(self.results) {
+            let result = result.context("Fetch isn't done")?;
+            repos.extend(result.with_context(|| format!("Failed to fetch {label}"))?);
+        }
+        let pinned = self
+            .pinned
+            .join()
+            .map_err(|_| anyhow::anyhow!("Pinned repo lookup panicked"))??;
+        Ok(RepoList {
+            fetched_at: Utc::now(),
+            repos,
+            pinned,
+        })
+    }
+}
@@ -0,0 +1,139 @@
+//! How forks relate to their upstream, for deciding between archiving and
+//! deleting a stale fork.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::{backend::Backend, graphql, http};
+
+/// Branches per page of a fork's branch list; the most GitHub allows.
+const BRANCH_PAGE: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct ForkStatus {
+    /// `owner/name` of the upstream; `None` once it's been deleted
+    pub upstream: Option<String>,
+    /// Commits on the fork's default branch that upstream doesn't have
+    pub ahead: u64,
+    /// Upstream commits the fork doesn't have
+    pub behind: u64,
+    /// Open PRs from the fork's owner against upstream
+    pub open_prs: u64,
+    /// The fork's other branches with commits upstream doesn't have
+    pub branches_ahead: Vec<String>,
+}
+
+impl ForkStatus {
+    /// Whether the fork holds nothing upstream doesn't: no commits of its
+    /// own on any branch and no open PRs, with the upstream still there.
+    pub fn even(&self) -> bool {
+        self.upstream.is_some()
+            && self.ahead == 0
+            && self.branches_ahead.is_empty()
+            && self.open_prs == 0
+    }
+}
+
+/// The status of each fork in `names`. A fork whose status can't be
+/// determined gets an error instead of failing the lot.
+pub fn fetch(names: &[&str], github: &dyn Backend) -> Result<Vec<Result<ForkStatus>>> {
+    const FIELDS: &str =
+        "defaultBranchRef { name } parent { nameWithOwner defaultBranchRef { name } }";
+
+    let results =
+        graphql::per_repo(names, FIELDS, github).context("Failed to look up upstreams")?;
+    Ok(names
+        .iter()
+        .zip(results)
+        .map(|(name, repo)| status(name, &repo, github))
+        .collect())
+}
+
+fn status(name_with_owner: &str, repo: &Value, github: &dyn Backend) -> Result<ForkStatus> {
+    let parent = &repo["parent"];
+    let (Some(upstream), Some(base), Some(head)) = (
+        parent["nameWithOwner"].as_str(),
+        parent["defaultBranchRef"]["name"].as_str(),
+        repo["defaultBranchRef"]["name"].as_str(),
+    ) else {
+        return Ok(ForkStatus {
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            open_prs: 0,
+            branches_ahead: Vec::new(),
+        });
+    };
+    let owner = name_with_owner
+        .split_once('/')
+        .map_or(name_with_owner, |(owner, _)| owner);
+
+    let compare = |branch: &str| {
+        github.rest(
+            "GET",
+            &format!("repos/{upstream}/compare/{base}...{owner}:{branch}"),
+            None,
+        )
+    };
+    let head_compare = compare(head)?;
+    let (Some(ahead), Some(behind)) = (
+        head_compare["ahead_by"].as_u64(),
+        head_compare["behind_by"].as_u64(),
+    ) else {
+        anyhow::bail!("Unexpected comparison of {head} from GitHub");
+    };
+    // Work on other branches goes with the fork just the same. A branch
+    // that can't be compared fails the lookup, so the fork isn't pruned
+    let mut branches_ahead = Vec::new();
+    for branch in branches(name_with_owner, github)?
+        .into_iter()
+        .filter(|branch| branch != head)
+    {
+        let ahead = compare(&branch)?["ahead_by"]
+            .as_u64()
+            .with_context(|| format!("Unexpected comparison of {branch} from GitHub"))?;
+        if ahead > 0 {
+            branches_ahead.push(branch);
+        }
+    }
+    let query = format!("is:pr is:open repo:{upstream} author:{owner}");
+    let open_prs = github.rest(
+        "GET",
+        &format!("search/issues?q={}", http::encode(&query)),
+        None,
+    )?["total_count"]
+        .as_u64()
+        .context("Unexpected search result from GitHub")?;
+
+    Ok(ForkStatus {
+        upstream: Some(upstream.to_string()),
+        ahead,
+        behind,
+        open_prs,
+        branches_ahead,
+    })
+}
+
+/// The names of all of the repo's branches.
+fn branches(name_with_owner: &str, github: &dyn Backend) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for page in 1.. {
+        let response = github.rest(
+            "GET",
+            &format!("repos/{name_with_owner}/branches?per_page={BRANCH_PAGE}&page={page}"),
+            None,
+        )?;
+        let branches = response
+            .as_array()
+            .context("Unexpected branch list from GitHub")?;
+        names.extend(
+            branches
+                .iter()
+                .filter_map(|branch| branch["name"].as_str().map(str::to_string)),
+        );
+        if branches.len() < BRANCH_PAGE {
+            break;
+        }
+    }
+    Ok(names)
+}
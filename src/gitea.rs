@@ -0,0 +1,197 @@
+//! Repos on a Gitea server, or a fork of it (Forgejo, Codeberg), for
+//! `--provider gitea --host HOST`: listed and archived through its REST API
+//! with a token from `REPO_ARCHIVER_TOKEN` or `GITEA_TOKEN`.
+//!
+//! The API mirrors GitHub's REST API closely (archiving is the same `PATCH`
+//! of `archived`), but there is no push time, so the last update stands in
+//! for the last push, and lists can't leave out archived repos, so they're
+//! dropped here.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+
+use crate::{
+    config::Config,
+    error,
+    http::Client,
+    provider::{self, Provider},
+    report::FinalState,
+    Repo,
+};
+
+/// Repos per page of a repo list; Gitea's default maximum.
+const PAGE: usize = 50;
+
+pub struct Gitea {
+    http: Client,
+}
+
+#[derive(Deserialize)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "mirrors the fields Gitea returns"
+)]
+struct GiteaRepo {
+    name: String,
+    full_name: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    website: String,
+    #[serde(default)]
+    fork: bool,
+    #[serde(default)]
+    mirror: bool,
+    #[serde(default)]
+    template: bool,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    stars_count: u64,
+    /// In KB
+    #[serde(default)]
+    size: u64,
+}
+
+impl From<GiteaRepo> for Repo {
+    fn from(repo: GiteaRepo) -> Self {
+        Self {
+            name: repo.name,
+            name_with_owner: repo.full_name,
+            created_at: repo.created_at,
+            pushed_at: repo.updated_at,
+            description: Some(repo.description).filter(|d| !d.is_empty()),
+            homepage_url: Some(repo.website).filter(|w| !w.is_empty()),
+            is_fork: repo.fork,
+            is_mirror: repo.mirror,
+            is_template: repo.template,
+            stargazer_count: repo.stars_count,
+            disk_usage: repo.size,
+            ..Self::default()
+        }
+    }
+}
+
+impl Gitea {
+    pub fn new(config: &Config) -> Result<Self> {
+        let host = config
+            .host
+            .as_deref()
+            .context("--provider gitea needs --host, e.g. --host codeberg.org")?;
+        let token = config
+            .token
+            .clone()
+            .or_else(|| env::var("GITEA_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+            .context("--provider gitea needs a token: set REPO_ARCHIVER_TOKEN or GITEA_TOKEN")?;
+        Ok(Self {
+            http: Client::new(
+                &format!("{}/api/v1", provider::server_url(host)),
+                token,
+                "application/json",
+                config,
+            ),
+        })
+    }
+
+    fn repo(&self, full_name: &str) -> Result<GiteaRepo> {
+        let response = self
+            .http
+            .request("GET", &format!("/repos/{full_name}"), None)?;
+        Ok(serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    /// Every page of the repo list at `path`, without archived repos.
+    fn list(&self, path: &str, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let response =
+                self.http
+                    .request("GET", &format!("{path}?limit={PAGE}&page={page}"), None)?;
+            let page: Vec<GiteaRepo> =
+                serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?;
+            let last = page.len() < PAGE;
+            repos.extend(page.into_iter().filter(|r| !r.archived).map(Repo::from));
+            on_page(repos.len());
+            if last {
+                break;
+            }
+        }
+        repos.sort_by_key(|r| r.created_at);
+        Ok(repos)
+    }
+
+    fn set_archived(&self, full_name: &str, archived: bool) -> Result<()> {
+        self.http.request(
+            "PATCH",
+            &format!("/repos/{full_name}"),
+            Some(&json!({"archived": archived})),
+        )?;
+        Ok(())
+    }
+}
+
+impl Provider for Gitea {
+    /// The user's own repos, or those of an organization or another user.
+    fn list_repos(&self, owner: Option<&str>, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let Some(owner) = owner else {
+            return self.list("/user/repos", on_page);
+        };
+        match self.list(&format!("/orgs/{owner}/repos"), on_page) {
+            Err(e) if matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => self
+                .list(&format!("/users/{owner}/repos"), on_page)
+                .map_err(|e| match error::Error::find(&e) {
+                    Some(error::Error::NotFound(_)) => error::Error::NotFound(format!(
+                        "Could not resolve to a user or organization with the login of '{owner}'"
+                    ))
+                    .into(),
+                    _ => e,
+                }),
+            result => result,
+        }
+    }
+
+    fn archive_repo(&self, full_name: &str) -> Result<()> {
+        self.set_archived(full_name, true)
+    }
+
+    fn unarchive_repo(&self, full_name: &str) -> Result<()> {
+        self.set_archived(full_name, false)
+    }
+
+    fn final_state(&self, full_name: &str) -> Result<Option<FinalState>> {
+        let repo = self
+            .repo(full_name)
+            .with_context(|| format!("Failed to look up {full_name} after archiving"))?;
+        Ok(Some(FinalState {
+            archived: repo.archived,
+            pushed_at: repo.updated_at,
+            updated_at: repo.updated_at,
+            access: None,
+            teams: None,
+        }))
+    }
+
+    fn dry_run(&self, full_name: &str) -> Result<String> {
+        if self.repo(full_name)?.archived {
+            anyhow::bail!("would fail: {full_name} is already archived");
+        }
+        Ok("would archive".to_string())
+    }
+
+    fn check_org(&self, org: &str) -> Result<()> {
+        match self.http.request("GET", &format!("/orgs/{org}"), None) {
+            Err(e) if !matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => Err(e),
+            Err(_) => Err(error::Error::NotFound(format!(
+                "'{org}' is not an organization, or you can't see it"
+            ))
+            .into()),
+            Ok(_) => Ok(()),
+        }
+    }
+}
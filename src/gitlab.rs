@@ -0,0 +1,216 @@
+//! GitLab projects, for `--provider gitlab`: listed and archived through the
+//! REST API of gitlab.com, or of a self-managed instance at `gitlab_url` or
+//! `--host`,
+//! with a token from `REPO_ARCHIVER_TOKEN` or `GITLAB_TOKEN`.
+//!
+//! Groups take the place of organizations and `namespace/project` paths the
+//! place of `owner/name`. GitLab has no pinned projects, templates or
+//! homepages, and no separate push time, so the last activity stands in for
+//! the last push.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{de::IgnoredAny, Deserialize};
+use std::env;
+
+use crate::{
+    config::Config,
+    error,
+    http::Client,
+    provider::{self, Provider},
+    report::FinalState,
+    Repo,
+};
+
+/// Where projects live, unless `gitlab_url` or `host` says otherwise
+const GITLAB_URL: &str = "https://gitlab.com";
+
+/// Projects per page of the project list; the most GitLab allows.
+const PAGE: usize = 100;
+
+pub struct GitLab {
+    http: Client,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    path: String,
+    path_with_namespace: String,
+    created_at: DateTime<Utc>,
+    last_activity_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+    description: Option<String>,
+    #[serde(default)]
+    star_count: u64,
+    #[serde(default)]
+    mirror: bool,
+    #[serde(default)]
+    archived: bool,
+    /// Only there for forks
+    #[serde(rename = "forked_from_project")]
+    upstream: Option<IgnoredAny>,
+    statistics: Option<Statistics>,
+}
+
+#[derive(Deserialize)]
+struct Statistics {
+    /// In bytes
+    repository_size: u64,
+}
+
+impl From<Project> for Repo {
+    fn from(project: Project) -> Self {
+        Self {
+            name: project.path,
+            name_with_owner: project.path_with_namespace,
+            created_at: project.created_at,
+            pushed_at: project.last_activity_at,
+            description: project.description.filter(|d| !d.is_empty()),
+            is_mirror: project.mirror,
+            is_fork: project.upstream.is_some(),
+            stargazer_count: project.star_count,
+            // Kept in KB, like GitHub's diskUsage
+            disk_usage: project.statistics.map_or(0, |s| s.repository_size / 1024),
+            ..Self::default()
+        }
+    }
+}
+
+impl GitLab {
+    pub fn new(config: &Config) -> Result<Self> {
+        let token = config
+            .token
+            .clone()
+            .or_else(|| env::var("GITLAB_TOKEN").ok())
+            .filter(|token| !token.is_empty())
+            .context("--provider gitlab needs a token: set REPO_ARCHIVER_TOKEN or GITLAB_TOKEN")?;
+        let url = config
+            .gitlab_url
+            .as_deref()
+            .or(config.host.as_deref())
+            .unwrap_or(GITLAB_URL);
+        Ok(Self {
+            http: Client::new(
+                &format!("{}/api/v4", provider::server_url(url)),
+                token,
+                "application/json",
+                config,
+            ),
+        })
+    }
+
+    fn project(&self, path: &str) -> Result<Project> {
+        let response = self
+            .http
+            .request("GET", &format!("/projects/{}", encode(path)), None)?;
+        Ok(serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    /// Every page of the project list at `path`, which ends in `?` or `&`
+    /// for the paging query to follow.
+    fn list(&self, path: &str, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let query = format!(
+                "archived=false&order_by=created_at&sort=asc&statistics=true&per_page={PAGE}&page={page}"
+            );
+            let response = self.http.request("GET", &format!("{path}{query}"), None)?;
+            let projects: Vec<Project> =
+                serde_json::from_value(response).map_err(|e| error::Error::Parse(e.to_string()))?;
+            let last = projects.len() < PAGE;
+            repos.extend(projects.into_iter().map(Repo::from));
+            on_page(repos.len());
+            if last {
+                break;
+            }
+        }
+        Ok(repos)
+    }
+}
+
+impl Provider for GitLab {
+    /// The user's own projects, or those of a group (with its subgroups) or
+    /// another user.
+    fn list_repos(&self, owner: Option<&str>, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let Some(owner) = owner else {
+            return self.list("/projects?owned=true&", on_page);
+        };
+        let group = self.list(
+            &format!("/groups/{}/projects?include_subgroups=true&", encode(owner)),
+            on_page,
+        );
+        match group {
+            Err(e) if matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => self
+                .list(&format!("/users/{}/projects?", encode(owner)), on_page)
+                .map_err(|e| match error::Error::find(&e) {
+                    Some(error::Error::NotFound(_)) => error::Error::NotFound(format!(
+                        "Could not resolve to a user or group with the path of '{owner}'"
+                    ))
+                    .into(),
+                    _ => e,
+                }),
+            result => result,
+        }
+    }
+
+    fn archive_repo(&self, path: &str) -> Result<()> {
+        self.http
+            .request("POST", &format!("/projects/{}/archive", encode(path)), None)?;
+        Ok(())
+    }
+
+    fn unarchive_repo(&self, path: &str) -> Result<()> {
+        self.http.request(
+            "POST",
+            &format!("/projects/{}/unarchive", encode(path)),
+            None,
+        )?;
+        Ok(())
+    }
+
+    fn final_state(&self, path: &str) -> Result<Option<FinalState>> {
+        let project = self
+            .project(path)
+            .with_context(|| format!("Failed to look up {path} after archiving"))?;
+        Ok(Some(FinalState {
+            archived: project.archived,
+            pushed_at: project.last_activity_at,
+            updated_at: project.updated_at.unwrap_or(project.last_activity_at),
+            access: None,
+            teams: None,
+        }))
+    }
+
+    fn dry_run(&self, path: &str) -> Result<String> {
+        if self.project(path)?.archived {
+            anyhow::bail!("would fail: {path} is already archived");
+        }
+        Ok("would archive".to_string())
+    }
+
+    fn check_org(&self, group: &str) -> Result<()> {
+        match self
+            .http
+            .request("GET", &format!("/groups/{}", encode(group)), None)
+        {
+            Err(e) if !matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => Err(e),
+            Err(_) => Err(error::Error::NotFound(format!(
+                "'{group}' is not a group, or you can't see it"
+            ))
+            .into()),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+/// Percent-encode a project or group path for use as an ID in a URL.
+fn encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,39 @@
+//! Batched per-repo GraphQL lookups through the configured backend.
+//!
+//! Looking repos up one `gh` call at a time is slow for hundreds of
+//! candidates, so each request carries a batch of aliased `repository`
+//! queries instead.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt::Write;
+
+use crate::backend::Backend;
+
+/// Repos per request; each one is a separate aliased lookup.
+const BATCH: usize = 50;
+
+/// Query `fields` (a GraphQL selection on `Repository`) for every repo in
+/// `names`, returning one object per repo in the same order. Repos that
+/// can't be seen come back as `null`.
+pub fn per_repo(names: &[&str], fields: &str, github: &dyn Backend) -> Result<Vec<Value>> {
+    let mut results = Vec::with_capacity(names.len());
+    for batch in names.chunks(BATCH) {
+        let mut data = query(batch, fields, github)?;
+        results.extend((0..batch.len()).map(|i| data[format!("r{i}")].take()));
+    }
+    Ok(results)
+}
+
+fn query(names: &[&str], fields: &str, github: &dyn Backend) -> Result<Value> {
+    let mut query = String::from("query {");
+    for (i, name_with_owner) in names.iter().enumerate() {
+        let (owner, name) = name_with_owner.split_once('/').unwrap_or_default();
+        let _ = write!(
+            query,
+            " r{i}: repository(owner: \"{owner}\", name: \"{name}\") {{ {fields} }}"
+        );
+    }
+    query.push_str(" }");
+    github.graphql(&query, &[])
+}
@@ -0,0 +1,157 @@
+//! `--non-interactive --yes`: archiving every candidate without the TUI, for
+//! cron jobs and CI. Each repo's outcome is printed as it's known, and the
+//! exit status says whether any failed.
+//!
+//! `apply-plan` and `apply` print their runs the same way.
+
+use anyhow::Result;
+use std::{path::PathBuf, sync::mpsc};
+
+use crate::{
+    access,
+    pipeline::{self, ArchiveJob, ArchiveResult},
+    preflight,
+    report::{Report, Stopwatch},
+    save_report, usage, Repo,
+};
+
+/// How a batch run by [`archive`] went.
+pub struct Outcome {
+    pub archived: Vec<String>,
+    pub failed: usize,
+    pub report: Report,
+}
+
+/// Archive `repos` without asking, skipping those the run may not touch,
+/// and fail if any of them failed.
+pub fn run(repos: &[Repo], dry_run: bool, job: &ArchiveJob, record_usage: bool) -> Result<()> {
+    let mut batch = Vec::new();
+    for repo in repos {
+        match repo.unselectable_reason() {
+            Some(why) => println!("- {}: {why}", repo.name_with_owner),
+            None => batch.push((repo.name_with_owner.as_str(), repo.disk_usage)),
+        }
+    }
+    let names = checked(&batch, dry_run, job)?;
+    if names.is_empty() {
+        println!("Nothing to archive.");
+        return Ok(());
+    }
+
+    println!(
+        "{} {} repos{}...",
+        job.action.doing(),
+        names.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    let mut outcome = archive(&names, dry_run, job, None);
+    if !dry_run && !job.demo {
+        save_report(&mut outcome.report);
+        if record_usage {
+            if let Err(e) = usage::record(&usage::Cleanup::new(job.action, outcome.archived.len()))
+            {
+                eprintln!("Warning: failed to record usage stats: {e:#}");
+            }
+        }
+    }
+    if outcome.failed > 0 {
+        anyhow::bail!("{} of {} repos failed", outcome.failed, names.len());
+    }
+    Ok(())
+}
+
+/// The `owner/name` of the repos of `batch` (with their size in KB) that
+/// pass the checks made before a batch starts, printing why for the others;
+/// fails if their backups won't fit (see `preflight::check_batch`).
+pub fn checked(batch: &[(&str, u64)], dry_run: bool, job: &ArchiveJob) -> Result<Vec<String>> {
+    let refused = preflight::check_batch(batch, dry_run, job)?;
+    for (name, why) in &refused {
+        println!("- {name}: {why}");
+    }
+    Ok(batch
+        .iter()
+        .filter(|(name, _)| !refused.iter().any(|(r, _)| r == name))
+        .map(|(name, _)| (*name).to_string())
+        .collect())
+}
+
+/// Run `names` through the pipeline, printing each one's result as it comes
+/// in. `plan` is the plan file they come from, if any.
+pub fn archive(
+    names: &[String],
+    dry_run: bool,
+    job: &ArchiveJob,
+    plan: Option<PathBuf>,
+) -> Outcome {
+    let (tx, rx) = mpsc::channel();
+    pipeline::start(
+        names.iter().cloned().enumerate().collect(),
+        dry_run,
+        job,
+        plan,
+        &tx,
+    );
+    drop(tx);
+
+    // The workers hang up once every repo is handled
+    let mut outcome = Outcome {
+        archived: Vec::new(),
+        failed: 0,
+        report: Report::new(job.action),
+    };
+    let mut stopwatch = Stopwatch::default();
+    for result in rx {
+        match result {
+            ArchiveResult::Started(idx) => stopwatch.start(idx),
+            ArchiveResult::Closing(idx, closed, total) if closed == total && total > 0 => {
+                let what = if total == 1 {
+                    "open issue or pull request"
+                } else {
+                    "open issues and pull requests"
+                };
+                println!("  closed {total} {what} in {}", names[idx]);
+            }
+            ArchiveResult::BackingUp(_)
+            | ArchiveResult::BackedUp(_)
+            | ArchiveResult::Step(_)
+            | ArchiveResult::Closing(..) => {}
+            ArchiveResult::Done(idx, state) => {
+                let name = &names[idx];
+                println!("✓ {name}");
+                if let Some(grants) = state.as_ref().and_then(|s| s.access.as_deref()) {
+                    if !grants.is_empty() {
+                        println!("  still writable by {}", access::describe(grants));
+                    }
+                }
+                outcome.report.done(name, state, stopwatch.stop(idx));
+                outcome.archived.push(name.clone());
+            }
+            ArchiveResult::AccessRemoved(idx, grant) => {
+                println!("  removed {grant} from {}", names[idx]);
+                outcome.report.access_removed(&names[idx], grant);
+            }
+            ArchiveResult::Wiki(idx, wiki) => {
+                println!("  {} wiki: {wiki}", names[idx]);
+                outcome.report.wiki(&names[idx], wiki);
+            }
+            ArchiveResult::Failed(idx, err) => {
+                println!("✗ {}: {err}", names[idx]);
+                outcome
+                    .report
+                    .failed(&names[idx], &err, stopwatch.stop(idx));
+                outcome.failed += 1;
+            }
+            ArchiveResult::DryRun(idx, Err(err)) => {
+                println!("✗ {}: {err}", names[idx]);
+                outcome.failed += 1;
+            }
+            ArchiveResult::DryRun(idx, Ok(prediction)) => {
+                println!("✓ {}: {prediction}", names[idx]);
+            }
+            ArchiveResult::CoolingDown(pause) => {
+                println!("Chunk done; cooling down for {}s...", pause.as_secs());
+            }
+        }
+    }
+    outcome
+}
@@ -0,0 +1,135 @@
+//! JSON APIs reached with a bearer token, for the GitHub API backend and the
+//! GitLab, Gitea and Bitbucket providers.
+//!
+//! Requests go through curl like everything else that talks to the network
+//! (see `tls`), so there is no HTTP stack to build in, and errors are worded
+//! like gh's so they're classified the same way (see `error`).
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{
+    fmt::Write as _,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use crate::{config::Config, error};
+
+pub struct Client {
+    url: String,
+    token: String,
+    /// `Accept` header sent with every request
+    accept: &'static str,
+    /// `ca_bundle`, `client_cert` and `client_key` from the config file
+    ca_bundle: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
+
+impl Client {
+    pub fn new(url: &str, token: String, accept: &'static str, config: &Config) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+            accept,
+            ca_bundle: config.ca_bundle.clone(),
+            cert: config.client_cert.clone(),
+            key: config.client_key.clone(),
+        }
+    }
+
+    /// Send `body`, if any, to `path` and return the JSON response, or a
+    /// typed error for an error status.
+    pub fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        // Options go in through stdin, so the token never shows up in `ps`
+        let mut options = format!(
+            "header = \"Authorization: Bearer {}\"\n",
+            escape(&self.token)
+        );
+        if let Some(body) = body {
+            let _ = writeln!(options, "data-binary = \"{}\"", escape(&body.to_string()));
+        }
+        let mut cmd = Command::new("curl");
+        for (option, path) in [
+            ("--cacert", &self.ca_bundle),
+            ("--cert", &self.cert),
+            ("--key", &self.key),
+        ] {
+            if let Some(path) = path {
+                cmd.arg(option).arg(path);
+            }
+        }
+        let mut child = cmd
+            .args([
+                "--silent",
+                "--show-error",
+                "--location",
+                "--request",
+                method,
+                "--header",
+                &format!("Accept: {}", self.accept),
+                "--header",
+                "Content-Type: application/json",
+                "--write-out",
+                "\n%{http_code}",
+                "--config",
+                "-",
+            ])
+            .arg(format!("{}{path}", self.url))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run curl. Is it installed?")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(options.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(error::Error::from_stderr(&output.stderr).into());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (response, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+        let status: u16 = status
+            .trim()
+            .parse()
+            .map_err(|_| error::Error::Parse(format!("no HTTP status from curl: {status}")))?;
+        let response: Value = if response.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(response).map_err(|e| error::Error::Parse(e.to_string()))?
+        };
+        if status >= 400 {
+            let message = response["message"]
+                .as_str()
+                .or_else(|| response["error"].as_str())
+                // Bitbucket's
+                .or_else(|| response["error"]["message"].as_str())
+                .unwrap_or("request failed");
+            return Err(error::Error::from_stderr(
+                format!("HTTP {status}: {message} ({method} {path})").as_bytes(),
+            )
+            .into());
+        }
+        Ok(response)
+    }
+}
+
+/// Percent-encode `value` for a query string.
+pub fn encode(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    })
+}
+
+/// Quote `value` for a curl config file.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
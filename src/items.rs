@@ -0,0 +1,119 @@
+//! Closing a repo's open issues and pull requests, each with an optional
+//! comment saying why, so nothing is left looking like it's still waiting
+//! for an answer once the repo is read-only.
+//!
+//! Both go through the issues API, which lists and closes pull requests as
+//! well as issues.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{error, gh, ssh::quote};
+
+/// Used by `--close-items` without a comment.
+pub const DEFAULT_COMMENT: &str = "Repository archived on {date}.";
+
+/// An open issue or pull request, as listed by `gh api`, trimmed by `--jq`.
+#[derive(Debug, Deserialize)]
+pub struct Item {
+    pub number: u64,
+    pub pull_request: bool,
+}
+
+/// The repo's open issues and pull requests, oldest first.
+pub fn open(name_with_owner: &str, token: Option<&str>) -> Result<Vec<Item>> {
+    let output = gh(token)
+        .args([
+            "api",
+            &format!("repos/{name_with_owner}/issues?state=open&direction=asc"),
+            "--paginate",
+            "--jq",
+            ".[] | {number, pull_request: (.pull_request != null)}",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    let stdout = error::check(output)
+        .with_context(|| format!("Failed to list the open issues of {name_with_owner}"))?;
+    Ok(serde_json::Deserializer::from_slice(&stdout)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| error::Error::Parse(e.to_string()))?)
+}
+
+/// The comment left on each item: `template` with `{name}` and `{date}`
+/// (today, as YYYY-MM-DD) filled in.
+fn comment(template: &str, name_with_owner: &str) -> String {
+    let name = name_with_owner
+        .split_once('/')
+        .map_or(name_with_owner, |(_, name)| name);
+    template
+        .replace("{name}", name)
+        .replace("{date}", &Utc::now().format("%Y-%m-%d").to_string())
+}
+
+/// Comment on the item, if there's a comment, and close it.
+fn close(
+    name_with_owner: &str,
+    item: &Item,
+    template: Option<&str>,
+    token: Option<&str>,
+) -> Result<()> {
+    let endpoint = format!("repos/{name_with_owner}/issues/{}", item.number);
+    let kind = if item.pull_request {
+        "pull request"
+    } else {
+        "issue"
+    };
+    if let Some(template) = template {
+        let output = gh(token)
+            .args([
+                "api",
+                "-X",
+                "POST",
+                &format!("{endpoint}/comments"),
+                "-f",
+                &format!("body={}", comment(template, name_with_owner)),
+            ])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+        error::check(output)
+            .with_context(|| format!("Failed to comment on {kind} #{}", item.number))?;
+    }
+    let output = gh(token)
+        .args(["api", "-X", "PATCH", &endpoint, "-f", "state=closed"])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    error::check(output).with_context(|| format!("Failed to close {kind} #{}", item.number))?;
+    Ok(())
+}
+
+/// Close every open issue and pull request, calling `progress` with how many
+/// are closed so far and how many there are, before the first and after each.
+/// Returns what was closed.
+pub fn close_all(
+    name_with_owner: &str,
+    template: Option<&str>,
+    token: Option<&str>,
+    progress: &dyn Fn(usize, usize),
+) -> Result<Vec<Item>> {
+    let open = open(name_with_owner, token)?;
+    progress(0, open.len());
+    for (i, item) in open.iter().enumerate() {
+        close(name_with_owner, item, template, token)?;
+        progress(i + 1, open.len());
+    }
+    Ok(open)
+}
+
+/// Shell commands that close every open issue and pull request, for
+/// `apply-plan --script`.
+pub fn script(name_with_owner: &str, template: &str) -> String {
+    let body = quote(&comment(template, name_with_owner));
+    format!(
+        "for n in $(gh api \"repos/{name_with_owner}/issues?state=open\" --paginate --jq '.[].number'); do\n\
+         \x20 gh api -X POST \"repos/{name_with_owner}/issues/$n/comments\" -f body={body} >/dev/null\n\
+         \x20 gh api -X PATCH \"repos/{name_with_owner}/issues/$n\" -f state=closed >/dev/null\n\
+         done\n"
+    )
+}
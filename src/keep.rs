@@ -0,0 +1,31 @@
+//! Opt-out sentinel files: a repo whose default branch has one of
+//! [`KEEP_FILES`] at its root is never offered for archiving.
+
+use anyhow::{Context, Result};
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{backend::Backend, graphql, Repo};
+
+pub const KEEP_FILES: [&str; 2] = [".repo-archiver-keep", ".repoarchiverignore"];
+
+/// `owner/name` of the `repos` that contain a keep file.
+pub fn fetch_kept(repos: &[Repo], github: &dyn Backend) -> Result<HashSet<String>> {
+    let mut fields = String::new();
+    for (f, file) in KEEP_FILES.iter().enumerate() {
+        let _ = write!(
+            fields,
+            " f{f}: object(expression: \"HEAD:{file}\") {{ id }}"
+        );
+    }
+
+    let names: Vec<&str> = repos.iter().map(|r| r.name_with_owner.as_str()).collect();
+    let results =
+        graphql::per_repo(&names, &fields, github).context("Failed to check for keep files")?;
+
+    Ok(names
+        .into_iter()
+        .zip(results)
+        .filter(|(_, files)| (0..KEEP_FILES.len()).any(|f| !files[format!("f{f}")].is_null()))
+        .map(|(name, _)| name.to_string())
+        .collect())
+}
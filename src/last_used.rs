@@ -0,0 +1,93 @@
+//! The age and filters used last time against each owner, offered again when
+//! the next run targets the same owner.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use crate::{age::Age, paths, RepoFilter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    /// In the form `--age` takes
+    pub age: String,
+    #[serde(flatten)]
+    pub filter: RepoFilter,
+}
+
+fn settings_file() -> Result<PathBuf> {
+    paths::state_dir()
+        .map(|dir| dir.join("last-used.json"))
+        .context("No home directory to remember settings in")
+}
+
+/// Settings per owner login. A missing or unreadable file just means
+/// nothing is remembered yet.
+fn load_all() -> BTreeMap<String, Settings> {
+    settings_file()
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|contents| serde_json::from_slice(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_all(all: &BTreeMap<String, Settings>) -> Result<()> {
+    let path = settings_file()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_vec_pretty(all)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn save(owner: &str, settings: Settings) -> Result<()> {
+    let mut all = load_all();
+    all.insert(owner.to_string(), settings);
+    save_all(&all)
+}
+
+/// The settings remembered for `owner`, if the user wants them again.
+///
+/// Asks first when there's a terminal to ask on; answering no forgets them.
+/// Without one they're reused as is.
+pub fn offer(owner: &str, describe: impl Fn(&Settings) -> String) -> Result<Option<Settings>> {
+    let mut all = load_all();
+    let Some(settings) = all.get(owner) else {
+        return Ok(None);
+    };
+    if !io::stdin().is_terminal() {
+        return Ok(Some(settings.clone()));
+    }
+
+    println!("Last time for {owner}: {}", describe(settings));
+    print!("Reuse these settings? [Y/n] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if answer.trim().eq_ignore_ascii_case("n") {
+        all.remove(owner);
+        save_all(&all)?;
+        return Ok(None);
+    }
+    Ok(all.remove(owner))
+}
+
+/// The age picked last time, so the picker can start from it.
+pub fn load_age() -> Option<Age> {
+    let contents = fs::read_to_string(paths::last_age_file()?).ok()?;
+    Age::parse(&contents).ok()
+}
+
+pub fn save_age(age: Age) -> Result<()> {
+    let path = paths::last_age_file().context("No home directory to remember the age in")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, age.arg()).with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,92 @@
+mod access;
+mod age;
+mod analyze;
+mod announce;
+mod app;
+mod assign;
+mod backend;
+mod backup;
+#[doc(hidden)]
+pub mod bench;
+mod bitbucket;
+mod cache;
+mod capabilities;
+mod challenge;
+mod cli;
+mod compare;
+mod config;
+mod daemon;
+mod dates;
+mod demo;
+mod description;
+mod diagnostics;
+mod draw;
+mod encrypt;
+mod error;
+mod fetch;
+mod forks;
+mod gitea;
+mod gitlab;
+mod graphql;
+mod headless;
+mod http;
+mod items;
+mod keep;
+mod last_used;
+mod lock;
+mod mirror;
+mod notice;
+mod paths;
+mod pipeline;
+mod plan;
+mod policy;
+mod preflight;
+mod provider;
+mod repo;
+mod report;
+mod restore;
+mod rules;
+mod run;
+mod sample;
+mod screen;
+mod serve;
+mod session;
+mod ssh;
+mod stats;
+mod steps;
+mod tls;
+mod tui;
+mod unarchive;
+mod usage;
+mod web;
+
+use std::process::{Command, ExitCode};
+
+// What the other modules and the benches reach through the crate root
+use app::{App, Column, SPINNER_FRAMES};
+use draw::ui;
+use repo::{
+    candidates, drop_recently_active, drop_too_new, fetch_ci_states, AgeBasis, Repo, RepoFilter,
+};
+use run::{parse_github_remote, save_report};
+
+/// Exit with a code per kind of GitHub failure (see [`error::Error::exit_code`]),
+/// so scripts can tell an expired login from a network outage.
+pub fn main() -> ExitCode {
+    match run::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(error::Error::find(&e).map_or(1, error::Error::exit_code))
+        }
+    }
+}
+
+/// Build a `gh` invocation, authenticating with the configured token if any.
+fn gh(token: Option<&str>) -> Command {
+    let mut cmd = Command::new("gh");
+    if let Some(token) = token {
+        cmd.env("GH_TOKEN", token);
+    }
+    cmd
+}
@@ -0,0 +1,183 @@
+//! `--lock-repo OWNER/NAME`: an open issue in a repo everyone sweeping an org
+//! can see, held for as long as a run may archive anything, so two people's
+//! runs don't race each other over the same repos.
+//!
+//! Taking the lock opens the issue, then looks again: if two runs opened one
+//! at about the same time, the older issue wins and the other run closes its
+//! own and stops. The issue is closed when the run ends; one left behind by a
+//! run that was killed can be closed by hand, or with `--break-lock`.
+//! `--web` and `serve` hold it for each batch instead (see [`for_batch`]).
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+
+use crate::{error, gh, pipeline::ArchiveJob};
+
+const TITLE: &str = "repo-archiver run in progress";
+
+const BODY: &str = "Opened by repo-archiver so that nobody else archives repos at the same \
+     time; it's closed when the run ends. If no run is going any more, close this issue or \
+     pass `--break-lock`.";
+
+/// An open lock issue, as listed by `gh api`, trimmed by `--jq`.
+#[derive(Debug, Deserialize)]
+struct Holder {
+    number: u64,
+    user: String,
+    created_at: DateTime<Utc>,
+}
+
+/// A lock taken by this run, released when dropped.
+pub struct Lock {
+    repo: String,
+    number: u64,
+    token: Option<String>,
+}
+
+impl Lock {
+    /// Take the lock in `repo`, first closing any open lock issue if
+    /// `break_lock`, or failing with who holds it otherwise.
+    pub fn acquire(repo: &str, break_lock: bool, token: Option<&str>) -> Result<Self> {
+        let held = holders(repo, token)?;
+        if let Some(holder) = held.first().filter(|_| !break_lock) {
+            return Err(busy(repo, holder));
+        }
+        for holder in &held {
+            comment(
+                repo,
+                holder.number,
+                "Lock broken with `--break-lock`.",
+                token,
+            )?;
+            close(repo, holder.number, token)?;
+        }
+
+        let lock = Self {
+            repo: repo.to_string(),
+            number: open(repo, token)?,
+            token: token.map(str::to_string),
+        };
+        // Another run may have opened its issue between the two lookups; the
+        // oldest one holds the lock, and dropping ours closes it again
+        match holders(repo, token)?.first() {
+            Some(holder) if holder.number != lock.number => Err(busy(repo, holder)),
+            _ => Ok(lock),
+        }
+    }
+}
+
+/// Take the lock in `repo`, if one is set, for a single batch of `--web` or
+/// `serve`: those run until they're killed, which would leave a lock held
+/// for the whole run behind. Dry runs don't take it.
+pub fn for_batch(repo: Option<&str>, dry_run: bool, job: &ArchiveJob) -> Result<Option<Lock>> {
+    match repo {
+        Some(repo) if !dry_run && !job.demo => {
+            Lock::acquire(repo, false, job.token.as_deref()).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(e) = close(&self.repo, self.number, self.token.as_deref()) {
+            eprintln!(
+                "Warning: failed to release the lock; close {}#{} by hand: {e:#}",
+                self.repo, self.number
+            );
+        }
+    }
+}
+
+fn busy(repo: &str, holder: &Holder) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{} has been running repo-archiver since {} ({repo}#{}); wait for that run to \
+         finish, or pass --break-lock if it's no longer going",
+        holder.user,
+        holder
+            .created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M"),
+        holder.number
+    )
+}
+
+/// The open lock issues in `repo`, oldest first.
+fn holders(repo: &str, token: Option<&str>) -> Result<Vec<Holder>> {
+    let output = gh(token)
+        .args([
+            "api",
+            &format!("repos/{repo}/issues?state=open&direction=asc"),
+            "--paginate",
+            "--jq",
+            &format!(
+                ".[] | select(.title == \"{TITLE}\") | {{number, user: .user.login, created_at}}"
+            ),
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    let stdout =
+        error::check(output).with_context(|| format!("Failed to look up the lock in {repo}"))?;
+    Ok(serde_json::Deserializer::from_slice(&stdout)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| error::Error::Parse(e.to_string()))?)
+}
+
+/// Open a lock issue, returning its number.
+fn open(repo: &str, token: Option<&str>) -> Result<u64> {
+    let output = gh(token)
+        .args([
+            "api",
+            "-X",
+            "POST",
+            &format!("repos/{repo}/issues"),
+            "-f",
+            &format!("title={TITLE}"),
+            "-f",
+            &format!("body={BODY}"),
+            "--jq",
+            ".number",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    let stdout =
+        error::check(output).with_context(|| format!("Failed to take the lock in {repo}"))?;
+    String::from_utf8_lossy(&stdout)
+        .trim()
+        .parse()
+        .map_err(|e: std::num::ParseIntError| error::Error::Parse(e.to_string()).into())
+}
+
+fn comment(repo: &str, number: u64, body: &str, token: Option<&str>) -> Result<()> {
+    let output = gh(token)
+        .args([
+            "api",
+            "-X",
+            "POST",
+            &format!("repos/{repo}/issues/{number}/comments"),
+            "-f",
+            &format!("body={body}"),
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    error::check(output).with_context(|| format!("Failed to comment on {repo}#{number}"))?;
+    Ok(())
+}
+
+fn close(repo: &str, number: u64, token: Option<&str>) -> Result<()> {
+    let output = gh(token)
+        .args([
+            "api",
+            "-X",
+            "PATCH",
+            &format!("repos/{repo}/issues/{number}"),
+            "-f",
+            "state=closed",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    error::check(output).with_context(|| format!("Failed to close {repo}#{number}"))?;
+    Ok(())
+}
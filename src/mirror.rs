@@ -0,0 +1,345 @@
+//! Bare mirror clones, used to move a repo to another forge before archiving it.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path, process::Command, str::FromStr};
+
+/// Only branches and tags are copied; GitHub's `refs/pull/*` are read-only
+/// and most forges reject them.
+const REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+/// Fill in `{owner}` and `{name}` in a `--migrate-to` URL template.
+pub fn remote_url(template: &str, name_with_owner: &str) -> String {
+    let (owner, name) = name_with_owner
+        .split_once('/')
+        .unwrap_or(("", name_with_owner));
+    template.replace("{owner}", owner).replace("{name}", name)
+}
+
+/// Bandwidth cap for clones and pushes, in KiB/s.
+///
+/// Git has no rate limit of its own, so transfers run under `trickle`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BwLimit(u32);
+
+impl FromStr for BwLimit {
+    type Err = anyhow::Error;
+
+    /// Parse "800" or "800k" (KiB/s) and "2m" (MiB/s).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        let (num, factor) = if let Some(num) = s.strip_suffix('m') {
+            (num, 1024)
+        } else {
+            (s.strip_suffix('k').unwrap_or(&s), 1)
+        };
+        let num: u32 = num
+            .parse()
+            .with_context(|| format!("Invalid bandwidth limit '{s}'. Use e.g. '500k' or '2m'"))?;
+        match num.checked_mul(factor) {
+            Some(0) => anyhow::bail!("Bandwidth limit must be greater than zero"),
+            Some(kbps) => Ok(Self(kbps)),
+            None => anyhow::bail!("Bandwidth limit '{s}' is too large"),
+        }
+    }
+}
+
+impl TryFrom<String> for BwLimit {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Bring the bare mirror at `dest` up to date with GitHub, returning whether
+/// any branch or tag changed.
+///
+/// An existing mirror is fetched into, which is much faster than cloning
+/// large repos again on repeat runs. Anything at `dest` that isn't a usable
+/// mirror is replaced by a fresh clone.
+pub fn sync(
+    name_with_owner: &str,
+    dest: &Path,
+    token: Option<&str>,
+    limit: Option<BwLimit>,
+) -> Result<bool> {
+    if dest.join("HEAD").exists() {
+        if let Ok(before) = local_refs(dest) {
+            fetch(dest, token, limit)?;
+            return Ok(local_refs(dest)? != before);
+        }
+    }
+
+    clone(name_with_owner, dest, token, limit)?;
+    Ok(true)
+}
+
+fn fetch(dir: &Path, token: Option<&str>, limit: Option<BwLimit>) -> Result<()> {
+    let mut cmd = transfer(limit);
+    let output = with_gh_credentials(&mut cmd, token)
+        .arg("-C")
+        .arg(dir)
+        .args(["fetch", "--prune", "--quiet", "origin"])
+        .output()
+        .context("Failed to run git (or trickle, with --bw-limit). Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Mirror fetch failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Create a fresh bare mirror of a GitHub repo at `dest`.
+///
+/// Uses plain git with `gh` as the credential helper so private repos work
+/// and the transfer can be throttled.
+fn clone(
+    name_with_owner: &str,
+    dest: &Path,
+    token: Option<&str>,
+    limit: Option<BwLimit>,
+) -> Result<()> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)
+            .with_context(|| format!("Failed to remove old mirror {}", dest.display()))?;
+    }
+
+    let mut cmd = transfer(limit);
+    let output = with_gh_credentials(&mut cmd, token)
+        .args(["clone", "--mirror", "--quiet", &github_url(name_with_owner)])
+        .arg(dest)
+        .output()
+        .context("Failed to run git (or trickle, with --bw-limit). Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Mirror clone failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Pack the whole mirror at `dir` into a single `git bundle` file.
+pub fn bundle(dir: &Path, dest: &Path) -> Result<()> {
+    let output = git(dir)
+        .args(["bundle", "create", "--quiet"])
+        .arg(dest)
+        .arg("--all")
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to create bundle: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Turn a `git bundle` file into a bare mirror at `dest`.
+pub fn clone_bundle(bundle: &Path, dest: &Path) -> Result<()> {
+    let output = Command::new("git")
+        .args(["clone", "--mirror", "--quiet"])
+        .arg(bundle)
+        .arg(dest)
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to clone bundle: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Push all branches and tags of the mirror at `dir` to a GitHub repo.
+pub fn push_to_github(dir: &Path, name_with_owner: &str, token: Option<&str>) -> Result<()> {
+    let mut cmd = git(dir);
+    let output = with_gh_credentials(&mut cmd, token)
+        .args(["push", "--quiet", &github_url(name_with_owner)])
+        .args(REFSPECS)
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Push to {name_with_owner} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Push all branches and tags of the mirror at `dir` to `remote`.
+pub fn push(dir: &Path, remote: &str, limit: Option<BwLimit>) -> Result<()> {
+    let output = transfer(limit)
+        .arg("-C")
+        .arg(dir)
+        .args(["push", "--quiet", remote])
+        .args(REFSPECS)
+        .output()
+        .context("Failed to run git (or trickle, with --bw-limit). Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Push to {remote} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Check that the mirror at `dir` is a complete, intact copy of the GitHub repo.
+///
+/// Runs a connectivity check so every ref's history is present, then makes
+/// sure every branch and tag on GitHub is in the mirror at the same commit.
+pub fn verify_local(dir: &Path, name_with_owner: &str, token: Option<&str>) -> Result<()> {
+    let output = git(dir)
+        .args(["fsck", "--connectivity-only", "--no-progress"])
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Mirror at {} is corrupt: {}",
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let mut cmd = git(dir);
+    with_gh_credentials(&mut cmd, token);
+    let source = remote_refs(cmd, &github_url(name_with_owner))?;
+    compare_refs(&source, &local_refs(dir)?)
+        .with_context(|| format!("Mirror of {name_with_owner} is incomplete"))
+}
+
+/// Check that `remote` has exactly the branches and tags of the mirror at `dir`.
+pub fn verify(dir: &Path, remote: &str) -> Result<()> {
+    compare_refs(&local_refs(dir)?, &remote_refs(git(dir), remote)?)
+        .with_context(|| format!("{remote} does not match the mirror"))
+}
+
+/// Fail unless every ref in `expected` exists in `actual` at the same commit.
+fn compare_refs(
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+) -> Result<()> {
+    let missing: Vec<&str> = expected
+        .iter()
+        .filter(|(name, sha)| actual.get(*name) != Some(sha))
+        .map(|(name, _)| name.as_str())
+        .collect();
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "{} ref(s) missing or different: {}",
+            missing.len(),
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn github_url(name_with_owner: &str) -> String {
+    format!("https://github.com/{name_with_owner}.git")
+}
+
+fn git(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir);
+    cmd
+}
+
+/// Whether the repo's wiki has any pages. GitHub only creates the wiki's git
+/// repo with the first page, so an enabled but empty wiki can't be fetched.
+pub fn wiki_has_pages(name_with_owner: &str, token: Option<&str>) -> Result<bool> {
+    let mut cmd = Command::new("git");
+    let output = with_gh_credentials(&mut cmd, token)
+        .args(["ls-remote", "--heads"])
+        .arg(format!("https://github.com/{name_with_owner}.wiki.git"))
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+    Ok(output.status.success() && !output.stdout.is_empty())
+}
+
+/// Let git authenticate to GitHub through `gh`, so private repos work.
+fn with_gh_credentials<'a>(cmd: &'a mut Command, token: Option<&str>) -> &'a mut Command {
+    if let Some(token) = token {
+        cmd.env("GH_TOKEN", token);
+    }
+    cmd.args([
+        "-c",
+        "credential.helper=",
+        "-c",
+        "credential.helper=!gh auth git-credential",
+    ])
+}
+
+/// A git command for a network transfer, throttled through `trickle` if limited.
+fn transfer(limit: Option<BwLimit>) -> Command {
+    match limit {
+        Some(BwLimit(kbps)) => {
+            let mut cmd = Command::new("trickle");
+            let kbps = kbps.to_string();
+            cmd.args(["-s", "-d", &kbps, "-u", &kbps, "git"]);
+            cmd
+        }
+        None => Command::new("git"),
+    }
+}
+
+fn local_refs(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let output = git(dir)
+        .args([
+            "for-each-ref",
+            "--format=%(objectname) %(refname)",
+            "refs/heads",
+            "refs/tags",
+        ])
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to list refs: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(parse_refs(&String::from_utf8_lossy(&output.stdout), ' '))
+}
+
+fn remote_refs(mut git: Command, remote: &str) -> Result<BTreeMap<String, String>> {
+    let output = git
+        .args(["ls-remote", "--heads", "--tags", remote])
+        .output()
+        .context("Failed to run git. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to list refs on {remote}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(parse_refs(&String::from_utf8_lossy(&output.stdout), '\t'))
+}
+
+/// Parse `<sha><sep><ref>` lines into a ref -> sha map, skipping peeled tags.
+fn parse_refs(output: &str, separator: char) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(separator))
+        .filter(|(_, name)| !name.ends_with("^{}"))
+        .map(|(sha, name)| (name.to_string(), sha.to_string()))
+        .collect()
+}
@@ -0,0 +1,256 @@
+//! Commits made right before archiving, for visitors to find: a notice at
+//! the top of each repo's README, so they (and search result snippets) see
+//! that the repo is archived before reading anything else, and with
+//! `--stamp` an `ARCHIVED.md` saying when, why and what replaced it.
+//!
+//! Files are read and written through the contents API, which commits
+//! straight to the default branch. A README that already starts with the
+//! notice, whatever day it was added, is left alone, so rerunning a batch
+//! doesn't stack notices; a repo without a README gets a `README.md` holding
+//! only the notice.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, fmt::Write as _};
+
+use crate::{
+    backend::{Backend, Gh},
+    error, gh,
+    ssh::quote,
+};
+
+/// Used by `--readme-notice` without a text.
+pub const DEFAULT: &str = "> ⚠️ This repository is archived and unmaintained.";
+
+const COMMIT_MESSAGE: &str = "Add archive notice to README";
+
+const STAMP_PATH: &str = "ARCHIVED.md";
+const STAMP_MESSAGE: &str = "Add ARCHIVED.md";
+
+/// A file as the contents API returns it.
+#[derive(Deserialize)]
+struct File {
+    path: String,
+    sha: String,
+    /// Base64, wrapped at 60 characters
+    content: String,
+}
+
+/// The notice for a repo: `template` with `{name}` and `{date}` (today, as
+/// YYYY-MM-DD) filled in.
+fn text(template: &str, name_with_owner: &str) -> String {
+    with_name(template, name_with_owner)
+        .replace("{date}", &Utc::now().format("%Y-%m-%d").to_string())
+}
+
+fn with_name(template: &str, name_with_owner: &str) -> String {
+    let name = name_with_owner
+        .split_once('/')
+        .map_or(name_with_owner, |(_, name)| name);
+    template.replace("{name}", name)
+}
+
+/// Whether `readme` starts with the notice for a repo, with any date where
+/// the template has `{date}`: one added by an earlier day's run counts.
+fn has_notice(readme: &str, template: &str, name_with_owner: &str) -> bool {
+    let notice = with_name(template, name_with_owner);
+    let mut parts = notice.trim().split("{date}");
+    let Some(mut rest) = parts
+        .next()
+        .and_then(|first| readme.trim_start().strip_prefix(first))
+    else {
+        return false;
+    };
+    for part in parts {
+        let Some((date, after)) = rest.split_at_checked(10) else {
+            return false;
+        };
+        match after.strip_prefix(part) {
+            Some(after) if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => rest = after,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Commit the notice to the top of the repo's README.
+pub fn add(name_with_owner: &str, template: &str, token: Option<&str>) -> Result<()> {
+    let notice = text(template, name_with_owner);
+    let readme = read(&format!("repos/{name_with_owner}/readme"), token)
+        .context("Failed to read the README")?;
+    let (path, sha, content) = match readme {
+        Some((file, current)) => {
+            if has_notice(&current, template, name_with_owner) {
+                return Ok(());
+            }
+            (file.path, Some(file.sha), format!("{notice}\n\n{current}"))
+        }
+        None => ("README.md".to_string(), None, format!("{notice}\n")),
+    };
+    write(
+        name_with_owner,
+        &path,
+        &content,
+        sha.as_deref(),
+        COMMIT_MESSAGE,
+        token,
+    )
+    .context("Failed to commit the README notice")
+}
+
+/// What `--stamp` writes to `ARCHIVED.md`.
+#[derive(Debug, Clone, Default)]
+pub struct Stamp {
+    /// Why the repos are archived
+    pub reason: Option<String>,
+    /// What replaced each repo, by `owner/name`: another repo's `owner/name`
+    /// or a URL
+    pub successors: HashMap<String, String>,
+}
+
+impl Stamp {
+    fn content(&self, name_with_owner: &str) -> String {
+        let mut content = format!(
+            "# Archived\n\nThis repository was archived on {}.\n",
+            Utc::now().format("%Y-%m-%d")
+        );
+        if let Some(reason) = &self.reason {
+            let _ = write!(content, "\n**Reason:** {}\n", text(reason, name_with_owner));
+        }
+        if let Some(successor) = self.successors.get(name_with_owner) {
+            let link = if successor.contains("://") {
+                successor.clone()
+            } else {
+                format!("[{successor}](https://github.com/{successor})")
+            };
+            let _ = write!(content, "\n**Successor:** {link}\n");
+        }
+        content
+    }
+
+    /// Commit `ARCHIVED.md`, replacing any left by an earlier run.
+    pub fn commit(&self, name_with_owner: &str, token: Option<&str>) -> Result<()> {
+        let existing = read(
+            &format!("repos/{name_with_owner}/contents/{STAMP_PATH}"),
+            token,
+        )
+        .with_context(|| format!("Failed to read {STAMP_PATH}"))?;
+        write(
+            name_with_owner,
+            STAMP_PATH,
+            &self.content(name_with_owner),
+            existing.as_ref().map(|(file, _)| file.sha.as_str()),
+            STAMP_MESSAGE,
+            token,
+        )
+        .with_context(|| format!("Failed to commit {STAMP_PATH}"))
+    }
+
+    /// Shell commands that commit `ARCHIVED.md`, for `apply-plan --script`.
+    pub fn script(&self, name_with_owner: &str) -> String {
+        let content = quote(&self.content(name_with_owner));
+        let message = quote(STAMP_MESSAGE);
+        format!(
+            "sha=$(gh api repos/{name_with_owner}/contents/{STAMP_PATH} --jq .sha 2>/dev/null || true)\n\
+             gh api -X PUT repos/{name_with_owner}/contents/{STAMP_PATH} -f message={message} \
+             -f content=\"$(printf '%s' {content} | base64 | tr -d '\\n')\" ${{sha:+-f sha=\"$sha\"}}\n"
+        )
+    }
+}
+
+/// The file at `endpoint` and its text, or `None` if there's no such file.
+fn read(endpoint: &str, token: Option<&str>) -> Result<Option<(File, String)>> {
+    let output = gh(token)
+        .args(["api", endpoint])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    let file: File = match error::check(output) {
+        Ok(stdout) => {
+            serde_json::from_slice(&stdout).map_err(|e| error::Error::Parse(e.to_string()))?
+        }
+        Err(error::Error::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let encoded: String = file.content.split_whitespace().collect();
+    let text = String::from_utf8(
+        STANDARD
+            .decode(encoded)
+            .map_err(|e| error::Error::Parse(e.to_string()))?,
+    )
+    .with_context(|| format!("{} isn't UTF-8", file.path))?;
+    Ok(Some((file, text)))
+}
+
+/// Commit `content` to `path` on the default branch; `sha` is that of the
+/// file being replaced, if there is one. The body goes in on stdin, since a
+/// long README wouldn't fit in `gh`'s arguments.
+fn write(
+    name_with_owner: &str,
+    path: &str,
+    content: &str,
+    sha: Option<&str>,
+    message: &str,
+    token: Option<&str>,
+) -> Result<()> {
+    let mut body = json!({
+        "message": message,
+        "content": STANDARD.encode(content),
+    });
+    if let Some(sha) = sha {
+        body["sha"] = json!(sha);
+    }
+    Gh::new(token.map(str::to_string)).rest(
+        "PUT",
+        &format!("repos/{name_with_owner}/contents/{path}"),
+        Some(&body),
+    )?;
+    Ok(())
+}
+
+/// Shell commands that add the notice, for `apply-plan --script`.
+pub fn script(name_with_owner: &str, template: &str) -> String {
+    let notice = quote(&text(template, name_with_owner));
+    let message = quote(COMMIT_MESSAGE);
+    format!(
+        "readme=$(gh api repos/{name_with_owner}/readme)\n\
+         {{ printf '%s\\n\\n' {notice}; echo \"$readme\" | jq -r .content | base64 -d; }} | base64 | tr -d '\\n' \\\n  \
+         | jq -Rs --arg message {message} --arg sha \"$(echo \"$readme\" | jq -r .sha)\" \
+         '{{message: $message, content: ., sha: $sha}}' \\\n  \
+         | gh api -X PUT \"repos/{name_with_owner}/contents/$(echo \"$readme\" | jq -r .path)\" --input -\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_added_on_another_day_are_recognised() {
+        let template = "> Archived on {date}: {name} is unmaintained.";
+        let readme = "\n> Archived on 2023-01-31: old is unmaintained.\n\n# old\n";
+
+        assert!(has_notice(readme, template, "octo/old"));
+        assert!(has_notice(
+            &text(template, "octo/old"),
+            template,
+            "octo/old"
+        ));
+        assert!(!has_notice(readme, template, "octo/new"));
+        assert!(!has_notice(
+            "> Archived on someday: old is unmaintained.",
+            template,
+            "octo/old"
+        ));
+        assert!(!has_notice("# old\n", template, "octo/old"));
+    }
+
+    #[test]
+    fn script_sends_the_readme_on_stdin() {
+        let script = script("octo/old", DEFAULT);
+        assert!(script.contains("--input -"));
+        assert!(!script.contains("-f content="));
+    }
+}
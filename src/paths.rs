@@ -0,0 +1,60 @@
+//! Where repo-archiver keeps its files.
+//!
+//! Follows the platform conventions from the `directories` crate: XDG on
+//! Linux, `~/Library/...` on macOS and `%APPDATA%` on Windows.
+
+use directories::ProjectDirs;
+use std::path::PathBuf;
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "repo-archiver")
+}
+
+/// Default location of `config.toml`, used unless `--config` is given.
+pub fn config_file() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+/// Directory for data that is safe to delete, such as fetched repo lists.
+pub fn cache_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+}
+
+/// Mirrors behind bundled backups, fetched into on later runs instead of
+/// cloned again. Kept out of the backup dir, where a plaintext mirror would
+/// undo `--encrypt-to`.
+pub fn mirrors_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("mirrors"))
+}
+
+/// Directory for state that should survive between runs.
+///
+/// Only Linux has a dedicated state directory; elsewhere this falls back to
+/// the local data directory.
+pub fn state_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| {
+        dirs.state_dir()
+            .unwrap_or_else(|| dirs.data_local_dir())
+            .to_path_buf()
+    })
+}
+
+/// Append-only log of past runs.
+pub fn history_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("history.jsonl"))
+}
+
+/// Opt-in log of past cleanups, for `repo-archiver stats`.
+pub fn usage_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("usage.jsonl"))
+}
+
+/// One JSON report per run that archived or deleted repos.
+pub fn reports_dir() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("reports"))
+}
+
+/// The age picked last time, which the age picker starts from.
+pub fn last_age_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("last-age"))
+}
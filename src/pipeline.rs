@@ -0,0 +1,1240 @@
+//! Background workers that back up and archive the selected repos.
+//!
+//! Backups (metadata snapshots, mirror clones) can take minutes per repo, so
+//! they run on their own pool and hand finished repos over to a separate pool
+//! that makes the archive calls. A big clone never holds up archiving repos
+//! whose backups are already done.
+//!
+//! Very large batches can be fed to the pools in chunks: after each chunk the
+//! archived repos are checked off in a checkpoint plan, so an interrupted run
+//! can be resumed with `apply-plan`, and the workers cool down before the next
+//! chunk to stay clear of GitHub's secondary rate limits.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    env, fs,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    access::{self, Grant},
+    announce::Announcement,
+    backend::Backend,
+    backup, description,
+    encrypt::Recipient,
+    error, gh, items,
+    mirror::{self, BwLimit},
+    notice, paths,
+    plan::Plan,
+    preflight::{self, RepoCheck},
+    provider::{self, Provider},
+    report::{FinalState, Wiki},
+    ssh::SshRemote,
+};
+
+pub const DEFAULT_BACKUP_WORKERS: NonZeroUsize = NonZeroUsize::new(2).unwrap();
+pub const DEFAULT_ARCHIVE_WORKERS: NonZeroUsize = NonZeroUsize::MIN;
+pub const DEFAULT_COOL_DOWN: Duration = Duration::from_mins(1);
+
+/// Consecutive failures that halve the archive pool's concurrency
+const BACK_OFF_AFTER: usize = 3;
+/// Consecutive successes that let one more archive worker back in
+const RAMP_UP_AFTER: usize = 5;
+/// How often a worker holding a repo checks for a free slot while throttled
+const THROTTLE_POLL: Duration = Duration::from_millis(100);
+/// Repo settings `--disable-features` turns off, as `gh api` fields
+pub const FEATURES: [&str; 3] = ["has_issues=false", "has_wiki=false", "has_projects=false"];
+
+#[derive(Debug)]
+pub enum ArchiveResult {
+    Started(usize),
+    /// The repo's backup started; its steps come before the archive's
+    BackingUp(usize),
+    /// Backed up, so archiving it is next
+    BackedUp(usize),
+    /// One more of the repo's steps (see `ArchiveJob::step_count`) is done
+    Step(usize),
+    /// Archived or deleted; with how GitHub shows the repo afterwards, if it
+    /// could be looked up
+    Done(usize, Option<FinalState>),
+    Failed(usize, String),
+    /// Access removed from the repo before archiving it, for the report
+    AccessRemoved(usize, Grant),
+    /// With `--export-wiki`: whether the repo's wiki was backed up
+    Wiki(usize, Wiki),
+    /// How many of the repo's open issues and pull requests are closed so
+    /// far, and how many there are
+    Closing(usize, usize, usize),
+    /// Dry runs only: what archiving would have done, or why it would fail
+    DryRun(usize, Result<String, String>),
+    /// A chunk is done; the next one starts after this long
+    CoolingDown(Duration),
+}
+
+/// What happens to each repo once it's backed up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Archive,
+    /// Permanently delete the repo; chosen with `--delete` or
+    /// `--prune-forks`, which come with their own confirmation
+    #[value(skip)]
+    Delete,
+    /// Hand the repo over to another user or organization (`transfer_to`)
+    Transfer,
+    /// Hide the repo by making it private, leaving it writable
+    Privatize,
+    /// Only add `topics`, leaving the repo as it is otherwise
+    Tag,
+}
+
+impl Action {
+    /// The `--action` value naming this action.
+    pub fn flag(self) -> String {
+        self.to_possible_value()
+            .map_or_else(String::new, |v| v.get_name().to_string())
+    }
+
+    /// As in "Archive 3 repos?"
+    pub const fn verb(self) -> &'static str {
+        match self {
+            Self::Archive => "Archive",
+            Self::Delete => "Delete",
+            Self::Transfer => "Transfer",
+            Self::Privatize => "Privatize",
+            Self::Tag => "Tag",
+        }
+    }
+
+    /// As in "Archiving (2/3)"
+    pub const fn doing(self) -> &'static str {
+        match self {
+            Self::Archive => "Archiving",
+            Self::Delete => "Deleting",
+            Self::Transfer => "Transferring",
+            Self::Privatize => "Privatizing",
+            Self::Tag => "Tagging",
+        }
+    }
+
+    /// As in "All repos archived!"
+    pub const fn done(self) -> &'static str {
+        match self {
+            Self::Archive => "archived",
+            Self::Delete => "deleted",
+            Self::Transfer => "transferred",
+            Self::Privatize => "made private",
+            Self::Tag => "tagged",
+        }
+    }
+}
+
+/// Feed the pools this many repos at a time, pausing in between.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunking {
+    pub size: NonZeroUsize,
+    pub cool_down: Duration,
+}
+
+/// Settings for the per-repo work done by the background workers.
+#[derive(Clone)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "one switch per optional step, each set by its own flag"
+)]
+pub struct ArchiveJob {
+    pub action: Action,
+    /// New owner of the repos, for `Action::Transfer`
+    pub transfer_to: Option<String>,
+    /// Record who can still write to each archived repo in the report
+    pub access_review: bool,
+    /// Record each archived repo's owning teams in the report, in org mode
+    pub team_summary: bool,
+    /// Collaborators and teams removed right before archiving
+    pub revoke: access::Revoke,
+    /// Close open issues and pull requests right before archiving, commenting
+    /// this on each
+    pub close_items: Option<String>,
+    pub token: Option<String>,
+    /// How GitHub is reached for lookups: the configured backend, or `gh`
+    /// where repos aren't on GitHub
+    pub github: Arc<dyn Backend>,
+    pub backup_dir: Option<PathBuf>,
+    /// How the git data is kept in `backup_dir`
+    pub backup_as: backup::Format,
+    /// Also back up team access and organization settings, for
+    /// `--export-org-settings`
+    pub org_settings: Option<backup::OrgExport>,
+    /// Also back up each repo's wiki, if it has pages, for `--export-wiki`
+    pub export_wiki: bool,
+    /// Also back up each repo's issues and pull requests with their
+    /// comments, for `--export-issues`
+    pub export_issues: bool,
+    pub migrate_to: Option<String>,
+    pub bw_limit: Option<BwLimit>,
+    pub encrypt_to: Option<Recipient>,
+    pub backup_workers: NonZeroUsize,
+    pub archive_workers: NonZeroUsize,
+    pub chunking: Option<Chunking>,
+    /// Archive bare repos on this server instead of on GitHub
+    pub ssh: Option<SshRemote>,
+    /// Edit applied to each repo's description right before archiving it
+    pub description_edit: Option<description::Edit>,
+    /// Topics added to each repo right before archiving it, while it's still
+    /// writable, or instead of archiving it for `Action::Tag`
+    pub topics: Vec<String>,
+    /// Notice committed to the top of each repo's README right before
+    /// archiving it
+    pub readme_notice: Option<String>,
+    /// ARCHIVED.md committed as the last commit before archiving each repo
+    pub stamp: Option<notice::Stamp>,
+    /// Final post made right before archiving each repo
+    pub announcement: Option<Announcement>,
+    /// Turn off issues, the wiki and projects as the last step before
+    /// archiving
+    pub disable_features: bool,
+    /// Where repos are listed and archived
+    pub provider: Arc<dyn Provider>,
+    /// Which forge `provider` is, unless `ssh` or `demo` replace it
+    pub forge: provider::Kind,
+    /// Only pretend to archive, for `--demo`
+    pub demo: bool,
+}
+
+impl ArchiveJob {
+    /// Whether repos are on GitHub (or the demo's pretend GitHub), where
+    /// everything besides archiving itself works.
+    pub fn on_github(&self) -> bool {
+        self.ssh.is_none() && self.forge == provider::Kind::GitHub
+    }
+
+    pub const fn has_backup(&self) -> bool {
+        self.backup_dir.is_some() || self.migrate_to.is_some()
+    }
+
+    /// How the git data ends up in the backup dir, if there is one: always
+    /// a bundle when encrypting, since a plain mirror can't be kept then.
+    fn git_backup(&self) -> Option<backup::Format> {
+        self.backup_dir.as_ref()?;
+        Some(if self.encrypt_to.is_some() {
+            backup::Format::Bundle
+        } else {
+            self.backup_as
+        })
+    }
+
+    /// Everything that has to succeed before a repo may be archived, calling
+    /// `step` after each step. Returns whether the wiki was exported, with
+    /// `--export-wiki`.
+    pub fn backup(&self, name_with_owner: &str, step: &dyn Fn()) -> Result<Option<Wiki>> {
+        if let Some(dir) = &self.backup_dir {
+            backup::snapshot_metadata(
+                dir,
+                name_with_owner,
+                self.token.as_deref(),
+                self.encrypt_to.as_ref(),
+            )?;
+            if let Some(export) = &self.org_settings {
+                let (token, recipient) = (self.token.as_deref(), self.encrypt_to.as_ref());
+                backup::snapshot_access(dir, name_with_owner, token, recipient)?;
+                export.snapshot(dir, name_with_owner, token, recipient)?;
+            }
+            if self.export_issues {
+                let (token, recipient) = (self.token.as_deref(), self.encrypt_to.as_ref());
+                backup::snapshot_issues(dir, name_with_owner, token, recipient)?;
+            }
+            step();
+        }
+
+        if self.has_backup() {
+            let remote = self
+                .migrate_to
+                .as_ref()
+                .map(|template| mirror::remote_url(template, name_with_owner));
+            self.mirror(name_with_owner, Git::Repo, remote.as_deref())?;
+            step();
+        }
+
+        if !self.export_wiki {
+            return Ok(None);
+        }
+        let wiki = if mirror::wiki_has_pages(name_with_owner, self.token.as_deref())? {
+            self.mirror(name_with_owner, Git::Wiki, None)?;
+            Wiki::Exported
+        } else {
+            Wiki::Missing
+        };
+        step();
+        Ok(Some(wiki))
+    }
+
+    /// What `backup` would do for the repo (e.g. "back up metadata and a
+    /// mirror to ~/backups").
+    pub fn backup_plan(&self, name_with_owner: &str) -> Vec<String> {
+        let mut steps = Vec::new();
+        if let (Some(dir), Some(format)) = (&self.backup_dir, self.git_backup()) {
+            let git = match format {
+                backup::Format::Mirror => "a mirror",
+                backup::Format::Bundle => "a bundle",
+            };
+            let wiki = if self.export_wiki {
+                " and its wiki"
+            } else {
+                ""
+            };
+            let issues = if self.export_issues {
+                ", issues, pull requests"
+            } else {
+                ""
+            };
+            let extra = if self.org_settings.is_some() {
+                " with team access and org settings"
+            } else {
+                ""
+            };
+            steps.push(format!(
+                "back up metadata{issues} and {git}{wiki} to {}{extra}",
+                dir.display()
+            ));
+        }
+        if let Some(template) = &self.migrate_to {
+            steps.push(format!(
+                "mirror to {}",
+                mirror::remote_url(template, name_with_owner)
+            ));
+        }
+        steps
+    }
+
+    /// How many steps each repo goes through, the archive itself included:
+    /// one per entry of `plan_summary`.
+    pub fn step_count(&self) -> usize {
+        self.plan_summary(1, 0).len()
+    }
+
+    /// What a batch of `repos` repos, `bytes` in total on GitHub, will go
+    /// through: one entry per step, in the order they run (e.g. "37 mirrors
+    /// ≈ 2.1 GB").
+    pub fn plan_summary(&self, repos: usize, bytes: u64) -> Vec<String> {
+        let count = |noun: &str| format!("{repos} {noun}{}", if repos == 1 { "" } else { "s" });
+        if !self.on_github() {
+            return vec![count("archive")];
+        }
+
+        let mut steps = Vec::new();
+        if self.backup_dir.is_some() {
+            steps.push(count(if self.export_issues {
+                "metadata and issue backup"
+            } else {
+                "metadata backup"
+            }));
+        }
+        if self.has_backup() {
+            let noun = match (&self.migrate_to, self.git_backup()) {
+                (Some(_), _) => "mirror",
+                (None, Some(backup::Format::Bundle)) => "bundle",
+                (None, _) => "local mirror",
+            };
+            steps.push(format!(
+                "{} ≈ {}",
+                count(noun),
+                preflight::format_size(bytes)
+            ));
+        }
+        if self.export_wiki {
+            steps.push(count("wiki export"));
+        }
+        if self.action == Action::Delete {
+            steps.push(count("deletion"));
+            return steps;
+        }
+        if self.action == Action::Transfer {
+            steps.push(count("transfer"));
+            return steps;
+        }
+        if self.action == Action::Privatize {
+            steps.push(count("visibility change"));
+            return steps;
+        }
+        if self.action == Action::Tag {
+            steps.push(count("topic"));
+            return steps;
+        }
+        if !self.revoke.is_empty() {
+            steps.push(count("access removal"));
+        }
+        if self.close_items.is_some() {
+            steps.push(count("issue/PR cleanup"));
+        }
+        if self.description_edit.is_some() {
+            steps.push(count("description edit"));
+        }
+        if !self.topics.is_empty() {
+            steps.push(count("topic"));
+        }
+        if self.readme_notice.is_some() {
+            steps.push(count("README notice"));
+        }
+        if let Some(announcement) = &self.announcement {
+            steps.push(count(&format!("announcement {}", announcement.kind)));
+        }
+        if self.stamp.is_some() {
+            steps.push(count("ARCHIVED.md commit"));
+        }
+        if self.disable_features {
+            steps.push(count("issues/wiki/projects shutoff"));
+        }
+        steps.push(count("archive"));
+        steps
+    }
+
+    /// Predict what archiving the repo would do, using only read-only calls.
+    pub fn dry_run(&self, name_with_owner: &str) -> Result<String> {
+        if self.demo || !self.on_github() {
+            return self.provider.dry_run(name_with_owner);
+        }
+        let check = RepoCheck::fetch(name_with_owner, self.github.as_ref())?;
+        if let Some(blocker) = check.blocker() {
+            anyhow::bail!("would fail: {blocker}");
+        }
+        if self.action == Action::Privatize && check.is_private() {
+            anyhow::bail!("would fail: already private");
+        }
+
+        let mut steps = self.backup_plan(name_with_owner);
+        if self.action == Action::Delete {
+            steps.push("delete".to_string());
+            return Ok(format!("would {}", steps.join(", ")));
+        }
+        if self.action == Action::Transfer {
+            steps.push(format!("transfer to {}", self.new_owner()?));
+            return Ok(format!("would {}", steps.join(", ")));
+        }
+        if self.action == Action::Privatize {
+            steps.push("make private".to_string());
+            return Ok(format!("would {}", steps.join(", ")));
+        }
+        if !self.topics.is_empty() {
+            steps.push(format!(
+                "add topic{} {}",
+                if self.topics.len() == 1 { "" } else { "s" },
+                self.topics.join(", ")
+            ));
+        }
+        if self.action == Action::Tag {
+            return Ok(format!("would {}", steps.join(", ")));
+        }
+        if !self.revoke.is_empty() {
+            let grants = self.revoke.grants(name_with_owner, self.token.as_deref())?;
+            if !grants.is_empty() {
+                steps.push(format!("remove {}", access::describe(&grants)));
+            }
+        }
+        if self.close_items.is_some() {
+            let open = check.open_items();
+            if !open.is_empty() {
+                steps.push(format!("close {}", open.join(" and ")));
+            }
+        }
+        if let Some(edit) = &self.description_edit {
+            steps.push(format!("{} to the description", edit.op));
+        }
+        if self.readme_notice.is_some() {
+            steps.push("add a README notice".to_string());
+        }
+        if let Some(announcement) = &self.announcement {
+            steps.push(format!("post an announcement {}", announcement.kind));
+        }
+        if self.stamp.is_some() {
+            steps.push("commit ARCHIVED.md".to_string());
+        }
+        if self.disable_features {
+            steps.push("turn off issues, wiki and projects".to_string());
+        }
+        steps.push("archive".to_string());
+
+        let warnings = check.warnings(self.close_items.is_some());
+        if warnings.is_empty() {
+            Ok(format!("would {}", steps.join(", ")))
+        } else {
+            Ok(format!(
+                "would {} ({})",
+                steps.join(", "),
+                warnings.join(", ")
+            ))
+        }
+    }
+
+    /// Archive the repo, calling `step` after each step before the archive
+    /// itself, `revoked` with any access removed on the way and `closing`
+    /// with how many open issues and pull requests are closed out of how many.
+    pub fn archive(
+        &self,
+        name_with_owner: &str,
+        step: &dyn Fn(),
+        revoked: &dyn Fn(Grant),
+        closing: &dyn Fn(usize, usize),
+    ) -> Result<()> {
+        // Nothing but the archive itself happens off GitHub
+        if self.demo || !self.on_github() {
+            return self.provider.archive_repo(name_with_owner);
+        }
+        if self.action == Action::Delete {
+            return self.delete(name_with_owner);
+        }
+        if self.action == Action::Transfer {
+            return self.transfer(name_with_owner, self.new_owner()?);
+        }
+        if self.action == Action::Privatize {
+            return self.privatize(name_with_owner);
+        }
+        if self.action == Action::Tag {
+            return self.add_topics(name_with_owner);
+        }
+        if !self.revoke.is_empty() {
+            self.revoke
+                .run(name_with_owner, self.token.as_deref(), revoked)?;
+            step();
+        }
+        // Before the announcement, which would be closed along with the rest
+        if let Some(comment) = &self.close_items {
+            items::close_all(
+                name_with_owner,
+                Some(comment),
+                self.token.as_deref(),
+                closing,
+            )?;
+            step();
+        }
+        if let Some(edit) = &self.description_edit {
+            let token = self.token.as_deref();
+            let current = description::fetch(name_with_owner, token)?;
+            description::set(
+                name_with_owner,
+                &edit.apply(name_with_owner, current.as_deref()),
+                token,
+            )?;
+            step();
+        }
+        if !self.topics.is_empty() {
+            self.add_topics(name_with_owner)?;
+            step();
+        }
+        if let Some(template) = &self.readme_notice {
+            notice::add(name_with_owner, template, self.token.as_deref())?;
+            step();
+        }
+        if let Some(announcement) = &self.announcement {
+            announcement.post(name_with_owner, self.token.as_deref())?;
+            step();
+        }
+        if let Some(stamp) = &self.stamp {
+            stamp.commit(name_with_owner, self.token.as_deref())?;
+            step();
+        }
+        // Last, since closing items and posting the announcement need issues
+        if self.disable_features {
+            self.disable_features(name_with_owner)?;
+            step();
+        }
+        self.provider.archive_repo(name_with_owner)
+    }
+
+    /// Look the repo up again once it's archived, where the provider can. A
+    /// failed lookup doesn't undo the archive, so it just leaves the state
+    /// (or who can write to it) unknown.
+    fn final_state(&self, name_with_owner: &str) -> Option<FinalState> {
+        // Deleted repos are gone, and transferred ones live under a new name
+        if self.action != Action::Archive {
+            return None;
+        }
+        let mut state = self.provider.final_state(name_with_owner).ok().flatten()?;
+        if self.access_review && self.on_github() && !self.demo {
+            state.access = access::writers(name_with_owner, self.token.as_deref()).ok();
+        }
+        if self.team_summary && self.on_github() && !self.demo {
+            state.teams = access::owning_teams(name_with_owner, self.token.as_deref()).ok();
+        }
+        Some(state)
+    }
+
+    fn delete(&self, name_with_owner: &str) -> Result<()> {
+        let output = gh(self.token.as_deref())
+            .args(["repo", "delete", name_with_owner, "--yes"])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output)?;
+        Ok(())
+    }
+
+    /// Start moving the repo to `new_owner`; GitHub finishes the move in the
+    /// background, and invites the owner instead if it's another user.
+    /// Where `Action::Transfer` moves repos; a transfer without one fails
+    /// rather than falling through to archiving.
+    fn new_owner(&self) -> Result<&str> {
+        self.transfer_to
+            .as_deref()
+            .context("--action transfer needs --to")
+    }
+
+    fn transfer(&self, name_with_owner: &str, new_owner: &str) -> Result<()> {
+        let output = gh(self.token.as_deref())
+            .args([
+                "api",
+                "-X",
+                "POST",
+                &format!("repos/{name_with_owner}/transfer"),
+                "-f",
+                &format!("new_owner={new_owner}"),
+            ])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output).with_context(|| format!("Failed to transfer to {new_owner}"))?;
+        Ok(())
+    }
+
+    fn privatize(&self, name_with_owner: &str) -> Result<()> {
+        let output = gh(self.token.as_deref())
+            .args([
+                "api",
+                "-X",
+                "PATCH",
+                &format!("repos/{name_with_owner}"),
+                "-f",
+                "visibility=private",
+            ])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output).context("Failed to make the repo private")?;
+        Ok(())
+    }
+
+    fn disable_features(&self, name_with_owner: &str) -> Result<()> {
+        let output = gh(self.token.as_deref())
+            .args(["api", "-X", "PATCH", &format!("repos/{name_with_owner}")])
+            .args(FEATURES.iter().flat_map(|field| ["-F", field]))
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output).context("Failed to turn off issues, wiki and projects")?;
+        Ok(())
+    }
+
+    fn add_topics(&self, name_with_owner: &str) -> Result<()> {
+        let topics = self.topics.join(",");
+        let output = gh(self.token.as_deref())
+            .args(["repo", "edit", name_with_owner, "--add-topic", &topics])
+            .output()
+            .context("Failed to run gh CLI. Is it installed?")?;
+
+        error::check(output).with_context(|| format!("Failed to add topics {topics}"))?;
+        Ok(())
+    }
+
+    /// Clone a mirror of the repo or its wiki (or fetch into the kept one)
+    /// and check it against GitHub, then push it to `remote`, if any, and
+    /// check that it arrived intact.
+    ///
+    /// The mirror is kept in the backup dir if there is one, as a bundle if
+    /// `git_backup` says so, and thrown away otherwise. A bundle's mirror is
+    /// cached in [`paths::mirrors_dir`] for the next run to fetch into.
+    fn mirror(&self, name_with_owner: &str, git: Git, remote: Option<&str>) -> Result<()> {
+        let source = git.source(name_with_owner);
+        let kept = self
+            .backup_dir
+            .as_ref()
+            .filter(|_| self.git_backup() == Some(backup::Format::Mirror));
+        let cached =
+            paths::mirrors_dir().filter(|_| self.git_backup() == Some(backup::Format::Bundle));
+        let dir = match (kept, &cached) {
+            (Some(backup_dir), _) => {
+                backup::repo_dir(backup_dir, name_with_owner).join(git.mirror())
+            }
+            (None, Some(mirrors)) => backup::repo_dir(mirrors, name_with_owner).join(git.mirror()),
+            (None, None) => backup::repo_dir(&env::temp_dir().join("repo-archiver"), &source),
+        };
+
+        // Nothing is pushed or stored, let alone archived, unless the local
+        // mirror checks out. If a kept mirror was already up to date, the
+        // destination only needs re-checking, not another push.
+        let token = self.token.as_deref();
+        let result = mirror::sync(&source, &dir, token, self.bw_limit)
+            .and_then(|changed| {
+                mirror::verify_local(&dir, &source, token)?;
+                let Some(remote) = remote else {
+                    return Ok(changed);
+                };
+                if !changed && mirror::verify(&dir, remote).is_ok() {
+                    return Ok(changed);
+                }
+                mirror::push(&dir, remote, self.bw_limit)?;
+                mirror::verify(&dir, remote)?;
+                Ok(changed)
+            })
+            .and_then(|changed| {
+                if kept.is_some() {
+                    return Ok(());
+                }
+                self.store_bundle(name_with_owner, git, &dir, changed)
+            });
+
+        // A cached mirror that didn't check out is cloned afresh next time
+        if kept.is_none() && (cached.is_none() || result.is_err()) {
+            let _ = fs::remove_dir_all(&dir);
+        }
+        result
+    }
+
+    /// Write `repo.bundle` (or `wiki.bundle`) to the backup dir, if there is
+    /// one, encrypted to `repo.bundle.<age|gpg>` when encrypting. A bundle
+    /// already there is left alone unless the fetch `changed` any refs.
+    fn store_bundle(
+        &self,
+        name_with_owner: &str,
+        git: Git,
+        mirror_dir: &Path,
+        changed: bool,
+    ) -> Result<()> {
+        let Some(backup_dir) = &self.backup_dir else {
+            return Ok(());
+        };
+
+        let dest = backup::repo_dir(backup_dir, name_with_owner).join(git.bundle());
+        let stored = match &self.encrypt_to {
+            Some(recipient) => recipient.encrypted_path(&dest),
+            None => dest.clone(),
+        };
+        if !changed && stored.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(dest.parent().unwrap_or(backup_dir))?;
+
+        let mut bundle = mirror_dir.as_os_str().to_owned();
+        bundle.push(".bundle");
+        let bundle = PathBuf::from(bundle);
+        let result = mirror::bundle(mirror_dir, &bundle).and_then(|()| match &self.encrypt_to {
+            Some(recipient) => recipient.encrypt_file(&bundle, &dest),
+            None => fs::copy(&bundle, &dest)
+                .map(drop)
+                .context("Failed to store the bundle"),
+        });
+        let _ = fs::remove_file(&bundle);
+        result
+    }
+}
+
+/// Which of a repo's git repos `ArchiveJob::mirror` copies.
+#[derive(Clone, Copy)]
+enum Git {
+    Repo,
+    Wiki,
+}
+
+impl Git {
+    /// Where it is on GitHub, as `mirror` takes it
+    fn source(self, name_with_owner: &str) -> String {
+        match self {
+            Self::Repo => name_with_owner.to_string(),
+            Self::Wiki => format!("{name_with_owner}.wiki"),
+        }
+    }
+
+    /// Name of the kept mirror in the repo's backup dir
+    const fn mirror(self) -> &'static str {
+        match self {
+            Self::Repo => "mirror.git",
+            Self::Wiki => "wiki.git",
+        }
+    }
+
+    /// Name of the bundle in the repo's backup dir
+    const fn bundle(self) -> &'static str {
+        match self {
+            Self::Repo => "repo.bundle",
+            Self::Wiki => "wiki.bundle",
+        }
+    }
+}
+
+/// How many workers of a pool are busy and how many may be, shared with
+/// the UI.
+///
+/// The archive pool adapts: a run of failures, or any rate limit response,
+/// halves how many of its workers may run at once, and a run of successes
+/// lets one more back in, up to the configured size.
+pub struct PoolUsage {
+    busy: AtomicUsize,
+    size: usize,
+    /// Workers allowed to run at once
+    limit: AtomicUsize,
+    streak: Mutex<Streak>,
+}
+
+/// Consecutive outcomes since the limit last changed; one of them is zero.
+#[derive(Default)]
+struct Streak {
+    successes: usize,
+    failures: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    Failure,
+    RateLimited,
+}
+
+impl Outcome {
+    fn of<T>(result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(e) if matches!(error::Error::find(e), Some(error::Error::RateLimit(_))) => {
+                Self::RateLimited
+            }
+            Err(_) => Self::Failure,
+        }
+    }
+}
+
+impl PoolUsage {
+    fn new(size: NonZeroUsize) -> Arc<Self> {
+        Arc::new(Self {
+            busy: AtomicUsize::new(0),
+            size: size.get(),
+            limit: AtomicUsize::new(size.get()),
+            streak: Mutex::new(Streak::default()),
+        })
+    }
+
+    pub fn busy(&self) -> usize {
+        self.busy.load(Ordering::Relaxed)
+    }
+
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// How many workers may run at once right now; below `size` while
+    /// backing off.
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Wait for a free slot under the limit and take it.
+    fn acquire(&self) {
+        loop {
+            let busy = self.busy();
+            if busy < self.limit()
+                && self
+                    .busy
+                    .compare_exchange(busy, busy + 1, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return;
+            }
+            thread::sleep(THROTTLE_POLL);
+        }
+    }
+
+    fn release(&self) {
+        self.busy.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Adjust the limit to how the last repo went.
+    fn record(&self, outcome: Outcome) {
+        let Ok(mut streak) = self.streak.lock() else {
+            return;
+        };
+        let limit = self.limit();
+        if outcome == Outcome::Success {
+            streak.failures = 0;
+            streak.successes += 1;
+            if streak.successes >= RAMP_UP_AFTER {
+                streak.successes = 0;
+                self.limit
+                    .store((limit + 1).min(self.size), Ordering::Relaxed);
+            }
+        } else {
+            streak.successes = 0;
+            streak.failures += 1;
+            if outcome == Outcome::RateLimited || streak.failures >= BACK_OFF_AFTER {
+                streak.failures = 0;
+                self.limit.store((limit / 2).max(1), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Usage of the pools working on a batch; there is no backup pool when
+/// nothing needs backing up.
+pub struct Pools {
+    pub backup: Option<Arc<PoolUsage>>,
+    pub archive: Arc<PoolUsage>,
+}
+
+/// Start working through `repos` (table index, `owner/name`) in the background,
+/// reporting progress on `tx`.
+///
+/// When chunking, archived repos are removed from the plan at `checkpoint`
+/// after every chunk; the caller has to have queued them there.
+pub fn start(
+    repos: Vec<(usize, String)>,
+    dry_run: bool,
+    job: &ArchiveJob,
+    checkpoint: Option<PathBuf>,
+    tx: &mpsc::Sender<ArchiveResult>,
+) -> Pools {
+    let (queue_tx, queue_rx) = mpsc::channel();
+    // Each repo's outcome, `owner/name` if it got archived, for the chunk feeder
+    let (finished_tx, finished_rx) = mpsc::channel::<Option<String>>();
+    let finished_tx = if let Some(chunking) = job.chunking {
+        let tx = tx.clone();
+        let checkpoint = checkpoint.filter(|_| !dry_run);
+        thread::spawn(move || {
+            feed_chunks(
+                &repos,
+                chunking,
+                checkpoint.as_deref(),
+                &queue_tx,
+                &finished_rx,
+                &tx,
+            );
+        });
+        Some(finished_tx)
+    } else {
+        for repo in repos {
+            let _ = queue_tx.send(repo);
+        }
+        None
+    };
+
+    // Dry runs only simulate the archive call, so they skip the backup stage
+    let backup_stage = !dry_run && job.has_backup();
+
+    let (archive_queue, backup) = if backup_stage {
+        let (archive_tx, archive_rx) = mpsc::channel();
+        let usage = PoolUsage::new(job.backup_workers);
+        let job = job.clone();
+        let tx = tx.clone();
+        let finished_tx = finished_tx.clone();
+
+        spawn_pool(&usage, queue_rx, move |idx, name| {
+            let _ = tx.send(ArchiveResult::Started(idx));
+            let _ = tx.send(ArchiveResult::BackingUp(idx));
+            match job.backup(&name, &|| {
+                let _ = tx.send(ArchiveResult::Step(idx));
+            }) {
+                Ok(wiki) => {
+                    if let Some(wiki) = wiki {
+                        let _ = tx.send(ArchiveResult::Wiki(idx, wiki));
+                    }
+                    let _ = tx.send(ArchiveResult::BackedUp(idx));
+                    let _ = archive_tx.send((idx, name));
+                }
+                Err(e) => {
+                    // Never archive a repo whose backup failed
+                    let _ = tx.send(ArchiveResult::Failed(idx, format!("{e:#}")));
+                    if let Some(finished_tx) = &finished_tx {
+                        let _ = finished_tx.send(None);
+                    }
+                }
+            }
+        });
+        (archive_rx, Some(usage))
+    } else {
+        (queue_rx, None)
+    };
+
+    let archive = PoolUsage::new(job.archive_workers);
+    let usage = Arc::clone(&archive);
+    let job = job.clone();
+    let tx = tx.clone();
+
+    spawn_pool(&archive, archive_queue, move |idx, name| {
+        if !backup_stage {
+            let _ = tx.send(ArchiveResult::Started(idx));
+        }
+
+        let archived = if dry_run {
+            let prediction = job.dry_run(&name).map_err(|e| format!("{e:#}"));
+            let _ = tx.send(ArchiveResult::DryRun(idx, prediction));
+            false
+        } else {
+            let result = job.archive(
+                &name,
+                &|| {
+                    let _ = tx.send(ArchiveResult::Step(idx));
+                },
+                &|grant| {
+                    let _ = tx.send(ArchiveResult::AccessRemoved(idx, grant));
+                },
+                &|closed, total| {
+                    let _ = tx.send(ArchiveResult::Closing(idx, closed, total));
+                },
+            );
+            usage.record(Outcome::of(&result));
+            match result.map(|()| job.final_state(&name)) {
+                Ok(Some(state)) if !state.archived => {
+                    let _ = tx.send(ArchiveResult::Failed(
+                        idx,
+                        format!("{} still shows the repo as not archived", job.forge.name()),
+                    ));
+                    false
+                }
+                Ok(state) => {
+                    let _ = tx.send(ArchiveResult::Done(idx, state));
+                    true
+                }
+                Err(e) => {
+                    let _ = tx.send(ArchiveResult::Failed(idx, format!("{e:#}")));
+                    false
+                }
+            }
+        };
+        if let Some(finished_tx) = &finished_tx {
+            let _ = finished_tx.send(archived.then_some(name));
+        }
+
+        // Small delay between requests to be nice to GitHub API
+        thread::sleep(Duration::from_millis(100));
+    });
+
+    Pools { backup, archive }
+}
+
+/// Queue `repos` one chunk at a time, waiting for each chunk to finish.
+///
+/// If a checkpoint can't be written the rest of the repos fail instead of
+/// running on without one.
+fn feed_chunks(
+    repos: &[(usize, String)],
+    chunking: Chunking,
+    checkpoint: Option<&Path>,
+    queue_tx: &mpsc::Sender<(usize, String)>,
+    finished_rx: &mpsc::Receiver<Option<String>>,
+    tx: &mpsc::Sender<ArchiveResult>,
+) {
+    let chunks: Vec<_> = repos.chunks(chunking.size.get()).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        for repo in *chunk {
+            let _ = queue_tx.send(repo.clone());
+        }
+        let archived: Vec<String> = finished_rx.iter().take(chunk.len()).flatten().collect();
+
+        if let Some(path) = checkpoint {
+            if let Err(e) = Plan::remove(path, &archived) {
+                for (idx, _) in chunks[i + 1..].iter().copied().flatten() {
+                    let _ = tx.send(ArchiveResult::Failed(
+                        *idx,
+                        format!("Failed to write checkpoint: {e:#}"),
+                    ));
+                }
+                return;
+            }
+        }
+        if i + 1 < chunks.len() {
+            let _ = tx.send(ArchiveResult::CoolingDown(chunking.cool_down));
+            thread::sleep(chunking.cool_down);
+        }
+    }
+}
+
+/// Run `work` for every queued repo on `usage.size` threads, at most
+/// `usage.limit()` at a time. The threads exit once the queue is drained and
+/// all its senders are gone.
+fn spawn_pool<F>(usage: &Arc<PoolUsage>, queue: mpsc::Receiver<(usize, String)>, work: F)
+where
+    F: Fn(usize, String) + Send + Sync + 'static,
+{
+    let queue = Arc::new(Mutex::new(queue));
+    let work = Arc::new(work);
+
+    for _ in 0..usage.size {
+        let queue = Arc::clone(&queue);
+        let work = Arc::clone(&work);
+        let usage = Arc::clone(usage);
+
+        thread::spawn(move || loop {
+            // The lock is only held while waiting for the next repo
+            let next = match queue.lock() {
+                Ok(queue) => queue.recv().ok(),
+                Err(_) => None,
+            };
+            let Some((idx, name)) = next else {
+                break;
+            };
+
+            usage.acquire();
+            work(idx, name);
+            usage.release();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend, Repo};
+    use chrono::Utc;
+
+    /// Archives everything but `octo/locked`, and still shows `octo/stuck`
+    /// as not archived afterwards.
+    struct Mock {
+        archived: Mutex<Vec<String>>,
+    }
+
+    impl Provider for Mock {
+        fn list_repos(&self, _: Option<&str>, _: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+            Ok(Vec::new())
+        }
+
+        fn archive_repo(&self, name_with_owner: &str) -> Result<()> {
+            if name_with_owner == "octo/locked" {
+                anyhow::bail!("locked");
+            }
+            self.archived
+                .lock()
+                .unwrap()
+                .push(name_with_owner.to_string());
+            Ok(())
+        }
+
+        fn unarchive_repo(&self, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn final_state(&self, name_with_owner: &str) -> Result<Option<FinalState>> {
+            Ok(Some(FinalState {
+                archived: name_with_owner != "octo/stuck",
+                pushed_at: Utc::now(),
+                updated_at: Utc::now(),
+                access: None,
+                teams: None,
+            }))
+        }
+    }
+
+    #[test]
+    fn concurrency_backs_off_on_failures_and_ramps_back_up() {
+        let usage = PoolUsage::new(NonZeroUsize::new(4).unwrap());
+        for _ in 0..BACK_OFF_AFTER {
+            usage.record(Outcome::Failure);
+        }
+        assert_eq!(usage.limit(), 2);
+        usage.record(Outcome::RateLimited);
+        assert_eq!(usage.limit(), 1);
+        usage.record(Outcome::RateLimited);
+        assert_eq!(usage.limit(), 1, "never below one worker");
+
+        for _ in 0..RAMP_UP_AFTER * 5 {
+            usage.record(Outcome::Success);
+        }
+        assert_eq!(usage.limit(), 4, "never above the configured size");
+    }
+
+    /// Archiving on GitHub through `mock`, with no other steps.
+    fn job(mock: &Arc<Mock>) -> ArchiveJob {
+        ArchiveJob {
+            action: Action::Archive,
+            transfer_to: None,
+            access_review: false,
+            team_summary: false,
+            revoke: access::Revoke::default(),
+            token: None,
+            github: Arc::new(backend::Gh::new(None)),
+            backup_dir: None,
+            backup_as: backup::Format::default(),
+            org_settings: None,
+            export_wiki: false,
+            export_issues: false,
+            migrate_to: None,
+            bw_limit: None,
+            encrypt_to: None,
+            backup_workers: DEFAULT_BACKUP_WORKERS,
+            archive_workers: DEFAULT_ARCHIVE_WORKERS,
+            chunking: None,
+            ssh: None,
+            description_edit: None,
+            topics: Vec::new(),
+            readme_notice: None,
+            close_items: None,
+            stamp: None,
+            disable_features: false,
+            announcement: None,
+            provider: mock.clone(),
+            forge: provider::Kind::GitHub,
+            demo: false,
+        }
+    }
+
+    #[test]
+    fn archives_through_the_provider() {
+        let mock = Arc::new(Mock {
+            archived: Mutex::new(Vec::new()),
+        });
+        let job = job(&mock);
+        let repos = ["octo/old", "octo/locked", "octo/stuck"];
+        let (tx, rx) = mpsc::channel();
+        start(
+            repos.iter().map(|r| (*r).to_string()).enumerate().collect(),
+            false,
+            &job,
+            None,
+            &tx,
+        );
+        drop(tx);
+
+        let mut outcomes = vec![String::new(); repos.len()];
+        for result in rx {
+            match result {
+                ArchiveResult::Done(idx, state) => {
+                    assert!(state.is_some_and(|s| s.archived));
+                    outcomes[idx] = "done".to_string();
+                }
+                ArchiveResult::Failed(idx, err) => outcomes[idx] = err,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            outcomes,
+            [
+                "done",
+                "locked",
+                "GitHub still shows the repo as not archived"
+            ]
+        );
+        assert_eq!(*mock.archived.lock().unwrap(), ["octo/old", "octo/stuck"]);
+    }
+
+    #[test]
+    fn a_transfer_without_a_new_owner_fails_instead_of_archiving() {
+        let mock = Arc::new(Mock {
+            archived: Mutex::new(Vec::new()),
+        });
+        let job = ArchiveJob {
+            action: Action::Transfer,
+            ..job(&mock)
+        };
+
+        let archived = job.archive("octo/old", &|| {}, &|_| {}, &|_, _| {});
+
+        assert_eq!(
+            archived.unwrap_err().to_string(),
+            "--action transfer needs --to"
+        );
+        assert!(mock.archived.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,687 @@
+//! Archives queued for later: while `--offline`, or until a second person
+//! approves them with `--require-approval`.
+//!
+//! `repo-archiver apply-plan` runs the queued repos through the same
+//! backup/archive pipeline as the TUI, or prints them as a shell script.
+//!
+//! `repo-archiver plan FILE` writes every candidate to a plan file without
+//! the TUI, with what the run's flags would do to each, for review (e.g. in
+//! a pull request) before `repo-archiver apply FILE` archives exactly those
+//! repos. `apply` refuses flags that would take them through different steps
+//! than the ones planned.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    path::{self as stdpath, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    backend::Backend,
+    description, headless, items, notice, paths,
+    pipeline::{self, ArchiveJob},
+    save_report, usage, Repo,
+};
+
+/// Namespace of approval signatures, so a signature made for anything else
+/// (a commit, say) doesn't pass for an approval.
+const NAMESPACE: &str = "repo-archiver-plan";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub repos: Vec<QueuedRepo>,
+    /// Present when the plan needs a second person's approval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<Review>,
+    /// Who the repos were handed to with `assign`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    /// With `plan`: the steps the batch goes through, as the confirmation
+    /// modal lists them (e.g. "37 local mirrors ≈ 2.1 GB", "37 archives")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedRepo {
+    pub name_with_owner: String,
+    pub queued_at: DateTime<Utc>,
+    /// Size in KB, with `plan`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_usage: Option<u64>,
+    /// With `plan`: what archiving it will do (e.g. "would back up metadata
+    /// and a mirror to ~/backups, archive")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    /// GitHub login of whoever queued the repos
+    pub requested_by: String,
+    pub approval: Option<Approval>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Approval {
+    pub approved_by: String,
+    pub approved_at: DateTime<Utc>,
+    /// The repos approved. The plan shrinks as they're archived or assigned,
+    /// but mustn't take on others.
+    pub repos: Vec<String>,
+    /// `ssh-keygen -Y sign` signature over the token of `repos`, made with a
+    /// key `approved_by` has on GitHub as an SSH signing key
+    pub signature: String,
+}
+
+#[derive(clap::Args)]
+pub struct ApplyArgs {
+    /// Print the plan as a shell script of `gh repo archive` calls instead of
+    /// applying it
+    #[arg(long)]
+    script: bool,
+
+    /// Plan file to apply [default: plan.json in the state dir]
+    #[arg(long)]
+    plan: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct PlanArgs {
+    /// File to write the plan to, replacing any plan already there
+    #[arg(value_name = "FILE")]
+    pub out: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub struct ApplyFileArgs {
+    /// Plan file written by `plan`
+    #[arg(value_name = "FILE")]
+    plan: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub struct ApproveArgs {
+    /// Plan file to approve [default: plan.json in the state dir]
+    plan: Option<PathBuf>,
+
+    /// SSH key to sign the approval with, one added to your GitHub account
+    /// as a signing key [default: `~/.ssh/id_ed25519`]
+    #[arg(long, value_name = "PATH")]
+    key: Option<PathBuf>,
+
+    /// Approve without asking for confirmation
+    #[arg(long)]
+    yes: bool,
+}
+
+/// `plan.json` in the state dir, where the TUI queues repos.
+pub fn default_file() -> Result<PathBuf> {
+    paths::state_dir()
+        .map(|dir| dir.join("plan.json"))
+        .context("No home directory to keep the plan in")
+}
+
+impl Plan {
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("Invalid plan in {}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if self.repos.is_empty() {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            // Whatever is queued here next starts afresh
+            return set_reviewed(path, false);
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Add `repos` to the plan at `path`, skipping ones already queued.
+    ///
+    /// `requested_by` marks the plan as needing approval. Any existing
+    /// approval is dropped, since it was for a different set of repos.
+    pub fn queue(path: &Path, repos: &[String], requested_by: Option<String>) -> Result<()> {
+        let mut plan = Self::load(path)?;
+        let now = Utc::now();
+        for repo in repos {
+            if !plan.repos.iter().any(|r| r.name_with_owner == *repo) {
+                plan.repos.push(QueuedRepo {
+                    name_with_owner: repo.clone(),
+                    queued_at: now,
+                    disk_usage: None,
+                    prediction: None,
+                });
+            }
+        }
+        let reviewed = requested_by.is_some();
+        if let Some(requested_by) = requested_by {
+            plan.review = Some(Review {
+                requested_by,
+                approval: None,
+            });
+        } else if let Some(review) = &mut plan.review {
+            review.approval = None;
+        }
+        plan.save(path)?;
+        if reviewed {
+            set_reviewed(path, true)?;
+        }
+        Ok(())
+    }
+
+    /// Drop `archived` from the plan at `path`. The repos left over stay
+    /// approved, since the approval covered them already.
+    pub fn remove(path: &Path, archived: &[String]) -> Result<()> {
+        let mut plan = Self::load(path)?;
+        plan.repos
+            .retain(|r| !archived.contains(&r.name_with_owner));
+        plan.save(path)
+    }
+
+    /// Fingerprint of who asked to archive which repos, which an approval
+    /// signs.
+    pub fn token(&self) -> String {
+        token(
+            self.review.as_ref().map(|r| r.requested_by.as_str()),
+            self.repos.iter().map(|r| r.name_with_owner.as_str()),
+        )
+    }
+
+    /// Why the plan can't be applied yet, if it lacks an approval it needs or
+    /// took on repos since it was approved. The signature is left to
+    /// [`Plan::check_approval`].
+    pub fn approval_error(&self) -> Option<String> {
+        let review = self.review.as_ref()?;
+        match &review.approval {
+            None => Some(format!(
+                "Plan needs approval from someone other than {}",
+                review.requested_by
+            )),
+            Some(approval)
+                if self
+                    .repos
+                    .iter()
+                    .any(|r| !approval.repos.contains(&r.name_with_owner)) =>
+            {
+                Some("Plan changed after it was approved; it needs approving again".to_string())
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Fail unless the plan at `path` may be applied by whoever is logged
+    /// in: if it needs approval (`required`, queued for review, or marked
+    /// as such in the state dir), the approval has to be signed by someone
+    /// other than the requester and the applier, with a signing key of
+    /// theirs on GitHub.
+    pub fn check_approval(&self, path: &Path, required: bool, github: &dyn Backend) -> Result<()> {
+        if !(required || self.review.is_some() || is_reviewed(path)?) {
+            return Ok(());
+        }
+        let Some(review) = &self.review else {
+            anyhow::bail!(
+                "{} needs approval but has no review record; queue the repos for approval again",
+                path.display()
+            );
+        };
+        if let Some(error) = self.approval_error() {
+            anyhow::bail!(error);
+        }
+        let Some(approval) = &review.approval else {
+            unreachable!("approval_error() reports a missing approval");
+        };
+        if approval
+            .approved_by
+            .eq_ignore_ascii_case(&review.requested_by)
+        {
+            anyhow::bail!(
+                "{} both queued and approved this plan; someone else has to approve it",
+                review.requested_by
+            );
+        }
+        let login = viewer_login(github)?;
+        if login.eq_ignore_ascii_case(&approval.approved_by) {
+            anyhow::bail!("{login} approved this plan, so someone else has to apply it");
+        }
+        let signed = self::token(
+            Some(&review.requested_by),
+            approval.repos.iter().map(String::as_str),
+        );
+        verify(&approval.approved_by, &signed, &approval.signature, github)
+    }
+
+    /// `ArchiveJob::plan_summary` for the plan's repos, sizes included.
+    fn summary(&self, job: &ArchiveJob) -> Vec<String> {
+        let kb: u64 = self.repos.iter().filter_map(|r| r.disk_usage).sum();
+        job.plan_summary(self.repos.len(), kb * 1024)
+    }
+
+    fn script(&self, job: &ArchiveJob) -> String {
+        let mut script = String::from("#!/bin/sh\nset -e\n\n");
+        for repo in &self.repos {
+            if let Some(comment) = &job.close_items {
+                script.push_str(&items::script(&repo.name_with_owner, comment));
+            }
+            if let Some(edit) = &job.description_edit {
+                script.push_str(&description::script(edit, &repo.name_with_owner));
+            }
+            if !job.topics.is_empty() {
+                let _ = writeln!(
+                    script,
+                    "gh repo edit {} --add-topic {}",
+                    repo.name_with_owner,
+                    job.topics.join(",")
+                );
+            }
+            if let Some(template) = &job.readme_notice {
+                script.push_str(&notice::script(&repo.name_with_owner, template));
+            }
+            if let Some(announcement) = &job.announcement {
+                script.push_str(&announcement.script(&repo.name_with_owner));
+            }
+            if let Some(stamp) = &job.stamp {
+                script.push_str(&stamp.script(&repo.name_with_owner));
+            }
+            if job.disable_features {
+                let _ = writeln!(
+                    script,
+                    "gh api -X PATCH repos/{} -F {} >/dev/null",
+                    repo.name_with_owner,
+                    pipeline::FEATURES.join(" -F ")
+                );
+            }
+            script.push_str("gh repo archive ");
+            script.push_str(&repo.name_with_owner);
+            script.push_str(" --yes\n");
+        }
+        script
+    }
+}
+
+/// GitHub login of the authenticated user.
+pub fn viewer_login(github: &dyn Backend) -> Result<String> {
+    let user = github
+        .rest("GET", "user", None)
+        .context("Failed to look up the GitHub user")?;
+    user["login"]
+        .as_str()
+        .map(str::to_string)
+        .context("No login in GitHub's answer for the user")
+}
+
+/// SHA-256 of `requested_by` and the sorted `repos`, in hex.
+fn token<'a>(requested_by: Option<&str>, repos: impl Iterator<Item = &'a str>) -> String {
+    let mut hasher = Sha256::new();
+    if let Some(requested_by) = requested_by {
+        hasher.update(requested_by.as_bytes());
+    }
+    let mut repos: Vec<&str> = repos.collect();
+    repos.sort_unstable();
+    for repo in repos {
+        hasher.update(b"\n");
+        hasher.update(repo.as_bytes());
+    }
+    hasher.finalize().iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+/// `approval-required` in the state dir: the plans queued for approval, one
+/// path per line. A plan listed there needs an approval even if its
+/// `review` is taken out of it.
+fn reviewed_file() -> Result<PathBuf> {
+    paths::state_dir()
+        .map(|dir| dir.join("approval-required"))
+        .context("No home directory to keep the plans awaiting approval in")
+}
+
+fn reviewed_plans(file: &Path) -> Result<Vec<PathBuf>> {
+    match fs::read_to_string(file) {
+        Ok(contents) => Ok(contents.lines().map(PathBuf::from).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", file.display())),
+    }
+}
+
+pub fn is_reviewed(path: &Path) -> Result<bool> {
+    let path = stdpath::absolute(path)?;
+    Ok(reviewed_plans(&reviewed_file()?)?.contains(&path))
+}
+
+/// Mark the plan at `path` as needing approval, or clear that mark.
+pub fn set_reviewed(path: &Path, reviewed: bool) -> Result<()> {
+    let path = stdpath::absolute(path)?;
+    let file = reviewed_file()?;
+    let mut plans = reviewed_plans(&file)?;
+    if plans.contains(&path) == reviewed {
+        return Ok(());
+    }
+    if reviewed {
+        plans.push(path);
+    } else {
+        plans.retain(|p| *p != path);
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents = plans.iter().fold(String::new(), |mut contents, p| {
+        let _ = writeln!(contents, "{}", p.display());
+        contents
+    });
+    fs::write(&file, contents).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Sign `token` with the SSH key at `key`.
+fn sign(key: &Path, token: &str) -> Result<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(["-q", "-Y", "sign", "-n", NAMESPACE, "-f"])
+        .arg(key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh-keygen. Is OpenSSH installed?")?;
+    child
+        .stdin
+        .take()
+        .context("ssh-keygen has no stdin")?
+        .write_all(token.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to sign the approval with {}: {}",
+            key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Fail unless `signature` is `login`'s over `token`, made with one of the
+/// SSH signing keys on their GitHub account.
+fn verify(login: &str, token: &str, signature: &str, github: &dyn Backend) -> Result<()> {
+    let keys = github
+        .rest("GET", &format!("users/{login}/ssh_signing_keys"), None)
+        .with_context(|| format!("Failed to look up {login}'s SSH signing keys"))?;
+    let keys = keys.as_array().map(Vec::as_slice).unwrap_or_default();
+    let allowed = keys.iter().filter_map(|key| key["key"].as_str()).fold(
+        String::new(),
+        |mut allowed, key| {
+            let _ = writeln!(allowed, "{login} namespaces=\"{NAMESPACE}\" {}", key.trim());
+            allowed
+        },
+    );
+    if allowed.is_empty() {
+        anyhow::bail!("{login} has no SSH signing keys on GitHub to check the approval against");
+    }
+
+    let dir = tempfile::tempdir()?;
+    let (signers, sig) = (
+        dir.path().join("allowed_signers"),
+        dir.path().join("approval.sig"),
+    );
+    fs::write(&signers, allowed)?;
+    fs::write(&sig, signature)?;
+    let mut child = Command::new("ssh-keygen")
+        .args(["-Y", "verify", "-n", NAMESPACE, "-I", login, "-f"])
+        .arg(&signers)
+        .arg("-s")
+        .arg(&sig)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run ssh-keygen. Is OpenSSH installed?")?;
+    child
+        .stdin
+        .take()
+        .context("ssh-keygen has no stdin")?
+        .write_all(token.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "The approval isn't signed by any of {login}'s SSH signing keys on GitHub: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// `~/.ssh/id_ed25519`, the key approvals are signed with unless `--key`
+/// says otherwise.
+fn default_key() -> Result<PathBuf> {
+    directories::BaseDirs::new()
+        .map(|dirs| dirs.home_dir().join(".ssh/id_ed25519"))
+        .context("No home directory to find an SSH key in; pass --key")
+}
+
+/// `repo-archiver approve`: sign off on a plan someone else queued.
+pub fn approve(args: &ApproveArgs, github: &dyn Backend) -> Result<()> {
+    let path = args.plan.clone().map_or_else(default_file, Ok)?;
+    let mut plan = Plan::load(&path)?;
+    let plan_token = plan.token();
+    let repos: Vec<String> = plan
+        .repos
+        .iter()
+        .map(|r| r.name_with_owner.clone())
+        .collect();
+    let Some(review) = &mut plan.review else {
+        anyhow::bail!("{} doesn't need approval", path.display());
+    };
+
+    let login = viewer_login(github)?;
+    if login.eq_ignore_ascii_case(&review.requested_by) {
+        anyhow::bail!("{login} queued this plan, so someone else has to approve it");
+    }
+
+    println!("{} asks to archive:", review.requested_by);
+    for repo in &plan.repos {
+        println!("  {}", repo.name_with_owner);
+    }
+    println!("Approval token: {plan_token}");
+
+    if !args.yes {
+        print!("Approve as {login}? [y/N] ");
+        io::Write::flush(&mut io::stdout())?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Not approved.");
+            return Ok(());
+        }
+    }
+
+    let key = args.key.clone().map_or_else(default_key, Ok)?;
+    let signature = sign(&key, &plan_token)?;
+    // Caught now rather than when someone tries to apply the plan
+    verify(&login, &plan_token, &signature, github).context(
+        "Add the key to your GitHub account as a signing key, or pick another with --key",
+    )?;
+    review.approval = Some(Approval {
+        approved_by: login,
+        approved_at: Utc::now(),
+        repos,
+        signature,
+    });
+    plan.save(&path)?;
+    println!("Approved. {} can now be applied.", path.display());
+    Ok(())
+}
+
+/// `repo-archiver plan`: write `repos` to a plan file with what archiving
+/// each would do, leaving out those that would fail. `predict` is false
+/// offline, where nothing can be looked up.
+pub fn write(args: &PlanArgs, repos: &[Repo], job: &ArchiveJob, predict: bool) -> Result<()> {
+    let now = Utc::now();
+    let mut plan = Plan::default();
+    for repo in repos {
+        let name = &repo.name_with_owner;
+        if let Some(why) = repo.unselectable_reason() {
+            println!("- {name}: {why}");
+            continue;
+        }
+        let prediction = if predict {
+            match job.dry_run(name) {
+                Ok(prediction) => Some(prediction),
+                Err(e) => {
+                    println!("✗ {name}: {e:#}");
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+        match &prediction {
+            Some(prediction) => println!("+ {name}: {prediction}"),
+            None => println!("+ {name}"),
+        }
+        if let Some(app) = &repo.app {
+            println!("  ! looks like the source of the GitHub App {app}");
+        }
+        plan.repos.push(QueuedRepo {
+            name_with_owner: name.clone(),
+            queued_at: now,
+            disk_usage: Some(repo.disk_usage),
+            prediction,
+        });
+    }
+    if plan.repos.is_empty() {
+        println!("Nothing to plan.");
+        return Ok(());
+    }
+
+    plan.steps = plan.summary(job);
+    plan.save(&args.out)?;
+    println!("\n{}", plan.steps.join(", "));
+    println!(
+        "Wrote {} repos to {}; archive them with `repo-archiver apply {}`.",
+        plan.repos.len(),
+        args.out.display(),
+        args.out.display()
+    );
+    Ok(())
+}
+
+/// How apply-plan and apply run: `require_approval` applies only approved
+/// plans, whether or not they were queued for approval.
+pub struct ApplyOptions {
+    pub dry_run: bool,
+    pub record_usage: bool,
+    pub require_approval: bool,
+}
+
+/// `repo-archiver apply-plan`: archive the queued repos, dropping each from
+/// the plan once it's archived, and recording the cleanup if `record_usage`.
+pub fn run(args: &ApplyArgs, options: &ApplyOptions, job: &ArchiveJob) -> Result<()> {
+    let path = args.plan.clone().map_or_else(default_file, Ok)?;
+    let plan = Plan::load(&path)?;
+    if args.script {
+        // The script archives as surely as applying does
+        if !plan.repos.is_empty() {
+            plan.check_approval(&path, options.require_approval, job.github.as_ref())?;
+        }
+        print!("{}", plan.script(job));
+        return Ok(());
+    }
+    apply(&path, &plan, options, job)
+}
+
+/// `repo-archiver apply`: archive the repos of a plan written by `plan`, as
+/// long as this run's flags take them through the steps that were planned.
+pub fn apply_file(args: &ApplyFileArgs, options: &ApplyOptions, job: &ArchiveJob) -> Result<()> {
+    if !args.plan.exists() {
+        anyhow::bail!("No plan at {}", args.plan.display());
+    }
+    let plan = Plan::load(&args.plan)?;
+    if plan.steps.is_empty() {
+        anyhow::bail!(
+            "{} wasn't written by `repo-archiver plan`; apply queued plans with apply-plan --plan",
+            args.plan.display()
+        );
+    }
+    let steps = plan.summary(job);
+    if steps != plan.steps {
+        anyhow::bail!(
+            "These flags would change what the plan does, so it isn't applied.\n  \
+             planned: {}\n  now:     {}\nPass the flags it was planned with, or plan again",
+            plan.steps.join(", "),
+            steps.join(", ")
+        );
+    }
+    apply(&args.plan, &plan, options, job)
+}
+
+fn apply(path: &Path, plan: &Plan, options: &ApplyOptions, job: &ArchiveJob) -> Result<()> {
+    let ApplyOptions {
+        dry_run,
+        record_usage,
+        require_approval,
+    } = *options;
+    if plan.repos.is_empty() {
+        println!("Nothing queued.");
+        return Ok(());
+    }
+    plan.check_approval(path, require_approval, job.github.as_ref())?;
+
+    println!(
+        "Applying {} {}{}...",
+        plan.repos.len(),
+        plan.assignee.as_ref().map_or_else(
+            || "queued repos".to_string(),
+            |login| format!("repos assigned to {login}")
+        ),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    let batch: Vec<(&str, u64)> = plan
+        .repos
+        .iter()
+        .map(|r| (r.name_with_owner.as_str(), r.disk_usage.unwrap_or(0)))
+        .collect();
+    // Repos that don't pass stay queued
+    let names = headless::checked(&batch, dry_run, job)?;
+    let mut outcome = headless::archive(&names, dry_run, job, Some(path.to_path_buf()));
+
+    if !dry_run {
+        Plan::remove(path, &outcome.archived)?;
+        if !plan.steps.is_empty() {
+            // What's left is retried with `apply`, which checks the counts
+            let mut left = Plan::load(path)?;
+            left.steps = left.summary(job);
+            left.save(path)?;
+        }
+        save_report(&mut outcome.report);
+        if record_usage {
+            if let Err(e) = usage::record(&usage::Cleanup::new(job.action, outcome.archived.len()))
+            {
+                eprintln!("Warning: failed to record usage stats: {e:#}");
+            }
+        }
+    }
+    if outcome.failed > 0 {
+        anyhow::bail!("{} repos failed and stay queued", outcome.failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Per-repo eligibility checks against an external policy endpoint.
+//!
+//! Each candidate's metadata is sent as JSON in a POST to the configured URL,
+//! which answers `{"decision": "allow" | "deny", "notes": "..."}`. Denied
+//! repos can't be selected, and notes show up in the Notes column.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::Repo;
+
+/// Requests in flight at once.
+const PARALLEL: usize = 8;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Decision {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Deserialize)]
+struct Verdict {
+    decision: Decision,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// Ask `url` about every repo, recording denials and notes on the repos.
+///
+/// A failed check counts as a denial, so an unreachable policy service
+/// never lets a repo through.
+pub fn check(url: &str, repos: &mut [Repo]) {
+    let chunk = repos.len().div_ceil(PARALLEL).max(1);
+    thread::scope(|s| {
+        for repos in repos.chunks_mut(chunk) {
+            s.spawn(move || {
+                for repo in repos {
+                    match ask(url, repo) {
+                        Ok(verdict) => {
+                            repo.policy_denied = matches!(verdict.decision, Decision::Deny);
+                            if verdict.notes.is_some() {
+                                repo.note = verdict.notes;
+                            }
+                        }
+                        Err(e) => {
+                            repo.policy_denied = true;
+                            repo.note = Some(format!("Policy check failed: {e:#}"));
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn ask(url: &str, repo: &Repo) -> Result<Verdict> {
+    let mut child = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl. Is it installed?")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&serde_json::to_vec(repo)?)?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    serde_json::from_slice(&output.stdout).context("Unexpected response")
+}
@@ -0,0 +1,469 @@
+//! Checks run before a batch starts, so problems surface in the confirm modal
+//! instead of halfway through a run.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{backend::Backend, error, gh, graphql, pipeline::ArchiveJob};
+
+/// Free space versus what the backups of a batch are expected to need.
+pub struct DiskCheck {
+    pub needed: u64,
+    pub available: u64,
+}
+
+impl DiskCheck {
+    /// Measure free space on the filesystem holding `dir`, which may not exist yet.
+    pub fn new(dir: &Path, needed: u64) -> Option<Self> {
+        // Relative paths run out of ancestors at "", which means the current dir
+        let existing = dir
+            .ancestors()
+            .find(|d| d.exists())
+            .unwrap_or_else(|| Path::new("."));
+        let available = fs4::available_space(existing).ok()?;
+        Some(Self { needed, available })
+    }
+
+    /// Not enough room: the batch must not start.
+    pub const fn insufficient(&self) -> bool {
+        self.needed > self.available
+    }
+
+    /// Fits, but would leave less than a fifth of the free space.
+    pub const fn tight(&self) -> bool {
+        !self.insufficient() && self.needed > self.available / 5 * 4
+    }
+}
+
+/// Whether the user's permission on repos gets checked before a batch
+/// starts: only GitHub says, and the demo's repos aren't real.
+pub fn checks_permissions(job: &ArchiveJob) -> bool {
+    job.on_github() && !job.demo
+}
+
+/// Compare the space the backups of repos of `sizes` (in KB, as GitHub
+/// reports them) need with what is free; `None` without backups.
+///
+/// Only mirrors take real space; metadata snapshots are tiny.
+pub fn disk_check(job: &ArchiveJob, sizes: impl Iterator<Item = u64>) -> Option<DiskCheck> {
+    if !job.has_backup() {
+        return None;
+    }
+    let sizes = sizes.map(|kb| kb * 1024);
+    if let Some(dir) = &job.backup_dir {
+        // Mirrors or bundles are kept, so all of them have to fit
+        DiskCheck::new(dir, sizes.sum())
+    } else {
+        // Each mirror is deleted after its push, so only the ones being
+        // worked on at the same time have to fit
+        let largest = sizes.max().unwrap_or(0);
+        let workers = job.backup_workers.get() as u64;
+        DiskCheck::new(&env::temp_dir(), largest * workers)
+    }
+}
+
+/// The confirm modal's checks, for batches started without it
+/// (`--non-interactive`, `apply-plan`, `apply`, the web UI and the daemon):
+/// returns the repos of `batch` (`owner/name` and size in KB) the user
+/// can't archive, each with why, and fails if the backups of the rest won't
+/// fit. Dry runs change nothing, so they aren't checked.
+///
+/// A failed permission lookup only warns, as it does in the modal.
+pub fn check_batch(
+    batch: &[(&str, u64)],
+    dry_run: bool,
+    job: &ArchiveJob,
+) -> Result<Vec<(String, String)>> {
+    if dry_run {
+        return Ok(Vec::new());
+    }
+    let mut refused = Vec::new();
+    if checks_permissions(job) {
+        let names: Vec<&str> = batch.iter().map(|&(name, _)| name).collect();
+        match fetch_permissions(&names, job.github.as_ref()) {
+            Ok(permissions) => {
+                for (name, permission) in names.into_iter().zip(permissions) {
+                    // Repos that vanished can't be archived either
+                    let permission = permission.unwrap_or_else(|| "NONE".to_string());
+                    if permission != "ADMIN" {
+                        refused.push((
+                            name.to_string(),
+                            format!(
+                                "needs admin permission, you have {}",
+                                permission.to_lowercase()
+                            ),
+                        ));
+                    }
+                }
+            }
+            Err(e) => eprintln!("Warning: {e:#}"),
+        }
+    }
+
+    let sizes = batch
+        .iter()
+        .filter(|(name, _)| !refused.iter().any(|(r, _)| r == name))
+        .map(|&(_, kb)| kb);
+    if let Some(check) = disk_check(job, sizes) {
+        let sizes = format!(
+            "need ~{}, {} free",
+            format_size(check.needed),
+            format_size(check.available)
+        );
+        if check.insufficient() {
+            anyhow::bail!("Not enough disk space for backups: {sizes}");
+        }
+        if check.tight() {
+            eprintln!("Warning: backups will nearly fill the disk: {sizes}");
+        }
+    }
+    Ok(refused)
+}
+
+/// What GitHub says about a repo that decides whether archiving it would work,
+/// read without changing anything.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoCheck {
+    is_archived: bool,
+    viewer_permission: String,
+    /// "PUBLIC", "PRIVATE" or "INTERNAL"
+    visibility: String,
+    fork_count: u64,
+    issues: Count,
+    pull_requests: Count,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Count {
+    total_count: u64,
+}
+
+impl RepoCheck {
+    pub fn fetch(name_with_owner: &str, github: &dyn Backend) -> Result<Self> {
+        const FIELDS: &str = "isArchived viewerPermission visibility forkCount \
+            issues(states: OPEN) { totalCount } pullRequests(states: OPEN) { totalCount }";
+
+        let repo = graphql::per_repo(&[name_with_owner], FIELDS, github)?
+            .pop()
+            .unwrap_or_default();
+        if repo.is_null() {
+            anyhow::bail!("Could not resolve to a Repository with the name '{name_with_owner}'");
+        }
+        Ok(serde_json::from_value(repo).map_err(|e| error::Error::Parse(e.to_string()))?)
+    }
+
+    /// Why archiving would fail, if it would.
+    pub fn blocker(&self) -> Option<String> {
+        if self.is_archived {
+            Some("already archived".to_string())
+        } else if self.viewer_permission != "ADMIN" {
+            Some(format!(
+                "needs admin permission, you have {}",
+                self.viewer_permission.to_lowercase()
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.visibility == "PRIVATE"
+    }
+
+    /// Things worth knowing that don't stop the archive: anything left open
+    /// gets frozen, unless `closing_items`, and forks point at a read-only
+    /// upstream.
+    pub fn warnings(&self, closing_items: bool) -> Vec<String> {
+        let mut warnings = if closing_items {
+            Vec::new()
+        } else {
+            self.open_items()
+        };
+        warnings.extend(counted([(self.fork_count, "fork")]));
+        warnings
+    }
+
+    /// How many issues and pull requests are open, e.g. "3 open issues".
+    pub fn open_items(&self) -> Vec<String> {
+        counted([
+            (self.issues.total_count, "open issue"),
+            (self.pull_requests.total_count, "open PR"),
+        ])
+    }
+}
+
+/// "3 open issues" for each nonzero count.
+fn counted<const N: usize>(counts: [(u64, &str); N]) -> Vec<String> {
+    counts
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{count} {what}{}", if count == 1 { "" } else { "s" }))
+        .collect()
+}
+
+/// The user's permission on each repo in `names` ("ADMIN", "WRITE", ...),
+/// `None` for repos they can't see at all.
+pub fn fetch_permissions(names: &[&str], github: &dyn Backend) -> Result<Vec<Option<String>>> {
+    let results = graphql::per_repo(names, "viewerPermission", github)
+        .context("Failed to check permissions")?;
+    Ok(results
+        .into_iter()
+        .map(|repo| repo["viewerPermission"].as_str().map(str::to_string))
+        .collect())
+}
+
+/// The combined check/status state of each repo's default branch head
+/// ("SUCCESS", "FAILURE", "PENDING", ...), `None` if it has no checks.
+pub fn fetch_ci_states(names: &[&str], github: &dyn Backend) -> Result<Vec<Option<String>>> {
+    const FIELDS: &str =
+        "defaultBranchRef { target { ... on Commit { statusCheckRollup { state } } } }";
+
+    let results = graphql::per_repo(names, FIELDS, github).context("Failed to check CI status")?;
+    Ok(results
+        .into_iter()
+        .map(|repo| {
+            repo["defaultBranchRef"]["target"]["statusCheckRollup"]["state"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .collect())
+}
+
+/// A repo's latest deployment.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub created_at: DateTime<Utc>,
+    pub environment: Option<String>,
+}
+
+/// The latest deployment of each repo in `names` if it happened after `since`.
+///
+/// No pushes doesn't mean nothing runs the code: a repo that still deploys
+/// is likely in production.
+pub fn fetch_recent_deployments(
+    names: &[&str],
+    since: DateTime<Utc>,
+    github: &dyn Backend,
+) -> Result<Vec<Option<Deployment>>> {
+    const FIELDS: &str = "deployments(first: 1, orderBy: {field: CREATED_AT, direction: DESC}) \
+        { nodes { createdAt environment } }";
+
+    let results =
+        graphql::per_repo(names, FIELDS, github).context("Failed to check deployments")?;
+    Ok(results
+        .into_iter()
+        .map(|mut repo| {
+            serde_json::from_value::<Deployment>(repo["deployments"]["nodes"][0].take())
+                .ok()
+                .filter(|d| d.created_at > since)
+        })
+        .collect())
+}
+
+/// The latest issue update (which includes comments), pull request update
+/// or release of each repo in `names`, `None` if it has none of them.
+pub fn fetch_last_activity(
+    names: &[&str],
+    github: &dyn Backend,
+) -> Result<Vec<Option<DateTime<Utc>>>> {
+    const FIELDS: &str = "issues(first: 1, orderBy: {field: UPDATED_AT, direction: DESC}) \
+        { nodes { updatedAt } } \
+        pullRequests(first: 1, orderBy: {field: UPDATED_AT, direction: DESC}) \
+        { nodes { updatedAt } } \
+        releases(first: 1, orderBy: {field: CREATED_AT, direction: DESC}) { nodes { createdAt } }";
+
+    let results =
+        graphql::per_repo(names, FIELDS, github).context("Failed to check issue activity")?;
+    Ok(results
+        .into_iter()
+        .map(|repo| {
+            [
+                &repo["issues"]["nodes"][0]["updatedAt"],
+                &repo["pullRequests"]["nodes"][0]["updatedAt"],
+                &repo["releases"]["nodes"][0]["createdAt"],
+            ]
+            .into_iter()
+            .filter_map(|at| at.as_str()?.parse::<DateTime<Utc>>().ok())
+            .max()
+        })
+        .collect())
+}
+
+/// A code search hit, trimmed down by `--jq`.
+#[derive(Deserialize)]
+struct SearchHit {
+    repo: String,
+    fragments: Vec<String>,
+}
+
+/// Repos of `owner` that reference each of its repos as a reusable workflow
+/// or action (`uses: owner/repo...`), keyed by the lowercased `owner/name`
+/// being used.
+///
+/// Relies on code search, which only sees default branches and may miss
+/// references in very large files.
+pub fn fetch_consumers(
+    owner: &str,
+    token: Option<&str>,
+) -> Result<HashMap<String, BTreeSet<String>>> {
+    let output = gh(token)
+        .args([
+            "api",
+            "--paginate",
+            "--method",
+            "GET",
+            "search/code",
+            "-H",
+            "Accept: application/vnd.github.text-match+json",
+            "-f",
+            &format!("q=\"uses: {owner}/\" language:YAML user:{owner}"),
+            "-f",
+            "per_page=100",
+            "--jq",
+            ".items[] | {repo: .repository.full_name, fragments: [.text_matches[]?.fragment]}",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    let stdout = error::check(output).context("Failed to search for workflow references")?;
+
+    let prefix = format!("{}/", owner.to_lowercase());
+    let mut consumers: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for line in String::from_utf8_lossy(&stdout).lines() {
+        let hit: SearchHit =
+            serde_json::from_str(line).map_err(|e| error::Error::Parse(e.to_string()))?;
+        for used in hit.fragments.iter().flat_map(|f| uses_references(f)) {
+            if used.starts_with(&prefix) && !used.eq_ignore_ascii_case(&hit.repo) {
+                consumers.entry(used).or_default().insert(hit.repo.clone());
+            }
+        }
+    }
+    Ok(consumers)
+}
+
+/// GitHub App lookups made at once
+const APP_CHECKS: usize = 8;
+
+/// For each of `names`, the slug of the GitHub App named after it and
+/// registered by the same owner, if there is one: archiving an app's source
+/// often comes before it breaks.
+///
+/// Apps are found by slug (the repo name, lowercased, with anything but
+/// letters and digits turned into hyphens), since GitHub has no API listing
+/// the apps an owner registered. Private apps only show up for tokens that
+/// can see them, and OAuth apps aren't in the API at all. Failed lookups
+/// count as no app.
+pub fn fetch_apps(names: &[&str], github: &dyn Backend) -> Vec<Option<String>> {
+    let mut apps = Vec::with_capacity(names.len());
+    for batch in names.chunks(APP_CHECKS) {
+        thread::scope(|s| {
+            let lookups: Vec<_> = batch
+                .iter()
+                .map(|name| s.spawn(move || app_for(name, github)))
+                .collect();
+            apps.extend(lookups.into_iter().map(|l| l.join().ok().flatten()));
+        });
+    }
+    apps
+}
+
+fn app_for(name_with_owner: &str, github: &dyn Backend) -> Option<String> {
+    let (owner, name) = name_with_owner.split_once('/')?;
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let app = github.rest("GET", &format!("apps/{slug}"), None).ok()?;
+    app["owner"]["login"]
+        .as_str()?
+        .eq_ignore_ascii_case(owner)
+        .then_some(slug)
+}
+
+/// Homepages checked at once
+const HOMEPAGE_CHECKS: usize = 16;
+
+/// Whether each site in `urls` still responds. A dead homepage is another
+/// sign of dormancy; a live one may mean the project is still in use.
+///
+/// Only unreachable sites, 404s, 410s and server errors count as dead; bot
+/// protection often answers curl with a 403 even though the site is up.
+pub fn check_homepages(urls: &[&str]) -> Vec<bool> {
+    let mut alive = Vec::with_capacity(urls.len());
+    for batch in urls.chunks(HOMEPAGE_CHECKS) {
+        thread::scope(|s| {
+            let checks: Vec<_> = batch
+                .iter()
+                .map(|url| s.spawn(move || homepage_alive(url)))
+                .collect();
+            alive.extend(checks.into_iter().map(|c| c.join().unwrap_or(false)));
+        });
+    }
+    alive
+}
+
+fn homepage_alive(url: &str) -> bool {
+    let Ok(output) = Command::new("curl")
+        .args(["--silent", "--location", "--max-time", "10"])
+        .args(["--output", "/dev/null", "--write-out", "%{http_code}"])
+        .arg(url)
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return false;
+    };
+    let status: u16 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .unwrap_or(0);
+    !matches!(status, 0 | 404 | 410 | 500..)
+}
+
+/// The `owner/repo` part of every `uses:` in a YAML fragment, lowercased.
+/// `uses: owner/repo/path/to/workflow.yml@v1` references `owner/repo`.
+fn uses_references(fragment: &str) -> impl Iterator<Item = String> + '_ {
+    fragment.split("uses:").skip(1).filter_map(|rest| {
+        let target = rest
+            .split_whitespace()
+            .next()?
+            .trim_matches(|c| c == '"' || c == '\'');
+        let target = target.split('@').next()?;
+        let mut parts = target.split('/');
+        let (owner, repo) = (parts.next()?, parts.next()?);
+        (!owner.is_empty() && !repo.is_empty() && !owner.starts_with('.'))
+            .then(|| format!("{owner}/{repo}").to_lowercase())
+    })
+}
+
+/// Human-readable byte count, e.g. "2.1 GB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
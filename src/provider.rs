@@ -0,0 +1,262 @@
+//! Where repos live: listing them, archiving them and unarchiving them,
+//! behind one trait so GitHub is just one forge among others. GitLab (see
+//! `gitlab`), Gitea and its forks (see `gitea`), Bitbucket (see `bitbucket`),
+//! the demo's fake repos and bare repos on an SSH server are providers too,
+//! and the pipeline can be run against a mock.
+//!
+//! Everything beyond the three core calls has a default for forges that
+//! don't have it: no pinned repos, no lookup after archiving, no
+//! organizations to check.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    backend::Backend, bitbucket::Bitbucket, config::Config, demo, error, gitea::Gitea,
+    gitlab::GitLab, report::FinalState, ssh::SshRemote, Repo,
+};
+
+/// Repos per page of the GitHub repo list; the most GitHub allows.
+const REPO_PAGE: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    #[value(name = "github")]
+    GitHub,
+    #[value(name = "gitlab")]
+    GitLab,
+    /// Gitea, Forgejo or Codeberg
+    #[value(name = "gitea")]
+    Gitea,
+    /// Bitbucket Cloud
+    #[value(name = "bitbucket")]
+    Bitbucket,
+}
+
+impl Kind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::GitHub => "GitHub",
+            Self::GitLab => "GitLab",
+            Self::Gitea => "Gitea",
+            Self::Bitbucket => "Bitbucket",
+        }
+    }
+
+    /// The `--provider` value naming this kind.
+    pub fn flag(self) -> String {
+        self.name().to_lowercase()
+    }
+}
+
+pub trait Provider: Send + Sync {
+    /// All non-archived repos of `owner`, or of the authenticated user, a
+    /// page at a time. `on_page` gets the number fetched so far after each
+    /// page.
+    fn list_repos(&self, owner: Option<&str>, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>>;
+
+    fn archive_repo(&self, name_with_owner: &str) -> Result<()>;
+
+    fn unarchive_repo(&self, name_with_owner: &str) -> Result<()>;
+
+    /// `owner/name` of the repos pinned on the profile of `owner`, or of the
+    /// authenticated user.
+    fn pinned(&self, _owner: Option<&str>) -> Result<HashSet<String>> {
+        Ok(HashSet::new())
+    }
+
+    /// Predict archiving the repo, using only read-only calls. GitHub's
+    /// prediction also covers backups and edits, so the pipeline makes that
+    /// one itself.
+    fn dry_run(&self, _name_with_owner: &str) -> Result<String> {
+        Ok("would archive".to_string())
+    }
+
+    /// How the repo looks right after archiving; `None` where the forge
+    /// can't be asked.
+    fn final_state(&self, _name_with_owner: &str) -> Result<Option<FinalState>> {
+        Ok(None)
+    }
+
+    /// Fail unless `org` is an organization the user can see, so a user's
+    /// login or a typo passed to `--org` is caught before anything is listed.
+    fn check_org(&self, _org: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The provider for this run: the demo's, the `--ssh` server, GitLab, Gitea,
+/// Bitbucket, or GitHub through `github`, the configured backend.
+pub fn new(
+    demo: bool,
+    ssh: Option<&SshRemote>,
+    kind: Kind,
+    github: Arc<dyn Backend>,
+    config: &Config,
+) -> Result<Arc<dyn Provider>> {
+    if demo {
+        return Ok(Arc::new(demo::Demo));
+    }
+    if let Some(remote) = ssh {
+        return Ok(Arc::new(remote.clone()));
+    }
+    match kind {
+        Kind::GitHub => Ok(Arc::new(GitHub { backend: github })),
+        Kind::GitLab => Ok(Arc::new(GitLab::new(config)?)),
+        Kind::Gitea => Ok(Arc::new(Gitea::new(config)?)),
+        Kind::Bitbucket => Ok(Arc::new(Bitbucket::new(config)?)),
+    }
+}
+
+/// Base URL of the server at `host`, which may be a bare host name
+/// (`codeberg.org`) or a URL (`http://localhost:3000`).
+pub fn server_url(host: &str) -> String {
+    let host = host.trim_end_matches('/');
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("https://{host}")
+    }
+}
+
+pub struct GitHub {
+    backend: Arc<dyn Backend>,
+}
+
+impl Provider for GitHub {
+    fn list_repos(&self, owner: Option<&str>, on_page: &mut dyn FnMut(usize)) -> Result<Vec<Repo>> {
+        let (variables, selection) = owner_selection(owner);
+        let query = format!(
+            "query({variables}$cursor: String) {{ owner: {selection} {{ \
+             repositories(first: {REPO_PAGE}, after: $cursor, isArchived: false, ownerAffiliations: OWNER, \
+             orderBy: {{field: CREATED_AT, direction: ASC}}) {{ \
+             pageInfo {{ hasNextPage endCursor }} \
+             nodes {{ name nameWithOwner createdAt description pushedAt isTemplate isMirror isFork \
+             stargazerCount homepageUrl diskUsage }} }} }} }}"
+        );
+
+        let mut repos = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut vars = Vec::new();
+            if let Some(owner) = owner {
+                vars.push(("login", owner));
+            }
+            if let Some(cursor) = &cursor {
+                vars.push(("cursor", cursor.as_str()));
+            }
+            let mut data = self.backend.graphql(&query, &vars)?;
+            let page = data["owner"]["repositories"].take();
+            if page.is_null() {
+                return Err(error::Error::NotFound(owner.map_or_else(
+                    || "No repos came back for the authenticated user".to_string(),
+                    |owner| {
+                        format!(
+                            "Could not resolve to a user or organization with the login of '{owner}'"
+                        )
+                    },
+                ))
+                .into());
+            }
+            let page: RepoPage =
+                serde_json::from_value(page).map_err(|e| error::Error::Parse(e.to_string()))?;
+            repos.extend(page.nodes);
+            on_page(repos.len());
+
+            match page.page_info.end_cursor {
+                Some(next) if page.page_info.has_next_page => cursor = Some(next),
+                _ => return Ok(repos),
+            }
+        }
+    }
+
+    fn archive_repo(&self, name_with_owner: &str) -> Result<()> {
+        self.backend.set_archived(name_with_owner, true)
+    }
+
+    fn unarchive_repo(&self, name_with_owner: &str) -> Result<()> {
+        self.backend.set_archived(name_with_owner, false)
+    }
+
+    fn pinned(&self, owner: Option<&str>) -> Result<HashSet<String>> {
+        let (_, selection) = owner_selection(owner);
+        let header = if owner.is_some() {
+            "query($login: String!)"
+        } else {
+            "query"
+        };
+        let query = format!(
+            "{header} {{ owner: {selection} {{ ... on ProfileOwner {{ \
+             pinnedItems(first: 6, types: REPOSITORY) {{ \
+             nodes {{ ... on Repository {{ nameWithOwner }} }} }} }} }} }}"
+        );
+        let vars: Vec<_> = owner.map(|owner| ("login", owner)).into_iter().collect();
+
+        let data = self
+            .backend
+            .graphql(&query, &vars)
+            .context("Failed to fetch pinned repos")?;
+        Ok(data["owner"]["pinnedItems"]["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|node| node["nameWithOwner"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    fn final_state(&self, name_with_owner: &str) -> Result<Option<FinalState>> {
+        const QUERY: &str = "query($owner: String!, $name: String!) { \
+            repository(owner: $owner, name: $name) { archived: isArchived pushedAt updatedAt } }";
+
+        let (owner, name) = name_with_owner.split_once('/').unwrap_or_default();
+        let mut data = self
+            .backend
+            .graphql(QUERY, &[("owner", owner), ("name", name)])
+            .with_context(|| format!("Failed to look up {name_with_owner} after archiving"))?;
+        Ok(Some(
+            serde_json::from_value(data["repository"].take())
+                .map_err(|e| error::Error::Parse(e.to_string()))?,
+        ))
+    }
+
+    fn check_org(&self, org: &str) -> Result<()> {
+        const QUERY: &str = "query($login: String!) { organization(login: $login) { login } }";
+
+        match self.backend.graphql(QUERY, &[("login", org)]) {
+            Ok(data) if !data["organization"].is_null() => Ok(()),
+            Err(e) if !matches!(error::Error::find(&e), Some(error::Error::NotFound(_))) => Err(e),
+            _ => Err(error::Error::NotFound(format!(
+                "'{org}' is not an organization, or you can't see it"
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Variable declarations and selection for a query about `owner`'s repos,
+/// or the authenticated user's.
+fn owner_selection(owner: Option<&str>) -> (&'static str, &'static str) {
+    match owner {
+        Some(_) => ("$login: String!, ", "repositoryOwner(login: $login)"),
+        None => ("", "viewer"),
+    }
+}
+
+/// A page of the repo list.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepoPage {
+    page_info: PageInfo,
+    nodes: Vec<Repo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
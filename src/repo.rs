@@ -0,0 +1,462 @@
+//! A candidate repo, the filter that decides which repos are candidates,
+//! and the lookups that drop or flag candidates before they're shown.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use crate::{
+    age::Age,
+    backend::Backend,
+    cache::RepoList,
+    compare::Decision,
+    forks::{self, ForkStatus},
+    preflight::{self, Deployment},
+    report::{FinalState, Wiki},
+    rules,
+};
+
+/// What the age cutoff is compared with, for `--filter-by`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AgeBasis {
+    /// When the repo was created
+    #[default]
+    Created,
+    /// The latest push, issue or comment, pull request update or release
+    Activity,
+}
+
+/// Which repos are candidates: what the age cutoff goes by, and which kinds
+/// besides it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "one per --include-* flag, saved with the cached list"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoFilter {
+    pub include_templates: bool,
+    pub include_mirrors: bool,
+    pub include_pinned: bool,
+    #[serde(default)]
+    pub include_forks: bool,
+    #[serde(default)]
+    pub age_basis: AgeBasis,
+    /// Repos created since this age's cutoff are never candidates, for
+    /// `--min-repo-age`; a guard from the flags or config, so not remembered
+    #[serde(skip)]
+    pub min_repo_age: Option<Age>,
+}
+
+impl RepoFilter {
+    /// Templates, mirrors and pinned repos are usually old on purpose, so they are opt-in.
+    const fn allows(self, repo: &Repo) -> bool {
+        (self.include_templates || !repo.is_template)
+            && (self.include_mirrors || !repo.is_mirror)
+            && (self.include_pinned || !repo.is_pinned)
+            && (self.include_forks || !repo.is_fork)
+    }
+
+    /// Include whatever either filter includes, going by activity if either
+    /// does.
+    pub const fn or(self, other: Self) -> Self {
+        Self {
+            include_templates: self.include_templates || other.include_templates,
+            include_mirrors: self.include_mirrors || other.include_mirrors,
+            include_pinned: self.include_pinned || other.include_pinned,
+            include_forks: self.include_forks || other.include_forks,
+            age_basis: match self.age_basis {
+                AgeBasis::Created => other.age_basis,
+                AgeBasis::Activity => AgeBasis::Activity,
+            },
+            min_repo_age: match self.min_repo_age {
+                Some(min) => Some(min),
+                None => other.min_repo_age,
+            },
+        }
+    }
+
+    /// ", by activity, including templates, pinned", or "" for the defaults.
+    pub fn display(self) -> String {
+        let basis = match self.age_basis {
+            AgeBasis::Created => "",
+            AgeBasis::Activity => ", by activity",
+        };
+        let included: Vec<&str> = [
+            (self.include_templates, "templates"),
+            (self.include_mirrors, "mirrors"),
+            (self.include_pinned, "pinned"),
+            (self.include_forks, "forks"),
+        ]
+        .into_iter()
+        .filter_map(|(include, kind)| include.then_some(kind))
+        .collect();
+        if included.is_empty() {
+            basis.to_string()
+        } else {
+            format!("{basis}, including {}", included.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "mirrors the fields GitHub returns, plus what screening marks"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Repo {
+    pub name: String,
+    pub name_with_owner: String,
+    pub created_at: DateTime<Utc>,
+    pub pushed_at: DateTime<Utc>,
+    pub description: Option<String>,
+    pub is_template: bool,
+    pub is_mirror: bool,
+    /// Missing from repo lists cached before forks were fetched
+    #[serde(default)]
+    pub is_fork: bool,
+    /// Missing from older cached repo lists too
+    #[serde(default)]
+    pub stargazer_count: u64,
+    /// Empty when not set; missing from older cached repo lists
+    #[serde(default)]
+    pub homepage_url: Option<String>,
+    /// Size in KB
+    pub disk_usage: u64,
+    /// Pinned on the owner's profile; filled in from a separate query
+    #[serde(skip)]
+    pub is_pinned: bool,
+    /// Decision from the `--compare` sheet, if the repo is listed there
+    #[serde(skip)]
+    pub decision: Option<Decision>,
+    /// Notes imported from a sheet or returned by the policy endpoint
+    #[serde(skip)]
+    pub note: Option<String>,
+    /// The policy endpoint doesn't allow archiving this repo
+    #[serde(skip)]
+    pub policy_denied: bool,
+    /// Description rule from the config that protects or preselects the repo
+    #[serde(skip)]
+    pub rule: Option<rules::Matched>,
+    /// The user's permission ("ADMIN", "WRITE", ...), once checked before
+    /// confirming
+    #[serde(skip)]
+    pub permission: Option<String>,
+    /// Deployed within the `--deploy-window`
+    #[serde(skip)]
+    pub recent_deployment: Option<Deployment>,
+    /// Other repos using this one as a reusable workflow or action
+    #[serde(skip)]
+    pub consumers: Vec<String>,
+    /// Slug of the owner's GitHub App named after this repo, likely its source
+    #[serde(skip)]
+    pub app: Option<String>,
+    /// How a fork compares to its upstream, once looked up
+    #[serde(skip)]
+    pub fork_status: Option<ForkStatus>,
+    /// How GitHub shows the repo after this run archived it
+    #[serde(skip)]
+    pub final_state: Option<FinalState>,
+    /// How long archiving took, once done or failed in this batch
+    #[serde(skip)]
+    pub took: Option<Duration>,
+    /// Marked with `P` to be archived before the rest of the batch
+    #[serde(skip)]
+    pub priority: bool,
+    /// Check state of the default branch head ("SUCCESS", "FAILURE", ...),
+    /// when fetched for the CI column or `--only-failing-ci`
+    #[serde(skip)]
+    pub ci_state: Option<String>,
+    /// Whether the homepage still responds, for repos that have one
+    #[serde(skip)]
+    pub homepage_alive: Option<bool>,
+    /// Latest issue or comment, pull request update or release, with
+    /// `--filter-by activity`
+    #[serde(skip)]
+    pub last_activity: Option<DateTime<Utc>>,
+    /// Whether this run backed up its wiki, with `--export-wiki`
+    #[serde(skip)]
+    pub wiki: Option<Wiki>,
+}
+
+impl Repo {
+    /// Whether `--prune-forks` may delete the repo: an unstarred fork even
+    /// with its upstream. Forks that couldn't be compared are kept.
+    pub fn prunable(&self) -> bool {
+        self.stargazer_count == 0 && self.fork_status.as_ref().is_some_and(ForkStatus::even)
+    }
+
+    /// Whether the repo is old enough to be a candidate.
+    fn old_enough(&self, age: Age, basis: AgeBasis) -> bool {
+        match (age, basis) {
+            // GitHub sets `pushedAt` when it creates the initial commit, so
+            // allow a little slack
+            (Age::NeverPushed, _) => {
+                self.pushed_at - self.created_at < chrono::Duration::minutes(1)
+            }
+            (_, AgeBasis::Created) => self.created_at.date_naive() < age.cutoff_date(),
+            (_, AgeBasis::Activity) => self.last_active().date_naive() < age.cutoff_date(),
+        }
+    }
+
+    /// The last push, or any later activity found by `drop_recently_active`.
+    fn last_active(&self) -> DateTime<Utc> {
+        self.last_activity
+            .map_or(self.pushed_at, |at| at.max(self.pushed_at))
+    }
+
+    pub fn ci_failing(&self) -> bool {
+        matches!(self.ci_state.as_deref(), Some("FAILURE" | "ERROR"))
+    }
+
+    /// Archiving needs admin permission; unchecked repos get the benefit of
+    /// the doubt.
+    pub fn lacks_admin(&self) -> bool {
+        self.permission.as_deref().is_some_and(|p| p != "ADMIN")
+    }
+
+    pub fn selectable(&self) -> bool {
+        !self.policy_denied && !self.protected() && !self.lacks_admin()
+    }
+
+    /// Why the repo can't be selected, for output without the table's
+    /// markers.
+    pub fn unselectable_reason(&self) -> Option<String> {
+        match &self.rule {
+            Some(m) if m.effect == rules::Effect::Protect => {
+                Some(format!("protected by the description rule '{}'", m.pattern))
+            }
+            _ if self.policy_denied => Some("the archive policy denies it".to_string()),
+            _ if self.lacks_admin() => Some("no admin permission".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Selected to begin with, by a description rule.
+    pub fn preselected(&self) -> bool {
+        self.rule
+            .as_ref()
+            .is_some_and(|m| m.effect == rules::Effect::Select)
+            && self.selectable()
+    }
+
+    fn protected(&self) -> bool {
+        self.rule
+            .as_ref()
+            .is_some_and(|m| m.effect == rules::Effect::Protect)
+    }
+}
+
+/// The repos from `list` that are up for archiving, oldest first.
+pub fn candidates(list: RepoList, age: Age, filter: RepoFilter) -> Vec<Repo> {
+    let mut filtered: Vec<Repo> = list
+        .repos
+        .into_iter()
+        .map(|mut repo| {
+            repo.is_pinned = list.pinned.contains(&repo.name_with_owner);
+            repo
+        })
+        .filter(|r| r.old_enough(age, filter.age_basis) && filter.allows(r))
+        .collect();
+
+    filtered.sort_by_key(|r| r.created_at);
+    filtered
+}
+
+/// Fill in each repo's CI state, keeping only failing ones if `only_failing`.
+/// The column alone is only informative, so a failed check just warns.
+pub fn fetch_ci_states(
+    repos: &mut Vec<Repo>,
+    only_failing: bool,
+    github: &dyn Backend,
+) -> Result<()> {
+    let names: Vec<&str> = repos.iter().map(|r| r.name_with_owner.as_str()).collect();
+    match preflight::fetch_ci_states(&names, github) {
+        Ok(states) => {
+            for (repo, state) in repos.iter_mut().zip(states) {
+                repo.ci_state = state;
+            }
+        }
+        Err(e) if only_failing => return Err(e),
+        Err(e) => eprintln!("Warning: {e:#}"),
+    }
+    if only_failing {
+        repos.retain(Repo::ci_failing);
+    }
+    Ok(())
+}
+
+/// Fill in how each fork compares to its upstream. Only informative, so a
+/// failed lookup doesn't stop the run.
+pub fn flag_fork_status(repos: &mut [Repo], github: &dyn Backend) {
+    let mut forks: Vec<&mut Repo> = repos.iter_mut().filter(|r| r.is_fork).collect();
+    if forks.is_empty() {
+        return;
+    }
+    println!("Comparing {} forks with their upstreams...", forks.len());
+    let names: Vec<&str> = forks.iter().map(|r| r.name_with_owner.as_str()).collect();
+    match forks::fetch(&names, github) {
+        Ok(statuses) => {
+            for (repo, status) in forks.iter_mut().zip(statuses) {
+                match status {
+                    Ok(status) => repo.fork_status = Some(status),
+                    Err(e) => eprintln!("Warning: {}: {e:#}", repo.name_with_owner),
+                }
+            }
+        }
+        Err(e) => eprintln!("Warning: {e:#}"),
+    }
+}
+
+/// Mark the repos that deployed in the last `days` days. Only a warning, so
+/// a failed check doesn't stop the run.
+pub fn flag_recent_deployments(repos: &mut [Repo], days: u32, github: &dyn Backend) {
+    let since = Utc::now() - chrono::Days::new(days.into());
+    let names: Vec<&str> = repos.iter().map(|r| r.name_with_owner.as_str()).collect();
+    match preflight::fetch_recent_deployments(&names, since, github) {
+        Ok(deployments) => {
+            for (repo, deployment) in repos.iter_mut().zip(deployments) {
+                repo.recent_deployment = deployment;
+            }
+        }
+        Err(e) => eprintln!("Warning: {e:#}"),
+    }
+}
+
+/// Drop the candidates created since `min`'s cutoff, for `--min-repo-age`,
+/// returning how many there were.
+pub fn drop_too_new(repos: &mut Vec<Repo>, min: Age) -> usize {
+    let before = repos.len();
+    repos.retain(|r| r.created_at.date_naive() < min.cutoff_date());
+    before - repos.len()
+}
+
+/// Drop the candidates with issue, pull request or release activity since
+/// `age`'s cutoff, for `--filter-by activity`: only their last push was
+/// old enough.
+pub fn drop_recently_active(repos: &mut Vec<Repo>, age: Age, github: &dyn Backend) -> Result<()> {
+    let names: Vec<&str> = repos.iter().map(|r| r.name_with_owner.as_str()).collect();
+    let activity = preflight::fetch_last_activity(&names, github)?;
+    for (repo, at) in repos.iter_mut().zip(activity) {
+        repo.last_activity = at;
+    }
+    repos.retain(|r| r.old_enough(age, AgeBasis::Activity));
+    Ok(())
+}
+
+/// Mark the repos that other repos still use as workflows or actions. Only a
+/// warning, so a failed search doesn't stop the run.
+pub fn flag_consumers(repos: &mut [Repo], token: Option<&str>) {
+    let owners: HashSet<&str> = repos
+        .iter()
+        .filter_map(|r| r.name_with_owner.split_once('/').map(|(owner, _)| owner))
+        .collect();
+    let mut consumers = HashMap::new();
+    for owner in owners {
+        match preflight::fetch_consumers(owner, token) {
+            Ok(found) => consumers.extend(found),
+            Err(e) => eprintln!("Warning: {e:#}"),
+        }
+    }
+    for repo in repos {
+        if let Some(used_by) = consumers.remove(&repo.name_with_owner.to_lowercase()) {
+            repo.consumers = used_by.into_iter().collect();
+        }
+    }
+}
+
+/// Mark the repos that look like the source of one of their owner's GitHub
+/// Apps. Only a warning, like the other checks.
+pub fn flag_apps(repos: &mut [Repo], github: &dyn Backend) {
+    println!("Checking {} repos for GitHub Apps...", repos.len());
+    let names: Vec<&str> = repos.iter().map(|r| r.name_with_owner.as_str()).collect();
+    let apps = preflight::fetch_apps(&names, github);
+    for (repo, app) in repos.iter_mut().zip(apps) {
+        repo.app = app;
+    }
+}
+
+/// Check whether the homepages set on repos still respond.
+pub fn check_homepages(repos: &mut [Repo]) {
+    let mut with_homepage: Vec<&mut Repo> = repos
+        .iter_mut()
+        .filter(|r| r.homepage_url.as_deref().is_some_and(|url| !url.is_empty()))
+        .collect();
+    if with_homepage.is_empty() {
+        return;
+    }
+    println!("Checking {} homepages...", with_homepage.len());
+    let urls: Vec<&str> = with_homepage
+        .iter()
+        .filter_map(|r| r.homepage_url.as_deref())
+        .collect();
+    let alive = preflight::check_homepages(&urls);
+    for (repo, alive) in with_homepage.iter_mut().zip(alive) {
+        repo.homepage_alive = Some(alive);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fork(stars: u64, status: Option<ForkStatus>) -> Repo {
+        Repo {
+            name_with_owner: "me/fork".to_string(),
+            is_fork: true,
+            stargazer_count: stars,
+            fork_status: status,
+            ..Repo::default()
+        }
+    }
+
+    fn even() -> ForkStatus {
+        ForkStatus {
+            upstream: Some("them/repo".to_string()),
+            ahead: 0,
+            behind: 3,
+            open_prs: 0,
+            branches_ahead: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn prunes_only_unstarred_forks_even_with_upstream() {
+        assert!(fork(0, Some(even())).prunable());
+        assert!(!fork(1, Some(even())).prunable());
+        assert!(!fork(0, None).prunable(), "not compared");
+        assert!(!fork(0, Some(ForkStatus { ahead: 2, ..even() })).prunable());
+        assert!(!fork(
+            0,
+            Some(ForkStatus {
+                open_prs: 1,
+                ..even()
+            })
+        )
+        .prunable());
+        assert!(!fork(
+            0,
+            Some(ForkStatus {
+                upstream: None,
+                ..even()
+            })
+        )
+        .prunable());
+    }
+
+    #[test]
+    fn keeps_forks_with_commits_on_other_branches() {
+        let status = ForkStatus {
+            branches_ahead: vec!["feature".to_string()],
+            ..even()
+        };
+        assert!(!fork(0, Some(status)).prunable());
+    }
+}
@@ -0,0 +1,282 @@
+//! The run report: every repo a run archived or deleted, or failed to, and
+//! how each archived repo looks on GitHub afterwards.
+//!
+//! A successful archive call only says GitHub accepted the request, so each
+//! archived repo is looked up again and the report records what GitHub
+//! actually shows (archived flag, last push, last update). One JSON file per
+//! run is kept in the state directory, with how long each repo took and the
+//! fastest, average and slowest, to tune worker counts and chunk pauses by.
+//! With `--access-review`, it also records who can still write to each
+//! archived repo, and with `--export-wiki` whether its wiki was backed up.
+//! In org mode it records each archived repo's owning teams and sums them up
+//! per team, for tracking an org-wide cleanup.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{access::Grant, paths, pipeline::Action};
+
+/// A repo as GitHub shows it right after archiving.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalState {
+    pub archived: bool,
+    pub pushed_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Who can still write to the repo, with `--access-review`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<Vec<Grant>>,
+    /// The teams that own the repo, as `org/slug`, in org mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teams: Option<Vec<String>>,
+}
+
+/// Whether a repo's wiki was backed up, with `--export-wiki`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wiki {
+    Exported,
+    /// It has no pages, so there was nothing to export
+    #[serde(rename = "none")]
+    Missing,
+}
+
+impl fmt::Display for Wiki {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Exported => "exported",
+            Self::Missing => "none",
+        })
+    }
+}
+
+/// How one repo ended up.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub name_with_owner: String,
+    /// Why the repo wasn't archived or deleted; `None` if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Missing when the repo couldn't be looked up again, and for deletes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_state: Option<FinalState>,
+    /// From the repo's first step starting to its result coming in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<f64>,
+    /// Collaborators and teams removed before archiving, to grant again if
+    /// the repo is brought back
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_access: Vec<Grant>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<Wiki>,
+}
+
+/// Fastest, average and slowest repo of a run, in seconds.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Timing {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl Timing {
+    /// "min 1.2s · avg 3.4s · max 1m 02s"
+    pub fn display(self) -> String {
+        format!(
+            "min {} · avg {} · max {}",
+            format_duration(Duration::from_secs_f64(self.min)),
+            format_duration(Duration::from_secs_f64(self.avg)),
+            format_duration(Duration::from_secs_f64(self.max))
+        )
+    }
+}
+
+/// How many repos one team had archived in a run.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamSummary {
+    /// `org/slug`; `None` sums up the repos no team owns
+    pub team: Option<String>,
+    pub archived: usize,
+    pub repos: Vec<String>,
+}
+
+/// "4.2s", "3m 07s" or "1h 02m".
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", d.as_secs_f64()),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// When each repo of a batch started, by index, so its time can be taken
+/// once its result comes in.
+#[derive(Default)]
+pub struct Stopwatch(HashMap<usize, Instant>);
+
+impl Stopwatch {
+    pub fn start(&mut self, idx: usize) {
+        self.0.entry(idx).or_insert_with(Instant::now);
+    }
+
+    /// How long the repo has been going, if it started.
+    pub fn running(&self, idx: usize) -> Option<Duration> {
+        self.0.get(&idx).map(Instant::elapsed)
+    }
+
+    /// How long the repo took; `None` if it never started, e.g. when its
+    /// checkpoint couldn't be written.
+    pub fn stop(&mut self, idx: usize) -> Option<Duration> {
+        self.0.remove(&idx).map(|started| started.elapsed())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub finished_at: DateTime<Utc>,
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<Timing>,
+    /// Archived repos per owning team, most first, in org mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<TeamSummary>,
+    pub repos: Vec<Entry>,
+    /// Access removed from repos that haven't finished yet, by `owner/name`
+    #[serde(skip)]
+    removed: HashMap<String, Vec<Grant>>,
+    /// Wikis of repos that haven't finished yet, by `owner/name`
+    #[serde(skip)]
+    wikis: HashMap<String, Wiki>,
+}
+
+impl Report {
+    pub fn new(action: Action) -> Self {
+        Self {
+            finished_at: Utc::now(),
+            action,
+            timing: None,
+            teams: Vec::new(),
+            repos: Vec::new(),
+            removed: HashMap::new(),
+            wikis: HashMap::new(),
+        }
+    }
+
+    pub fn wiki(&mut self, name_with_owner: &str, wiki: Wiki) {
+        self.wikis.insert(name_with_owner.to_string(), wiki);
+    }
+
+    pub fn access_removed(&mut self, name_with_owner: &str, grant: Grant) {
+        self.removed
+            .entry(name_with_owner.to_string())
+            .or_default()
+            .push(grant);
+    }
+
+    pub fn done(
+        &mut self,
+        name_with_owner: &str,
+        final_state: Option<FinalState>,
+        took: Option<Duration>,
+    ) {
+        self.repos.push(Entry {
+            name_with_owner: name_with_owner.to_string(),
+            error: None,
+            final_state,
+            seconds: took.map(|d| d.as_secs_f64()),
+            removed_access: self.removed.remove(name_with_owner).unwrap_or_default(),
+            wiki: self.wikis.remove(name_with_owner),
+        });
+    }
+
+    pub fn failed(&mut self, name_with_owner: &str, error: &str, took: Option<Duration>) {
+        self.repos.push(Entry {
+            name_with_owner: name_with_owner.to_string(),
+            error: Some(error.to_string()),
+            final_state: None,
+            seconds: took.map(|d| d.as_secs_f64()),
+            removed_access: self.removed.remove(name_with_owner).unwrap_or_default(),
+            wiki: self.wikis.remove(name_with_owner),
+        });
+    }
+
+    /// Timing stats over the repos that were timed, failed ones included.
+    pub fn timing(&self) -> Option<Timing> {
+        let seconds: Vec<f64> = self.repos.iter().filter_map(|e| e.seconds).collect();
+        if seconds.is_empty() {
+            return None;
+        }
+        Some(Timing {
+            min: seconds.iter().copied().fold(f64::INFINITY, f64::min),
+            avg: seconds.iter().sum::<f64>() / seconds.len() as f64,
+            max: seconds.iter().copied().fold(0.0, f64::max),
+        })
+    }
+
+    /// The archived repos of each owning team, over the repos whose teams
+    /// were looked up. A repo several teams own counts for each.
+    pub fn teams(&self) -> Vec<TeamSummary> {
+        let mut by_team: BTreeMap<Option<&str>, Vec<String>> = BTreeMap::new();
+        for entry in &self.repos {
+            let Some(teams) = entry.final_state.as_ref().and_then(|s| s.teams.as_ref()) else {
+                continue;
+            };
+            let owners: Vec<Option<&str>> = if teams.is_empty() {
+                vec![None]
+            } else {
+                teams.iter().map(|t| Some(t.as_str())).collect()
+            };
+            for team in owners {
+                by_team
+                    .entry(team)
+                    .or_default()
+                    .push(entry.name_with_owner.clone());
+            }
+        }
+        let mut summary: Vec<TeamSummary> = by_team
+            .into_iter()
+            .map(|(team, mut repos)| {
+                repos.sort();
+                TeamSummary {
+                    team: team.map(str::to_string),
+                    archived: repos.len(),
+                    repos,
+                }
+            })
+            .collect();
+        // Most archived first, and the unowned repos last
+        summary.sort_by_key(|s| (s.team.is_none(), std::cmp::Reverse(s.archived)));
+        summary
+    }
+
+    /// Write the report to the reports directory, unless nothing happened.
+    /// Returns where it went.
+    pub fn save(&mut self) -> Result<Option<PathBuf>> {
+        if self.repos.is_empty() {
+            return Ok(None);
+        }
+        self.finished_at = Utc::now();
+        self.timing = self.timing();
+        self.teams = self.teams();
+        let dir = paths::reports_dir().context("No home directory to keep run reports in")?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "{}.json",
+            self.finished_at.format("%Y-%m-%dT%H-%M-%S%.3fZ")
+        ));
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Some(path))
+    }
+}
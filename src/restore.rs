@@ -0,0 +1,193 @@
+//! `repo-archiver restore`: recreate a repo from its backup.
+//!
+//! Uses the metadata snapshot plus either the kept `mirror.git` or the
+//! (possibly encrypted) `repo.bundle` from the backup directory. Teams get
+//! their access back if it was exported with `--export-org-settings`.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    backup,
+    backup::{Access, Metadata},
+    encrypt, gh, mirror,
+};
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Repo to restore, as "owner/name"
+    name: String,
+
+    /// age identity file, for backups encrypted with an age key
+    #[arg(long)]
+    identity: Option<PathBuf>,
+}
+
+pub fn run(args: &RestoreArgs, backup_dir: &Path, token: Option<&str>) -> Result<()> {
+    let name = args.name.as_str();
+    if !name.contains('/') {
+        anyhow::bail!("Repo must be given as owner/name, got '{name}'");
+    }
+
+    let dir = backup::repo_dir(backup_dir, name);
+    let identity = args.identity.as_deref();
+    let metadata: Metadata = serde_json::from_slice(
+        &encrypt::read(&dir.join("metadata.json"), identity)
+            .with_context(|| format!("No metadata snapshot for {name} in {}", dir.display()))?,
+    )?;
+
+    // Prepare the git data before creating anything, so a broken backup
+    // doesn't leave an empty repo behind. The unpacked bundle may be the
+    // plaintext of an encrypted backup, so it goes in a directory only the
+    // user can read, removed however this returns
+    let work_dir = tempfile::Builder::new()
+        .prefix("repo-archiver-restore-")
+        .tempdir()
+        .context("Failed to create a directory to unpack the backup in")?;
+    let mirror_dir = prepare_mirror(&dir, work_dir.path(), identity)?;
+
+    println!("Creating {name}...");
+    create_repo(&metadata, token)?;
+
+    if let Some(mirror_dir) = &mirror_dir {
+        println!("Pushing branches and tags...");
+        mirror::push_to_github(mirror_dir, name, token)?;
+    } else {
+        println!("No mirror or bundle in the backup; the repo will be empty.");
+    }
+
+    println!("Restoring settings...");
+    restore_settings(&metadata, mirror_dir.is_some(), token)?;
+
+    let access = dir.join("access.json");
+    if encrypt::exists(&access) {
+        let access: Access = serde_json::from_slice(&encrypt::read(&access, identity)?)?;
+        restore_access(&access, token);
+    }
+
+    if let Some(file) = &metadata.social_preview {
+        println!(
+            "GitHub has no API for social previews; upload {} in the repo settings.",
+            dir.join(file).display()
+        );
+    }
+    let org_settings = backup_dir
+        .join(name.split('/').next().unwrap_or_default())
+        .join("org-settings.json");
+    if encrypt::exists(&org_settings) {
+        println!(
+            "Organization settings aren't restored with a repo; compare them with {}.",
+            org_settings.display()
+        );
+    }
+    println!("Restored {name}.");
+    Ok(())
+}
+
+/// Find the repo's git data in `dir`: a kept mirror is used as is, a bundle
+/// is decrypted if needed and unpacked into `work_dir`, which must exist.
+fn prepare_mirror(dir: &Path, work_dir: &Path, identity: Option<&Path>) -> Result<Option<PathBuf>> {
+    let kept = dir.join("mirror.git");
+    if kept.join("HEAD").exists() {
+        return Ok(Some(kept));
+    }
+
+    let bundle = dir.join("repo.bundle");
+    if !encrypt::exists(&bundle) {
+        return Ok(None);
+    }
+
+    let plain = work_dir.join("repo.bundle");
+    fs::write(&plain, encrypt::read(&bundle, identity)?)?;
+    let mirror_dir = work_dir.join("mirror.git");
+    let cloned = mirror::clone_bundle(&plain, &mirror_dir);
+    fs::remove_file(&plain)?;
+    cloned?;
+    Ok(Some(mirror_dir))
+}
+
+fn create_repo(metadata: &Metadata, token: Option<&str>) -> Result<()> {
+    let visibility = format!("--{}", metadata.visibility.to_lowercase());
+    let mut cmd = gh(token);
+    cmd.args(["repo", "create", &metadata.name_with_owner, &visibility]);
+    if let Some(description) = &metadata.description {
+        cmd.args(["--description", description]);
+    }
+    if let Some(homepage) = &metadata.homepage_url {
+        cmd.args(["--homepage", homepage]);
+    }
+
+    let output = cmd
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to create {}: {}",
+            metadata.name_with_owner,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Give each team its access back; a team that's gone since is reported and
+/// skipped.
+fn restore_access(access: &Access, token: Option<&str>) {
+    let org = access.name_with_owner.split('/').next().unwrap_or_default();
+    for team in &access.teams {
+        println!("Giving team {} {} access...", team.slug, team.permission);
+        let output = gh(token)
+            .args([
+                "api",
+                "-X",
+                "PUT",
+                &format!(
+                    "orgs/{org}/teams/{}/repos/{}",
+                    team.slug, access.name_with_owner
+                ),
+                "-f",
+                &format!("permission={}", team.permission),
+            ])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => println!(
+                "  Couldn't: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => println!("  Couldn't run gh: {e}"),
+        }
+    }
+}
+
+fn restore_settings(metadata: &Metadata, has_branches: bool, token: Option<&str>) -> Result<()> {
+    let mut cmd = gh(token);
+    cmd.args(["repo", "edit", &metadata.name_with_owner])
+        .arg(format!("--enable-issues={}", metadata.has_issues_enabled))
+        .arg(format!("--enable-wiki={}", metadata.has_wiki_enabled))
+        .arg(format!(
+            "--enable-projects={}",
+            metadata.has_projects_enabled
+        ));
+    if !metadata.topics.is_empty() {
+        cmd.args(["--add-topic", &metadata.topics.join(",")]);
+    }
+    if let (Some(branch), true) = (&metadata.default_branch, has_branches) {
+        cmd.args(["--default-branch", branch]);
+    }
+
+    let output = cmd
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to restore settings: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Description keyword rules from the config file's `[[description_rules]]`.
+//!
+//! Each rule is a regular expression matched (ignoring case) anywhere in a
+//! candidate's description. Repos matching a `protect` rule can't be
+//! selected, like those the policy endpoint denies; repos matching a `select`
+//! rule start out selected. A repo matching both is protected.
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::Repo;
+
+/// One `[[description_rules]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DescriptionRule {
+    /// e.g. "client project", or ".*hackathon.*"
+    pub pattern: String,
+    pub action: Effect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Protect,
+    Select,
+}
+
+/// The rule a repo's description matched, shown next to its name.
+#[derive(Debug, Clone)]
+pub struct Matched {
+    pub effect: Effect,
+    pub pattern: String,
+}
+
+/// The config's rules, compiled.
+#[derive(Clone)]
+pub struct Rules(Vec<(Regex, DescriptionRule)>);
+
+impl Rules {
+    pub fn compile(rules: &[DescriptionRule]) -> Result<Self> {
+        rules
+            .iter()
+            .map(|rule| {
+                let regex = RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .with_context(|| {
+                        format!("Invalid pattern '{}' in description_rules", rule.pattern)
+                    })?;
+                Ok((regex, rule.clone()))
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+
+    /// The first protect rule matching `description`, or else the first
+    /// select rule.
+    fn matching(&self, description: &str) -> Option<Matched> {
+        let first = |effect| {
+            self.0
+                .iter()
+                .find(|(regex, rule)| rule.action == effect && regex.is_match(description))
+                .map(|(_, rule)| Matched {
+                    effect,
+                    pattern: rule.pattern.clone(),
+                })
+        };
+        first(Effect::Protect).or_else(|| first(Effect::Select))
+    }
+}
+
+/// Mark the repos whose description matches a rule, returning how many were
+/// protected and how many selected.
+pub fn apply(repos: &mut [Repo], rules: &Rules) -> (usize, usize) {
+    let (mut protected, mut selected) = (0, 0);
+    for repo in repos {
+        repo.rule = repo
+            .description
+            .as_deref()
+            .and_then(|description| rules.matching(description));
+        match repo.rule.as_ref().map(|m| m.effect) {
+            Some(Effect::Protect) => protected += 1,
+            Some(Effect::Select) => selected += 1,
+            None => {}
+        }
+    }
+    (protected, selected)
+}
@@ -0,0 +1,194 @@
+//! `--sample`: trial a policy on a manageable subset of a huge candidate list.
+
+use chrono::Datelike;
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::Repo;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Strategy {
+    /// Any N candidates
+    #[default]
+    Random,
+    /// N candidates spread over creation years in proportion to how many
+    /// candidates each year has
+    Age,
+}
+
+/// splitmix64; plenty for picking a sample, and reproducible from a seed.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: Option<u64>) -> Self {
+        Self(seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        }))
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Move `n` randomly chosen items to the front of `items`.
+    fn choose<T>(&mut self, items: &mut [T], n: usize) {
+        for i in 0..n.min(items.len()) {
+            let j = i + (self.next() % (items.len() - i) as u64) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Keep `n` of `repos`, still oldest first.
+pub fn sample(repos: Vec<Repo>, n: usize, strategy: Strategy, seed: Option<u64>) -> Vec<Repo> {
+    if repos.len() <= n {
+        return repos;
+    }
+    let mut rng = Rng::new(seed);
+    let mut sampled = match strategy {
+        Strategy::Random => {
+            let mut repos = repos;
+            rng.choose(&mut repos, n);
+            repos.truncate(n);
+            repos
+        }
+        Strategy::Age => stratified(repos, n, &mut rng),
+    };
+    sampled.sort_by_key(|r| r.created_at);
+    sampled
+}
+
+fn stratified(repos: Vec<Repo>, n: usize, rng: &mut Rng) -> Vec<Repo> {
+    let total = repos.len();
+    let mut buckets: BTreeMap<i32, Vec<Repo>> = BTreeMap::new();
+    for repo in repos {
+        buckets
+            .entry(repo.created_at.year())
+            .or_default()
+            .push(repo);
+    }
+
+    // Largest remainder: every year gets its whole share, then the years
+    // with the biggest leftover fractions get the rest
+    let mut shares: Vec<(usize, usize, i32)> = buckets
+        .iter()
+        .map(|(year, bucket)| {
+            let exact = bucket.len() * n;
+            (exact / total, exact % total, *year)
+        })
+        .collect();
+    let mut left = n - shares.iter().map(|(share, ..)| share).sum::<usize>();
+    shares.sort_by_key(|&(_, remainder, _)| std::cmp::Reverse(remainder));
+    for (share, ..) in &mut shares {
+        if left == 0 {
+            break;
+        }
+        *share += 1;
+        left -= 1;
+    }
+
+    let mut sampled = Vec::with_capacity(n);
+    for (share, _, year) in shares {
+        let mut bucket = buckets.remove(&year).unwrap_or_default();
+        rng.choose(&mut bucket, share);
+        bucket.truncate(share);
+        sampled.extend(bucket);
+    }
+    sampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    /// `count` repos created in each of the given years.
+    fn repos(years: &[(i32, usize)]) -> Vec<Repo> {
+        years
+            .iter()
+            .flat_map(|&(year, count)| {
+                (0..count).map(move |i| Repo {
+                    name_with_owner: format!("octo/{year}-{i}"),
+                    created_at: Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()
+                        + chrono::Duration::hours(i as i64),
+                    ..Repo::default()
+                })
+            })
+            .collect()
+    }
+
+    fn per_year(repos: &[Repo]) -> BTreeMap<i32, usize> {
+        let mut counts = BTreeMap::new();
+        for repo in repos {
+            *counts.entry(repo.created_at.year()).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn age_samples_each_year_in_proportion() {
+        let sampled = sample(
+            repos(&[(2015, 60), (2018, 30), (2021, 10)]),
+            10,
+            Strategy::Age,
+            Some(1),
+        );
+
+        assert_eq!(
+            per_year(&sampled),
+            BTreeMap::from([(2015, 6), (2018, 3), (2021, 1)])
+        );
+        assert!(sampled.is_sorted_by_key(|r| r.created_at), "oldest first");
+    }
+
+    #[test]
+    fn shares_add_up_to_the_sample_size() {
+        for n in 1..=7 {
+            let sampled = stratified(
+                repos(&[(2015, 3), (2016, 3), (2017, 1), (2018, 1)]),
+                n,
+                &mut Rng::new(Some(n as u64)),
+            );
+            assert_eq!(sampled.len(), n);
+            let mut names: Vec<_> = sampled.iter().map(|r| &r.name_with_owner).collect();
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), n, "no repo twice");
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_sample() {
+        let names = |strategy, seed| -> Vec<String> {
+            sample(repos(&[(2015, 40), (2020, 40)]), 8, strategy, Some(seed))
+                .into_iter()
+                .map(|r| r.name_with_owner)
+                .collect()
+        };
+
+        for strategy in [Strategy::Random, Strategy::Age] {
+            assert_eq!(names(strategy, 7), names(strategy, 7));
+            assert_ne!(names(strategy, 7), names(strategy, 8));
+        }
+    }
+
+    #[test]
+    fn rng_is_reproducible_from_its_seed() {
+        let draws = |seed| {
+            let mut rng = Rng::new(Some(seed));
+            (0..4).map(|_| rng.next()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+    }
+}
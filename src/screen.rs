@@ -0,0 +1,130 @@
+//! The checks every way of listing candidates goes through, after the age
+//! cutoff and the [`RepoFilter`]: the TUI, `plan`, `--non-interactive`,
+//! `list`, `serve` and the daemon all screen repos here, so none of them
+//! offers a repo that another would keep out or protect.
+
+use anyhow::Result;
+
+use crate::{
+    age::Age,
+    dates::DateFormat,
+    drop_recently_active, drop_too_new, fetch_ci_states, keep,
+    pipeline::ArchiveJob,
+    policy,
+    rules::{self, Rules},
+    AgeBasis, Repo, RepoFilter,
+};
+
+/// The guards from the flags and config that aren't part of the
+/// [`RepoFilter`].
+#[derive(Clone, Default)]
+pub struct Guards {
+    /// `[[description_rules]]`
+    pub rules: Option<Rules>,
+    /// `--respect-keep-files`
+    pub keep_files: bool,
+    /// `--only-failing-ci`
+    pub only_failing_ci: bool,
+    /// `--policy-url`
+    pub policy_url: Option<String>,
+}
+
+/// Drop the candidates the guards keep out, and mark the ones they protect
+/// or preselect. `offline` leaves out the checks that look things up.
+///
+/// Progress is printed with dates in `progress`; without it screening is
+/// quiet, for output that has to stay machine-readable.
+pub fn screen(
+    repos: &mut Vec<Repo>,
+    age: Age,
+    filter: RepoFilter,
+    guards: &Guards,
+    job: &ArchiveJob,
+    offline: bool,
+    progress: Option<&DateFormat>,
+) -> Result<()> {
+    let say = |line: String| {
+        if progress.is_some() {
+            println!("{line}");
+        }
+    };
+    let date = |age: Age| {
+        progress
+            .map(|f| f.format(age.cutoff_date()))
+            .unwrap_or_default()
+    };
+    let github = !offline && job.on_github() && !job.demo;
+
+    if let Some(description_rules) = &guards.rules {
+        let (protected, selected) = rules::apply(repos, description_rules);
+        if protected + selected > 0 {
+            say(format!(
+                "Description rules protect {protected} repos and preselect {selected}."
+            ));
+        }
+    }
+    if let Some(min) = filter.min_repo_age {
+        let dropped = drop_too_new(repos, min);
+        if dropped > 0 {
+            say(format!(
+                "Skipping {dropped} repos created since {} (--min-repo-age).",
+                date(min)
+            ));
+        }
+    }
+    if filter.age_basis == AgeBasis::Activity && age != Age::NeverPushed {
+        if github {
+            let before = repos.len();
+            drop_recently_active(repos, age, job.github.as_ref())?;
+            if repos.len() < before {
+                say(format!(
+                    "Skipping {} repos with issue, pull request or release activity since {}.",
+                    before - repos.len(),
+                    date(age)
+                ));
+            }
+        } else {
+            say("Only pushes count as activity offline or off GitHub.".to_string());
+        }
+    }
+
+    if guards.keep_files {
+        if job.ssh.is_some() {
+            say("Not checking for keep files on an SSH remote.".to_string());
+        } else if !job.on_github() {
+            say(format!(
+                "Not checking for keep files on {}.",
+                job.forge.name()
+            ));
+        } else if offline {
+            say("Offline: not checking for keep files.".to_string());
+        } else if github {
+            let kept = keep::fetch_kept(repos, job.github.as_ref())?;
+            if !kept.is_empty() {
+                say(format!(
+                    "Skipping {} repos that contain a keep file.",
+                    kept.len()
+                ));
+                repos.retain(|r| !kept.contains(&r.name_with_owner));
+            }
+        }
+    }
+
+    if guards.only_failing_ci {
+        if github {
+            fetch_ci_states(repos, true, job.github.as_ref())?;
+        } else {
+            say("Not checking CI offline or off GitHub; showing all candidates.".to_string());
+        }
+    }
+
+    // Checked offline too, so a queued plan never bypasses the policy
+    if let Some(url) = guards.policy_url.as_deref().filter(|_| !job.demo) {
+        say(format!(
+            "Checking {} repos against the archive policy...",
+            repos.len()
+        ));
+        policy::check(url, repos);
+    }
+    Ok(())
+}
@@ -0,0 +1,411 @@
+//! `repo-archiver serve --stdio`: listing, planning and archiving as a
+//! JSON-RPC 2.0 server, one message per line on stdin and stdout, so editor
+//! plugins and other front ends can drive repo-archiver without its TUI.
+//!
+//! Methods:
+//! - `list {age, owners?}`: the candidates older than `age` (as for `--age`),
+//!   filtered as the flags and config say
+//! - `plan {repos}`: the steps the batch goes through and, for each repo, what
+//!   archiving it would do or why it would fail
+//! - `archive {repos}`: archive them, sending a `progress` notification as
+//!   each repo starts, starts or finishes its backup, finishes a step, has
+//!   its wiki exported (or has none), loses access, closes an issue, is done
+//!   or fails, and answering with the run report
+//!
+//! Repos are given as "owner/name". `archive` only takes repos the last
+//! `list` answered with as selectable, refusing others with code -32602.
+//! Errors from the work itself come back as code -32000 with the message the
+//! CLI would print.
+//!
+//! `serve --http` serves the same over REST instead (see `daemon`).
+
+use anyhow::Result;
+use clap::Args;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, Write},
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::{
+    age::Age,
+    candidates, demo, fetch, lock,
+    pipeline::{self, ArchiveJob, ArchiveResult},
+    report::{Report, Stopwatch},
+    screen::{self, Guards},
+    usage, Repo, RepoFilter,
+};
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Speak JSON-RPC over stdin and stdout
+    #[arg(long, required_unless_present = "http", conflicts_with = "http")]
+    pub stdio: bool,
+
+    /// Run as a daemon serving a REST API on this address instead (e.g.
+    /// "0.0.0.0:8080"), for internal portals; requests must send
+    /// `REPO_ARCHIVER_API_TOKEN` as a bearer token
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<String>,
+}
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Missing for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    age: String,
+    #[serde(default)]
+    owners: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RepoParams {
+    repos: Vec<String>,
+}
+
+/// A candidate as `list` answers with it: the repo, and whether it may be
+/// archived and why not.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Listed<'a> {
+    #[serde(flatten)]
+    repo: &'a Repo,
+    selectable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unselectable_reason: Option<String>,
+    /// Selected to begin with in the TUI, by a description rule
+    preselected: bool,
+    /// From the policy endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<&'a str>,
+}
+
+/// One repo's entry in the answer to `plan`.
+#[derive(Serialize)]
+struct RepoPlan {
+    repo: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prediction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Why a request failed, as a JSON-RPC error.
+struct Failure {
+    code: i64,
+    message: String,
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            code: SERVER_ERROR,
+            message: format!("{e:#}"),
+        }
+    }
+}
+
+/// What the server needs from the run's flags and config.
+pub struct Server<'a> {
+    job: &'a ArchiveJob,
+    dry_run: bool,
+    filter: RepoFilter,
+    guards: Guards,
+    /// Owners listed when a `list` request names none
+    owners: Vec<String>,
+    record_usage: bool,
+    /// Size in KB of each repo from the last `list`, for `plan`
+    sizes: HashMap<String, u64>,
+    /// The repos from the last `list` that may be archived; `archive` takes
+    /// no others
+    selectable: HashSet<String>,
+    /// `--lock-repo`, held while `archive` goes
+    lock_repo: Option<String>,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(
+        job: &'a ArchiveJob,
+        dry_run: bool,
+        filter: RepoFilter,
+        guards: Guards,
+        owners: Vec<String>,
+        record_usage: bool,
+        lock_repo: Option<String>,
+    ) -> Self {
+        Self {
+            job,
+            dry_run,
+            filter,
+            guards,
+            owners,
+            record_usage,
+            sizes: HashMap::new(),
+            selectable: HashSet::new(),
+            lock_repo,
+        }
+    }
+
+    /// Answer requests from stdin until it's closed.
+    pub fn run(&mut self) -> Result<()> {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match serde_json::from_str::<Value>(&line) {
+                Ok(value) => value,
+                Err(e) => {
+                    send(&error(&Value::Null, PARSE_ERROR, &e.to_string()))?;
+                    continue;
+                }
+            };
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let request = match serde_json::from_value::<Request>(request) {
+                Ok(request) if request.jsonrpc == "2.0" => request,
+                _ => {
+                    send(&error(&id, INVALID_REQUEST, "Not a JSON-RPC 2.0 request"))?;
+                    continue;
+                }
+            };
+            let result = self.handle(&request.method, request.params);
+            let Some(id) = request.id else { continue };
+            send(&match result {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err(failure) => error(&id, failure.code, &failure.message),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, method: &str, params: Value) -> Result<Value, Failure> {
+        match method {
+            "list" => self.list(&parse(params)?),
+            "plan" => Ok(self.plan(&parse(params)?)),
+            "archive" => self.archive(&parse(params)?),
+            _ => Err(Failure {
+                code: METHOD_NOT_FOUND,
+                message: format!("No method '{method}'"),
+            }),
+        }
+    }
+
+    fn list(&mut self, params: &ListParams) -> Result<Value, Failure> {
+        let age = Age::parse(&params.age).map_err(|e| Failure {
+            code: INVALID_PARAMS,
+            message: format!("{e:#}"),
+        })?;
+        let owners = if params.owners.is_empty() {
+            &self.owners
+        } else {
+            &params.owners
+        };
+        let repos = list(self.job, owners, age, self.filter, &self.guards)?;
+        self.sizes = repos
+            .iter()
+            .map(|r| (r.name_with_owner.clone(), r.disk_usage))
+            .collect();
+        self.selectable = repos
+            .iter()
+            .filter(|r| r.selectable())
+            .map(|r| r.name_with_owner.clone())
+            .collect();
+        Ok(serde_json::to_value(listing(&repos)).map_err(anyhow::Error::from)?)
+    }
+
+    fn plan(&self, params: &RepoParams) -> Value {
+        let kb: u64 = params
+            .repos
+            .iter()
+            .filter_map(|name| self.sizes.get(name))
+            .sum();
+        let predictions: Vec<RepoPlan> = params
+            .repos
+            .iter()
+            .map(|name| {
+                let (prediction, error) = match self.job.dry_run(name) {
+                    Ok(prediction) => (Some(prediction), None),
+                    Err(e) => (None, Some(format!("{e:#}"))),
+                };
+                RepoPlan {
+                    repo: name.clone(),
+                    prediction,
+                    error,
+                }
+            })
+            .collect();
+        json!({
+            "steps": self.job.plan_summary(params.repos.len(), kb * 1024),
+            "repos": predictions,
+        })
+    }
+
+    fn archive(&self, params: &RepoParams) -> Result<Value, Failure> {
+        // Only what `list` offered, so the policy, keep files and
+        // description rules can't be bypassed by naming a repo
+        let refused: Vec<&str> = params
+            .repos
+            .iter()
+            .filter(|name| !self.selectable.contains(*name))
+            .map(String::as_str)
+            .collect();
+        if !refused.is_empty() {
+            return Err(Failure {
+                code: INVALID_PARAMS,
+                message: format!(
+                    "Not among the repos the last list offered for archiving: {}",
+                    refused.join(", ")
+                ),
+            });
+        }
+        if self.dry_run {
+            return Ok(self.plan(params));
+        }
+        let _lock = lock::for_batch(self.lock_repo.as_deref(), false, self.job)?;
+        let names = &params.repos;
+        let (tx, rx) = mpsc::channel();
+        pipeline::start(
+            names.iter().cloned().enumerate().collect(),
+            false,
+            self.job,
+            None,
+            &tx,
+        );
+        drop(tx);
+
+        let mut report = Report::new(self.job.action);
+        let mut stopwatch = Stopwatch::default();
+        let mut archived = 0;
+        for result in rx {
+            let progress = match result {
+                ArchiveResult::Started(idx) => {
+                    stopwatch.start(idx);
+                    json!({"repo": names[idx], "event": "started"})
+                }
+                ArchiveResult::BackingUp(idx) => {
+                    json!({"repo": names[idx], "event": "backingUp"})
+                }
+                ArchiveResult::BackedUp(idx) => json!({"repo": names[idx], "event": "backedUp"}),
+                ArchiveResult::Step(idx) => json!({"repo": names[idx], "event": "step"}),
+                ArchiveResult::Done(idx, state) => {
+                    let progress = json!({"repo": names[idx], "event": "done"});
+                    report.done(&names[idx], state, stopwatch.stop(idx));
+                    archived += 1;
+                    progress
+                }
+                ArchiveResult::AccessRemoved(idx, grant) => {
+                    let progress =
+                        json!({"repo": names[idx], "event": "accessRemoved", "grant": grant});
+                    report.access_removed(&names[idx], grant);
+                    progress
+                }
+                ArchiveResult::Wiki(idx, wiki) => {
+                    let progress = json!({"repo": names[idx], "event": "wiki", "wiki": wiki});
+                    report.wiki(&names[idx], wiki);
+                    progress
+                }
+                ArchiveResult::Failed(idx, err) => {
+                    let progress = json!({"repo": names[idx], "event": "failed", "error": err});
+                    report.failed(&names[idx], &err, stopwatch.stop(idx));
+                    progress
+                }
+                ArchiveResult::Closing(idx, closed, total) => json!({
+                    "repo": names[idx],
+                    "event": "closing",
+                    "closed": closed,
+                    "total": total,
+                }),
+                ArchiveResult::CoolingDown(pause) => {
+                    json!({"event": "coolingDown", "seconds": pause.as_secs()})
+                }
+                ArchiveResult::DryRun(..) => continue,
+            };
+            send(&json!({"jsonrpc": "2.0", "method": "progress", "params": progress}))
+                .map_err(anyhow::Error::from)?;
+        }
+
+        if let Err(e) = report.save() {
+            eprintln!("Warning: failed to save the run report: {e:#}");
+        }
+        if self.record_usage {
+            if let Err(e) = usage::record(&usage::Cleanup::new(self.job.action, archived)) {
+                eprintln!("Warning: failed to record usage stats: {e:#}");
+            }
+        }
+        Ok(serde_json::to_value(&report).map_err(anyhow::Error::from)?)
+    }
+}
+
+/// The candidates among `owners`' repos (the user's own if none), screened
+/// as a normal run screens them, quietly.
+pub fn list(
+    job: &ArchiveJob,
+    owners: &[String],
+    age: Age,
+    filter: RepoFilter,
+    guards: &Guards,
+) -> Result<Vec<Repo>> {
+    let list = if job.demo {
+        demo::repo_list()
+    } else if let Some(remote) = &job.ssh {
+        remote.list()?
+    } else {
+        // Not `wait`, whose progress lines would get in the way of responses
+        let mut fetch = fetch::start(owners, &job.provider);
+        while !fetch.is_done() {
+            fetch.poll(Duration::from_secs(1));
+        }
+        fetch.finish()?
+    };
+    let mut repos = candidates(list, age, filter);
+    screen::screen(&mut repos, age, filter, guards, job, false, None)?;
+    Ok(repos)
+}
+
+/// `repos` as `list` answers with them.
+pub fn listing(repos: &[Repo]) -> Vec<Listed<'_>> {
+    repos
+        .iter()
+        .map(|repo| Listed {
+            repo,
+            selectable: repo.selectable(),
+            unselectable_reason: repo.unselectable_reason(),
+            preselected: repo.preselected(),
+            note: repo.note.as_deref(),
+        })
+        .collect()
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, Failure> {
+    serde_json::from_value(params).map_err(|e| Failure {
+        code: INVALID_PARAMS,
+        message: e.to_string(),
+    })
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn send(message: &Value) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{message}")?;
+    stdout.flush()
+}
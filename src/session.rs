@@ -0,0 +1,273 @@
+//! `--record` and `--replay`: key presses and the screens they led to, saved
+//! to a file that can be attached to a bug report and played back against
+//! the `--demo` repos.
+//!
+//! The file is JSON lines: a header with the settings that shape the session
+//! (never tokens), then each key press and each state the UI went through,
+//! with milliseconds since the start. Replaying presses the same keys at the
+//! same times and reports where the UI went somewhere else.
+
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Settings that change what a session looks like, so a replay can match them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Header {
+    pub age: Option<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    /// Milliseconds since the session started
+    at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+}
+
+enum Input {
+    Live,
+    Record(BufWriter<File>),
+    Replay(VecDeque<(Duration, KeyEvent)>),
+}
+
+/// Where key presses come from, and where they go.
+pub struct Session {
+    input: Input,
+    started: Instant,
+    last_state: Option<String>,
+    /// States the recording went through, when replaying
+    expected: Vec<String>,
+    /// States this session went through, when replaying
+    seen: Vec<String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new(Input::Live)
+    }
+}
+
+impl Session {
+    fn new(input: Input) -> Self {
+        Self {
+            input,
+            started: Instant::now(),
+            last_state: None,
+            expected: Vec::new(),
+            seen: Vec::new(),
+        }
+    }
+
+    /// Record to `path`, starting with `header`.
+    pub fn record(path: &Path, header: &Header) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", serde_json::to_string(header)?)?;
+        writer.flush()?;
+        Ok(Self::new(Input::Record(writer)))
+    }
+
+    /// Load the recording at `path` to replay it.
+    pub fn replay(path: &Path) -> Result<(Self, Header)> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        let mut lines = contents.lines();
+        let header: Header = serde_json::from_str(lines.next().unwrap_or_default())
+            .with_context(|| format!("{} isn't a recording", path.display()))?;
+
+        let mut keys = VecDeque::new();
+        let mut expected = Vec::new();
+        for (number, line) in lines.enumerate() {
+            let entry: Entry = serde_json::from_str(line)
+                .with_context(|| format!("Invalid entry on line {}", number + 2))?;
+            if let Some(key) = &entry.key {
+                let key = parse_key(key)
+                    .with_context(|| format!("Unknown key '{key}' on line {}", number + 2))?;
+                keys.push_back((Duration::from_millis(entry.at), key));
+            }
+            expected.extend(entry.state);
+        }
+
+        let mut session = Self::new(Input::Replay(keys));
+        session.expected = expected;
+        Ok((session, header))
+    }
+
+    /// Recording or replaying; screens that normally start from remembered
+    /// settings start from their defaults instead, so both sides match.
+    pub const fn is_scripted(&self) -> bool {
+        !matches!(self.input, Input::Live)
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Like [`event::poll`]. A replay that has run out of keys hands over to
+    /// the keyboard, so the final screen can be looked at.
+    pub fn poll(&mut self, timeout: Duration) -> Result<bool> {
+        let Input::Replay(keys) = &self.input else {
+            return Ok(event::poll(timeout)?);
+        };
+        let Some(&(at, _)) = keys.front() else {
+            self.input = Input::Live;
+            return Ok(event::poll(timeout)?);
+        };
+        let wait = at.saturating_sub(self.started.elapsed());
+        if wait > timeout {
+            thread::sleep(timeout);
+            return Ok(false);
+        }
+        thread::sleep(wait);
+        Ok(true)
+    }
+
+    /// Like [`event::read`], recording key presses when recording.
+    pub fn read(&mut self) -> Result<Event> {
+        let at = self.elapsed_ms();
+        match &mut self.input {
+            Input::Live => Ok(event::read()?),
+            Input::Record(writer) => {
+                let event = event::read()?;
+                if let Event::Key(key) = event {
+                    if let (KeyEventKind::Press, Some(name)) = (key.kind, key_name(key)) {
+                        write_entry(
+                            writer,
+                            &Entry {
+                                at,
+                                key: Some(name),
+                                state: None,
+                            },
+                        )?;
+                    }
+                }
+                Ok(event)
+            }
+            Input::Replay(keys) => match keys.pop_front() {
+                Some((due, key)) => {
+                    thread::sleep(due.saturating_sub(self.started.elapsed()));
+                    Ok(Event::Key(key))
+                }
+                None => Ok(event::read()?),
+            },
+        }
+    }
+
+    /// Note the state the UI is in, if it changed.
+    pub fn observe(&mut self, state: String) -> Result<()> {
+        if self.last_state.as_ref() == Some(&state) {
+            return Ok(());
+        }
+        let at = self.elapsed_ms();
+        if let Input::Record(writer) = &mut self.input {
+            write_entry(
+                writer,
+                &Entry {
+                    at,
+                    key: None,
+                    state: Some(state.clone()),
+                },
+            )?;
+        }
+        if !self.expected.is_empty() {
+            self.seen.push(state.clone());
+        }
+        self.last_state = Some(state);
+        Ok(())
+    }
+
+    /// Where a replay went somewhere the recording didn't, if it did.
+    pub fn divergence(&self) -> Option<String> {
+        if self.expected.is_empty() {
+            return None;
+        }
+        let same = self
+            .expected
+            .iter()
+            .zip(&self.seen)
+            .take_while(|(expected, seen)| expected == seen)
+            .count();
+        match (self.expected.get(same), self.seen.get(same)) {
+            (None, _) => None,
+            (Some(expected), Some(seen)) => Some(format!(
+                "Replay diverged after {same} states: expected \"{expected}\", got \"{seen}\""
+            )),
+            (Some(expected), None) => Some(format!(
+                "Replay ended after {same} states, before reaching \"{expected}\""
+            )),
+        }
+    }
+}
+
+fn write_entry(writer: &mut BufWriter<File>, entry: &Entry) -> Result<()> {
+    writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+    // Flushed every time, so a crash still leaves a useful recording
+    writer.flush()?;
+    Ok(())
+}
+
+/// e.g. "j", "ctrl+a", "enter"; `None` for keys the UI doesn't use.
+fn key_name(key: KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        _ => return None,
+    };
+    Some(if key.modifiers.contains(KeyModifiers::CONTROL) {
+        format!("ctrl+{name}")
+    } else {
+        name
+    })
+}
+
+fn parse_key(name: &str) -> Option<KeyEvent> {
+    let (modifiers, name) = match name.strip_prefix("ctrl+") {
+        Some(name) => (KeyModifiers::CONTROL, name),
+        None => (KeyModifiers::NONE, name),
+    };
+    let code = match name {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        _ => {
+            let mut chars = name.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            KeyCode::Char(c)
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
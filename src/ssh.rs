@@ -0,0 +1,173 @@
+//! Bare repos on a plain git server reached over SSH, for teams whose legacy
+//! repos don't live on GitHub.
+//!
+//! Repos are listed by when their refs last changed, and "archived" by moving
+//! them under `archive/` in the same root and making them read-only.
+//! Unarchiving moves them back.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{collections::HashSet, process::Command, str::FromStr};
+
+use crate::{cache::RepoList, provider::Provider, Repo};
+
+/// Repos are looked for this many directories below the root.
+const MAX_DEPTH: u32 = 3;
+
+/// `host:/path/to/repos`, where `host` is anything `ssh` accepts
+/// (`user@host`, an alias from `~/.ssh/config`, ...).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SshRemote {
+    host: String,
+    root: String,
+}
+
+impl FromStr for SshRemote {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some((host, root)) if !host.is_empty() && !root.is_empty() => Ok(Self {
+                host: host.to_string(),
+                root: root.to_string(),
+            }),
+            _ => anyhow::bail!("Invalid SSH remote '{s}'. Use e.g. 'git@git.example.com:/srv/git'"),
+        }
+    }
+}
+
+impl TryFrom<String> for SshRemote {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl SshRemote {
+    /// Whose repos these are, for remembering settings per owner.
+    pub fn owner(&self) -> String {
+        format!("{}:{}", self.host, self.root)
+    }
+
+    /// Run `script` in the root directory on the server.
+    fn run(&self, script: &str) -> Result<String> {
+        let output = Command::new("ssh")
+            .args(["-o", "BatchMode=yes"])
+            .arg(&self.host)
+            .arg(format!("cd {} && {script}", quote(&self.root)))
+            .output()
+            .context("Failed to run ssh. Is it installed?")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "ssh {} failed: {}",
+                self.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Every bare repo under the root, except ones already archived. Since a
+    /// bare repo doesn't record when it was created, both `createdAt` and
+    /// `pushedAt` are when its refs last changed.
+    pub fn list(&self) -> Result<RepoList> {
+        let script = format!(
+            "find . -mindepth 1 -maxdepth {MAX_DEPTH} -path ./archive -prune \
+             -o -type d -name '*.git' -print -prune | while read -r dir; do \
+             modified=$(find \"$dir/refs\" \"$dir/packed-refs\" -printf '%T@\\n' 2>/dev/null | sort -n | tail -n 1); \
+             size=$(du -sk \"$dir\" | cut -f1); \
+             printf '%s\\t%s\\t%s\\n' \"${{modified%.*}}\" \"$size\" \"${{dir#./}}\"; \
+             done"
+        );
+        let stdout = self.run(&script)?;
+
+        let repos = stdout
+            .lines()
+            .map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let (Some(modified), Some(size), Some(path)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    anyhow::bail!("Unexpected repo listing from {}: {line}", self.host);
+                };
+                let modified = modified
+                    .parse()
+                    .ok()
+                    .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                    .unwrap_or_default();
+                Ok(Repo {
+                    name: path.trim_end_matches(".git").to_string(),
+                    name_with_owner: path.to_string(),
+                    created_at: modified,
+                    pushed_at: modified,
+                    disk_usage: size.parse().unwrap_or(0),
+                    ..Repo::default()
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(RepoList {
+            fetched_at: Utc::now(),
+            repos,
+            pinned: HashSet::new(),
+        })
+    }
+}
+
+impl Provider for SshRemote {
+    fn list_repos(
+        &self,
+        _owner: Option<&str>,
+        on_page: &mut dyn FnMut(usize),
+    ) -> Result<Vec<Repo>> {
+        let repos = self.list()?.repos;
+        on_page(repos.len());
+        Ok(repos)
+    }
+
+    /// Move `path` under `archive/` and make it read-only.
+    fn archive_repo(&self, path: &str) -> Result<()> {
+        let dest = format!("archive/{path}");
+        self.run(&format!(
+            "test ! -e {dest} && mkdir -p \"$(dirname {dest})\" && mv {src} {dest} && chmod -R a-w {dest}",
+            src = quote(path),
+            dest = quote(&dest),
+        ))
+        .map(|_| ())
+    }
+
+    /// Make `archive/<path>` writable again and move it back to `path`.
+    fn unarchive_repo(&self, path: &str) -> Result<()> {
+        let src = format!("archive/{path}");
+        self.run(&format!(
+            "test -d {src} && test ! -e {dest} && chmod -R u+w {src} && mkdir -p \"$(dirname {dest})\" && mv {src} {dest}",
+            src = quote(&src),
+            dest = quote(path),
+        ))
+        .map(|_| ())
+    }
+
+    /// Check that `path` could be archived, without changing anything.
+    fn dry_run(&self, path: &str) -> Result<String> {
+        let dest = format!("archive/{path}");
+        let stdout = self.run(&format!(
+            "if [ ! -d {src} ]; then echo missing; elif [ -e {dest} ]; then echo taken; fi",
+            src = quote(path),
+            dest = quote(&dest),
+        ))?;
+        match stdout.trim() {
+            "missing" => anyhow::bail!("would fail: {path} no longer exists"),
+            "taken" => anyhow::bail!("would fail: {dest} already exists"),
+            _ => Ok(format!("would move to {dest} and make it read-only")),
+        }
+    }
+}
+
+/// Quote `s` for the remote shell.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,108 @@
+//! Repo counts over time, for the stats view.
+//!
+//! Every run appends a snapshot of how many repos the user owns and how many
+//! of them are archived to the history file, so the stats view can show the
+//! trend across runs.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+use crate::{gh, paths};
+
+/// Repo counts at the time of a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub at: DateTime<Utc>,
+    pub total: u64,
+    pub archived: u64,
+}
+
+impl Snapshot {
+    pub const fn active(&self) -> u64 {
+        self.total.saturating_sub(self.archived)
+    }
+}
+
+/// Count the authenticated user's own (non-fork) repos.
+pub fn fetch(token: Option<&str>) -> Result<Snapshot> {
+    const QUERY: &str = "query { viewer { \
+        total: repositories(ownerAffiliations: OWNER, isFork: false) { totalCount } \
+        archived: repositories(ownerAffiliations: OWNER, isFork: false, isArchived: true) { totalCount } } }";
+
+    let output = gh(token)
+        .args([
+            "api",
+            "graphql",
+            "-f",
+            &format!("query={QUERY}"),
+            "--jq",
+            ".data.viewer | [.total.totalCount, .archived.totalCount] | @tsv",
+        ])
+        .output()
+        .context("Failed to run gh CLI. Is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to fetch repo counts: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (total, archived) = stdout
+        .trim()
+        .split_once('\t')
+        .context("Unexpected repo counts from gh")?;
+    Ok(Snapshot {
+        at: Utc::now(),
+        total: total.parse()?,
+        archived: archived.parse()?,
+    })
+}
+
+/// Snapshots from earlier runs, oldest first. Lines that don't parse are
+/// skipped rather than hiding the whole history.
+pub fn load() -> Vec<Snapshot> {
+    let Some(contents) = paths::history_file().and_then(|path| fs::read_to_string(path).ok())
+    else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Append `snapshot` to the history file.
+pub fn record(snapshot: &Snapshot) -> Result<()> {
+    let path = paths::history_file().context("No home directory to keep history in")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(snapshot)?)?;
+    Ok(())
+}
+
+/// How active repos changed since the first snapshot of the year, e.g.
+/// "You've gone from 240 to 180 active repos this year".
+pub fn headline(history: &[Snapshot]) -> Option<String> {
+    let latest = history.last()?;
+    let first = history.iter().find(|s| s.at.year() == latest.at.year())?;
+    let (from, to) = (first.active(), latest.active());
+    Some(if from == to {
+        format!("Steady at {to} active repos this year")
+    } else {
+        format!("You've gone from {from} to {to} active repos this year")
+    })
+}
@@ -0,0 +1,134 @@
+//! `repo-archiver backup`, `notice`, `close-items` and `archive`: the steps
+//! the interactive flow takes each repo through, one at a time and without
+//! the TUI, for scripts that want only some of them or their own order in
+//! between. `unarchive` (see `unarchive`) is the way back.
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::{items, notice, pipeline::ArchiveJob};
+
+#[derive(Args)]
+pub struct RepoArgs {
+    /// Repos, as "owner/name" (paths under the root with --ssh)
+    #[arg(required = true)]
+    pub repos: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct CloseArgs {
+    #[command(flatten)]
+    repos: RepoArgs,
+
+    /// Comment left on each issue and pull request before closing it; may use
+    /// {name} and {date}
+    #[arg(long)]
+    comment: Option<String>,
+}
+
+/// Back the repos up to `--backup-dir` and/or mirror them to `--migrate-to`.
+pub fn backup(args: &RepoArgs, dry_run: bool, job: &ArchiveJob) -> Result<()> {
+    if !job.has_backup() {
+        anyhow::bail!("backup needs --backup-dir or --migrate-to");
+    }
+    each(&args.repos, job, "backed up", |name| {
+        if !dry_run {
+            let wiki = job.backup(name, &|| {})?;
+            return Ok(wiki.map(|wiki| format!("wiki: {wiki}")).unwrap_or_default());
+        }
+        Ok(format!("would {}", job.backup_plan(name).join(", ")))
+    })
+}
+
+/// Commit the `--readme-notice` text, or the default notice, to the top of
+/// each repo's README.
+pub fn notice(args: &RepoArgs, dry_run: bool, job: &ArchiveJob) -> Result<()> {
+    require_github(job, "notice")?;
+    let template = job.readme_notice.as_deref().unwrap_or(notice::DEFAULT);
+    each(&args.repos, job, "given a notice", |name| {
+        if dry_run {
+            return Ok("would add a README notice".to_string());
+        }
+        notice::add(name, template, job.token.as_deref())?;
+        Ok(String::new())
+    })
+}
+
+/// Close each repo's open issues and pull requests.
+pub fn close_items(args: &CloseArgs, dry_run: bool, job: &ArchiveJob) -> Result<()> {
+    require_github(job, "close-items")?;
+    let token = job.token.as_deref();
+    each(&args.repos.repos, job, "cleared", |name| {
+        let open = if dry_run {
+            items::open(name, token)?
+        } else {
+            items::close_all(name, args.comment.as_deref(), token, &|_, _| {})?
+        };
+        let pulls = open.iter().filter(|item| item.pull_request).count();
+        let closed = format!(
+            "{} issue{}, {pulls} pull request{}",
+            open.len() - pulls,
+            if open.len() - pulls == 1 { "" } else { "s" },
+            if pulls == 1 { "" } else { "s" }
+        );
+        Ok(format!(
+            "{} {closed}",
+            if dry_run { "would close" } else { "closed" }
+        ))
+    })
+}
+
+/// Archive the repos, with whatever pre-archive steps (`--topic`,
+/// `--readme-notice`, ...) and `--action` are given, but no backup.
+pub fn archive(args: &RepoArgs, dry_run: bool, job: &ArchiveJob) -> Result<()> {
+    each(&args.repos, job, job.action.done(), |name| {
+        if dry_run {
+            return job.dry_run(name);
+        }
+        job.archive(
+            name,
+            &|| {},
+            &|grant| println!("  removed {grant} from {name}"),
+            &|_, _| {},
+        )?;
+        Ok(String::new())
+    })
+}
+
+fn require_github(job: &ArchiveJob, command: &str) -> Result<()> {
+    if job.demo || !job.on_github() {
+        anyhow::bail!("{command} only works on GitHub");
+    }
+    Ok(())
+}
+
+/// Run `step` on each repo in turn, printing ✓ or ✗ and what it returned,
+/// and fail at the end if any repo couldn't be `past` (e.g. "unarchived").
+pub fn each(
+    repos: &[String],
+    job: &ArchiveJob,
+    past: &str,
+    step: impl Fn(&str) -> Result<String>,
+) -> Result<()> {
+    if job.ssh.is_none() {
+        if let Some(name) = repos.iter().find(|name| !name.contains('/')) {
+            anyhow::bail!("Repo must be given as owner/name, got '{name}'");
+        }
+    }
+
+    let mut failed = 0;
+    for name in repos {
+        match step(name) {
+            Ok(note) if note.is_empty() => println!("✓ {name}"),
+            Ok(note) => println!("✓ {name}: {note}"),
+            Err(e) => {
+                println!("✗ {name}: {e:#}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} repos couldn't be {past}", repos.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Custom CA bundles and client certificates for networks that intercept TLS.
+//!
+//! Everything that talks to the network is a child process (`gh`, `git`,
+//! `curl`), so the settings are exported as the environment variables those
+//! tools read. curl has none for client certificates, so API requests pass
+//! all three settings as options instead (see `http`). Proxies need no setup: all three already honor `HTTPS_PROXY`
+//! and `NO_PROXY` from the inherited environment.
+
+use anyhow::Result;
+use std::{env, path::Path};
+
+use crate::config::Config;
+
+/// Export the TLS settings from `config` to the environment of every child
+/// process.
+///
+/// Must run before any threads are spawned, since it modifies the process
+/// environment.
+pub fn configure(config: &Config) -> Result<()> {
+    if let Some(ca_bundle) = &config.ca_bundle {
+        check_exists(ca_bundle, "ca_bundle")?;
+        // gh (Go), git and curl each read a different variable
+        env::set_var("SSL_CERT_FILE", ca_bundle);
+        env::set_var("GIT_SSL_CAINFO", ca_bundle);
+        env::set_var("CURL_CA_BUNDLE", ca_bundle);
+    }
+
+    // gh can't present client certificates, so these only reach git (and
+    // the API requests, which pass them to curl themselves)
+    if let Some(cert) = &config.client_cert {
+        check_exists(cert, "client_cert")?;
+        env::set_var("GIT_SSL_CERT", cert);
+    }
+    if let Some(key) = &config.client_key {
+        check_exists(key, "client_key")?;
+        env::set_var("GIT_SSL_KEY", key);
+    }
+    Ok(())
+}
+
+fn check_exists(path: &Path, setting: &str) -> Result<()> {
+    if !path.is_file() {
+        anyhow::bail!("{setting} {} does not exist", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,830 @@
+//! The terminal screens and their event loops: the loading and error
+//! screens, the age picker, and the repo table with its key handling, which
+//! hands confirmed batches to the pipeline.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+use std::{
+    io::{self, IsTerminal},
+    process::Command,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    age::{Age, AGE_PRESETS},
+    app::{App, Column, Mode, RepoStatus},
+    cache::{self, RepoList},
+    dates::DateFormat,
+    draw::ui,
+    error, fetch, last_used,
+    pipeline::{self, Action, ArchiveResult},
+    plan,
+    provider::Provider,
+    report::Wiki,
+    session::Session,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum AgeUnit {
+    Months,
+    Years,
+}
+
+#[derive(Clone, Copy)]
+struct AgePicker {
+    value: u32,
+    unit: AgeUnit,
+    /// The "never pushed" preset is picked; the stepper is ignored
+    never_pushed: bool,
+}
+
+impl AgePicker {
+    fn new() -> Self {
+        Self {
+            value: 2,
+            unit: AgeUnit::Years,
+            never_pushed: false,
+        }
+    }
+
+    /// Start from `age` instead of the default of 2 years.
+    fn from_age(age: Age) -> Self {
+        let mut picker = Self::new();
+        picker.set(age);
+        picker
+    }
+
+    fn set(&mut self, age: Age) {
+        match age {
+            Age::Months(m) => {
+                self.value = m;
+                self.unit = AgeUnit::Months;
+            }
+            Age::Years(y) => {
+                self.value = y;
+                self.unit = AgeUnit::Years;
+            }
+            Age::NeverPushed => {}
+        }
+        self.never_pushed = matches!(age, Age::NeverPushed);
+    }
+
+    fn increment(&mut self) {
+        self.never_pushed = false;
+        let max = match self.unit {
+            AgeUnit::Months => 11,
+            AgeUnit::Years => 10,
+        };
+        if self.value < max {
+            self.value += 1;
+        }
+    }
+
+    fn decrement(&mut self) {
+        self.never_pushed = false;
+        if self.value > 1 {
+            self.value -= 1;
+        }
+    }
+
+    fn toggle_unit(&mut self) {
+        self.never_pushed = false;
+        self.unit = match self.unit {
+            AgeUnit::Months => AgeUnit::Years,
+            AgeUnit::Years => AgeUnit::Months,
+        };
+        // Clamp value to valid range
+        let max = match self.unit {
+            AgeUnit::Months => 11,
+            AgeUnit::Years => 10,
+        };
+        if self.value > max {
+            self.value = max;
+        }
+    }
+
+    fn to_age(self) -> Age {
+        if self.never_pushed {
+            return Age::NeverPushed;
+        }
+        match self.unit {
+            AgeUnit::Months => Age::Months(self.value),
+            AgeUnit::Years => Age::Years(self.value),
+        }
+    }
+
+    const fn unit_str(self) -> &'static str {
+        match self.unit {
+            AgeUnit::Months => "months",
+            AgeUnit::Years => "years",
+        }
+    }
+}
+
+/// Run `f` with the terminal in raw mode on the alternate screen, restoring
+/// it afterwards even if `f` fails.
+pub fn with_terminal<T>(
+    f: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<T>,
+) -> Result<T> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = f(&mut terminal);
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+    result
+}
+
+/// Where the repo list came from, when it wasn't fetched offline to begin with.
+pub enum Fetched {
+    Online(RepoList),
+    /// Fetching failed and the user fell back to the cached list
+    Cached(RepoList),
+    Cancelled,
+}
+
+/// What to do about a failed fetch, picked on the error screen.
+enum Recovery {
+    Retry,
+    Reauthenticate,
+    UseCache,
+    Quit,
+}
+
+/// Fetch the repo lists of `owners` behind the loading screen. If that
+/// fails, the error screen offers retrying, logging in to gh again, and (if
+/// `allow_cache` and there is one) the cached list. Without a terminal, or
+/// unless `interactive`, errors are returned as they are.
+pub fn fetch_repo_list(
+    owners: &[String],
+    provider: &Arc<dyn Provider>,
+    allow_cache: bool,
+    interactive: bool,
+) -> Result<Fetched> {
+    if !interactive || !io::stdout().is_terminal() {
+        let mut fetch = fetch::start(owners, provider);
+        fetch.wait();
+        return fetch.finish().map(Fetched::Online);
+    }
+
+    loop {
+        let mut fetch = fetch::start(owners, provider);
+        if !with_terminal(|terminal| run_loading_screen(terminal, &mut fetch))? {
+            return Ok(Fetched::Cancelled);
+        }
+        let error = match fetch.finish() {
+            Ok(list) => return Ok(Fetched::Online(list)),
+            Err(e) => e,
+        };
+
+        let cached = allow_cache.then(|| cache::load().ok()).flatten();
+        let fetched_at = cached.as_ref().map(|list| list.fetched_at);
+        match with_terminal(|terminal| run_error_screen(terminal, &error, fetched_at))? {
+            Recovery::Retry => {}
+            Recovery::Reauthenticate => {
+                let status = Command::new("gh")
+                    .args(["auth", "login"])
+                    .status()
+                    .context("Failed to run gh CLI. Is it installed?")?;
+                if !status.success() {
+                    eprintln!("gh auth login failed; retrying anyway");
+                }
+            }
+            Recovery::UseCache => {
+                if let Some(list) = cached {
+                    return Ok(Fetched::Cached(list));
+                }
+            }
+            Recovery::Quit => return Err(error),
+        }
+    }
+}
+
+/// Explain why fetching failed and let the user pick a way forward.
+fn run_error_screen<B: Backend>(
+    terminal: &mut Terminal<B>,
+    error: &anyhow::Error,
+    cached_at: Option<DateTime<Utc>>,
+) -> Result<Recovery> {
+    let (cause, hint) =
+        error::Error::find(error).map_or(("Fetching repos failed", ""), error::Error::explain);
+    loop {
+        terminal.draw(|f| {
+            let area = f.area();
+            let mut options = vec![
+                Line::from("r: Retry"),
+                Line::from("a: Log in to gh again (gh auth login)"),
+            ];
+            if let Some(at) = cached_at {
+                options.push(Line::from(format!(
+                    "o: Work offline with the repo list cached {}",
+                    at.format("%Y-%m-%d %H:%M")
+                )));
+            }
+            options.push(Line::from("q/Esc: Quit"));
+
+            let mut lines = vec![
+                Line::from(""),
+                Line::from(cause)
+                    .style(Style::default().fg(Color::Red).bold())
+                    .centered(),
+                Line::from(""),
+                Line::from(format!("{error:#}")).style(Style::default().fg(Color::DarkGray)),
+                Line::from(""),
+            ];
+            if !hint.is_empty() {
+                lines.push(Line::from(hint).style(Style::default().fg(Color::Yellow)));
+                lines.push(Line::from(""));
+            }
+            lines.extend(options);
+
+            // Sized to the contents, with room for the error to wrap
+            let width = 70;
+            let height = lines.len() as u16 + 2 + (format!("{error:#}").len() / 66) as u16;
+            let error_area = Rect {
+                x: area.width.saturating_sub(width) / 2,
+                y: area.height.saturating_sub(height) / 2,
+                width: width.min(area.width),
+                height: height.min(area.height),
+            };
+            let widget = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red))
+                    .title(" Couldn't fetch repos "),
+            );
+            f.render_widget(widget, error_area);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('r') => return Ok(Recovery::Retry),
+                KeyCode::Char('a') => return Ok(Recovery::Reauthenticate),
+                KeyCode::Char('o') if cached_at.is_some() => return Ok(Recovery::UseCache),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Recovery::Quit),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Show progress until every repo list is in. Returns false if the user
+/// cancelled with Esc.
+fn run_loading_screen<B: Backend>(
+    terminal: &mut Terminal<B>,
+    fetch: &mut fetch::Fetch,
+) -> Result<bool> {
+    let mut frame = 0;
+    while !fetch.is_done() {
+        terminal.draw(|f| {
+            let area = f.area();
+            let mut lines = vec![Line::from("")];
+            lines.extend(
+                (0..fetch.owners()).map(|i| Line::from(format!("  {}", fetch.line(i, frame)))),
+            );
+            lines.extend([
+                Line::from(""),
+                Line::from(format!("{}s elapsed", fetch.elapsed().as_secs()))
+                    .style(Style::default().fg(Color::Yellow))
+                    .centered(),
+                Line::from(""),
+                Line::from("Esc: Cancel")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .centered(),
+            ]);
+
+            let width = 60;
+            let height = lines.len() as u16 + 2;
+            let loading_area = Rect {
+                x: area.width.saturating_sub(width) / 2,
+                y: area.height.saturating_sub(height) / 2,
+                width: width.min(area.width),
+                height: height.min(area.height),
+            };
+            let widget = Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Cyan))
+                    .title(" Fetching repos "),
+            );
+            f.render_widget(widget, loading_area);
+        })?;
+
+        fetch.poll(Duration::from_millis(40));
+        if event::poll(Duration::from_millis(40))? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (key.code == KeyCode::Esc || ctrl_c) {
+                    return Ok(false);
+                }
+            }
+        }
+        frame += 1;
+    }
+    Ok(true)
+}
+
+fn cutoff_display(age: Age, date_format: &DateFormat) -> String {
+    match age {
+        Age::NeverPushed => "Pushed to: never since created".to_string(),
+        _ => format!("Created before: {}", date_format.format(age.cutoff_date())),
+    }
+}
+
+pub fn run_age_picker<B: Backend>(
+    terminal: &mut Terminal<B>,
+    date_format: &DateFormat,
+    session: &mut Session,
+) -> Result<Option<Age>> {
+    let mut picker = if session.is_scripted() {
+        AgePicker::new()
+    } else {
+        last_used::load_age().map_or_else(AgePicker::new, AgePicker::from_age)
+    };
+
+    loop {
+        let age = picker.to_age();
+        session.observe(format!("age picker at {}", age.arg()))?;
+
+        terminal.draw(|f| {
+            let area = f.area();
+
+            // Center the picker
+            let picker_width = 68;
+            let picker_height = 11;
+            let picker_area = Rect {
+                x: area.width.saturating_sub(picker_width) / 2,
+                y: area.height.saturating_sub(picker_height) / 2,
+                width: picker_width.min(area.width),
+                height: picker_height.min(area.height),
+            };
+
+            // Build the stepper display
+            let value_style = if picker.never_pushed {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default().fg(Color::Cyan).bold()
+            };
+            let value_display = Line::from(vec![
+                Span::styled("  ◀  ", Style::default().fg(Color::DarkGray)),
+                Span::styled(format!(" {} ", picker.value), value_style),
+                Span::styled(
+                    format!(" {} ", picker.unit_str()),
+                    Style::default().fg(Color::White),
+                ),
+                Span::styled("  ▶  ", Style::default().fg(Color::DarkGray)),
+            ]);
+
+            let mut presets = Vec::new();
+            for (i, preset) in AGE_PRESETS.iter().enumerate() {
+                let style = if preset.arg() == age.arg() {
+                    Style::default().fg(Color::Black).bg(Color::Cyan)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                presets.push(Span::styled(format!("{}:{}", i + 1, preset.arg()), style));
+                presets.push(Span::raw(" "));
+            }
+
+            let lines = vec![
+                Line::from(""),
+                Line::from("Archive repos older than:")
+                    .style(Style::default().fg(Color::White))
+                    .centered(),
+                Line::from(""),
+                value_display.centered(),
+                Line::from(""),
+                Line::from(cutoff_display(age, date_format))
+                    .style(Style::default().fg(Color::Yellow))
+                    .centered(),
+                Line::from(""),
+                Line::from(presets).centered(),
+                Line::from(""),
+                Line::from("↑/↓: Adjust | ←/→: Unit | 1-6: Preset | Enter: Confirm | q: Quit")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .centered(),
+            ];
+
+            let widget = Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Cyan))
+                    .title(" Repo Archiver "),
+            );
+
+            f.render_widget(widget, picker_area);
+        })?;
+
+        if let Event::Key(key) = session.read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                KeyCode::Up | KeyCode::Char('k') => picker.increment(),
+                KeyCode::Down | KeyCode::Char('j') => picker.decrement(),
+                KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l') | KeyCode::Tab => {
+                    picker.toggle_unit();
+                }
+                KeyCode::Char(c @ '1'..='6') => {
+                    picker.set(AGE_PRESETS[c as usize - '1' as usize]);
+                }
+                KeyCode::Enter => return Ok(Some(picker.to_age())),
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+    let (tx, rx) = mpsc::channel::<ArchiveResult>();
+
+    loop {
+        // Update spinner
+        app.tick_spinner();
+        app.poll_checks();
+
+        // Check for archive results
+        let mut backlog = 0;
+        while let Ok(result) = rx.try_recv() {
+            backlog += 1;
+            match result {
+                ArchiveResult::Started(idx) => {
+                    app.statuses[idx] = RepoStatus::Archiving(0);
+                    app.stopwatch.start(idx);
+                    app.cool_down_until = None;
+                }
+                ArchiveResult::BackingUp(idx) => {
+                    app.statuses[idx] = RepoStatus::BackingUp(0);
+                }
+                ArchiveResult::BackedUp(idx) => {
+                    if let RepoStatus::BackingUp(done) = app.statuses[idx] {
+                        app.statuses[idx] = RepoStatus::Archiving(done);
+                    }
+                }
+                ArchiveResult::Step(idx) => match app.statuses[idx] {
+                    RepoStatus::BackingUp(done) => {
+                        app.statuses[idx] = RepoStatus::BackingUp(done + 1);
+                    }
+                    RepoStatus::Archiving(done) | RepoStatus::Closing { done, .. } => {
+                        app.statuses[idx] = RepoStatus::Archiving(done + 1);
+                    }
+                    _ => {}
+                },
+                ArchiveResult::Closing(idx, closed, total) => {
+                    if let RepoStatus::Archiving(done) | RepoStatus::Closing { done, .. } =
+                        app.statuses[idx]
+                    {
+                        app.statuses[idx] = RepoStatus::Closing {
+                            done,
+                            closed,
+                            total,
+                        };
+                    }
+                }
+                ArchiveResult::CoolingDown(pause) => {
+                    app.cool_down_until = Some(Instant::now() + pause);
+                }
+                ArchiveResult::Done(idx, state) => {
+                    app.statuses[idx] = RepoStatus::Done;
+                    app.repos[idx].took = app.stopwatch.stop(idx);
+                    app.report.done(
+                        &app.repos[idx].name_with_owner,
+                        state.clone(),
+                        app.repos[idx].took,
+                    );
+                    if let Some(state) = state {
+                        let repo = &mut app.repos[idx];
+                        repo.pushed_at = state.pushed_at;
+                        let writers = match state.access.as_deref() {
+                            Some(grants) if !grants.is_empty() => {
+                                format!(" · {} can still write", grants.len())
+                            }
+                            _ => String::new(),
+                        };
+                        let wiki = match repo.wiki {
+                            Some(Wiki::Exported) => " · wiki exported",
+                            Some(Wiki::Missing) => " · no wiki",
+                            None => "",
+                        };
+                        repo.note = Some(format!(
+                            "✓ archived, updated {}{writers}{wiki}",
+                            app.date_format.format(state.updated_at.date_naive())
+                        ));
+                        repo.final_state = Some(state);
+                        if !app.columns.contains(&Column::Notes) {
+                            app.columns.push(Column::Notes);
+                        }
+                    }
+                    if let (Some(snapshot), false) = (&mut app.snapshot, app.dry_run) {
+                        snapshot.archived += 1;
+                    }
+                }
+                ArchiveResult::AccessRemoved(idx, grant) => {
+                    app.report
+                        .access_removed(&app.repos[idx].name_with_owner, grant);
+                }
+                ArchiveResult::Wiki(idx, wiki) => {
+                    let repo = &mut app.repos[idx];
+                    app.report.wiki(&repo.name_with_owner, wiki);
+                    repo.wiki = Some(wiki);
+                    repo.note = Some(format!("wiki: {wiki}"));
+                    if !app.columns.contains(&Column::Notes) {
+                        app.columns.push(Column::Notes);
+                    }
+                }
+                ArchiveResult::Failed(idx, err) => {
+                    app.repos[idx].took = app.stopwatch.stop(idx);
+                    app.report
+                        .failed(&app.repos[idx].name_with_owner, &err, app.repos[idx].took);
+                    app.statuses[idx] = RepoStatus::Failed(err);
+                }
+                ArchiveResult::DryRun(idx, prediction) => {
+                    app.repos[idx].took = app.stopwatch.stop(idx);
+                    let (status, line) = match prediction {
+                        Ok(p) => (RepoStatus::Done, format!("✓ {p}")),
+                        Err(e) => (RepoStatus::Failed(e.clone()), format!("✗ {e}")),
+                    };
+                    app.statuses[idx] = status;
+                    app.repos[idx].note = Some(line.clone());
+                    app.dry_run_report
+                        .push(format!("{}: {line}", app.repos[idx].name_with_owner));
+                    if !app.columns.contains(&Column::Notes) {
+                        app.columns.push(Column::Notes);
+                    }
+                }
+            }
+            if app.is_all_done() {
+                // Remove successfully archived repos and reset
+                app.remove_archived_and_reset();
+
+                if app.repos.is_empty() {
+                    app.mode = Mode::Done;
+                } else {
+                    // Go back to selection mode to archive more
+                    app.mode = Mode::Selecting;
+                }
+            }
+        }
+
+        app.diagnostics.backlog(backlog);
+
+        let started = Instant::now();
+        terminal.draw(|f| ui(f, app))?;
+        app.diagnostics.frame(started.elapsed());
+        app.session.observe(app.session_state())?;
+
+        // Poll for events with timeout to keep spinner animating
+        if app.session.poll(app.refresh.poll(app.idle()))? {
+            if let Event::Key(key) = app.session.read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                app.diagnostics.key();
+                if key.code == KeyCode::F(12) {
+                    app.diagnostics.toggle();
+                    continue;
+                }
+
+                match app.mode {
+                    Mode::Selecting => match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Down | KeyCode::Char('j') => app.next(),
+                        KeyCode::Up | KeyCode::Char('k') => app.previous(),
+                        KeyCode::Char(' ') | KeyCode::Tab => app.toggle_selection(),
+                        KeyCode::Char('P') => app.toggle_priority(),
+                        KeyCode::Char('i') => app.open_import(),
+                        KeyCode::Char('e') if app.selected_count() > 0 && app.job.on_github() => {
+                            app.open_describe();
+                        }
+                        KeyCode::Char('s') => app.mode = Mode::Stats,
+                        KeyCode::Char('L') => app.legend = !app.legend,
+                        KeyCode::Enter if app.selected_count() > 0 => {
+                            app.open_confirm_modal();
+                        }
+                        _ => {}
+                    },
+                    Mode::Import => match (&app.import.report, key.code) {
+                        // A failed import goes back to editing the path
+                        (Some(Err(_)), KeyCode::Enter | KeyCode::Esc) => app.import.report = None,
+                        (Some(Ok(_)), KeyCode::Enter | KeyCode::Esc) | (None, KeyCode::Esc) => {
+                            app.mode = Mode::Selecting;
+                        }
+                        (None, KeyCode::Enter) => app.import_sheet(),
+                        (None, KeyCode::Backspace) => {
+                            app.import.path.pop();
+                        }
+                        (None, KeyCode::Char(c)) => app.import.path.push(c),
+                        _ => {}
+                    },
+                    Mode::Describe if app.describe.failed.is_some() => {
+                        if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+                            app.mode = Mode::Selecting;
+                        }
+                    }
+                    Mode::Describe => match key.code {
+                        KeyCode::Esc => app.mode = Mode::Selecting,
+                        KeyCode::Tab => app.describe.edit.op = app.describe.edit.op.next(),
+                        KeyCode::Enter => app.edit_descriptions(),
+                        // Leave the descriptions alone until the repos get archived
+                        KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.job.description_edit = Some(app.describe.edit.clone());
+                            app.mode = Mode::Selecting;
+                        }
+                        KeyCode::Backspace => {
+                            app.describe.edit.template.pop();
+                        }
+                        KeyCode::Char(c) => app.describe.edit.template.push(c),
+                        _ => {}
+                    },
+                    Mode::Stats => match key.code {
+                        KeyCode::Char('s' | 'q') | KeyCode::Esc | KeyCode::Enter => {
+                            app.mode = Mode::Selecting;
+                        }
+                        _ => {}
+                    },
+                    // Deleting takes typing the phrase, so letters go there
+                    // until Tab moves on to the buttons
+                    // The names to type aren't known until the checks are in
+                    Mode::ConfirmModal if app.typing => match key.code {
+                        KeyCode::Char(c) if app.checking.is_none() => {
+                            app.type_delete_confirmation(Some(c));
+                        }
+                        KeyCode::Backspace => app.type_delete_confirmation(None),
+                        KeyCode::Tab | KeyCode::Enter => app.typing = false,
+                        KeyCode::Esc => app.mode = Mode::Selecting,
+                        _ => {}
+                    },
+                    Mode::ConfirmModal => match key.code {
+                        KeyCode::Left | KeyCode::Char('h') => {
+                            app.modal_button = 0;
+                        }
+                        KeyCode::Right | KeyCode::Char('l') => {
+                            app.modal_button = 1;
+                        }
+                        KeyCode::Tab if app.takes_typing() => app.typing = true,
+                        KeyCode::Tab => {
+                            app.modal_button = 1 - app.modal_button;
+                        }
+                        KeyCode::Enter => {
+                            if app.modal_button == 1 && app.can_proceed() {
+                                app.mark_selected_as_pending();
+                                app.mode = Mode::Archiving;
+                                start_archiving(app, &tx);
+                            } else {
+                                app.mode = Mode::Selecting;
+                            }
+                        }
+                        KeyCode::Char('y')
+                            if app.can_proceed() && app.selected_current().is_none() =>
+                        {
+                            app.mark_selected_as_pending();
+                            app.mode = Mode::Archiving;
+                            start_archiving(app, &tx);
+                        }
+                        KeyCode::Char('o') if app.can_proceed() => app.mode = Mode::Order,
+                        KeyCode::Char('n') | KeyCode::Esc => {
+                            app.mode = Mode::Selecting;
+                        }
+                        _ => {}
+                    },
+                    Mode::Order => match key.code {
+                        KeyCode::Char('J') => app.shift_in_order(1),
+                        KeyCode::Char('K') => app.shift_in_order(-1),
+                        KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.shift_in_order(1);
+                        }
+                        KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                            app.shift_in_order(-1);
+                        }
+                        KeyCode::Char('P') => app.toggle_priority_in_order(),
+                        KeyCode::Down | KeyCode::Char('j') => app.order.move_cursor(1),
+                        KeyCode::Up | KeyCode::Char('k') => app.order.move_cursor(-1),
+                        KeyCode::Char('s') => app.sort_order_by_size(),
+                        KeyCode::Enter => {
+                            app.mark_selected_as_pending();
+                            app.mode = Mode::Archiving;
+                            start_archiving(app, &tx);
+                        }
+                        KeyCode::Esc => app.mode = Mode::ConfirmModal,
+                        _ => {}
+                    },
+                    Mode::Archiving => match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Down | KeyCode::Char('j') => app.next(),
+                        KeyCode::Up | KeyCode::Char('k') => app.previous(),
+                        KeyCode::Char('L') => app.legend = !app.legend,
+                        _ => {}
+                    },
+                    Mode::Done => match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => return Ok(()),
+                        _ => {}
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn start_archiving(app: &mut App, tx: &mpsc::Sender<ArchiveResult>) {
+    // In the order set on the order screen, if it was opened, high priority
+    // repos first
+    let repos_to_archive: Vec<(usize, String)> = app
+        .order
+        .repos
+        .iter()
+        .filter(|&&i| app.selected[i])
+        .map(|&i| (i, app.repos[i].name_with_owner.clone()))
+        .collect();
+
+    if app.queues() {
+        // Nothing to wait for: report the repos as handled once they're queued
+        let names: Vec<String> = repos_to_archive
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect();
+        let queued = queue(app, &names).map_err(|e| format!("{e:#}"));
+        for (idx, _) in repos_to_archive {
+            let _ = tx.send(match &queued {
+                Ok(()) => ArchiveResult::Done(idx, None),
+                Err(e) => ArchiveResult::Failed(idx, e.clone()),
+            });
+        }
+        return;
+    }
+
+    app.batch_started = Some(Instant::now());
+    if !app.columns.contains(&Column::Time) {
+        app.columns.push(Column::Time);
+    }
+
+    // Chunked runs check repos off in the plan as they go, so whatever is left
+    // after an interruption can be resumed with `apply-plan`
+    let checkpoint =
+        if app.job.chunking.is_some() && app.job.action == Action::Archive && !app.dry_run {
+            let names: Vec<String> = repos_to_archive
+                .iter()
+                .map(|(_, name)| name.clone())
+                .collect();
+            match plan::default_file().and_then(|path| {
+                plan::Plan::queue(&path, &names, None)?;
+                Ok(path)
+            }) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    for (idx, _) in repos_to_archive {
+                        let _ = tx.send(ArchiveResult::Failed(
+                            idx,
+                            format!("Failed to write checkpoint: {e:#}"),
+                        ));
+                    }
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
+    app.pools = Some(pipeline::start(
+        repos_to_archive,
+        app.dry_run,
+        &app.job,
+        checkpoint,
+        tx,
+    ));
+}
+
+fn queue(app: &App, repos: &[String]) -> Result<()> {
+    let requested_by = if app.require_approval {
+        Some(plan::viewer_login(app.job.github.as_ref())?)
+    } else {
+        None
+    };
+    plan::Plan::queue(&plan::default_file()?, repos, requested_by)
+}
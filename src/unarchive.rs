@@ -0,0 +1,24 @@
+//! `repo-archiver unarchive`: make archived repos writable again, e.g. ones
+//! archived by mistake, through the same provider that archived them.
+
+use anyhow::Result;
+use clap::Args;
+
+use crate::{pipeline::ArchiveJob, steps};
+
+#[derive(Args)]
+pub struct UnarchiveArgs {
+    /// Repos to unarchive, as "owner/name" (paths under the root with --ssh)
+    #[arg(required = true)]
+    repos: Vec<String>,
+}
+
+pub fn run(args: &UnarchiveArgs, dry_run: bool, job: &ArchiveJob) -> Result<()> {
+    steps::each(&args.repos, job, "unarchived", |name| {
+        if dry_run {
+            return Ok("would unarchive".to_string());
+        }
+        job.provider.unarchive_repo(name)?;
+        Ok(String::new())
+    })
+}
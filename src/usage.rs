@@ -0,0 +1,139 @@
+//! Opt-in record of past cleanups (`usage_stats = true` in the config), for
+//! `repo-archiver stats`. It's a local file only; nothing is ever sent
+//! anywhere.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+use crate::{dates::DateFormat, paths, pipeline::Action};
+
+/// Rough time it takes to archive or delete a repo by hand: open it, find
+/// the danger zone in the settings, type the confirmation.
+const MINUTES_PER_REPO: u64 = 2;
+
+/// Cleanups listed as the largest
+const LARGEST: usize = 3;
+
+/// One batch of repos archived, deleted, transferred, made private or tagged.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cleanup {
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub archived: usize,
+    #[serde(default)]
+    pub deleted: usize,
+    #[serde(default)]
+    pub transferred: usize,
+    #[serde(default)]
+    pub privatized: usize,
+    #[serde(default)]
+    pub tagged: usize,
+}
+
+impl Cleanup {
+    pub fn new(action: Action, repos: usize) -> Self {
+        let mut cleanup = Self {
+            at: Utc::now(),
+            archived: 0,
+            deleted: 0,
+            transferred: 0,
+            privatized: 0,
+            tagged: 0,
+        };
+        *match action {
+            Action::Archive => &mut cleanup.archived,
+            Action::Delete => &mut cleanup.deleted,
+            Action::Transfer => &mut cleanup.transferred,
+            Action::Privatize => &mut cleanup.privatized,
+            Action::Tag => &mut cleanup.tagged,
+        } = repos;
+        cleanup
+    }
+
+    const fn repos(&self) -> usize {
+        self.archived + self.deleted + self.transferred + self.privatized + self.tagged
+    }
+}
+
+/// Append `cleanup` to the usage file, if anything was cleaned up at all.
+pub fn record(cleanup: &Cleanup) -> Result<()> {
+    if cleanup.repos() == 0 {
+        return Ok(());
+    }
+    let path = paths::usage_file().context("No home directory to keep usage stats in")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(cleanup)?)?;
+    Ok(())
+}
+
+/// Past cleanups, oldest first; lines that don't parse are skipped.
+fn load() -> Vec<Cleanup> {
+    let Some(contents) = paths::usage_file().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Print totals, the estimated time saved and the largest cleanups.
+pub fn run(enabled: bool, date_format: &DateFormat) {
+    let cleanups = load();
+    if cleanups.is_empty() {
+        if enabled {
+            println!("No cleanups recorded yet.");
+        } else {
+            println!(
+                "Usage stats are off. Set usage_stats = true in the config file to keep a \
+                 local record of your cleanups."
+            );
+        }
+        return;
+    }
+
+    let archived: usize = cleanups.iter().map(|c| c.archived).sum();
+    let deleted: usize = cleanups.iter().map(|c| c.deleted).sum();
+    let transferred: usize = cleanups.iter().map(|c| c.transferred).sum();
+    let privatized: usize = cleanups.iter().map(|c| c.privatized).sum();
+    let tagged: usize = cleanups.iter().map(|c| c.tagged).sum();
+    let minutes = cleanups.iter().map(Cleanup::repos).sum::<usize>() as u64 * MINUTES_PER_REPO;
+    println!(
+        "Since {}: {archived} repos archived, {deleted} deleted, {transferred} transferred, \
+         {privatized} made private and {tagged} tagged in {} cleanups",
+        date_format.format(cleanups[0].at.date_naive()),
+        cleanups.len()
+    );
+    println!(
+        "Time saved: about {}h {}m over doing it by hand",
+        minutes / 60,
+        minutes % 60
+    );
+    if !enabled {
+        println!("(Recording is off now; set usage_stats = true to keep counting.)");
+    }
+
+    let mut largest: Vec<&Cleanup> = cleanups.iter().collect();
+    largest.sort_by_key(|c| std::cmp::Reverse(c.repos()));
+    println!("\nLargest cleanups:");
+    for cleanup in largest.into_iter().take(LARGEST) {
+        println!(
+            "  {}  {} repos",
+            date_format.format(cleanup.at.date_naive()),
+            cleanup.repos()
+        );
+    }
+}
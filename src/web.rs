@@ -0,0 +1,566 @@
+//! `--web`: the candidate table, selection and progress view in a browser
+//! instead of the terminal, e.g. to share a screen with people who don't
+//! live in one.
+//!
+//! Like everything else here the server has no HTTP stack to build in: it's
+//! a small HTTP/1.1 loop over `std::net`, serving the page (`web/`) compiled
+//! into the binary and a JSON API the page polls:
+//!
+//! - `GET /api/repos`: the candidates, each with its status
+//! - `POST /api/archive` `{"repos": ["owner/name", ...]}`: archive those, in
+//!   the background, through the same pipeline as the TUI
+//!
+//! It listens on localhost unless told otherwise. Posts must be sent as
+//! `application/json`, which a page on another site can't do without a CORS
+//! preflight the server never answers, so other sites can't start a batch.
+//! Requests whose `Host` or `Origin` isn't the address listened on are
+//! refused, so a site can't get around that by pointing its own name at
+//! localhost (DNS rebinding) either.
+//!
+//! The loop and the batch bookkeeping are shared with `serve --http` (see
+//! `daemon`).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    lock,
+    pipeline::{self, ArchiveJob, ArchiveResult},
+    preflight,
+    report::{Report, Stopwatch, Wiki},
+    usage, Repo,
+};
+
+/// Used by `--web` without an address.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+const INDEX: &str = include_str!("web/index.html");
+const SCRIPT: &str = include_str!("web/app.js");
+const STYLE: &str = include_str!("web/style.css");
+
+/// Requests bigger than this are refused.
+const MAX_BODY: usize = 1 << 20;
+
+/// How long a connection may take to send its request, so idle ones don't
+/// keep a thread each forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status line, content type and body.
+pub type Response = (&'static str, &'static str, String);
+
+/// A parsed request.
+pub struct Request {
+    pub method: String,
+    /// Without the query string
+    pub path: String,
+    /// Query string parameters, decoded, in order
+    pub query: Vec<(String, String)>,
+    /// Sent as `application/json`
+    pub json: bool,
+    /// Token from an `Authorization: Bearer` header
+    pub bearer: Option<String>,
+    pub host: Option<String>,
+    pub origin: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// Where a repo is at, as the page shows it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// "idle", "pending", "backing up", "archiving", "done" or "failed"
+    state: &'static str,
+    /// How many of the repo's steps are done, while archiving
+    steps_done: usize,
+    /// Open issues and pull requests closed so far, and how many there are
+    #[serde(skip_serializing_if = "Option::is_none")]
+    closing: Option<(usize, usize)>,
+    /// The error, the dry run's prediction or what was removed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// Whether its wiki was backed up, with `--export-wiki`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wiki: Option<Wiki>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Row<'a> {
+    #[serde(flatten)]
+    repo: &'a Repo,
+    selectable: bool,
+    status: &'a Status,
+}
+
+struct State {
+    repos: Vec<Repo>,
+    statuses: Vec<Status>,
+    /// A batch is being archived
+    running: bool,
+}
+
+#[derive(Deserialize)]
+struct ArchiveRequest {
+    repos: Vec<String>,
+}
+
+/// What the server needs besides the candidates.
+#[derive(Clone)]
+pub struct Settings {
+    pub job: ArchiveJob,
+    pub dry_run: bool,
+    pub record_usage: bool,
+    /// `--lock-repo`, held while a batch is going
+    pub lock_repo: Option<String>,
+}
+
+/// Serve the dashboard for `repos` on `addr` until the process is stopped.
+pub fn run(addr: &str, repos: Vec<Repo>, settings: Settings) -> Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to listen on {addr}"))?;
+    println!(
+        "Dashboard for {} repos at http://{}{} (Ctrl+C to stop)",
+        repos.len(),
+        listener.local_addr()?,
+        if settings.dry_run { ", dry run" } else { "" }
+    );
+    let state = Arc::new(Mutex::new(State {
+        statuses: vec![Status::idle(); repos.len()],
+        repos,
+        running: false,
+    }));
+    let settings = Arc::new(settings);
+    listen(&listener, move |request| handle(request, &state, &settings));
+    Ok(())
+}
+
+/// Answer each connection to `listener` on its own thread with `handle`,
+/// until the process is stopped. Requests for another host or from another
+/// origin are refused before they get to `handle`.
+pub fn listen(
+    listener: &TcpListener,
+    handle: impl Fn(&Request) -> Response + Send + Sync + 'static,
+) {
+    let Ok(bound) = listener.local_addr() else {
+        return;
+    };
+    let handle = Arc::new(handle);
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let handle = Arc::clone(&handle);
+        thread::spawn(move || {
+            let result = stream
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(anyhow::Error::from)
+                .and_then(|()| read_request(&stream))
+                .and_then(|request| {
+                    let (status, content_type, body) =
+                        foreign(&request, bound).unwrap_or_else(|| handle(&request));
+                    respond(&stream, status, content_type, &body)
+                });
+            if let Err(e) = result {
+                eprintln!("Warning: request failed: {e:#}");
+            }
+        });
+    }
+}
+
+/// A refusal for a request that isn't addressed to `bound`, the address
+/// listened on, or that comes from a page served from anywhere else.
+fn foreign(request: &Request, bound: SocketAddr) -> Option<Response> {
+    if !request
+        .host
+        .as_deref()
+        .is_some_and(|host| same_host(host, bound))
+    {
+        return Some(refuse("403 Forbidden", "Unexpected Host"));
+    }
+    let foreign_origin = request.origin.as_deref().is_some_and(|origin| {
+        origin
+            .strip_prefix("http://")
+            .is_none_or(|host| !same_host(host, bound))
+    });
+    foreign_origin.then(|| refuse("403 Forbidden", "Unexpected Origin"))
+}
+
+/// Whether `host` (a `Host` header, `name[:port]`) names `bound`, the
+/// address listened on: its port, and its IP unless that's every interface's.
+/// `localhost` stands for the loopback addresses.
+fn same_host(host: &str, bound: SocketAddr) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !port.ends_with(']') => (name, port.parse().ok()),
+        _ => (host, Some(80)),
+    };
+    if port != Some(bound.port()) {
+        return false;
+    }
+    let ip = bound.ip();
+    if ip.is_unspecified() {
+        return true;
+    }
+    match name.trim_start_matches('[').trim_end_matches(']') {
+        "localhost" => ip.is_loopback(),
+        name => name.parse::<IpAddr>().is_ok_and(|name| name == ip),
+    }
+}
+
+/// A JSON response.
+pub fn reply(status: &'static str, body: &Value) -> Response {
+    (status, "application/json", body.to_string())
+}
+
+/// A JSON error response.
+pub fn refuse(status: &'static str, message: &str) -> Response {
+    reply(status, &json!({ "error": message }))
+}
+
+impl Status {
+    const fn idle() -> Self {
+        Self {
+            state: "idle",
+            steps_done: 0,
+            closing: None,
+            note: None,
+            wiki: None,
+        }
+    }
+
+    pub const fn pending() -> Self {
+        Self {
+            state: "pending",
+            steps_done: 0,
+            closing: None,
+            note: None,
+            wiki: None,
+        }
+    }
+}
+
+/// The run report and archive count of a batch, kept along with each repo's
+/// status as the pipeline's results come in.
+pub struct Tally {
+    report: Report,
+    stopwatch: Stopwatch,
+    archived: usize,
+}
+
+impl Tally {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            report: Report::new(settings.job.action),
+            stopwatch: Stopwatch::default(),
+            archived: 0,
+        }
+    }
+
+    pub const fn archived(&self) -> usize {
+        self.archived
+    }
+
+    /// Apply `result` to `statuses`, which like `names` are indexed as the
+    /// batch was given to the pipeline.
+    pub fn record(&mut self, result: ArchiveResult, names: &[String], statuses: &mut [Status]) {
+        match result {
+            ArchiveResult::Started(idx) => {
+                self.stopwatch.start(idx);
+                statuses[idx].state = "archiving";
+            }
+            ArchiveResult::BackingUp(idx) => statuses[idx].state = "backing up",
+            ArchiveResult::BackedUp(idx) => statuses[idx].state = "archiving",
+            ArchiveResult::Step(idx) => {
+                statuses[idx].steps_done += 1;
+                statuses[idx].closing = None;
+            }
+            ArchiveResult::Closing(idx, closed, total) => {
+                statuses[idx].closing = Some((closed, total));
+            }
+            ArchiveResult::AccessRemoved(idx, grant) => {
+                let status = &mut statuses[idx];
+                status.note = Some(match status.note.take() {
+                    Some(note) => format!("{note}, {grant}"),
+                    None => format!("removed {grant}"),
+                });
+                self.report.access_removed(&names[idx], grant);
+            }
+            ArchiveResult::Wiki(idx, wiki) => {
+                statuses[idx].wiki = Some(wiki);
+                self.report.wiki(&names[idx], wiki);
+            }
+            ArchiveResult::Done(idx, final_state) => {
+                statuses[idx].state = "done";
+                statuses[idx].closing = None;
+                self.report
+                    .done(&names[idx], final_state, self.stopwatch.stop(idx));
+                self.archived += 1;
+            }
+            ArchiveResult::Failed(idx, err) => {
+                self.report
+                    .failed(&names[idx], &err, self.stopwatch.stop(idx));
+                statuses[idx].state = "failed";
+                statuses[idx].closing = None;
+                statuses[idx].note = Some(err);
+            }
+            ArchiveResult::DryRun(idx, prediction) => {
+                let status = &mut statuses[idx];
+                (status.state, status.note) = match prediction {
+                    Ok(p) => ("done", Some(p)),
+                    Err(e) => ("failed", Some(e)),
+                };
+            }
+            ArchiveResult::CoolingDown(_) => {}
+        }
+    }
+
+    /// Save the run report and record usage, unless it was a dry run.
+    /// Returns where the report went, if anywhere.
+    pub fn finish(mut self, settings: &Settings) -> Option<PathBuf> {
+        if settings.dry_run {
+            return None;
+        }
+        let path = self.report.save().unwrap_or_else(|e| {
+            eprintln!("Warning: failed to save the run report: {e:#}");
+            None
+        });
+        if settings.record_usage && !settings.job.demo {
+            let cleanup = usage::Cleanup::new(settings.job.action, self.archived);
+            if let Err(e) = usage::record(&cleanup) {
+                eprintln!("Warning: failed to record usage stats: {e:#}");
+            }
+        }
+        path
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (mut length, mut json, mut bearer, mut host, mut origin) = (0, false, None, None, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse().context("Bad Content-Length")?;
+        } else if name.eq_ignore_ascii_case("content-type") {
+            json = value.starts_with("application/json");
+        } else if name.eq_ignore_ascii_case("authorization") {
+            bearer = value.strip_prefix("Bearer ").map(str::to_string);
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            origin = Some(value.to_string());
+        }
+    }
+    if length > MAX_BODY {
+        anyhow::bail!("Request body too large");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key), decode(value))
+            })
+            .collect(),
+        json,
+        bearer,
+        host,
+        origin,
+        body,
+    })
+}
+
+/// Undo a query string's percent-encoding, with `+` for spaces.
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = s
+                    .get(i + 1..i + 3)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                if let Some(byte) = hex {
+                    decoded.push(byte);
+                    i += 2;
+                } else {
+                    decoded.push(b'%');
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn handle(request: &Request, state: &Arc<Mutex<State>>, settings: &Arc<Settings>) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", INDEX.to_string()),
+        ("GET", "/app.js") => ("200 OK", "text/javascript", SCRIPT.to_string()),
+        ("GET", "/style.css") => ("200 OK", "text/css", STYLE.to_string()),
+        ("GET", "/api/repos") => {
+            let state = state.lock().unwrap();
+            let rows: Vec<Row> = state
+                .repos
+                .iter()
+                .zip(&state.statuses)
+                .map(|(repo, status)| Row {
+                    repo,
+                    selectable: repo.selectable(),
+                    status,
+                })
+                .collect();
+            reply(
+                "200 OK",
+                &json!({
+                    "action": settings.job.action,
+                    "dryRun": settings.dry_run,
+                    "running": state.running,
+                    "steps": settings.job.step_count(),
+                    "repos": rows,
+                }),
+            )
+        }
+        ("POST", "/api/archive") if !request.json => {
+            refuse("415 Unsupported Media Type", "Send application/json")
+        }
+        ("POST", "/api/archive") => match serde_json::from_slice::<ArchiveRequest>(&request.body) {
+            Ok(archive) => match start(state, settings, &archive.repos) {
+                Ok(()) => reply("202 Accepted", &json!({})),
+                Err(e) => refuse("409 Conflict", &format!("{e:#}")),
+            },
+            Err(e) => refuse("400 Bad Request", &e.to_string()),
+        },
+        _ => ("404 Not Found", "text/plain", "Not found".to_string()),
+    }
+}
+
+/// Start archiving the named repos in the background, unless a batch is
+/// already going.
+fn start(state: &Arc<Mutex<State>>, settings: &Arc<Settings>, names: &[String]) -> Result<()> {
+    let (batch, sizes) = {
+        let state = state.lock().unwrap();
+        if state.running {
+            anyhow::bail!("A batch is already being archived");
+        }
+        let indices: HashMap<&str, usize> = state
+            .repos
+            .iter()
+            .enumerate()
+            .filter(|(_, repo)| repo.selectable())
+            .map(|(i, repo)| (repo.name_with_owner.as_str(), i))
+            .collect();
+        let batch: Vec<(usize, String)> = names
+            .iter()
+            .filter_map(|name| indices.get(name.as_str()).map(|&i| (i, name.clone())))
+            .collect();
+        let sizes: Vec<u64> = batch
+            .iter()
+            .map(|&(i, _)| state.repos[i].disk_usage)
+            .collect();
+        (batch, sizes)
+    };
+    if batch.is_empty() {
+        anyhow::bail!("None of those repos can be archived");
+    }
+    // Looked up without holding the state, so the page keeps updating
+    let checked: Vec<(&str, u64)> = batch
+        .iter()
+        .zip(&sizes)
+        .map(|((_, name), &size)| (name.as_str(), size))
+        .collect();
+    let refused = preflight::check_batch(&checked, settings.dry_run, &settings.job)?;
+    if !refused.is_empty() {
+        let refused: Vec<String> = refused
+            .iter()
+            .map(|(name, why)| format!("{name} ({why})"))
+            .collect();
+        anyhow::bail!("Can't archive {}", refused.join(", "));
+    }
+
+    // Taken before the state, which would otherwise stay locked while GitHub
+    // is asked for the lock issue
+    let lock = lock::for_batch(
+        settings.lock_repo.as_deref(),
+        settings.dry_run,
+        &settings.job,
+    )?;
+    let names = {
+        let mut state = state.lock().unwrap();
+        if state.running {
+            anyhow::bail!("A batch is already being archived");
+        }
+        for (i, _) in &batch {
+            state.statuses[*i] = Status::pending();
+        }
+        state.running = true;
+        let names: Vec<String> = state
+            .repos
+            .iter()
+            .map(|repo| repo.name_with_owner.clone())
+            .collect();
+        names
+    };
+
+    let (tx, rx) = mpsc::channel();
+    pipeline::start(batch.clone(), settings.dry_run, &settings.job, None, &tx);
+    drop(tx);
+    let state = Arc::clone(state);
+    let settings = Arc::clone(settings);
+    thread::spawn(move || {
+        let mut tally = Tally::new(&settings);
+        for result in rx {
+            tally.record(result, &names, &mut state.lock().unwrap().statuses);
+        }
+        let archived = tally.archived();
+        tally.finish(&settings);
+        drop(lock);
+        state.lock().unwrap().running = false;
+        println!(
+            "Batch of {} repos finished: {archived} {}.",
+            batch.len(),
+            settings.job.action.done()
+        );
+    });
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// Polls /api/repos and redraws the table; the checkboxes are the only state
+// kept here.
+const selected = new Set();
+let latest = null;
+
+function capitalized(word) {
+  return word.charAt(0).toUpperCase() + word.slice(1);
+}
+
+function size(kb) {
+  if (kb < 1024) return `${kb} KB`;
+  if (kb < 1024 * 1024) return `${(kb / 1024).toFixed(1)} MB`;
+  return `${(kb / 1024 / 1024).toFixed(1)} GB`;
+}
+
+function status(repo, steps) {
+  const s = repo.status;
+  switch (s.state) {
+    case "idle":
+      return "";
+    case "backing up":
+      return `backing up ${s.stepsDone}/${steps}`;
+    case "archiving":
+      return s.closing ? `closing ${s.closing[0]}/${s.closing[1]}` : `${s.stepsDone}/${steps}`;
+    default: {
+      const text = s.note ? `${s.state}: ${s.note}` : s.state;
+      return s.wiki ? `${text} (wiki: ${s.wiki})` : text;
+    }
+  }
+}
+
+function cell(row, text, className) {
+  const td = row.insertCell();
+  td.textContent = text;
+  if (className) td.className = className;
+  return td;
+}
+
+function render() {
+  const { repos, dryRun, running, steps, action } = latest;
+  document.getElementById("mode").textContent = dryRun ? "DRY RUN" : "";
+  document.getElementById("summary").textContent = running
+    ? "Working..."
+    : `${repos.length} candidates, ${selected.size} selected`;
+  const go = document.getElementById("go");
+  go.textContent = capitalized(action);
+  go.disabled = running || selected.size === 0;
+
+  const body = document.getElementById("repos");
+  body.replaceChildren();
+  for (const repo of repos) {
+    const row = body.insertRow();
+    if (!repo.selectable) row.className = "disabled";
+    const box = document.createElement("input");
+    box.type = "checkbox";
+    box.checked = selected.has(repo.nameWithOwner);
+    box.disabled = running || !repo.selectable || repo.status.state !== "idle";
+    box.onchange = () => {
+      if (box.checked) selected.add(repo.nameWithOwner);
+      else selected.delete(repo.nameWithOwner);
+      render();
+    };
+    row.insertCell().append(box);
+    cell(row, repo.nameWithOwner);
+    cell(row, repo.createdAt.slice(0, 10));
+    cell(row, repo.pushedAt.slice(0, 10));
+    cell(row, size(repo.diskUsage), "size");
+    cell(row, repo.description || "");
+    cell(row, status(repo, steps), repo.status.state.replace(" ", "-"));
+  }
+}
+
+async function refresh() {
+  try {
+    const response = await fetch("/api/repos");
+    latest = await response.json();
+    render();
+  } catch (e) {
+    // The server was stopped; keep showing the last state
+  }
+}
+
+document.getElementById("all").onchange = (event) => {
+  for (const repo of latest.repos) {
+    if (!repo.selectable || repo.status.state !== "idle") continue;
+    if (event.target.checked) selected.add(repo.nameWithOwner);
+    else selected.delete(repo.nameWithOwner);
+  }
+  render();
+};
+
+document.getElementById("go").onclick = async () => {
+  const names = [...selected];
+  const verb = capitalized(latest.action);
+  const dry = latest.dryRun ? " (dry run)" : "";
+  if (!confirm(`${verb} ${names.length} repos${dry}?`)) return;
+  const response = await fetch("/api/archive", {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ repos: names }),
+  });
+  const error = document.getElementById("error");
+  error.hidden = response.ok;
+  if (!response.ok) error.textContent = (await response.json()).error;
+  selected.clear();
+  document.getElementById("all").checked = false;
+  refresh();
+};
+
+refresh();
+setInterval(refresh, 1000);
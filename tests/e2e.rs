@@ -0,0 +1,2587 @@
+//! End-to-end runs of the non-interactive commands against a mock GitHub, so
+//! changes to how repo-archiver talks to GitHub are checked without real
+//! credentials.
+
+#![cfg(unix)]
+
+mod support;
+
+use base64::Engine as _;
+use serde_json::json;
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Child, Command, Output, Stdio},
+};
+use support::{repo, stderr, stdout, Sandbox};
+use wiremock::{
+    matchers::{body_string_contains, header, method, path, path_regex},
+    Mock, ResponseTemplate,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_archives_queued_repos() {
+    let sandbox = Sandbox::new("apply-plan").await;
+    sandbox.allow_archive("octo/old").await;
+    sandbox.allow_archive("octo/older").await;
+    let plan = sandbox.queue(&["octo/old", "octo/older"]);
+
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert!(stdout(&output).contains("✓ octo/older\n"));
+    let mut requests = sandbox.requests().await;
+    requests.sort();
+    // The permission check, then the archive and the lookup after it for each
+    assert_eq!(
+        requests,
+        [
+            "PATCH /repos/octo/old",
+            "PATCH /repos/octo/older",
+            "POST /graphql",
+            "POST /graphql",
+            "POST /graphql"
+        ]
+    );
+    assert!(!plan.exists(), "an applied plan is removed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_reports_the_final_state() {
+    let sandbox = Sandbox::new("apply-plan-report").await;
+    sandbox.allow_archive("octo/old").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Report: "));
+    assert!(stdout(&output).contains("Timing: min "));
+    let mut reports = sandbox.reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0]["action"], "archive");
+    let seconds = reports[0]["repos"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("seconds")
+        .unwrap();
+    assert!(seconds.as_f64().is_some(), "{seconds}");
+    assert_eq!(reports[0]["timing"]["max"], seconds);
+    assert_eq!(
+        reports[0]["repos"],
+        json!([{
+            "nameWithOwner": "octo/old",
+            "finalState": {
+                "archived": true,
+                "pushedAt": "2019-05-01T00:00:00Z",
+                "updatedAt": "2024-06-01T12:00:00Z",
+            },
+        }])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_fails_repos_github_doesnt_show_as_archived() {
+    let sandbox = Sandbox::new("apply-plan-unconfirmed").await;
+    sandbox
+        .respond_to_archive("octo/old", ResponseTemplate::new(200))
+        .await;
+    sandbox.show_repo("octo/old", false).await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("✗ octo/old: GitHub still shows the repo as not archived"));
+    assert!(fs::read_to_string(&plan).unwrap().contains("octo/old"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_keeps_failed_repos_queued() {
+    let sandbox = Sandbox::new("apply-plan-failure").await;
+    sandbox.allow_archive("octo/old").await;
+    sandbox
+        .respond_to_archive(
+            "octo/locked",
+            ResponseTemplate::new(403)
+                .set_body_json(json!({"message": "Must have admin rights to Repository."})),
+        )
+        .await;
+    let plan = sandbox.queue(&["octo/old", "octo/locked"]);
+
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert!(stdout(&output).contains("✗ octo/locked: "));
+    assert!(stderr(&output).contains("1 repos failed and stay queued"));
+    let plan = fs::read_to_string(&plan).unwrap();
+    assert!(plan.contains("octo/locked"));
+    assert!(!plan.contains("octo/old\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_skips_repos_without_admin_permission() {
+    let sandbox = Sandbox::new("apply-plan-permission").await;
+    sandbox
+        .graphql(
+            "{ viewerPermission }",
+            json!({"r0": {"viewerPermission": "ADMIN"}, "r1": {"viewerPermission": "WRITE"}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    let plan = sandbox.queue(&["octo/old", "octo/shared"]);
+
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("- octo/shared: needs admin permission, you have write\n"),
+        "{out}"
+    );
+    assert!(out.contains("✓ octo/old\n"), "{out}");
+    assert!(!sandbox
+        .requests()
+        .await
+        .contains(&"PATCH /repos/octo/shared".to_string()));
+    assert!(fs::read_to_string(&plan).unwrap().contains("octo/shared"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_refuses_backups_that_dont_fit_on_disk() {
+    let sandbox = Sandbox::new("apply-plan-disk").await;
+    let plan = sandbox.path("plan.json");
+    // A petabyte
+    fs::write(
+        &plan,
+        json!({"repos": [{
+            "nameWithOwner": "octo/huge",
+            "queuedAt": "2024-01-01T00:00:00Z",
+            "diskUsage": 1_u64 << 40,
+        }]})
+        .to_string(),
+    )
+    .unwrap();
+    let backups = sandbox.path("backups");
+
+    let output = sandbox.run(&[
+        "--backup-dir",
+        backups.to_str().unwrap(),
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("Not enough disk space for backups"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox
+        .requests()
+        .await
+        .iter()
+        .any(|request| request.starts_with("PATCH")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn apply_plan_adds_the_topic_before_archiving() {
+    let sandbox = Sandbox::new("apply-plan-topic").await;
+    Mock::given(path("/repos/octo/old/topics"))
+        .and(body_string_contains("archived-2024"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&sandbox.github)
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&[
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+        "--topic",
+        "archived-2024",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sandbox.requests().await,
+        [
+            "POST /graphql",
+            "POST /repos/octo/old/topics",
+            "PATCH /repos/octo/old",
+            "POST /graphql"
+        ]
+    );
+}
+
+/// Answer `gh api user` as `login`, and `bob`'s SSH signing keys with
+/// `keys`, replacing whatever was answered before.
+async fn log_in_as(sandbox: &Sandbox, login: &str, keys: serde_json::Value) {
+    sandbox.github.reset().await;
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "login": login })))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/users/bob/ssh_signing_keys"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(keys))
+        .mount(&sandbox.github)
+        .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn approved_plans_need_a_signature_by_someone_else_than_the_applier() {
+    let sandbox = Sandbox::new("approve").await;
+    let plan = sandbox.path("plan.json");
+    let queued = json!({
+        "repos": [{"nameWithOwner": "octo/old", "queuedAt": "2024-01-01T00:00:00Z"}],
+        "review": {"requestedBy": "alice", "approval": null},
+    });
+    fs::write(&plan, queued.to_string()).unwrap();
+    let key = sandbox.path("id_ed25519");
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", "bob", "-f"])
+        .arg(&key)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let public_key = fs::read_to_string(key.with_extension("pub")).unwrap();
+    let keys = json!([{ "key": public_key.trim() }]);
+    let apply = |sandbox: &Sandbox| sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap()]);
+
+    log_in_as(&sandbox, "bob", keys.clone()).await;
+    let output = sandbox.run(&[
+        "approve",
+        plan.to_str().unwrap(),
+        "--key",
+        key.to_str().unwrap(),
+        "--yes",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let approved = fs::read_to_string(&plan).unwrap();
+
+    let output = apply(&sandbox);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("bob approved this plan, so someone else has to apply it"));
+
+    // A signature by a key bob doesn't have on GitHub doesn't count
+    log_in_as(&sandbox, "alice", json!([])).await;
+    let output = apply(&sandbox);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("bob has no SSH signing keys on GitHub"));
+    let output = sandbox.run(&["apply-plan", "--plan", plan.to_str().unwrap(), "--script"]);
+    assert!(!output.status.success(), "the script archives too");
+
+    // Nor does taking the review out of the plan skip the approval
+    fs::write(&plan, json!({ "repos": queued["repos"] }).to_string()).unwrap();
+    let output = sandbox.run(&[
+        "--require-approval",
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("needs approval but has no review record"));
+
+    fs::write(&plan, approved).unwrap();
+    log_in_as(&sandbox, "alice", keys).await;
+    sandbox.allow_archive("octo/old").await;
+    let output = apply(&sandbox);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disable_features_turns_off_issues_wiki_and_projects_before_archiving() {
+    let sandbox = Sandbox::new("disable-features").await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/old"))
+        .and(body_string_contains(r#""has_issues":"false""#))
+        .and(body_string_contains(r#""has_wiki":"false""#))
+        .and(body_string_contains(r#""has_projects":"false""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&[
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+        "--disable-features",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sandbox.requests().await,
+        [
+            "POST /graphql",
+            "PATCH /repos/octo/old",
+            "PATCH /repos/octo/old",
+            "POST /graphql"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disable_features_refuses_to_hide_an_announcement_issue() {
+    let sandbox = Sandbox::new("disable-features-announce").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&[
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+        "--disable-features",
+        "--announce",
+        "Moved to octo/new.",
+    ]);
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--announce-as discussion"));
+    assert!(sandbox.requests().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lock_repo_holds_an_issue_for_the_length_of_the_run() {
+    let sandbox = Sandbox::new("lock").await;
+    sandbox.allow_archive("octo/old").await;
+    let ours = json!([{
+        "number": 8,
+        "title": "repo-archiver run in progress",
+        "user": {"login": "me"},
+        "created_at": "2024-06-01T12:00:00Z",
+    }]);
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .up_to_n_times(1)
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(ours))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"number": 8})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/ops/issues/8"))
+        .and(body_string_contains(r#""state":"closed""#))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&[
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+        "--lock-repo",
+        "octo/ops",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = sandbox.requests().await;
+    let at = |request: &str| requests.iter().position(|r| r == request).unwrap();
+    assert!(at("POST /repos/octo/ops/issues") < at("PATCH /repos/octo/old"));
+    assert!(at("PATCH /repos/octo/old") < at("PATCH /repos/octo/ops/issues/8"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lock_repo_refuses_to_run_while_someone_else_holds_the_lock() {
+    let sandbox = Sandbox::new("lock-held").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "number": 3,
+                "title": "Flaky deploys",
+                "user": {"login": "alice"},
+                "created_at": "2024-05-01T12:00:00Z",
+            },
+            {
+                "number": 7,
+                "title": "repo-archiver run in progress",
+                "user": {"login": "alice"},
+                "created_at": "2024-06-01T12:00:00Z",
+            },
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox.run(&[
+        "apply-plan",
+        "--plan",
+        plan.to_str().unwrap(),
+        "--lock-repo",
+        "octo/ops",
+    ]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("alice has been running repo-archiver since 2024-06-01"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("(octo/ops#7)"));
+    assert_eq!(sandbox.requests().await, ["GET /repos/octo/ops/issues"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn assign_splits_the_plan_between_teammates_and_tracks_their_progress() {
+    let sandbox = Sandbox::new("assign").await;
+    let plan = sandbox.queue(&["octo/old", "octo/older", "octo/oldest"]);
+    let out = sandbox.path("shares");
+
+    let output = sandbox
+        .command()
+        .args(["assign", "--to", "alice,bob", "--plan"])
+        .arg(&plan)
+        .arg("--out")
+        .arg(&out)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!plan.exists());
+    let share = |login: &str| -> serde_json::Value {
+        serde_json::from_slice(&fs::read(out.join(format!("{login}.json"))).unwrap()).unwrap()
+    };
+    let names = |share: &serde_json::Value| -> Vec<String> {
+        share["repos"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["nameWithOwner"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(share("alice")["assignee"], "alice");
+    assert_eq!(names(&share("alice")), ["octo/old", "octo/older"]);
+    assert_eq!(names(&share("bob")), ["octo/oldest"]);
+
+    sandbox
+        .graphql(
+            "isArchived",
+            json!({
+                "r0": {"isArchived": true},
+                "r1": {"isArchived": false},
+                "r2": {"isArchived": true},
+            }),
+        )
+        .await;
+    let output = sandbox.run(&["assignments"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "  alice: 1 of 2 archived\n    octo/older\n✓ bob: all 1 archived\n1 of 2 assignments done.\n"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn analyze_lists_source_repos() {
+    let sandbox = Sandbox::new("analyze").await;
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            repo("octo/someone-elses", "2019-05-01T00:00:00Z", true),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "repository(owner: \\\"octo\\\", name: \\\"old\\\")",
+            json!({"r0": {
+                "pushedAt": "2019-05-01T00:00:00Z",
+                "stargazerCount": 3,
+                "forkCount": 0,
+                "hasWikiEnabled": false,
+            }}),
+        )
+        .await;
+
+    let output = sandbox.run(&["analyze"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("octo/old - suggest: "));
+    assert!(
+        !stdout(&output).contains("someone-elses"),
+        "forks aren't analyzed"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn analyze_fetches_every_page_of_the_repo_list() {
+    let sandbox = Sandbox::new("analyze-pages").await;
+    sandbox
+        .list_page(
+            None,
+            Some("page2"),
+            &[repo("octo/first", "2019-05-01T00:00:00Z", false)],
+        )
+        .await;
+    sandbox
+        .list_page(
+            Some("page2"),
+            None,
+            &[repo("octo/second", "2019-05-01T00:00:00Z", false)],
+        )
+        .await;
+    sandbox
+        .graphql(
+            "r1: repository(",
+            json!({
+                "r0": {"pushedAt": "2019-05-01T00:00:00Z", "stargazerCount": 0, "forkCount": 0},
+                "r1": {"pushedAt": "2019-05-01T00:00:00Z", "stargazerCount": 0, "forkCount": 0},
+            }),
+        )
+        .await;
+
+    let output = sandbox.run(&["analyze"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("octo/first - suggest: "));
+    assert!(stdout(&output).contains("octo/second - suggest: "));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_login_exits_with_the_auth_code() {
+    let sandbox = Sandbox::new("auth").await;
+    Mock::given(path("/graphql"))
+        .respond_with(
+            ResponseTemplate::new(401).set_body_json(json!({"message": "Bad credentials"})),
+        )
+        .mount(&sandbox.github)
+        .await;
+
+    let output = sandbox.run(&["analyze"]);
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Bad credentials"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn not_found_exits_with_its_code() {
+    let sandbox = Sandbox::new("not-found").await;
+    Mock::given(path("/graphql"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({"message": "Not Found"})))
+        .mount(&sandbox.github)
+        .await;
+
+    let output = sandbox.run(&["analyze"]);
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn org_that_is_a_user_is_rejected() {
+    let sandbox = Sandbox::new("org-is-user").await;
+    Mock::given(path("/graphql"))
+        .and(body_string_contains("organization(login: $login)"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": {"organization": null},
+            "errors": [{
+                "type": "NOT_FOUND",
+                "message": "Could not resolve to an Organization with the login of 'octocat'.",
+            }],
+        })))
+        .mount(&sandbox.github)
+        .await;
+
+    let output = sandbox.run(&["--org", "octocat", "--age", "2y"]);
+
+    assert_eq!(output.status.code(), Some(5), "{}", stderr(&output));
+    assert!(stderr(&output).contains("'octocat' is not an organization"));
+    assert_eq!(sandbox.requests().await, ["POST /graphql"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_backend_archives_without_gh() {
+    let sandbox = Sandbox::new("api-backend").await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/old"))
+        .and(header("Authorization", "Bearer secret"))
+        .and(body_string_contains(r#""archived":true"#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"archived": true})))
+        .mount(&sandbox.github)
+        .await;
+    sandbox.show_repo("octo/old", true).await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--backend", "api", "apply-plan", "--plan"])
+        .arg(&plan)
+        // Without the gh shim; the bearer token shows the request came from curl
+        .env("PATH", env::var("PATH").unwrap_or_default())
+        .env("GITHUB_TOKEN", "secret")
+        .env("REPO_ARCHIVER_API_URL", sandbox.github.uri())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["finalState"]["archived"],
+        true
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_backend_dry_runs_without_gh() {
+    let sandbox = Sandbox::new("api-backend-dry-run").await;
+    sandbox.check_repo("octo/old", false, "PUBLIC").await;
+    sandbox.check_repo("octo/taken", true, "PUBLIC").await;
+    let plan = sandbox.queue(&["octo/old", "octo/taken"]);
+
+    let output = sandbox
+        .command()
+        .args(["--backend", "api", "--dry-run", "apply-plan", "--plan"])
+        .arg(&plan)
+        .env("PATH", env::var("PATH").unwrap_or_default())
+        .env("GITHUB_TOKEN", "secret")
+        .env("REPO_ARCHIVER_API_URL", sandbox.github.uri())
+        .output()
+        .unwrap();
+
+    assert!(
+        stdout(&output).contains("✓ octo/old: would archive\n"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stdout(&output).contains("✗ octo/taken: would fail: already archived\n"));
+    let from_curl = sandbox
+        .github
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .all(|request| request.headers.get("Authorization").is_some());
+    assert!(from_curl, "every lookup went through the API");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fallback_backend_retries_a_failed_api_call_through_gh() {
+    let sandbox = Sandbox::new("fallback-backend").await;
+    // Only requests from curl carry the token; gh's go through the shim
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/old"))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(502).set_body_json(json!({"message": "Server Error"})))
+        .mount(&sandbox.github)
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--backend", "api-then-gh", "apply-plan", "--plan"])
+        .arg(&plan)
+        .env("GITHUB_TOKEN", "secret")
+        .env("REPO_ARCHIVER_API_URL", sandbox.github.uri())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    let patches: Vec<_> = sandbox
+        .requests()
+        .await
+        .into_iter()
+        .filter(|r| r.starts_with("PATCH"))
+        .collect();
+    assert_eq!(patches, ["PATCH /repos/octo/old", "PATCH /repos/octo/old"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unarchive_makes_repos_writable_again() {
+    let sandbox = Sandbox::new("unarchive").await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/old"))
+        .and(body_string_contains(r#""archived":false"#))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&sandbox.github)
+        .await;
+
+    let output = sandbox.run(&["unarchive", "octo/old", "octo/gone"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert!(stdout(&output).contains("✗ octo/gone: "));
+    assert!(stderr(&output).contains("1 of 2 repos couldn't be unarchived"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gitlab_provider_archives_projects() {
+    let sandbox = Sandbox::new("gitlab").await;
+    Mock::given(method("POST"))
+        .and(path("/api/v4/projects/group%2Fold/archive"))
+        .and(header("Authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"archived": true})))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v4/projects/group%2Fold"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "path": "old",
+            "path_with_namespace": "group/old",
+            "created_at": "2015-01-01T00:00:00Z",
+            "last_activity_at": "2019-05-01T00:00:00Z",
+            "updated_at": "2024-06-01T12:00:00Z",
+            "archived": true,
+        })))
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["group/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--provider", "gitlab", "apply-plan", "--plan"])
+        .arg(&plan)
+        .env("GITLAB_TOKEN", "secret")
+        .env(
+            "REPO_ARCHIVER_GITLAB_URL",
+            format!("{}/", sandbox.github.uri()),
+        )
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ group/old\n"));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["finalState"]["updatedAt"],
+        "2024-06-01T12:00:00Z"
+    );
+}
+
+#[tokio::test]
+async fn gitea_provider_archives_repos() {
+    let sandbox = Sandbox::new("gitea").await;
+    Mock::given(method("PATCH"))
+        .and(path("/api/v1/repos/someone/old"))
+        .and(header("Authorization", "Bearer secret"))
+        .and(body_string_contains(r#""archived":true"#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"archived": true})))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/repos/someone/old"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "name": "old",
+            "full_name": "someone/old",
+            "created_at": "2015-01-01T00:00:00Z",
+            "updated_at": "2024-06-01T12:00:00Z",
+            "archived": true,
+        })))
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["someone/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--provider", "gitea", "--host"])
+        .arg(sandbox.github.uri())
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .env("GITEA_TOKEN", "secret")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ someone/old\n"));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["finalState"]["updatedAt"],
+        "2024-06-01T12:00:00Z"
+    );
+}
+
+#[tokio::test]
+async fn bitbucket_provider_moves_repos_into_the_archive_project() {
+    let sandbox = Sandbox::new("bitbucket").await;
+    Mock::given(method("PUT"))
+        .and(path("/2.0/repositories/workspace/old"))
+        .and(header("Authorization", "Bearer secret"))
+        .and(body_string_contains(r#""key":"ARCHIVE""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/2.0/repositories/workspace/old"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "slug": "old",
+            "full_name": "workspace/old",
+            "created_on": "2015-01-01T00:00:00Z",
+            "updated_on": "2024-06-01T12:00:00Z",
+            "project": {"key": "ARCHIVE"},
+        })))
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["workspace/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--provider", "bitbucket", "--host"])
+        .arg(sandbox.github.uri())
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .env("BITBUCKET_TOKEN", "secret")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ workspace/old\n"));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["finalState"]["archived"],
+        true
+    );
+}
+
+#[tokio::test]
+async fn old_gh_is_caught_before_anything_is_archived() {
+    let sandbox = Sandbox::new("old-gh").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .env("MOCK_GH_VERSION", "2.10.0")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains(
+            "gh 2.10.0 is too old for this run:\n  ✗ archive repos: `gh repo archive --yes` needs gh 2.19.0 or newer"
+        ),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("Upgrade gh: "));
+    assert!(sandbox.reports().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backups_export_team_access_and_org_settings_once_per_org() {
+    let sandbox = Sandbox::new("export-org-settings").await;
+    for name in ["old", "older"] {
+        sandbox.allow_archive(&format!("octo/{name}")).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/octo/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "nameWithOwner": format!("octo/{name}"),
+                "description": "",
+                "homepageUrl": "",
+                "repositoryTopics": null,
+                "visibility": "PUBLIC",
+                "defaultBranchRef": {"name": "main"},
+                "hasIssuesEnabled": true,
+                "hasWikiEnabled": false,
+                "hasProjectsEnabled": false,
+                "openGraphImageUrl": "",
+                "usesCustomOpenGraphImage": false,
+            })))
+            .mount(&sandbox.github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/octo/{name}/teams")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {"slug": "core", "name": "Core", "permission": "admin", "id": 1},
+            ])))
+            .mount(&sandbox.github)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path("/orgs/octo"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "login": "octo",
+            "default_repository_permission": "read",
+            "members_can_create_repositories": false,
+        })))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old", "octo/older"]);
+    let backups = sandbox.path("backups");
+    sandbox.git_repo("octo/old");
+    sandbox.git_repo("octo/older");
+
+    let output = sandbox
+        .command()
+        .arg("--backup-dir")
+        .arg(&backups)
+        .args(["--export-org-settings", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let read = |file: &str| -> serde_json::Value {
+        serde_json::from_slice(&fs::read(backups.join(file)).unwrap()).unwrap()
+    };
+    assert_eq!(
+        read("octo/older/access.json")["teams"],
+        json!([{"slug": "core", "name": "Core", "permission": "admin"}])
+    );
+    let settings = read("octo/org-settings.json");
+    assert_eq!(settings["defaultRepositoryPermission"], "read");
+    assert_eq!(settings["membersCanCreateRepositories"], false);
+    assert_eq!(settings["webCommitSignoffRequired"], json!(null));
+    assert!(backups.join("octo/old/mirror.git/HEAD").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backups_as_bundles_and_skips_archiving_repos_that_fail_to_back_up() {
+    let sandbox = Sandbox::new("backup-bundle").await;
+    for name in ["old", "gone"] {
+        sandbox.allow_archive(&format!("octo/{name}")).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/octo/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "nameWithOwner": format!("octo/{name}"),
+                "description": "",
+                "homepageUrl": "",
+                "repositoryTopics": null,
+                "visibility": "PUBLIC",
+                "defaultBranchRef": {"name": "main"},
+                "hasIssuesEnabled": true,
+                "hasWikiEnabled": false,
+                "hasProjectsEnabled": false,
+                "openGraphImageUrl": "",
+                "usesCustomOpenGraphImage": false,
+            })))
+            .mount(&sandbox.github)
+            .await;
+    }
+    // octo/gone has no git data to clone
+    sandbox.git_repo("octo/old");
+    let plan = sandbox.queue(&["octo/old", "octo/gone"]);
+    let backups = sandbox.path("backups");
+
+    let output = sandbox
+        .command()
+        .arg("--backup-dir")
+        .arg(&backups)
+        .args(["--backup-as", "bundle", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let bundle = backups.join("octo/old/repo.bundle");
+    let verify = Command::new("git")
+        .args(["bundle", "verify", "--quiet"])
+        .arg(&bundle)
+        .current_dir(&backups)
+        .output()
+        .unwrap();
+    assert!(verify.status.success(), "{}", stderr(&verify));
+    assert!(!backups.join("octo/old/mirror.git").exists());
+    // The mirror is kept to fetch into next time, out of the backup dir
+    let mirrors = sandbox.path("cache/repo-archiver/mirrors/octo");
+    assert!(mirrors.join("old/mirror.git/HEAD").exists());
+    assert!(!mirrors.join("gone/mirror.git").exists());
+    assert!(
+        stdout(&output).contains("✗ octo/gone: Mirror clone failed"),
+        "{}",
+        stdout(&output)
+    );
+    let requests = sandbox.requests().await;
+    assert!(requests.contains(&"PATCH /repos/octo/old".to_string()));
+    assert!(!requests.contains(&"PATCH /repos/octo/gone".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bundles_are_only_rewritten_when_the_repo_changed() {
+    let sandbox = Sandbox::new("backup-bundle-again").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "nameWithOwner": "octo/old",
+            "description": "",
+            "homepageUrl": "",
+            "repositoryTopics": null,
+            "visibility": "PUBLIC",
+            "defaultBranchRef": {"name": "main"},
+            "hasIssuesEnabled": true,
+            "hasWikiEnabled": false,
+            "hasProjectsEnabled": false,
+            "openGraphImageUrl": "",
+            "usesCustomOpenGraphImage": false,
+        })))
+        .mount(&sandbox.github)
+        .await;
+    sandbox.git_repo("octo/old");
+    let backups = sandbox.path("backups");
+    let bundle = backups.join("octo/old/repo.bundle");
+    let back_up = || {
+        let plan = sandbox.queue(&["octo/old"]);
+        let output = sandbox
+            .command()
+            .arg("--backup-dir")
+            .arg(&backups)
+            .args(["--backup-as", "bundle", "apply-plan", "--plan"])
+            .arg(&plan)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        fs::metadata(&bundle).unwrap().modified().unwrap()
+    };
+
+    let first = back_up();
+    assert_eq!(back_up(), first, "nothing was fetched");
+
+    let work = sandbox.path("git-work/octo/old");
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .current_dir(&work)
+            .args(["-c", "user.name=Octo", "-c", "user.email=octo@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    };
+    git(&["commit", "--quiet", "--allow-empty", "-m", "More"]);
+    let bare = sandbox.path("git/octo/old.git");
+    git(&["push", "--quiet", bare.to_str().unwrap(), "main"]);
+    assert_ne!(back_up(), first, "a new commit was fetched");
+}
+
+#[tokio::test]
+async fn exporting_org_settings_needs_a_backup_dir() {
+    let sandbox = Sandbox::new("export-org-settings-alone").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--export-org-settings", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--export-org-settings needs --backup-dir"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_wiki_backs_up_wikis_that_have_pages() {
+    let sandbox = Sandbox::new("export-wiki").await;
+    for name in ["old", "bare"] {
+        sandbox.allow_archive(&format!("octo/{name}")).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/octo/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "nameWithOwner": format!("octo/{name}"),
+                "description": "",
+                "homepageUrl": "",
+                "repositoryTopics": null,
+                "visibility": "PUBLIC",
+                "defaultBranchRef": {"name": "main"},
+                "hasIssuesEnabled": true,
+                "hasWikiEnabled": true,
+                "hasProjectsEnabled": false,
+                "openGraphImageUrl": "",
+                "usesCustomOpenGraphImage": false,
+            })))
+            .mount(&sandbox.github)
+            .await;
+        sandbox.git_repo(&format!("octo/{name}"));
+    }
+    // Only octo/old's wiki has pages
+    sandbox.git_repo("octo/old.wiki");
+    let plan = sandbox.queue(&["octo/old", "octo/bare"]);
+    let backups = sandbox.path("backups");
+
+    let output = sandbox
+        .command()
+        .arg("--backup-dir")
+        .arg(&backups)
+        .args(["--export-wiki", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(backups.join("octo/old/wiki.git/HEAD").exists());
+    assert!(!backups.join("octo/bare/wiki.git").exists());
+    assert!(stdout(&output).contains("octo/old wiki: exported"));
+    assert!(stdout(&output).contains("octo/bare wiki: none"));
+    let report = &sandbox.reports()[0];
+    let wiki = |name: &str| {
+        report["repos"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["nameWithOwner"] == name)
+            .unwrap()["wiki"]
+            .clone()
+    };
+    assert_eq!(wiki("octo/old"), "exported");
+    assert_eq!(wiki("octo/bare"), "none");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_issues_writes_issues_and_pull_requests_with_their_comments() {
+    let sandbox = Sandbox::new("export-issues").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "nameWithOwner": "octo/old",
+            "description": "",
+            "homepageUrl": "",
+            "repositoryTopics": null,
+            "visibility": "PUBLIC",
+            "defaultBranchRef": {"name": "main"},
+            "hasIssuesEnabled": true,
+            "hasWikiEnabled": false,
+            "hasProjectsEnabled": false,
+            "openGraphImageUrl": "",
+            "usesCustomOpenGraphImage": false,
+        })))
+        .mount(&sandbox.github)
+        .await;
+    let user = |login: &str| json!({"login": login});
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "number": 1,
+                "title": "Crashes on start",
+                "body": "Steps to reproduce...",
+                "state": "closed",
+                "user": user("alice"),
+                "labels": [{"name": "bug"}],
+                "created_at": "2019-01-01T00:00:00Z",
+                "closed_at": "2019-01-02T00:00:00Z",
+            },
+            {
+                "number": 2,
+                "title": "Fix the crash",
+                "body": null,
+                "state": "open",
+                "user": user("bob"),
+                "labels": [],
+                "created_at": "2019-01-03T00:00:00Z",
+                "closed_at": null,
+                "pull_request": {"url": "https://api.github.com/repos/octo/old/pulls/2"},
+            },
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/issues/comments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "issue_url": "https://api.github.com/repos/octo/old/issues/1",
+            "user": user("bob"),
+            "body": "Fixed in #2",
+            "created_at": "2019-01-03T00:00:00Z",
+        }])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/pulls/comments"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "pull_request_url": "https://api.github.com/repos/octo/old/pulls/2",
+            "user": user("alice"),
+            "body": "Needs a test",
+            "created_at": "2019-01-04T00:00:00Z",
+            "path": "src/main.rs",
+        }])))
+        .mount(&sandbox.github)
+        .await;
+    sandbox.git_repo("octo/old");
+    let plan = sandbox.queue(&["octo/old"]);
+    let backups = sandbox.path("backups");
+
+    let output = sandbox
+        .command()
+        .arg("--backup-dir")
+        .arg(&backups)
+        .args(["--export-issues", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let issues: serde_json::Value =
+        serde_json::from_slice(&fs::read(backups.join("octo/old/issues.json")).unwrap()).unwrap();
+    assert_eq!(issues["nameWithOwner"], "octo/old");
+    let items = &issues["items"];
+    assert_eq!(items[0]["title"], "Crashes on start");
+    assert_eq!(items[0]["author"], "alice");
+    assert_eq!(items[0]["labels"], json!(["bug"]));
+    assert_eq!(items[0]["pullRequest"], false);
+    assert_eq!(
+        items[0]["comments"],
+        json!([{"author": "bob", "body": "Fixed in #2", "createdAt": "2019-01-03T00:00:00Z"}])
+    );
+    assert_eq!(items[1]["pullRequest"], true);
+    assert_eq!(items[1]["comments"][0]["path"], "src/main.rs");
+    assert!(sandbox
+        .requests()
+        .await
+        .contains(&"PATCH /repos/octo/old".to_string()));
+}
+
+#[tokio::test]
+async fn exporting_wikis_needs_a_backup_dir() {
+    let sandbox = Sandbox::new("export-wiki-alone").await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--export-wiki", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--export-wiki needs --backup-dir"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transfer_action_moves_repos_to_the_new_owner() {
+    let sandbox = Sandbox::new("transfer").await;
+    Mock::given(method("POST"))
+        .and(path("/repos/octo/old/transfer"))
+        .and(body_string_contains(r#""new_owner":"attic""#))
+        .respond_with(ResponseTemplate::new(202).set_body_json(json!({"full_name": "attic/old"})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args([
+            "--action",
+            "transfer",
+            "--to",
+            "attic",
+            "apply-plan",
+            "--plan",
+        ])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    let reports = sandbox.reports();
+    assert_eq!(reports[0]["action"], "transfer");
+    assert_eq!(reports[0]["repos"][0]["finalState"], json!(null));
+    assert_eq!(
+        sandbox.requests().await,
+        ["POST /graphql", "POST /repos/octo/old/transfer"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn access_review_reports_who_can_still_write() {
+    let sandbox = Sandbox::new("access-review").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/collaborators"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"login": "reader", "role_name": "read"},
+            {"login": "dev", "role_name": "write"},
+            {"login": "lead", "role_name": "admin"},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/teams"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"slug": "core", "name": "Core", "permission": "push"},
+            {"slug": "everyone", "name": "Everyone", "permission": "pull"},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--access-review", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(
+        "✓ octo/old\n  still writable by lead (admin), dev (write), octo/core (write)\n"
+    ));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["finalState"]["access"],
+        json!([
+            {"who": "lead", "kind": "user", "permission": "admin"},
+            {"who": "dev", "kind": "user", "permission": "write"},
+            {"who": "octo/core", "kind": "team", "permission": "write"},
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn org_runs_sum_up_archived_repos_per_owning_team() {
+    let sandbox = Sandbox::new("team-summary").await;
+    for (name, teams) in [
+        (
+            "old",
+            json!([
+                {"slug": "core", "name": "Core", "permission": "admin"},
+                {"slug": "everyone", "name": "Everyone", "permission": "push"},
+            ]),
+        ),
+        (
+            "older",
+            json!([{"slug": "core", "name": "Core", "permission": "maintain"}]),
+        ),
+        ("orphan", json!([])),
+    ] {
+        sandbox.allow_archive(&format!("octo/{name}")).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/octo/{name}/teams")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(teams))
+            .mount(&sandbox.github)
+            .await;
+    }
+    let plan = sandbox.queue(&["octo/old", "octo/older", "octo/orphan"]);
+
+    let output = sandbox
+        .command()
+        .args(["--org", "octo", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output)
+        .contains("Archived per team:\n  octo/core: 2 archived\n  (no team): 1 archived\n"));
+    let report = &sandbox.reports()[0];
+    let old = report["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["nameWithOwner"] == "octo/old")
+        .unwrap();
+    assert_eq!(old["finalState"]["teams"], json!(["octo/core"]));
+    assert_eq!(
+        report["teams"],
+        json!([
+            {"team": "octo/core", "archived": 2, "repos": ["octo/old", "octo/older"]},
+            {"team": null, "archived": 1, "repos": ["octo/orphan"]},
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn privatize_action_makes_repos_private() {
+    let sandbox = Sandbox::new("privatize").await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/old"))
+        .and(body_string_contains(r#""visibility":"private""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"private": true})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--action", "privatize", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert_eq!(sandbox.reports()[0]["action"], "privatize");
+    assert_eq!(
+        sandbox.requests().await,
+        ["POST /graphql", "PATCH /repos/octo/old"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn plan_writes_a_reviewable_plan_that_apply_archives() {
+    let sandbox = Sandbox::new("plan-apply").await;
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            repo("octo/taken", "2019-05-01T00:00:00Z", false),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    for (name, archived) in [("old", false), ("taken", true)] {
+        sandbox
+            .check_repo(&format!("octo/{name}"), archived, "PUBLIC")
+            .await;
+    }
+    let file = sandbox.path("archive-plan.json");
+    let file_arg = file.to_str().unwrap();
+
+    let output = sandbox.run(&["--age", "2y", "--deploy-window", "0", "plan", file_arg]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✗ octo/taken: would fail: already archived"));
+    let plan: serde_json::Value = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+    assert_eq!(plan["steps"], json!(["1 archive"]));
+    assert_eq!(plan["repos"].as_array().unwrap().len(), 1);
+    assert_eq!(plan["repos"][0]["nameWithOwner"], "octo/old");
+    assert_eq!(plan["repos"][0]["prediction"], "would archive");
+    assert!(!sandbox
+        .requests()
+        .await
+        .iter()
+        .any(|r| r.starts_with("PATCH")));
+
+    // Flags that would do more than was reviewed are refused
+    let output = sandbox.run(&["--topic", "archived", "apply", file_arg]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("These flags would change what the plan does"),
+        "{}",
+        stderr(&output)
+    );
+
+    sandbox.allow_archive("octo/old").await;
+    let output = sandbox.run(&["apply", file_arg]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = sandbox.requests().await;
+    assert!(requests.contains(&"PATCH /repos/octo/old".to_string()));
+    assert!(!requests.contains(&"PATCH /repos/octo/taken".to_string()));
+    assert!(!file.exists(), "an applied plan is used up");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn non_interactive_archives_every_candidate_and_fails_if_any_did() {
+    let sandbox = Sandbox::new("non-interactive").await;
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            repo("octo/stuck", "2019-05-01T00:00:00Z", false),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    sandbox
+        .respond_to_archive("octo/stuck", ResponseTemplate::new(403))
+        .await;
+
+    let output = sandbox.run(&[
+        "--age",
+        "2y",
+        "--deploy-window",
+        "0",
+        "--non-interactive",
+        "--yes",
+    ]);
+
+    assert!(!output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("Archiving 2 repos..."), "{out}");
+    assert!(out.contains("✓ octo/old\n"), "{out}");
+    assert!(out.contains("✗ octo/stuck: "), "{out}");
+    assert!(
+        stderr(&output).contains("1 of 2 repos failed"),
+        "{}",
+        stderr(&output)
+    );
+    let report = &sandbox.reports()[0];
+    assert_eq!(report["repos"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn non_interactive_needs_yes_unless_it_is_a_dry_run() {
+    let sandbox = Sandbox::new("non-interactive-yes").await;
+
+    let output = sandbox.run(&["--age", "2y", "--non-interactive"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("pass --yes to go ahead"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(sandbox.requests().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn description_rules_protect_matching_repos() {
+    let sandbox = Sandbox::new("description-rules").await;
+    let described = |name, description| {
+        let mut repo = repo(name, "2019-05-01T00:00:00Z", false);
+        repo["description"] = json!(description);
+        repo
+    };
+    sandbox
+        .list(&[
+            described("octo/acme-site", "Acme CLIENT PROJECT, do not touch"),
+            described("octo/hack", "Hackathon 2019 entry"),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.check_repo("octo/hack", false, "PUBLIC").await;
+    let config = sandbox.path("config.toml");
+    fs::write(
+        &config,
+        "[[description_rules]]\npattern = \".*client project.*\"\naction = \"protect\"\n\n\
+         [[description_rules]]\npattern = \"hackathon\"\naction = \"select\"\n",
+    )
+    .unwrap();
+    let run = || {
+        sandbox
+            .command()
+            .arg("--config")
+            .arg(&config)
+            .args(["--age", "2y", "--deploy-window", "0"])
+            .args(["--non-interactive", "--dry-run"])
+            .output()
+            .unwrap()
+    };
+
+    let output = run();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Description rules protect 1 repos and preselect 1."),
+        "{out}"
+    );
+    assert!(
+        out.contains("- octo/acme-site: protected by the description rule '.*client project.*'"),
+        "{out}"
+    );
+    assert!(out.contains("✓ octo/hack: would archive"), "{out}");
+
+    // A bad pattern stops the run before anything is fetched
+    fs::write(
+        &config,
+        "[[description_rules]]\npattern = \"(\"\naction = \"protect\"\n",
+    )
+    .unwrap();
+    let output = run();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Invalid pattern '(' in description_rules"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn list_prints_the_candidates_as_json() {
+    let sandbox = Sandbox::new("list").await;
+    let mut young = repo("octo/young", "2099-01-01T00:00:00Z", false);
+    young["createdAt"] = json!("2099-01-01T00:00:00Z");
+    let mut client = repo("octo/client", "2019-05-01T00:00:00Z", false);
+    client["description"] = json!("Client project");
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            young,
+            client,
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+
+    let config = sandbox.path("config.toml");
+    fs::write(
+        &config,
+        "[[description_rules]]\npattern = \"client\"\naction = \"protect\"\n",
+    )
+    .unwrap();
+
+    let output = sandbox
+        .command()
+        .arg("--config")
+        .arg(&config)
+        .args(["--age", "2y", "list"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let repos: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| panic!("{e}: {}", stdout(&output)));
+    let names: Vec<&str> = repos
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["nameWithOwner"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["octo/old", "octo/client"]);
+    assert_eq!(repos[0]["diskUsage"], 120);
+    assert_eq!(repos[0]["selectable"], true);
+    assert!(repos[0].get("unselectableReason").is_none());
+    assert_eq!(repos[1]["selectable"], false);
+    assert_eq!(
+        repos[1]["unselectableReason"],
+        "protected by the description rule 'client'"
+    );
+    assert!(!sandbox
+        .requests()
+        .await
+        .iter()
+        .any(|r| r.starts_with("PATCH")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repos_named_after_their_owners_github_apps_are_flagged() {
+    let sandbox = Sandbox::new("apps").await;
+    sandbox
+        .list(&[
+            repo("octo/Deploy_Bot", "2019-05-01T00:00:00Z", false),
+            repo("octo/lib", "2019-05-01T00:00:00Z", false),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    for name in ["Deploy_Bot", "lib"] {
+        sandbox
+            .check_repo(&format!("octo/{name}"), false, "PUBLIC")
+            .await;
+    }
+    // octo/lib shares its name with someone else's app
+    for (slug, owner) in [("deploy-bot", "octo"), ("lib", "stranger")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/apps/{slug}")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"slug": slug, "owner": {"login": owner}})),
+            )
+            .mount(&sandbox.github)
+            .await;
+    }
+    let file = sandbox.path("archive-plan.json");
+
+    let output = sandbox.run(&[
+        "--age",
+        "2y",
+        "--deploy-window",
+        "0",
+        "plan",
+        file.to_str().unwrap(),
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("+ octo/Deploy_Bot: would archive\n  ! looks like the source of the GitHub App deploy-bot\n"),
+        "{out}"
+    );
+    assert!(!out.contains("GitHub App lib"), "{out}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn privatize_dry_run_flags_repos_that_are_already_private() {
+    let sandbox = Sandbox::new("privatize-dry-run").await;
+    for (name, visibility) in [("old", "PUBLIC"), ("hidden", "PRIVATE")] {
+        sandbox
+            .check_repo(&format!("octo/{name}"), false, visibility)
+            .await;
+    }
+    let plan = sandbox.queue(&["octo/old", "octo/hidden"]);
+
+    let output = sandbox
+        .command()
+        .args(["--action", "privatize", "--dry-run", "apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(stdout(&output).contains("✓ octo/old: would make private\n"));
+    assert!(stdout(&output).contains("✗ octo/hidden: would fail: already private\n"));
+    // Only the read-only checks
+    assert_eq!(sandbox.requests().await, ["POST /graphql", "POST /graphql"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tag_action_only_adds_topics() {
+    let sandbox = Sandbox::new("tag").await;
+    Mock::given(method("POST"))
+        .and(path("/repos/octo/old/topics"))
+        .and(body_string_contains(
+            r#"{"names":["graveyard","archived-2024"]}"#,
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--action", "tag", "--topic", "graveyard,archived-2024"])
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert_eq!(
+        sandbox.requests().await,
+        ["POST /graphql", "POST /repos/octo/old/topics"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn removed_collaborators_and_teams_are_recorded_in_the_report() {
+    let sandbox = Sandbox::new("remove-access").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/collaborators"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"login": "contractor", "role_name": "write"},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/teams"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"slug": "core", "name": "Core", "permission": "push"},
+            {"slug": "everyone", "name": "Everyone", "permission": "pull"},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    for endpoint in [
+        "/repos/octo/old/collaborators/contractor",
+        "/orgs/octo/teams/core/repos/octo/old",
+    ] {
+        Mock::given(method("DELETE"))
+            .and(path(endpoint))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&sandbox.github)
+            .await;
+    }
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["--remove-outside-collaborators", "--remove-team", "core"])
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("  removed contractor (write) from octo/old\n"));
+    assert_eq!(
+        sandbox.reports()[0]["repos"][0]["removedAccess"],
+        json!([
+            {"who": "contractor", "kind": "user", "permission": "write"},
+            {"who": "octo/core", "kind": "team", "permission": "write"},
+        ])
+    );
+    let requests = sandbox.requests().await;
+    let position = |request: &str| requests.iter().position(|r| r == request).unwrap();
+    assert!(
+        position("DELETE /orgs/octo/teams/core/repos/octo/old") < position("PATCH /repos/octo/old"),
+        "access is removed before archiving: {requests:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn readme_notice_is_committed_before_archiving() {
+    let sandbox = Sandbox::new("readme-notice").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/readme"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "path": "README.md",
+            "sha": "abc123",
+            // "# old\n", wrapped like GitHub's
+            "content": "IyBv\nbGQK\n",
+        })))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/repos/octo/old/contents/README.md"))
+        .and(body_string_contains(r#""sha":"abc123""#))
+        // "> Archived: old is no longer maintained.\n\n# old\n"
+        .and(body_string_contains(
+            "PiBBcmNoaXZlZDogb2xkIGlzIG5vIGxvbmdlciBtYWludGFpbmVkLgoKIyBvbGQK",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args([
+            "--readme-notice",
+            "> Archived: {name} is no longer maintained.",
+        ])
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = sandbox.requests().await;
+    let position = |request: &str| requests.iter().position(|r| r == request).unwrap();
+    assert!(
+        position("PUT /repos/octo/old/contents/README.md") < position("PATCH /repos/octo/old"),
+        "the notice is committed before archiving: {requests:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_items_comments_on_and_closes_open_issues_and_pull_requests() {
+    let sandbox = Sandbox::new("close-items").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"number": 3, "title": "Crash on start"},
+            {"number": 4, "title": "Fix crash", "pull_request": {"url": "…"}},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    for number in [3, 4] {
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/octo/old/issues/{number}/comments")))
+            .and(body_string_contains("old is archived"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+            .expect(1)
+            .mount(&sandbox.github)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(format!("/repos/octo/old/issues/{number}")))
+            .and(body_string_contains(r#""state":"closed""#))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&sandbox.github)
+            .await;
+    }
+
+    let output = sandbox.run(&[
+        "close-items",
+        "octo/old",
+        "--comment",
+        "Closing: {name} is archived.",
+    ]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old: closed 1 issue, 1 pull request\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn archive_command_archives_named_repos_without_the_tui() {
+    let sandbox = Sandbox::new("archive-command").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("POST"))
+        .and(path("/repos/octo/old/topics"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+
+    let output = sandbox.run(&["archive", "octo/old", "--topic", "graveyard"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ octo/old\n"));
+    assert_eq!(
+        sandbox.requests().await,
+        ["POST /repos/octo/old/topics", "PATCH /repos/octo/old"]
+    );
+}
+
+/// Run `serve --stdio` with `requests` as its input, one per line.
+fn serve(sandbox: &Sandbox, requests: &[serde_json::Value]) -> Output {
+    serve_with(sandbox.command(), requests)
+}
+
+/// `serve`, with flags of its own on `cmd`.
+fn serve_with(mut cmd: Command, requests: &[serde_json::Value]) -> Output {
+    let mut child = cmd
+        .args(["serve", "--stdio"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for request in requests {
+        writeln!(stdin, "{request}").unwrap();
+    }
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_lists_and_archives_over_json_rpc() {
+    let sandbox = Sandbox::new("serve").await;
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            repo("octo/fork", "2019-05-01T00:00:00Z", true),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+
+    let output = serve(
+        &sandbox,
+        &[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "list", "params": {"age": "2y"}}),
+            // Left out by the list, since forks aren't included
+            json!({"jsonrpc": "2.0", "id": 4, "method": "archive", "params": {"repos": ["octo/fork"]}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "archive", "params": {"repos": ["octo/old"]}}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "delete", "params": {}}),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let messages: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let response = |id: u64| messages.iter().find(|m| m["id"] == id).unwrap();
+    let listed: Vec<&str> = response(1)["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["nameWithOwner"].as_str().unwrap())
+        .collect();
+    assert_eq!(listed, ["octo/old"]);
+    let events: Vec<&str> = messages
+        .iter()
+        .filter(|m| m["method"] == "progress")
+        .map(|m| m["params"]["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(events, ["started", "done"]);
+    assert_eq!(
+        response(2)["result"]["repos"][0]["nameWithOwner"],
+        "octo/old"
+    );
+    assert_eq!(
+        response(2)["result"]["repos"][0]["finalState"]["archived"],
+        true
+    );
+    assert_eq!(response(3)["error"]["code"], -32601);
+    assert_eq!(response(4)["error"]["code"], -32602);
+    assert!(!sandbox
+        .requests()
+        .await
+        .contains(&"PATCH /repos/octo/fork".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_takes_the_lock_for_each_archive() {
+    let sandbox = Sandbox::new("serve-lock").await;
+    sandbox
+        .list(&[repo("octo/old", "2019-05-01T00:00:00Z", false)])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "number": 7,
+            "title": "repo-archiver run in progress",
+            "user": {"login": "alice"},
+            "created_at": "2024-06-01T12:00:00Z",
+        }])))
+        .mount(&sandbox.github)
+        .await;
+    let mut cmd = sandbox.command();
+    cmd.args(["--lock-repo", "octo/ops"]);
+
+    let output = serve_with(
+        cmd,
+        &[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "list", "params": {"age": "2y"}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "archive", "params": {"repos": ["octo/old"]}}),
+        ],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    let response: serde_json::Value = serde_json::from_str(out.lines().last().unwrap()).unwrap();
+    assert_eq!(response["id"], 2);
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("alice has been running repo-archiver"),
+        "{out}"
+    );
+    assert!(!sandbox
+        .requests()
+        .await
+        .contains(&"PATCH /repos/octo/old".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn filter_by_activity_skips_repos_still_discussed_or_pushed_to() {
+    let sandbox = Sandbox::new("filter-by-activity").await;
+    let now = chrono::Utc::now().to_rfc3339();
+    sandbox
+        .list(&[
+            repo("octo/quiet", "2019-05-01T00:00:00Z", false),
+            repo("octo/discussed", "2019-05-01T00:00:00Z", false),
+            repo("octo/pushed", &now, false),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    let none = json!({"nodes": []});
+    sandbox
+        .graphql(
+            "pullRequests(first: 1",
+            json!({
+                "r0": {"issues": none, "pullRequests": none, "releases": {"nodes": [
+                    {"createdAt": "2020-01-01T00:00:00Z"},
+                ]}},
+                "r1": {"issues": {"nodes": [{"updatedAt": now}]}, "pullRequests": none, "releases": none},
+            }),
+        )
+        .await;
+
+    let mut cmd = sandbox.command();
+    cmd.args(["--filter-by", "activity"]);
+    let output = serve_with(
+        cmd,
+        &[json!({"jsonrpc": "2.0", "id": 1, "method": "list", "params": {"age": "2y"}})],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let response: serde_json::Value =
+        serde_json::from_str(stdout(&output).lines().next().unwrap()).unwrap();
+    let listed: Vec<&str> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["nameWithOwner"].as_str().unwrap())
+        .collect();
+    assert_eq!(listed, ["octo/quiet"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn min_repo_age_keeps_brand_new_repos_out_of_never_pushed() {
+    let sandbox = Sandbox::new("min-repo-age").await;
+    let last_week = (chrono::Utc::now() - chrono::Duration::days(7)).to_rfc3339();
+    let mut new = repo("octo/new", &last_week, false);
+    new["createdAt"] = json!(last_week);
+    sandbox
+        .list(&[repo("octo/empty", "2015-01-01T00:00:00Z", false), new])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+
+    let mut cmd = sandbox.command();
+    cmd.args(["--min-repo-age", "1m"]);
+    let output = serve_with(
+        cmd,
+        &[json!({"jsonrpc": "2.0", "id": 1, "method": "list", "params": {"age": "never"}})],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let response: serde_json::Value =
+        serde_json::from_str(stdout(&output).lines().next().unwrap()).unwrap();
+    let listed: Vec<&str> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["nameWithOwner"].as_str().unwrap())
+        .collect();
+    assert_eq!(listed, ["octo/empty"]);
+}
+
+#[tokio::test]
+async fn min_repo_age_must_be_an_age() {
+    let sandbox = Sandbox::new("min-repo-age-never").await;
+
+    let output = sandbox.run(&["--min-repo-age", "never", "--offline", "--age", "2y"]);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("--min-repo-age takes an age like '3m', not 'never'"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stamp_commits_archived_md_last_before_archiving() {
+    let sandbox = Sandbox::new("stamp").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/contents/ARCHIVED.md"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({"message": "Not Found"})))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/repos/octo/old/contents/ARCHIVED.md"))
+        .and(body_string_contains(r#""message":"Add ARCHIVED.md""#))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+        .expect(1)
+        .mount(&sandbox.github)
+        .await;
+    let config = sandbox.path("config.toml");
+    fs::write(&config, "[successors]\n\"octo/old\" = \"octo/new\"\n").unwrap();
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .arg("--config")
+        .arg(&config)
+        .args(["--stamp", "--stamp-reason", "Replaced by {name}-v2"])
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let requests = sandbox.github.received_requests().await.unwrap();
+    let put = requests
+        .iter()
+        .find(|r| r.method.as_str() == "PUT")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&put.body).unwrap();
+    assert!(
+        body.get("sha").is_none(),
+        "a new file has no sha to replace"
+    );
+    let content = String::from_utf8(
+        base64::engine::general_purpose::STANDARD
+            .decode(body["content"].as_str().unwrap())
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(content.contains("**Reason:** Replaced by old-v2\n"));
+    assert!(content.contains("**Successor:** [octo/new](https://github.com/octo/new)\n"));
+    let requests = sandbox.requests().await;
+    assert_eq!(
+        requests[requests.len() - 3..],
+        [
+            "PUT /repos/octo/old/contents/ARCHIVED.md",
+            "PATCH /repos/octo/old",
+            "POST /graphql"
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_items_closes_issues_with_a_comment_before_archiving() {
+    let sandbox = Sandbox::new("close-before-archive").await;
+    sandbox.allow_archive("octo/old").await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/old/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"number": 7, "title": "Add dark mode"},
+            {"number": 8, "title": "Dark mode", "pull_request": {"url": "…"}},
+        ])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/repos/octo/old/issues/[78]/comments$"))
+        .and(body_string_contains("Repository archived on 20"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+        .expect(2)
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path_regex(r"^/repos/octo/old/issues/[78]$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(2)
+        .mount(&sandbox.github)
+        .await;
+    let plan = sandbox.queue(&["octo/old"]);
+
+    let output = sandbox
+        .command()
+        .args(["apply-plan", "--plan"])
+        .arg(&plan)
+        .arg("--close-items")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("  closed 2 open issues and pull requests in octo/old\n"));
+    let requests = sandbox.requests().await;
+    let position = |request: &str| requests.iter().position(|r| r == request).unwrap();
+    assert!(
+        position("PATCH /repos/octo/old/issues/8") < position("PATCH /repos/octo/old"),
+        "issues are closed before archiving: {requests:?}"
+    );
+}
+
+/// Send an HTTP request to the dashboard at `addr`, returning the status code
+/// and the body.
+fn http(addr: &str, method: &str, path: &str, body: Option<&serde_json::Value>) -> (u16, String) {
+    http_as(None, addr, method, path, body)
+}
+
+/// Like `http`, authenticated with `token` as a bearer token.
+fn http_as(
+    token: Option<&str>,
+    addr: &str,
+    method: &str,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let body = body.map(ToString::to_string).unwrap_or_default();
+    let auth = token.map_or(String::new(), |t| format!("Authorization: Bearer {t}\r\n"));
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\n{auth}Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (code, body.to_string())
+}
+
+/// Start a server with `command` and wait for it to print where it listens.
+fn spawn_server(mut command: Command) -> (Child, String) {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let addr = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .find_map(|line| {
+            let url = line.split_once("http://")?.1;
+            Some(url.split([' ', ',']).next()?.to_string())
+        })
+        .expect("the server's address");
+    (child, addr)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn web_dashboard_lists_and_archives_the_selected_repos() {
+    let sandbox = Sandbox::new("web").await;
+    sandbox
+        .list(&[
+            repo("octo/old", "2019-05-01T00:00:00Z", false),
+            repo("octo/older", "2018-05-01T00:00:00Z", false),
+        ])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+
+    let mut command = sandbox.command();
+    command.args(["--age", "2y", "--web", "127.0.0.1:0"]);
+    let (mut child, addr) = spawn_server(command);
+
+    let (code, page) = http(&addr, "GET", "/", None);
+    assert_eq!(code, 200);
+    assert!(page.contains("app.js"));
+    let repos = |addr: &str| -> serde_json::Value {
+        serde_json::from_str(&http(addr, "GET", "/api/repos", None).1).unwrap()
+    };
+    let candidates = repos(&addr);
+    let listed: Vec<&str> = candidates["repos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["nameWithOwner"].as_str().unwrap())
+        .collect();
+    assert_eq!(listed.len(), 2);
+    assert!(listed.contains(&"octo/old"));
+
+    let (code, _) = http(
+        &addr,
+        "POST",
+        "/api/archive",
+        Some(&json!({"repos": ["octo/old"]})),
+    );
+    assert_eq!(code, 202);
+    let mut state = serde_json::Value::Null;
+    for _ in 0..100 {
+        state = repos(&addr);
+        if state["running"] == false {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let status = |name: &str| {
+        state["repos"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["nameWithOwner"] == name)
+            .unwrap()["status"]["state"]
+            .clone()
+    };
+    assert_eq!(status("octo/old"), "done");
+    assert_eq!(status("octo/older"), "idle");
+    assert!(sandbox
+        .requests()
+        .await
+        .contains(&"PATCH /repos/octo/old".to_string()));
+    assert_eq!(sandbox.reports().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn web_dashboard_refuses_requests_for_other_hosts_and_origins() {
+    let sandbox = Sandbox::new("web-rebinding").await;
+    sandbox
+        .list(&[repo("octo/old", "2019-05-01T00:00:00Z", false)])
+        .await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+
+    let mut command = sandbox.command();
+    command.args(["--age", "2y", "--web", "127.0.0.1:0"]);
+    let (mut child, addr) = spawn_server(command);
+    let port = addr.rsplit_once(':').unwrap().1;
+    let status = |headers: &str| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET /api/repos HTTP/1.1\r\n{headers}\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.split_whitespace().nth(1).unwrap().to_string()
+    };
+
+    let evil_host = status(&format!("Host: evil.example:{port}\r\n"));
+    let evil_origin = status(&format!(
+        "Host: {addr}\r\nOrigin: http://evil.example:{port}\r\n"
+    ));
+    let no_host = status("");
+    let localhost = status(&format!(
+        "Host: localhost:{port}\r\nOrigin: http://localhost:{port}\r\n"
+    ));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(evil_host, "403");
+    assert_eq!(evil_origin, "403");
+    assert_eq!(no_host, "403");
+    assert_eq!(localhost, "200");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_http_lists_archives_and_reports_for_authenticated_clients() {
+    let sandbox = Sandbox::new("serve-http").await;
+    let repos = [
+        repo("octo/old", "2019-05-01T00:00:00Z", false),
+        repo("octo/fork", "2019-05-01T00:00:00Z", true),
+    ];
+    sandbox.list(&repos).await;
+    // Runs list the owners of the repos they name
+    sandbox.list_of("octo", &repos).await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    sandbox.allow_archive("octo/old").await;
+
+    let mut command = sandbox.command();
+    command
+        .args(["serve", "--http", "127.0.0.1:0"])
+        .env("REPO_ARCHIVER_API_TOKEN", "s3cret");
+    let (mut child, addr) = spawn_server(command);
+    let api = |method: &str, path: &str, body: Option<&serde_json::Value>| {
+        let (code, body) = http_as(Some("s3cret"), &addr, method, path, body);
+        (
+            code,
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        )
+    };
+
+    assert_eq!(http(&addr, "GET", "/api/runs", None).0, 401);
+    assert_eq!(
+        http_as(Some("guess"), &addr, "GET", "/api/runs", None).0,
+        401
+    );
+    let (code, candidates) = api("GET", "/api/candidates?age=2y", None);
+    assert_eq!(code, 200);
+    assert_eq!(candidates[0]["nameWithOwner"], "octo/old");
+    assert_eq!(candidates.as_array().unwrap().len(), 1);
+
+    // Only selectable candidates, for the age given
+    let (code, _) = api("POST", "/api/runs", Some(&json!({"repos": ["octo/old"]})));
+    assert_eq!(code, 422, "no age");
+    let (code, refused) = api(
+        "POST",
+        "/api/runs",
+        Some(&json!({"repos": ["octo/old", "octo/fork"], "age": "2y"})),
+    );
+    assert_eq!(code, 422);
+    assert!(
+        refused["error"].as_str().unwrap().ends_with(": octo/fork"),
+        "{refused}"
+    );
+    let (code, started) = api(
+        "POST",
+        "/api/runs",
+        Some(&json!({"repos": ["octo/old"], "age": "2y"})),
+    );
+    assert_eq!(code, 202);
+    let path = format!("/api/runs/{}", started["id"]);
+    let mut run = serde_json::Value::Null;
+    for _ in 0..100 {
+        run = api("GET", &path, None).1;
+        if run["finished"] == true {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert_eq!(run["statuses"][0]["state"], "done");
+    let (_, reports) = api("GET", "/api/reports", None);
+    assert_eq!(reports[0], run["report"]);
+    let report_path = format!("/api/reports/{}", run["report"].as_str().unwrap());
+    let (code, report) = api("GET", &report_path, None);
+    let (missing, _) = api("GET", "/api/reports/nope", None);
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(code, 200);
+    assert_eq!(report["repos"][0]["nameWithOwner"], "octo/old");
+    assert_eq!(missing, 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_http_refuses_a_second_run_before_asking_for_the_lock() {
+    let sandbox = Sandbox::new("serve-http-busy").await;
+    let repos = [repo("octo/old", "2019-05-01T00:00:00Z", false)];
+    sandbox.list(&repos).await;
+    sandbox.list_of("octo", &repos).await;
+    sandbox
+        .graphql(
+            "pinnedItems",
+            json!({"owner": {"pinnedItems": {"nodes": []}}}),
+        )
+        .await;
+    // Slow enough for the second request to arrive mid-run
+    sandbox
+        .respond_to_archive(
+            "octo/old",
+            ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)),
+        )
+        .await;
+    sandbox.show_repo("octo/old", true).await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .up_to_n_times(1)
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "number": 8,
+            "title": "repo-archiver run in progress",
+            "user": {"login": "me"},
+            "created_at": "2024-06-01T12:00:00Z",
+        }])))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/octo/ops/issues"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"number": 8})))
+        .mount(&sandbox.github)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/octo/ops/issues/8"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&sandbox.github)
+        .await;
+
+    let mut command = sandbox.command();
+    command
+        .args(["--lock-repo", "octo/ops", "serve", "--http", "127.0.0.1:0"])
+        .env("REPO_ARCHIVER_API_TOKEN", "s3cret");
+    let (mut child, addr) = spawn_server(command);
+    let run = json!({"repos": ["octo/old"], "age": "2y"});
+    let (first, _) = http_as(Some("s3cret"), &addr, "POST", "/api/runs", Some(&run));
+    let (second, refused) = http_as(Some("s3cret"), &addr, "POST", "/api/runs", Some(&run));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert_eq!(first, 202);
+    assert_eq!(second, 409);
+    assert!(refused.contains("A run is already going"), "{refused}");
+    let lookups = sandbox
+        .requests()
+        .await
+        .iter()
+        .filter(|r| *r == "GET /repos/octo/ops/issues")
+        .count();
+    assert_eq!(lookups, 2, "only the first run looks for the lock");
+}
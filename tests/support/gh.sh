@@ -0,0 +1,100 @@
+#!/bin/sh
+# Stand-in for the gh CLI: turns the calls repo-archiver makes into requests
+# to the mock GitHub server at $MOCK_GITHUB_URL, and reports failures the way
+# gh does ("HTTP 404: ..." on stderr, exit status 1).
+set -eu
+
+body=$(mktemp)
+trap 'rm -f "$body"' EXIT
+
+# request METHOD PATH [DATA]: print the response body, or fail like gh
+request() {
+    status=$(curl -sS -o "$body" -w '%{http_code}' -X "$1" \
+        -H 'Content-Type: application/json' --data-binary "${3:-}" \
+        "$MOCK_GITHUB_URL$2")
+    if [ "$status" -ge 400 ]; then
+        echo "HTTP $status: $(cat "$body")" >&2
+        exit 1
+    fi
+    cat "$body"
+}
+
+if [ "$1" = --version ]; then
+    echo "gh version ${MOCK_GH_VERSION:-2.60.0} (2024-10-29)"
+    exit 0
+fi
+
+case "$1 $2" in
+"repo archive")
+    request PATCH "/repos/$3" '{"archived":true}' >/dev/null
+    ;;
+"repo unarchive")
+    request PATCH "/repos/$3" '{"archived":false}' >/dev/null
+    ;;
+"repo edit")
+    # gh repo edit OWNER/NAME --add-topic TOPIC[,TOPIC...]
+    request POST "/repos/$3/topics" "$(jq -cn --arg topics "$5" '{names: ($topics / ",")}')" >/dev/null
+    ;;
+"repo view")
+    # gh repo view OWNER/NAME --json FIELDS: the mock answers in gh's shape
+    request GET "/repos/$3"
+    ;;
+"api user")
+    request GET /user | jq -r .login
+    ;;
+"api graphql")
+    shift 2
+    query="" variables="{}" filter="."
+    while [ $# -gt 0 ]; do
+        case "$1" in
+        -f | -F)
+            case "$2" in
+            query=*) query=${2#query=} ;;
+            *) variables=$(echo "$variables" |
+                jq -c --arg key "${2%%=*}" --arg value "${2#*=}" '. + {($key): $value}') ;;
+            esac
+            shift 2
+            ;;
+        --jq) filter=$2; shift 2 ;;
+        *) shift ;;
+        esac
+    done
+    response=$(request POST /graphql "$(jq -cn --arg query "$query" \
+        --argjson variables "$variables" '{query: $query, variables: $variables}')") || exit 1
+    # Like gh, fail on GraphQL errors even though the status is 200
+    if echo "$response" | jq -e '.errors | length > 0' >/dev/null 2>&1; then
+        echo "GraphQL: $(echo "$response" | jq -r '[.errors[].message] | join(", ")')" >&2
+        exit 1
+    fi
+    echo "$response" | jq -r "$filter"
+    ;;
+"api "*)
+    # gh api [-X METHOD] PATH [-f KEY=VALUE]... [--input FILE] [--paginate]
+    # [--jq FILTER], one page of it
+    shift
+    verb=GET endpoint="" fields="{}" filter="."
+    while [ $# -gt 0 ]; do
+        case "$1" in
+        -X) verb=$2; shift 2 ;;
+        --input) fields=$(cat "$2"); shift 2 ;;
+        -f | -F) fields=$(echo "$fields" |
+            jq -c --arg key "${2%%=*}" --arg value "${2#*=}" '. + {($key): $value}')
+            shift 2 ;;
+        --jq) filter=$2; shift 2 ;;
+        -*) shift ;;
+        *) endpoint=$1; shift ;;
+        esac
+    done
+    if [ "$verb" = GET ]; then
+        response=$(request GET "/$endpoint") || exit 1
+    else
+        response=$(request "$verb" "/$endpoint" "$fields") || exit 1
+    fi
+    # Strings come out raw, as gh prints them
+    printf '%s\n' "$response" | jq -cr "$filter"
+    ;;
+*)
+    echo "mock gh: unsupported command: $*" >&2
+    exit 1
+    ;;
+esac
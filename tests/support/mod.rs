@@ -0,0 +1,276 @@
+//! A mock GitHub server, and a sandbox that runs repo-archiver against it.
+//!
+//! repo-archiver talks to GitHub through `gh`, so the sandbox puts a shim
+//! named `gh` first on `PATH` that turns each call into a request to the
+//! mock (see `gh.sh`). Git is pointed at local repos in place of github.com
+//! (see `git_repo`). Config, cache and state dirs live in the sandbox too,
+//! so nothing from the machine running the tests leaks in.
+
+use serde_json::{json, Value};
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use wiremock::{
+    matchers::{body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const GH_SHIM: &str = include_str!("gh.sh");
+
+pub struct Sandbox {
+    dir: PathBuf,
+    pub github: MockServer,
+}
+
+impl Sandbox {
+    /// A fresh sandbox; `name` keeps tests running in parallel apart.
+    pub async fn new(name: &str) -> Self {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("e2e-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        let bin = dir.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let shim = bin.join("gh");
+        fs::write(&shim, GH_SHIM).unwrap();
+        fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)).unwrap();
+
+        Self {
+            dir,
+            github: MockServer::start().await,
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_repo-archiver"));
+        for (key, _) in env::vars() {
+            if key.starts_with("REPO_ARCHIVER_") || key.starts_with("GH_") {
+                cmd.env_remove(key);
+            }
+        }
+        let path = env::var("PATH").unwrap_or_default();
+        cmd.current_dir(&self.dir)
+            .env("PATH", format!("{}:{path}", self.dir.join("bin").display()))
+            .env("MOCK_GITHUB_URL", self.github.uri())
+            .env("HOME", self.dir.join("home"))
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_CACHE_HOME", self.dir.join("cache"))
+            .env("XDG_STATE_HOME", self.dir.join("state"))
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env("GIT_CONFIG_COUNT", "1")
+            .env(
+                "GIT_CONFIG_KEY_0",
+                format!("url.{}/.insteadOf", self.dir.join("git").display()),
+            )
+            .env("GIT_CONFIG_VALUE_0", "https://github.com/")
+            .env_remove("GITHUB_TOKEN");
+        cmd
+    }
+
+    /// Give `name_with_owner` git data: a bare repo with one commit, which
+    /// repo-archiver clones in place of the one on github.com.
+    pub fn git_repo(&self, name_with_owner: &str) {
+        let work = self.path("git-work").join(name_with_owner);
+        fs::create_dir_all(&work).unwrap();
+        fs::write(work.join("README.md"), name_with_owner).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .current_dir(&work)
+                .args(["-c", "user.name=Octo", "-c", "user.email=octo@example.com"])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "--quiet", "--initial-branch=main"]);
+        git(&["add", "README.md"]);
+        git(&["commit", "--quiet", "-m", "Initial commit"]);
+        let bare = self.path("git").join(format!("{name_with_owner}.git"));
+        git(&["clone", "--quiet", "--bare", ".", bare.to_str().unwrap()]);
+    }
+
+    /// Run repo-archiver with `args` to completion.
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().unwrap()
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Write a plan queueing `repos`, as the TUI does with `--offline`, and
+    /// return its path.
+    pub fn queue(&self, repos: &[&str]) -> PathBuf {
+        let plan = self.path("plan.json");
+        let repos: Vec<Value> = repos
+            .iter()
+            .map(|repo| json!({"nameWithOwner": repo, "queuedAt": "2024-01-01T00:00:00Z"}))
+            .collect();
+        fs::write(&plan, json!({ "repos": repos }).to_string()).unwrap();
+        plan
+    }
+
+    /// Serve `repos` as the authenticated user's repo list, in one page.
+    pub async fn list(&self, repos: &[Value]) {
+        self.list_page(None, None, repos).await;
+    }
+
+    /// Serve `repos` as the page of the repo list after `cursor`, with
+    /// `next` as the cursor of the page after it.
+    pub async fn list_page(&self, cursor: Option<&str>, next: Option<&str>, repos: &[Value]) {
+        let variable = cursor.map_or_else(
+            || "\"variables\":{}".to_string(),
+            |cursor| format!("\"cursor\":\"{cursor}\""),
+        );
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains("repositories("))
+            .and(body_string_contains(variable))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"data": {"owner": {
+                    "repositories": {
+                        "pageInfo": {"hasNextPage": next.is_some(), "endCursor": next},
+                        "nodes": repos,
+                    },
+                }}})),
+            )
+            .mount(&self.github)
+            .await;
+    }
+
+    /// Serve `repos` as the one-page repo list of `owner`, as listed when
+    /// owners are named.
+    pub async fn list_of(&self, owner: &str, repos: &[Value]) {
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains("repositories("))
+            .and(body_string_contains(format!(r#""login":"{owner}""#)))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"data": {"owner": {
+                    "repositories": {
+                        "pageInfo": {"hasNextPage": false, "endCursor": null},
+                        "nodes": repos,
+                    },
+                }}})),
+            )
+            .mount(&self.github)
+            .await;
+    }
+
+    /// Accept archiving `name_with_owner`, and show it as archived afterwards.
+    pub async fn allow_archive(&self, name_with_owner: &str) {
+        self.respond_to_archive(name_with_owner, ResponseTemplate::new(200))
+            .await;
+        self.show_repo(name_with_owner, true).await;
+    }
+
+    /// Answer lookups of `name_with_owner` after archiving it.
+    pub async fn show_repo(&self, name_with_owner: &str, archived: bool) {
+        let (owner, name) = name_with_owner.split_once('/').unwrap();
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains("archived: isArchived"))
+            .and(body_string_contains(format!(r#""owner":"{owner}""#)))
+            .and(body_string_contains(format!(r#""name":"{name}""#)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": {
+                "repository": {
+                    "archived": archived,
+                    "pushedAt": "2019-05-01T00:00:00Z",
+                    "updatedAt": "2024-06-01T12:00:00Z",
+                },
+            }})))
+            .mount(&self.github)
+            .await;
+    }
+
+    /// Answer the read-only checks a dry run makes on `name_with_owner`
+    /// before predicting anything: an admin's view of a repo with nothing open.
+    pub async fn check_repo(&self, name_with_owner: &str, archived: bool, visibility: &str) {
+        let (owner, name) = name_with_owner.split_once('/').unwrap();
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains("isArchived viewerPermission"))
+            .and(body_string_contains(format!(
+                r#"repository(owner: \"{owner}\", name: \"{name}\")"#
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": {
+                "r0": {
+                    "isArchived": archived,
+                    "viewerPermission": "ADMIN",
+                    "visibility": visibility,
+                    "forkCount": 0,
+                    "issues": {"totalCount": 0},
+                    "pullRequests": {"totalCount": 0},
+                },
+            }})))
+            .mount(&self.github)
+            .await;
+    }
+
+    /// The run reports written so far, parsed.
+    pub fn reports(&self) -> Vec<Value> {
+        let Ok(entries) = fs::read_dir(self.dir.join("state/repo-archiver/reports")) else {
+            return Vec::new();
+        };
+        entries
+            .map(|entry| serde_json::from_slice(&fs::read(entry.unwrap().path()).unwrap()).unwrap())
+            .collect()
+    }
+
+    pub async fn respond_to_archive(&self, name_with_owner: &str, response: ResponseTemplate) {
+        Mock::given(method("PATCH"))
+            .and(path(format!("/repos/{name_with_owner}")))
+            .and(body_string_contains(r#""archived":true"#))
+            .respond_with(response)
+            .mount(&self.github)
+            .await;
+    }
+
+    /// Answer GraphQL queries containing `needle` with `data`.
+    pub async fn graphql(&self, needle: &str, data: Value) {
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains(needle))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": data })))
+            .mount(&self.github)
+            .await;
+    }
+
+    /// Every request the mock received, as "METHOD /path", in order.
+    pub async fn requests(&self) -> Vec<String> {
+        self.github
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.url.path()))
+            .collect()
+    }
+}
+
+/// A repo as the repo list query returns it.
+pub fn repo(name_with_owner: &str, pushed_at: &str, is_fork: bool) -> Value {
+    let name = name_with_owner.split_once('/').unwrap().1;
+    json!({
+        "name": name,
+        "nameWithOwner": name_with_owner,
+        "createdAt": "2015-01-01T00:00:00Z",
+        "pushedAt": pushed_at,
+        "description": null,
+        "isTemplate": false,
+        "isMirror": false,
+        "isFork": is_fork,
+        "stargazerCount": 0,
+        "homepageUrl": "",
+        "diskUsage": 120,
+    })
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}